@@ -139,6 +139,7 @@ const spec: Spec = {
            { name: "rust", alias: "r", type: channel, defaultValue: "default" },
            { name: "modules", type: String, multiple: true, defaultOption: true },
            { name: "node_module_version", type: Number },
+           { name: "features", type: String },
            { name: "help", alias: "h", type: Boolean }],
     usage: [{
       header: "neon build",
@@ -164,6 +165,10 @@ const spec: Spec = {
         alias: "p",
         type: Boolean,
         description: "Specify modules by path instead of name."
+      }, {
+        name: "features",
+        type: String,
+        description: "Space-separated list of Cargo features to build with."
       }]
     }],
     action: async function(options) {
@@ -182,7 +187,8 @@ const spec: Spec = {
         await neon_build(module,
                          options.rust as Toolchain,
                          !options.debug,
-                         String(options.node_module_version));
+                         String(options.node_module_version),
+                         options.features as string | undefined);
       }
     }
   },
@@ -1,7 +1,7 @@
 import * as TOML from 'toml';
 import * as path from 'path';
 import { readFileSync } from 'fs';
-import { remove, copy } from './async/fs';
+import { remove, copy, writeFile } from './async/fs';
 import Artifacts from './artifacts';
 import Project from './project';
 
@@ -22,6 +22,8 @@ export default class Crate {
   readonly root: string;
   /** The absolute path of the `.node` addon. */
   readonly addon: string;
+  /** The absolute path of the ES module wrapper written for addons that opt in. */
+  readonly esmodule: string;
   /** The crate name extracted from the manifest. */
   readonly name: string;
   /** The absolute path of the artifacts file. */
@@ -36,6 +38,7 @@ export default class Crate {
     this.nodefile = nodefile;
     this.root = path.resolve(project.root, subdirectory);
     this.addon = path.resolve(this.root, nodefile);
+    this.esmodule = path.resolve(this.root, path.basename(nodefile, '.node') + '.mjs');
     this.name = loadLibName(path.resolve(this.root, 'Cargo.toml'))
     this.artifactsfile =
       path.resolve(this.root, 'artifacts.json');
@@ -44,11 +47,41 @@ export default class Crate {
 
   async finish(dylib: string) {
     await remove(this.addon);
+    await remove(this.esmodule);
     await copy(dylib, this.addon);
+    await this.writeESModule();
   }
 
   async removeAddon() {
     await remove(this.addon);
+    await remove(this.esmodule);
+  }
+
+  // Addons that mark their exports with `__esModule` get a wrapper declaring each
+  // export as a named export, since Node can't detect the names of a native module's
+  // exports when it's imported from an ES module.
+  private async writeESModule() {
+    let addon: any;
+    try {
+      addon = require(this.addon);
+    } catch (e) {
+      // The addon was built for a different version of Node.
+      return;
+    }
+    if (!addon || !addon.__esModule) {
+      return;
+    }
+    // Lazy exports are left out, since reading them would defeat their laziness.
+    let names = Object.keys(addon).filter(name => {
+      let descriptor = Object.getOwnPropertyDescriptor(addon, name);
+      return /^[A-Za-z_$][\w$]*$/.test(name) && name !== 'default' && !descriptor.get;
+    });
+    let lines = [
+      "import { createRequire } from 'module';",
+      "const addon = createRequire(import.meta.url)('./" + this.nodefile + "');",
+      "export default addon;"
+    ].concat(names.map(name => "export const " + name + " = addon." + name + ";"));
+    await writeFile(this.esmodule, lines.join('\n') + '\n', { encoding: 'utf8' });
   }
 
   resetArtifacts() {
@@ -4,7 +4,8 @@ import * as rust from '../rust';
 export default async function neon_build(root: string,
                                          toolchain: rust.Toolchain,
                                          release: boolean,
-                                         abi: string) {
+                                         abi: string,
+                                         features?: string) {
   let project = new Project(root);
-  await project.build(toolchain, release, abi);
+  await project.build(toolchain, release, abi, features);
 }
@@ -23,7 +23,8 @@ export default class Project {
 
   async build(toolchain: rust.Toolchain,
               release: boolean,
-              abi: string)
+              abi: string,
+              features?: string)
   {
     let target = new Target(this.crate, { release: release });
     let settings = BuildSettings.current(toolchain);
@@ -36,7 +37,7 @@ export default class Project {
 
     // 2. Build the dylib.
     log("running cargo");
-    await target.build(toolchain, settings, abi);
+    await target.build(toolchain, settings, abi, features);
 
     // 3. Copy the dylib as the main addon file.
     log("generating " + path.join(this.crate.subdirectory, this.crate.nodefile));
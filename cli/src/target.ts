@@ -72,7 +72,8 @@ export default class Target {
 
   async build(toolchain: rust.Toolchain,
               settings: BuildSettings,
-              abi: string = process.versions.modules)
+              abi: string = process.versions.modules,
+              features?: string)
   {
     let macos = process.platform === 'darwin';
 
@@ -80,8 +81,10 @@ export default class Target {
     let releaseFlags = this.release ? ["--release"] : [];
     let extraFlags = macos ? ["--", "-C", "link-args=-Wl,-undefined,dynamic_lookup"] : [];
     let targetFlags = this.triple ? ["--target=" + this.triple] : [];
+    let featureFlags = features ? ["--features", features] : [];
 
-    let args = [command].concat(releaseFlags, extraFlags, targetFlags);
+    // Everything after `extraFlags` is passed to rustc on macOS, so the feature flags go before it.
+    let args = [command].concat(releaseFlags, featureFlags, extraFlags, targetFlags);
 
     try {
       let result = await rust.spawn("cargo", args, toolchain, {
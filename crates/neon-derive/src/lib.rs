@@ -0,0 +1,497 @@
+//! Custom derives for Neon.
+
+extern crate proc_macro;
+extern crate proc_macro2;
+extern crate syn;
+#[macro_use]
+extern crate quote;
+
+use proc_macro::TokenStream;
+use proc_macro2::Span;
+use syn::{Attribute, Data, DataEnum, DeriveInput, Error, Field, Fields, Ident, Lit, Meta, NestedMeta};
+use syn::spanned::Spanned;
+
+/// Derives `neon::js::binary::BufferLayout` for a `#[repr(C)]` struct whose fields are all
+/// `BufferLayout` types, so that it can be viewed in place over binary data with
+/// `BinaryData::view` and `BinaryData::view_mut`. The struct can't have padding, which
+/// would leave uninitialized bytes in the buffer, so a struct whose size isn't the sum
+/// of its fields' sizes fails to compile.
+///
+/// ```rust,ignore
+/// #[repr(C)]
+/// #[derive(Clone, Copy, BufferLayout)]
+/// struct Header {
+///     id: u32,
+///     flags: u16,
+///     kind: u16,
+///     timestamp: f64
+/// }
+/// ```
+#[proc_macro_derive(BufferLayout)]
+pub fn derive_buffer_layout(input: TokenStream) -> TokenStream {
+    let input: DeriveInput = match syn::parse(input) {
+        Ok(input) => input,
+        Err(err) => return err.to_compile_error().into()
+    };
+    match buffer_layout(&input) {
+        Ok(tokens) => tokens.into(),
+        Err(err) => err.to_compile_error().into()
+    }
+}
+
+fn buffer_layout(input: &DeriveInput) -> Result<proc_macro2::TokenStream, Error> {
+    let name = &input.ident;
+
+    if !input.generics.params.is_empty() {
+        return Err(Error::new(input.generics.span(), "BufferLayout cannot be derived for generic types"));
+    }
+
+    if !is_repr_c(input) {
+        return Err(Error::new(Span::call_site(), "BufferLayout can only be derived for #[repr(C)] structs"));
+    }
+
+    let fields = match input.data {
+        Data::Struct(ref data) => &data.fields,
+        _ => return Err(Error::new(Span::call_site(), "BufferLayout can only be derived for structs"))
+    };
+
+    let types: Vec<_> = fields.iter().map(|field| &field.ty).collect();
+    let sizes = types.clone();
+
+    Ok(quote! {
+        unsafe impl ::neon::js::binary::BufferLayout for #name {
+            fn assert_fields() {
+                fn assert_field<T: ::neon::js::binary::BufferLayout>() { }
+                #(assert_field::<#types>();)*
+                // The array is only empty if the struct has no padding.
+                let _no_padding: [(); 0] = [(); ::std::mem::size_of::<#name>() - (0 #(+ ::std::mem::size_of::<#sizes>())*)];
+            }
+        }
+    })
+}
+
+/// Derives `neon::convert::TryIntoJs` for a struct with named fields, converting it to a
+/// JavaScript object with a property for each field. A property can be given a different
+/// name with `#[neon(rename = "...")]`.
+///
+/// ```rust,ignore
+/// #[derive(ToJsObject, FromJsObject)]
+/// struct User {
+///     #[neon(rename = "userName")]
+///     name: String,
+///     age: u32
+/// }
+/// ```
+#[proc_macro_derive(ToJsObject, attributes(neon))]
+pub fn derive_to_js_object(input: TokenStream) -> TokenStream {
+    expand(input, to_js_object)
+}
+
+/// Derives `neon::convert::TryFromJs` for a struct with named fields, reading each field
+/// from the property of the same name, or the name given with `#[neon(rename = "...")]`.
+#[proc_macro_derive(FromJsObject, attributes(neon))]
+pub fn derive_from_js_object(input: TokenStream) -> TokenStream {
+    expand(input, from_js_object)
+}
+
+/// Derives `neon::convert::FromOptions` for a struct with named fields that implements
+/// `Default`, so that it can be read from an options argument with
+/// `FunctionCall::options`. Each field is read from the property of the same name, or
+/// the name given with `#[neon(rename = "...")]`, and keeps its default if the property
+/// is missing.
+///
+/// ```rust,ignore
+/// #[derive(Default, FromJsOptions)]
+/// struct WatchOptions {
+///     recursive: bool,
+///     #[neon(rename = "pollInterval")]
+///     poll_interval: Option<u32>
+/// }
+/// ```
+#[proc_macro_derive(FromJsOptions, attributes(neon))]
+pub fn derive_from_js_options(input: TokenStream) -> TokenStream {
+    expand(input, from_js_options)
+}
+
+fn expand(input: TokenStream, derive: fn(&DeriveInput) -> Result<proc_macro2::TokenStream, Error>) -> TokenStream {
+    let input: DeriveInput = match syn::parse(input) {
+        Ok(input) => input,
+        Err(err) => return err.to_compile_error().into()
+    };
+    match derive(&input) {
+        Ok(tokens) => tokens.into(),
+        Err(err) => err.to_compile_error().into()
+    }
+}
+
+fn to_js_object(input: &DeriveInput) -> Result<proc_macro2::TokenStream, Error> {
+    let name = &input.ident;
+    let fields = object_fields(input, "ToJsObject")?;
+    let idents = fields.iter().map(|&(field, _)| &field.ident);
+    let keys = fields.iter().map(|&(_, ref key)| key);
+
+    Ok(quote! {
+        impl ::neon::convert::TryIntoJs for #name {
+            type Value = ::neon::js::JsObject;
+
+            fn try_into_js<'a, S: ::neon::scope::Scope<'a>>(self, scope: &mut S) -> ::neon::vm::JsResult<'a, ::neon::js::JsObject> {
+                let object = ::neon::js::JsObject::new(scope);
+                #(::neon::convert::derive_internal::write(scope, object, #keys, self.#idents)?;)*
+                Ok(object)
+            }
+        }
+    })
+}
+
+fn from_js_object(input: &DeriveInput) -> Result<proc_macro2::TokenStream, Error> {
+    let name = &input.ident;
+    let fields = object_fields(input, "FromJsObject")?;
+    let idents: Vec<_> = fields.iter().map(|&(field, _)| &field.ident).collect();
+    let idents2 = idents.clone();
+    let keys: Vec<_> = fields.iter().map(|&(_, ref key)| key).collect();
+    let keys2 = keys.clone();
+    let types = fields.iter().map(|&(field, _)| &field.ty);
+
+    Ok(quote! {
+        impl ::neon::convert::TryFromJs for #name {
+            fn from_js<'a, 'b, S: ::neon::scope::Scope<'a>>(scope: &mut S, value: ::neon::mem::Handle<'b, ::neon::js::JsValue>) -> ::neon::vm::VmResult<Option<Self>> {
+                let object = match ::neon::convert::derive_internal::object(value) {
+                    Some(object) => object,
+                    None => return Ok(None)
+                };
+                #(
+                    let #idents = match ::neon::convert::derive_internal::read(scope, object, #keys)? {
+                        Some(value) => value,
+                        None => return Ok(None)
+                    };
+                )*
+                Ok(Some(#name { #(#idents2),* }))
+            }
+
+            fn describe() -> String {
+                let properties: Vec<String> = vec![
+                    #(format!("{}: {}", #keys2, <#types as ::neon::convert::TryFromJs>::describe())),*
+                ];
+                format!("an object of the form {{ {} }}", properties.join(", "))
+            }
+        }
+    })
+}
+
+/// Derives `neon::convert::TryIntoJs` for an enum. If every variant is fieldless, each is
+/// converted to a string of its name; otherwise each variant is converted to an object
+/// with its fields and a `type` property holding the variant's name. Variants and fields
+/// can be renamed with `#[neon(rename = "...")]`, and the tag property with
+/// `#[neon(tag = "...")]` on the enum, which also forces fieldless enums to be tagged.
+///
+/// ```rust,ignore
+/// #[derive(ToJsEnum, FromJsEnum)]
+/// enum Shape {
+///     Circle { radius: f64 },
+///     Rectangle { width: f64, height: f64 },
+///     #[neon(rename = "nothing")]
+///     Empty
+/// }
+/// ```
+#[proc_macro_derive(ToJsEnum, attributes(neon))]
+pub fn derive_to_js_enum(input: TokenStream) -> TokenStream {
+    expand(input, to_js_enum)
+}
+
+/// Derives `neon::convert::TryFromJs` for an enum, reading the representation produced
+/// by `ToJsEnum`.
+#[proc_macro_derive(FromJsEnum, attributes(neon))]
+pub fn derive_from_js_enum(input: TokenStream) -> TokenStream {
+    expand(input, from_js_enum)
+}
+
+fn to_js_enum(input: &DeriveInput) -> Result<proc_macro2::TokenStream, Error> {
+    let name = &input.ident;
+    let (tag, variants) = enum_variants(input, "ToJsEnum")?;
+
+    let tag = match tag {
+        Some(tag) => tag,
+        None => {
+            let arms = variants.iter().map(|variant| {
+                let ident = variant.ident;
+                let js_name = &variant.name;
+                quote! { #name::#ident => #js_name }
+            });
+            return Ok(quote! {
+                impl ::neon::convert::TryIntoJs for #name {
+                    type Value = ::neon::js::JsString;
+
+                    fn try_into_js<'a, S: ::neon::scope::Scope<'a>>(self, scope: &mut S) -> ::neon::vm::JsResult<'a, ::neon::js::JsString> {
+                        let name = match self { #(#arms),* };
+                        ::neon::js::JsString::new_or_throw(scope, name)
+                    }
+                }
+            });
+        }
+    };
+
+    let arms = variants.iter().map(|variant| {
+        let ident = variant.ident;
+        let js_name = &variant.name;
+        let bindings: Vec<_> = variant.fields.iter().map(|&(field, _)| &field.ident).collect();
+        let values = bindings.clone();
+        let keys = variant.fields.iter().map(|&(_, ref key)| key);
+        quote! {
+            #name::#ident { #(#bindings),* } => {
+                ::neon::convert::derive_internal::write(scope, object, #tag, #js_name)?;
+                #(::neon::convert::derive_internal::write(scope, object, #keys, #values)?;)*
+            }
+        }
+    });
+
+    Ok(quote! {
+        impl ::neon::convert::TryIntoJs for #name {
+            type Value = ::neon::js::JsObject;
+
+            fn try_into_js<'a, S: ::neon::scope::Scope<'a>>(self, scope: &mut S) -> ::neon::vm::JsResult<'a, ::neon::js::JsObject> {
+                let object = ::neon::js::JsObject::new(scope);
+                match self { #(#arms)* }
+                Ok(object)
+            }
+        }
+    })
+}
+
+fn from_js_options(input: &DeriveInput) -> Result<proc_macro2::TokenStream, Error> {
+    let name = &input.ident;
+    let fields = object_fields(input, "FromJsOptions")?;
+    let idents = fields.iter().map(|&(field, _)| &field.ident);
+    let keys: Vec<_> = fields.iter().map(|&(_, ref key)| key).collect();
+    let keys2 = keys.clone();
+
+    Ok(quote! {
+        impl ::neon::convert::FromOptions for #name {
+            fn keys() -> &'static [&'static str] {
+                &[#(#keys),*]
+            }
+
+            fn read_options<'a, 'b, S: ::neon::scope::Scope<'a>>(&mut self, scope: &mut S, object: ::neon::mem::Handle<'b, ::neon::js::JsObject>) -> ::neon::vm::VmResult<()> {
+                #(::neon::convert::derive_internal::read_option(scope, object, #keys2, &mut self.#idents)?;)*
+                Ok(())
+            }
+        }
+    })
+}
+
+fn from_js_enum(input: &DeriveInput) -> Result<proc_macro2::TokenStream, Error> {
+    let name = &input.ident;
+    let (tag, variants) = enum_variants(input, "FromJsEnum")?;
+
+    let tag = match tag {
+        Some(tag) => tag,
+        None => {
+            let arms = variants.iter().map(|variant| {
+                let ident = variant.ident;
+                let js_name = &variant.name;
+                quote! { #js_name => Some(#name::#ident), }
+            });
+            let quoted: Vec<String> = variants.iter().map(|variant| format!("{:?}", variant.name)).collect();
+            let description = format!("one of {}", quoted.join(", "));
+            return Ok(quote! {
+                impl ::neon::convert::TryFromJs for #name {
+                    fn from_js<'a, 'b, S: ::neon::scope::Scope<'a>>(scope: &mut S, value: ::neon::mem::Handle<'b, ::neon::js::JsValue>) -> ::neon::vm::VmResult<Option<Self>> {
+                        let name = match <String as ::neon::convert::TryFromJs>::from_js(scope, value)? {
+                            Some(name) => name,
+                            None => return Ok(None)
+                        };
+                        Ok(match name.as_str() {
+                            #(#arms)*
+                            _ => None
+                        })
+                    }
+
+                    fn describe() -> String {
+                        String::from(#description)
+                    }
+                }
+            });
+        }
+    };
+
+    let arms = variants.iter().map(|variant| {
+        let ident = variant.ident;
+        let js_name = &variant.name;
+        let bindings: Vec<_> = variant.fields.iter().map(|&(field, _)| &field.ident).collect();
+        let fields = bindings.clone();
+        let keys = variant.fields.iter().map(|&(_, ref key)| key);
+        quote! {
+            #js_name => {
+                #(
+                    let #bindings = match ::neon::convert::derive_internal::read(scope, object, #keys)? {
+                        Some(value) => value,
+                        None => return Ok(None)
+                    };
+                )*
+                Ok(Some(#name::#ident { #(#fields),* }))
+            }
+        }
+    });
+
+    let descriptions = variants.iter().map(|variant| {
+        let js_name = &variant.name;
+        let keys = variant.fields.iter().map(|&(_, ref key)| key);
+        let types = variant.fields.iter().map(|&(field, _)| &field.ty);
+        quote! {
+            {
+                let mut properties = vec![format!("{}: {:?}", #tag, #js_name)];
+                #(properties.push(format!("{}: {}", #keys, <#types as ::neon::convert::TryFromJs>::describe()));)*
+                format!("{{ {} }}", properties.join(", "))
+            }
+        }
+    });
+
+    Ok(quote! {
+        impl ::neon::convert::TryFromJs for #name {
+            fn from_js<'a, 'b, S: ::neon::scope::Scope<'a>>(scope: &mut S, value: ::neon::mem::Handle<'b, ::neon::js::JsValue>) -> ::neon::vm::VmResult<Option<Self>> {
+                let object = match ::neon::convert::derive_internal::object(value) {
+                    Some(object) => object,
+                    None => return Ok(None)
+                };
+                let tag: String = match ::neon::convert::derive_internal::read(scope, object, #tag)? {
+                    Some(tag) => tag,
+                    None => return Ok(None)
+                };
+                match tag.as_str() {
+                    #(#arms)*
+                    _ => Ok(None)
+                }
+            }
+
+            fn describe() -> String {
+                let variants: Vec<String> = vec![#(#descriptions),*];
+                format!("one of {}", variants.join(" or "))
+            }
+        }
+    })
+}
+
+// A variant of an enum, with its JavaScript name and its fields paired with their
+// property names.
+struct EnumVariant<'i> {
+    ident: &'i Ident,
+    name: String,
+    fields: Vec<(&'i Field, String)>
+}
+
+// The variants of a non-generic enum, along with the name of its tag property, which
+// is `None` if the enum is represented as strings.
+fn enum_variants<'i>(input: &'i DeriveInput, derive: &str) -> Result<(Option<String>, Vec<EnumVariant<'i>>), Error> {
+    if !input.generics.params.is_empty() {
+        return Err(Error::new(input.generics.span(), format!("{} cannot be derived for generic types", derive)));
+    }
+
+    let data: &DataEnum = match input.data {
+        Data::Enum(ref data) => data,
+        _ => return Err(Error::new(Span::call_site(), format!("{} can only be derived for enums", derive)))
+    };
+
+    let mut variants = Vec::new();
+    for variant in data.variants.iter() {
+        let name = match neon_attr(&variant.attrs, "rename")? {
+            Some(name) => name,
+            None => variant.ident.to_string()
+        };
+        let fields = match variant.fields {
+            Fields::Unit => Vec::new(),
+            Fields::Named(ref fields) => {
+                let mut result = Vec::new();
+                for field in fields.named.iter() {
+                    let key = match neon_attr(&field.attrs, "rename")? {
+                        Some(key) => key,
+                        None => field.ident.as_ref().unwrap().to_string()
+                    };
+                    result.push((field, key));
+                }
+                result
+            }
+            Fields::Unnamed(ref fields) => {
+                return Err(Error::new(fields.span(), format!("{} doesn't support tuple variants; use named fields", derive)));
+            }
+        };
+        variants.push(EnumVariant { ident: &variant.ident, name: name, fields: fields });
+    }
+
+    let tag = match neon_attr(&input.attrs, "tag")? {
+        Some(tag) => tag,
+        None if variants.iter().all(|variant| variant.fields.is_empty()) => return Ok((None, variants)),
+        None => String::from("type")
+    };
+
+    for variant in &variants {
+        for &(field, ref key) in &variant.fields {
+            if *key == tag {
+                return Err(Error::new(field.span(), format!("the property {:?} is already used for the enum's tag", key)));
+            }
+        }
+    }
+
+    Ok((Some(tag), variants))
+}
+
+// The named fields of a non-generic struct, paired with their property names.
+fn object_fields<'i>(input: &'i DeriveInput, derive: &str) -> Result<Vec<(&'i Field, String)>, Error> {
+    if !input.generics.params.is_empty() {
+        return Err(Error::new(input.generics.span(), format!("{} cannot be derived for generic types", derive)));
+    }
+
+    let fields = match input.data {
+        Data::Struct(ref data) => match data.fields {
+            Fields::Named(ref fields) => &fields.named,
+            _ => return Err(Error::new(Span::call_site(), format!("{} can only be derived for structs with named fields", derive)))
+        },
+        _ => return Err(Error::new(Span::call_site(), format!("{} can only be derived for structs", derive)))
+    };
+
+    fields.iter().map(|field| {
+        let key = match neon_attr(&field.attrs, "rename")? {
+            Some(key) => key,
+            None => field.ident.as_ref().unwrap().to_string()
+        };
+        Ok((field, key))
+    }).collect()
+}
+
+// Reads the value of a `#[neon(name = "...")]` attribute, the only kind of neon
+// attribute allowed in that position.
+fn neon_attr(attrs: &[Attribute], name: &str) -> Result<Option<String>, Error> {
+    let mut result = None;
+    for attr in attrs {
+        let meta = match attr.parse_meta() {
+            Ok(Meta::List(ref list)) if list.ident == "neon" => list.clone(),
+            _ => continue
+        };
+        for nested in meta.nested.iter() {
+            match *nested {
+                NestedMeta::Meta(Meta::NameValue(ref pair)) if pair.ident == name => {
+                    match pair.lit {
+                        Lit::Str(ref s) => result = Some(s.value()),
+                        _ => return Err(Error::new(pair.lit.span(), "expected a string"))
+                    }
+                }
+                _ => return Err(Error::new(nested.span(), "unknown neon attribute"))
+            }
+        }
+    }
+    Ok(result)
+}
+
+fn is_repr_c(input: &DeriveInput) -> bool {
+    input.attrs.iter().filter_map(|attr| attr.parse_meta().ok()).any(|meta| {
+        match meta {
+            Meta::List(ref list) if list.ident == "repr" => {
+                list.nested.iter().any(|nested| {
+                    match *nested {
+                        NestedMeta::Meta(Meta::Word(ref ident)) => ident == "C",
+                        _ => false
+                    }
+                })
+            }
+            _ => false
+        }
+    })
+}
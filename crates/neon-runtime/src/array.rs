@@ -8,6 +8,16 @@ extern "C" {
     #[link_name = "Neon_Array_New"]
     pub fn new(out: &mut Local, isolate: *mut Isolate, length: u32);
 
+    /// Mutates the `out` argument provided to refer to a newly created `v8::Array` holding
+    /// the `len` values at `elements`. Returns `false` if the array couldn't be filled.
+    #[link_name = "Neon_Array_FromValues"]
+    pub fn from_values(out: &mut Local, isolate: *mut Isolate, elements: *const Local, len: u32) -> bool;
+
+    /// Mutates the `out` argument provided to refer to a newly created `v8::Array` holding
+    /// the `len` numbers at `elements`. Returns `false` if the array couldn't be filled.
+    #[link_name = "Neon_Array_FromNumbers"]
+    pub fn from_numbers(out: &mut Local, isolate: *mut Isolate, elements: *const f64, len: u32) -> bool;
+
     /// Gets the length of an `v8::Array`.
     #[link_name = "Neon_Array_Length"]
     pub fn len(array: Local) -> u32;
@@ -17,4 +17,13 @@ extern "C" {
     #[link_name = "Neon_ArrayBuffer_Data"]
     pub fn data<'a, 'b>(out: &'a mut CMutSlice<'b, u8>, obj: Local);
 
+    /// Detaches the `v8::ArrayBuffer`, freeing its contents if V8 owns them. Returns `false`
+    /// and throws if the buffer can't be detached.
+    #[link_name = "Neon_ArrayBuffer_Detach"]
+    pub fn detach(isolate: *mut c_void, obj: Local) -> bool;
+
+    /// Indicates whether the `v8::ArrayBuffer` has been detached.
+    #[link_name = "Neon_ArrayBuffer_IsDetached"]
+    pub fn is_detached(obj: Local) -> bool;
+
 }
@@ -0,0 +1,19 @@
+//! Facilities for working with `v8::BigInt`s.
+
+use raw::Local;
+
+extern "C" {
+
+    /// Mutates the `out` argument provided to hold the value of a `v8::BigInt` as an `i64`,
+    /// wrapping modulo 2^64. Returns `false` if the conversion was lossy, which is always
+    /// the case before V8 6.8.
+    #[link_name = "Neon_BigInt_ToInt64"]
+    pub fn to_i64(out: &mut i64, bigint: Local) -> bool;
+
+    /// Mutates the `out` argument provided to hold the value of a `v8::BigInt` as a `u64`,
+    /// wrapping modulo 2^64. Returns `false` if the conversion was lossy, which is always
+    /// the case before V8 6.8.
+    #[link_name = "Neon_BigInt_ToUint64"]
+    pub fn to_u64(out: &mut u64, bigint: Local) -> bool;
+
+}
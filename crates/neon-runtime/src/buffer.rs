@@ -1,5 +1,6 @@
 //! Facilities for working with `node::Buffer`s.
 
+use std::os::raw::c_void;
 use raw::Local;
 use cslice::CMutSlice;
 
@@ -7,11 +8,29 @@ use cslice::CMutSlice;
 #[allow(improper_ctypes)]
 extern "C" {
 
-    /// Mutates the `out` argument provided to refer to a newly created `node::Buffer` object.
-    /// Returns `false` if the value couldn't be created.
+    /// Mutates the `out` argument provided to refer to a newly created and zero-filled
+    /// `node::Buffer` object. Returns `false` if the value couldn't be created.
     #[link_name = "Neon_Buffer_New"]
     pub fn new(out: &mut Local, size: u32) -> bool;
 
+    /// Mutates the `out` argument provided to refer to a newly created `node::Buffer` object
+    /// whose contents are left uninitialized. Returns `false` if the value couldn't be created.
+    #[link_name = "Neon_Buffer_Uninitialized"]
+    pub fn uninitialized(out: &mut Local, size: u32) -> bool;
+
+    /// Mutates the `out` argument provided to refer to a newly created `node::Buffer` object
+    /// containing a copy of `size` bytes starting at `data`. Returns `false` if the value
+    /// couldn't be created.
+    #[link_name = "Neon_Buffer_FromSlice"]
+    pub fn from_slice(out: &mut Local, data: *const u8, size: u32) -> bool;
+
+    /// Mutates the `out` argument provided to refer to a newly created `node::Buffer` object
+    /// that takes ownership of `size` bytes at `data`, calling `free` with `data` and `hint`
+    /// once the buffer is garbage collected. Returns `false` if the value couldn't be created,
+    /// in which case ownership of the data is not taken.
+    #[link_name = "Neon_Buffer_External"]
+    pub fn external(out: &mut Local, data: *mut u8, size: u32, free: extern "C" fn(*mut u8, *mut c_void), hint: *mut c_void) -> bool;
+
     /// Mutates the `out` argument provided populating the `data` and `len` properties.
     #[link_name = "Neon_Buffer_Data"]
     pub fn data<'a, 'b>(out: &'a mut CMutSlice<'b, u8>, obj: Local);
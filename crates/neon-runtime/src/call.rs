@@ -44,4 +44,10 @@ extern "C" {
     #[link_name = "Neon_Call_Get"]
     pub fn get(info: &FunctionCallbackInfo, i: i32, out: &mut Local);
 
+    /// Copies the `v8::Local` handles of the first `len` arguments passed to the function
+    /// into the buffer at `out`, which must have room for `len` handles. `len` must not
+    /// exceed the number of arguments.
+    #[link_name = "Neon_Call_GetAll"]
+    pub fn get_all(info: &FunctionCallbackInfo, out: *mut Local, len: i32);
+
 }
@@ -28,6 +28,9 @@ extern "C" {
     #[link_name = "Neon_Class_AddMethod"]
     pub fn add_method(isolate: *mut Isolate, metadata: *mut c_void, name: *const u8, byte_length: u32, method: Local) -> bool;
 
+    #[link_name = "Neon_Class_AddIterator"]
+    pub fn add_iterator(isolate: *mut Isolate, metadata: *mut c_void, is_async: bool, method: Local) -> bool;
+
     #[link_name = "Neon_Class_MetadataToClass"]
     pub fn metadata_to_class(out: &mut Local, isolate: *mut Isolate, metadata: *mut c_void);
 
@@ -16,4 +16,15 @@ extern "C" {
     #[link_name = "Neon_Convert_ToString"]
     pub fn to_string(out: &mut Local, value: Local) -> bool;
 
+    /// Converts the value provided to a `v8::Number` as JavaScript's `Number(value)` does, and
+    /// mutates the `out` argument provided to refer to the result. Returns `false` if the
+    /// conversion threw, e.g. from a `valueOf` method.
+    #[link_name = "Neon_Convert_ToNumber"]
+    pub fn to_number(out: &mut Local, value: Local) -> bool;
+
+    /// Converts the value provided to a `v8::Boolean` by its truthiness, and mutates the `out`
+    /// argument provided to refer to the result.
+    #[link_name = "Neon_Convert_ToBoolean"]
+    pub fn to_boolean(out: &mut Local, value: Local);
+
 }
@@ -0,0 +1,18 @@
+//! Facilities for working with `v8::Date`s.
+
+use raw::Local;
+
+extern "C" {
+
+    /// Mutates the `out` argument provided to refer to a newly created `v8::Date` with the
+    /// given time value, in milliseconds since the Unix epoch. Returns `false` if the value
+    /// couldn't be created.
+    #[link_name = "Neon_Date_New"]
+    pub fn new(out: &mut Local, time: f64) -> bool;
+
+    /// Gets the time value of a `v8::Date`, in milliseconds since the Unix epoch. This is
+    /// `NaN` for an invalid date.
+    #[link_name = "Neon_Date_Value"]
+    pub fn value(p: Local) -> f64;
+
+}
@@ -28,6 +28,12 @@ extern "C" {
     #[link_name = "Neon_Error_NewSyntaxError"]
     pub fn new_syntax_error(out: &mut Local, msg: Local);
 
+    /// Inserts `len` bytes of UTF-8 `frames` into an error's `stack` right after the line
+    /// describing the error, so they read as its innermost frames. Each frame should be a
+    /// newline followed by an indented `at` line. Does nothing if the error has no stack.
+    #[link_name = "Neon_Error_AddFrames"]
+    pub fn add_frames(error: Local, frames: *const u8, len: i32);
+
     /// Throws an `Error` object in the current context.
     #[link_name = "Neon_Error_ThrowErrorFromCString"]
     pub fn throw_error_from_cstring(msg: *const u8);
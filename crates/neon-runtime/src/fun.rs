@@ -10,6 +10,38 @@ extern "C" {
     #[link_name = "Neon_Fun_New"]
     pub fn new(out: &mut Local, isolate: *mut c_void, callback: *mut c_void, kernel: *mut c_void) -> bool;
 
+    /// Mutates the `out` argument provided to refer to a newly created `v8::Function` whose
+    /// kernel is an owned `closure`, which is freed with `drop` once the function is
+    /// collected. Returns `false`, without taking ownership of `closure`, if the value
+    /// couldn't be created.
+    #[link_name = "Neon_Fun_NewClosure"]
+    pub fn new_closure(out: &mut Local, isolate: *mut c_void, callback: *mut c_void, closure: *mut c_void, drop: unsafe extern fn(*mut c_void)) -> bool;
+
+    /// Mutates the `out` argument provided to refer to the `v8::Function` of a cached
+    /// `v8::FunctionTemplate`, creating the template and storing it in `cache` if the slot is
+    /// null. Returns `false` if the value couldn't be created.
+    #[link_name = "Neon_Fun_NewCached"]
+    pub fn new_cached(out: &mut Local, isolate: *mut c_void, callback: *mut c_void, kernel: *mut c_void, cache: &mut *mut c_void) -> bool;
+
+    /// Sets the name of a function, as reported by its `name` property, in stack traces,
+    /// and in CPU profiles.
+    #[link_name = "Neon_Fun_SetName"]
+    pub fn set_name(fun: Local, name: Local);
+
+    /// Mutates the `out` argument provided to refer to the name of a function.
+    #[link_name = "Neon_Fun_GetName"]
+    pub fn get_name(out: &mut Local, fun: Local);
+
+    /// Mutates the arguments provided to refer to the name of the script a function was
+    /// defined in and the 0-based line and column of its definition. Returns `false` if
+    /// the function wasn't defined in a script, e.g. if it's native or bound.
+    #[link_name = "Neon_Fun_GetScriptLocation"]
+    pub fn get_script_location(resource: &mut Local, line: &mut i32, column: &mut i32, fun: Local) -> bool;
+
+    /// Releases and frees a template cached by `new_cached`.
+    #[link_name = "Neon_Fun_DropTemplate"]
+    pub fn drop_template(cache: *mut c_void);
+
     /// Mutates the `out` argument provided to refer to a newly created `v8::FunctionTemplate`.
     /// Returns `false` if the value couldn't be created.
     #[link_name = "Neon_Fun_Template_New"]
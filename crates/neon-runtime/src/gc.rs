@@ -0,0 +1,19 @@
+//! Facilities for observing the garbage collector.
+use raw::Isolate;
+use std::os::raw::c_void;
+
+extern "C" {
+
+    /// Registers `callback` to be called with `data` before and after every garbage
+    /// collection, with `true` for the epilogue and the collection's `v8::GCType`.
+    /// Returns null if the runtime can't register callbacks with data.
+    #[link_name = "Neon_Gc_AddCallbacks"]
+    pub fn add_callbacks(isolate: *mut Isolate,
+                         data: *mut c_void,
+                         callback: unsafe extern fn(*mut c_void, bool, u32)) -> *mut c_void;
+
+    /// Unregisters and frees callbacks registered with `add_callbacks`.
+    #[link_name = "Neon_Gc_RemoveCallbacks"]
+    pub fn remove_callbacks(callbacks: *mut c_void);
+
+}
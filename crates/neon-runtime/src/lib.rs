@@ -10,6 +10,9 @@ pub mod primitive;
 pub mod error;
 pub mod arraybuffer;
 pub mod buffer;
+pub mod typedarray;
+pub mod date;
+pub mod bigint;
 pub mod tag;
 pub mod module;
 pub mod mem;
@@ -17,3 +20,5 @@ pub mod fun;
 pub mod convert;
 pub mod class;
 pub mod task;
+pub mod gc;
+pub mod profiler;
@@ -1,5 +1,6 @@
-//! A helper function for comparing `v8::Local` handles.
-use raw::Local;
+//! Helper functions for comparing `v8::Local` handles and managing persistent handles.
+use raw::{Local, Isolate};
+use std::os::raw::c_void;
 
 extern "C" {
 
@@ -7,4 +8,55 @@ extern "C" {
     #[link_name = "Neon_Mem_SameHandle"]
     pub fn same_handle(h1: Local, h2: Local) -> bool;
 
+    /// Compares two values with JavaScript's `===` operator.
+    #[link_name = "Neon_Mem_StrictEquals"]
+    pub fn strict_equals(h1: Local, h2: Local) -> bool;
+
+    /// Compares two values with the SameValue algorithm used by `Object.is`.
+    #[link_name = "Neon_Mem_SameValue"]
+    pub fn same_value(h1: Local, h2: Local) -> bool;
+
+    /// Mutates the `out` argument provided to refer to a name for the value's type: its
+    /// constructor's name for an object, `null`, or what `typeof` reports otherwise.
+    #[link_name = "Neon_Mem_TypeName"]
+    pub fn type_name(out: &mut Local, isolate: *mut Isolate, value: Local);
+
+    /// Creates a persistent handle to the value, keeping it alive until the handle is
+    /// dropped with `drop_persistent`.
+    #[link_name = "Neon_Mem_NewPersistent"]
+    pub fn new_persistent(isolate: *mut Isolate, value: Local) -> *mut c_void;
+
+    /// Mutates the `out` argument provided to refer to the value of a persistent handle.
+    #[link_name = "Neon_Mem_ReadPersistent"]
+    pub fn read_persistent(out: &mut Local, isolate: *mut Isolate, persistent: *mut c_void);
+
+    /// Releases and frees a persistent handle created with `new_persistent`.
+    #[link_name = "Neon_Mem_DropPersistent"]
+    pub fn drop_persistent(persistent: *mut c_void);
+
+    /// Creates a weak reference to the object, which doesn't keep it alive. If `data`
+    /// isn't null, `callback` is called with it once the object is collected, unless
+    /// the reference is dropped first; `drop` is called with it either way.
+    #[link_name = "Neon_Mem_NewWeak"]
+    pub fn new_weak(isolate: *mut Isolate,
+                    value: Local,
+                    data: *mut c_void,
+                    callback: unsafe extern fn(*mut c_void),
+                    drop: unsafe extern fn(*mut c_void)) -> *mut c_void;
+
+    /// Mutates the `out` argument provided to refer to the object of a weak reference.
+    /// Returns `false` if the object has been collected.
+    #[link_name = "Neon_Mem_ReadWeak"]
+    pub fn read_weak(out: &mut Local, weak: *mut c_void) -> bool;
+
+    /// Releases and frees a weak reference created with `new_weak`.
+    #[link_name = "Neon_Mem_DropWeak"]
+    pub fn drop_weak(weak: *mut c_void);
+
+    /// Tells V8 that objects it manages keep `change` more (or, if negative, fewer)
+    /// bytes of memory alive outside of its heap, and returns the new total. Does
+    /// nothing if there's no current isolate.
+    #[link_name = "Neon_Mem_AdjustExternalMemory"]
+    pub fn adjust_external_memory(change: i64) -> i64;
+
 }
@@ -8,9 +8,15 @@ extern "C" {
     /// Creates a new `v8::HandleScope` and calls `callback` provided with the argument signature
     /// `(kernal, exports, scope)`.
     #[link_name = "Neon_Module_ExecKernel"]
-    pub fn exec_kernel(kernel: *mut c_void, callback: extern fn(*mut c_void, *mut c_void, *mut c_void), exports: Local, scope: *mut c_void);
+    pub fn exec_kernel(kernel: *mut c_void, callback: extern fn(*mut c_void, Local, *mut c_void), exports: Local, scope: *mut c_void);
 
     #[link_name = "Neon_Module_GetVersion"]
     pub fn get_version() -> i32;
 
+    /// Sets the context-aware initialization function that Node calls when the module is
+    /// loaded again in another context, such as a worker thread, whose arguments are
+    /// `(exports, module, context, priv)`.
+    #[link_name = "Neon_Module_SetInitializer"]
+    pub fn set_initializer(init: *mut c_void);
+
 }
@@ -2,11 +2,15 @@
 #include <nan.h>
 #include <stdint.h>
 #include <stdio.h>
+#include <string>
 #include "node.h"
 #include "neon.h"
 #include "neon_string.h"
 #include "neon_class_metadata.h"
 #include "neon_task.h"
+#include "neon_weak.h"
+#include "neon_gc.h"
+#include "neon_profiler.h"
 
 extern "C" void Neon_Call_SetReturn(v8::FunctionCallbackInfo<v8::Value> *info, v8::Local<v8::Value> value) {
   info->GetReturnValue().Set(value);
@@ -50,6 +54,12 @@ extern "C" void Neon_Call_Get(v8::FunctionCallbackInfo<v8::Value> *info, int32_t
   *out = (*info)[i];
 }
 
+extern "C" void Neon_Call_GetAll(v8::FunctionCallbackInfo<v8::Value> *info, v8::Local<v8::Value> *out, int32_t len) {
+  for (int32_t i = 0; i < len; i++) {
+    out[i] = (*info)[i];
+  }
+}
+
 extern "C" void Neon_Object_New(v8::Local<v8::Object> *out) {
   *out = Nan::New<v8::Object>();
 }
@@ -145,6 +155,31 @@ extern "C" bool Neon_Object_Set_String(bool *out, v8::Local<v8::Object> obj, con
   return maybe.IsJust() && (*out = maybe.FromJust(), true);
 }
 
+extern "C" bool Neon_Object_GetHidden(v8::Local<v8::Value> *out, v8::Local<v8::Object> obj, const uint8_t *data, int32_t len) {
+  Nan::EscapableHandleScope scope;
+  v8::Local<v8::String> key;
+  if (!Neon_ASCII_Key(&key, data, len)) {
+    return false;
+  }
+  Nan::MaybeLocal<v8::Value> maybe = Nan::GetPrivate(obj, key);
+  v8::Local<v8::Value> result;
+  if (!maybe.ToLocal(&result)) {
+    return false;
+  }
+  *out = scope.Escape(result);
+  return true;
+}
+
+extern "C" bool Neon_Object_SetHidden(bool *out, v8::Local<v8::Object> obj, const uint8_t *data, int32_t len, v8::Local<v8::Value> val) {
+  Nan::HandleScope scope;
+  v8::Local<v8::String> key;
+  if (!Neon_ASCII_Key(&key, data, len)) {
+    return false;
+  }
+  Nan::Maybe<bool> maybe = Nan::SetPrivate(obj, key, val);
+  return maybe.IsJust() && (*out = maybe.FromJust(), true);
+}
+
 extern "C" bool Neon_Object_Get(v8::Local<v8::Value> *out, v8::Local<v8::Object> obj, v8::Local<v8::Value> key) {
   Nan::MaybeLocal<v8::Value> maybe = Nan::Get(obj, key);
   return maybe.ToLocal(out);
@@ -163,6 +198,30 @@ extern "C" void Neon_Array_New(v8::Local<v8::Array> *out, v8::Isolate *isolate,
   *out = v8::Array::New(isolate, length);
 }
 
+extern "C" bool Neon_Array_FromValues(v8::Local<v8::Array> *out, v8::Isolate *isolate, v8::Local<v8::Value> *elements, uint32_t len) {
+  v8::Local<v8::Array> array = v8::Array::New(isolate, len);
+  for (uint32_t i = 0; i < len; i++) {
+    if (!Nan::Set(array, i, elements[i]).FromMaybe(false)) {
+      return false;
+    }
+  }
+  *out = array;
+  return true;
+}
+
+extern "C" bool Neon_Array_FromNumbers(v8::Local<v8::Array> *out, v8::Isolate *isolate, const double *elements, uint32_t len) {
+  // The numbers' handles are released as soon as they're stored in the array.
+  Nan::EscapableHandleScope scope;
+  v8::Local<v8::Array> array = v8::Array::New(isolate, len);
+  for (uint32_t i = 0; i < len; i++) {
+    if (!Nan::Set(array, i, v8::Number::New(isolate, elements[i])).FromMaybe(false)) {
+      return false;
+    }
+  }
+  *out = scope.Escape(array);
+  return true;
+}
+
 extern "C" uint32_t Neon_Array_Length(v8::Local<v8::Array> array) {
   return array->Length();
 }
@@ -172,6 +231,11 @@ extern "C" bool Neon_String_New(v8::Local<v8::String> *out, v8::Isolate *isolate
   return maybe.ToLocal(out);
 }
 
+extern "C" bool Neon_String_NewInternalized(v8::Local<v8::String> *out, v8::Isolate *isolate, const uint8_t *data, int32_t len) {
+  Nan::MaybeLocal<v8::String> maybe = v8::String::NewFromUtf8(isolate, (const char*)data, v8::NewStringType::kInternalized, len);
+  return maybe.ToLocal(out);
+}
+
 extern "C" int32_t Neon_String_Utf8Length(v8::Local<v8::String> str) {
   return str->Utf8Length();
 }
@@ -180,11 +244,54 @@ extern "C" size_t Neon_String_Data(char *out, size_t len, v8::Local<v8::Value> s
   return Nan::DecodeWrite(out, len, str, Nan::UTF8);
 }
 
+extern "C" bool Neon_String_NewFromUtf16(v8::Local<v8::String> *out, v8::Isolate *isolate, const uint16_t *data, int32_t len) {
+  Nan::MaybeLocal<v8::String> maybe = v8::String::NewFromTwoByte(isolate, data, v8::NewStringType::kNormal, len);
+  return maybe.ToLocal(out);
+}
+
+extern "C" bool Neon_String_NewFromLatin1(v8::Local<v8::String> *out, v8::Isolate *isolate, const uint8_t *data, int32_t len) {
+  Nan::MaybeLocal<v8::String> maybe = v8::String::NewFromOneByte(isolate, data, v8::NewStringType::kNormal, len);
+  return maybe.ToLocal(out);
+}
+
+extern "C" int32_t Neon_String_Length(v8::Local<v8::String> str) {
+  return str->Length();
+}
+
+// The isolate-less overloads of the String::Write family were removed in V8 7.
+#if V8_MAJOR_VERSION >= 7
+#define NEON_STRING_WRITE_ARGS v8::Isolate::GetCurrent(),
+#else
+#define NEON_STRING_WRITE_ARGS
+#endif
+
+extern "C" int32_t Neon_String_WriteUtf16(v8::Local<v8::String> str, uint16_t *out, int32_t len) {
+  return str->Write(NEON_STRING_WRITE_ARGS out, 0, len, v8::String::NO_NULL_TERMINATION);
+}
+
+extern "C" int32_t Neon_String_WriteLatin1(v8::Local<v8::String> str, uint8_t *out, int32_t len) {
+  return str->WriteOneByte(NEON_STRING_WRITE_ARGS out, 0, len, v8::String::NO_NULL_TERMINATION);
+}
+
+extern "C" int32_t Neon_String_WriteUtf8(v8::Local<v8::String> str, char *out, int32_t len) {
+  return str->WriteUtf8(NEON_STRING_WRITE_ARGS out, len, nullptr, v8::String::NO_NULL_TERMINATION | v8::String::REPLACE_INVALID_UTF8);
+}
+
 extern "C" bool Neon_Convert_ToString(v8::Local<v8::String> *out, v8::Local<v8::Value> value) {
   Nan::MaybeLocal<v8::String> maybe = Nan::To<v8::String>(value);
   return maybe.ToLocal(out);
 }
 
+extern "C" bool Neon_Convert_ToNumber(v8::Local<v8::Number> *out, v8::Local<v8::Value> value) {
+  Nan::MaybeLocal<v8::Number> maybe = Nan::To<v8::Number>(value);
+  return maybe.ToLocal(out);
+}
+
+extern "C" void Neon_Convert_ToBoolean(v8::Local<v8::Boolean> *out, v8::Local<v8::Value> value) {
+  // Unlike the other conversions, ToBoolean never calls back into JavaScript.
+  *out = Nan::To<v8::Boolean>(value).ToLocalChecked();
+}
+
 extern "C" bool Neon_Convert_ToObject(v8::Local<v8::Object> *out, v8::Local<v8::Value> *value) {
   Nan::MaybeLocal<v8::Object> maybe = Nan::To<v8::Object>(*value);
   return maybe.ToLocal(out);
@@ -192,6 +299,25 @@ extern "C" bool Neon_Convert_ToObject(v8::Local<v8::Object> *out, v8::Local<v8::
 
 extern "C" bool Neon_Buffer_New(v8::Local<v8::Object> *out, uint32_t size) {
   Nan::MaybeLocal<v8::Object> maybe = Nan::NewBuffer(size);
+  if (!maybe.ToLocal(out)) {
+    return false;
+  }
+  memset(node::Buffer::Data(*out), 0, size);
+  return true;
+}
+
+extern "C" bool Neon_Buffer_Uninitialized(v8::Local<v8::Object> *out, uint32_t size) {
+  Nan::MaybeLocal<v8::Object> maybe = Nan::NewBuffer(size);
+  return maybe.ToLocal(out);
+}
+
+extern "C" bool Neon_Buffer_FromSlice(v8::Local<v8::Object> *out, const char *data, uint32_t size) {
+  Nan::MaybeLocal<v8::Object> maybe = Nan::CopyBuffer(data, size);
+  return maybe.ToLocal(out);
+}
+
+extern "C" bool Neon_Buffer_External(v8::Local<v8::Object> *out, char *data, uint32_t size, Nan::FreeCallback free, void *hint) {
+  Nan::MaybeLocal<v8::Object> maybe = Nan::NewBuffer(data, size, free, hint);
   return maybe.ToLocal(out);
 }
 
@@ -215,10 +341,143 @@ extern "C" void Neon_ArrayBuffer_Data(buf_t *out, v8::Local<v8::ArrayBuffer> buf
   out->len = contents.ByteLength();
 }
 
+// V8 renamed neutering to detaching in 7.3, and only started exposing whether a
+// buffer was detached in 9.0. On older versions we can only tell whether a buffer
+// was detached through Neon, which we record in a private property.
+#define NEON_DETACHED_KEY "neon::detached"
+
+extern "C" bool Neon_ArrayBuffer_Detach(v8::Isolate *isolate, v8::Local<v8::ArrayBuffer> buffer) {
+#if NODE_MODULE_VERSION < 57 // Node 8
+  Nan::ThrowError("detaching an ArrayBuffer requires Node 8 or later");
+  return false;
+#else
+  Nan::HandleScope scope;
+#if V8_MAJOR_VERSION > 7 || (V8_MAJOR_VERSION == 7 && V8_MINOR_VERSION >= 3)
+  bool detachable = buffer->IsDetachable();
+#else
+  bool detachable = buffer->IsNeuterable();
+#endif
+  if (!detachable) {
+    Nan::ThrowTypeError("ArrayBuffer cannot be detached");
+    return false;
+  }
+  // If V8 still owns the backing store, take ownership of it so it can be freed
+  // once the buffer no longer refers to it.
+  bool owned = !buffer->IsExternal();
+  v8::ArrayBuffer::Contents contents = owned ? buffer->Externalize() : buffer->GetContents();
+#if V8_MAJOR_VERSION > 7 || (V8_MAJOR_VERSION == 7 && V8_MINOR_VERSION >= 3)
+  buffer->Detach();
+#else
+  buffer->Neuter();
+#endif
+  if (owned) {
+    isolate->GetArrayBufferAllocator()->Free(contents.Data(), contents.ByteLength());
+  }
+  return Nan::SetPrivate(buffer, Nan::New(NEON_DETACHED_KEY).ToLocalChecked(), Nan::True()).FromMaybe(false);
+#endif
+}
+
+extern "C" bool Neon_ArrayBuffer_IsDetached(v8::Local<v8::ArrayBuffer> buffer) {
+#if V8_MAJOR_VERSION >= 9
+  return buffer->WasDetached();
+#else
+  Nan::HandleScope scope;
+  v8::Local<v8::Value> detached;
+  return Nan::GetPrivate(buffer, Nan::New(NEON_DETACHED_KEY).ToLocalChecked()).ToLocal(&detached) && detached->IsTrue();
+#endif
+}
+
 extern "C" bool Neon_Tag_IsArrayBuffer(v8::Local<v8::Value> value) {
   return value->IsArrayBuffer();
 }
 
+// BigInt typed arrays first shipped in V8 6.7 (Node 10.4).
+#define NEON_HAS_BIGINT_ARRAYS (V8_MAJOR_VERSION > 6 || (V8_MAJOR_VERSION == 6 && V8_MINOR_VERSION >= 7))
+
+extern "C" bool Neon_Tag_IsBigInt64Array(v8::Local<v8::Value> value) {
+#if NEON_HAS_BIGINT_ARRAYS
+  return value->IsBigInt64Array();
+#else
+  return false;
+#endif
+}
+
+extern "C" bool Neon_Tag_IsBigUint64Array(v8::Local<v8::Value> value) {
+#if NEON_HAS_BIGINT_ARRAYS
+  return value->IsBigUint64Array();
+#else
+  return false;
+#endif
+}
+
+// The BigInt conversion API first shipped in V8 6.8.
+#define NEON_HAS_BIGINT (V8_MAJOR_VERSION > 6 || (V8_MAJOR_VERSION == 6 && V8_MINOR_VERSION >= 8))
+
+extern "C" bool Neon_Tag_IsBigInt(v8::Local<v8::Value> value) {
+#if NEON_HAS_BIGINT
+  return value->IsBigInt();
+#else
+  return false;
+#endif
+}
+
+extern "C" bool Neon_BigInt_ToInt64(int64_t *out, v8::Local<v8::Value> bigint) {
+#if NEON_HAS_BIGINT
+  bool lossless = false;
+  *out = bigint.As<v8::BigInt>()->Int64Value(&lossless);
+  return lossless;
+#else
+  return false;
+#endif
+}
+
+extern "C" bool Neon_BigInt_ToUint64(uint64_t *out, v8::Local<v8::Value> bigint) {
+#if NEON_HAS_BIGINT
+  bool lossless = false;
+  *out = bigint.As<v8::BigInt>()->Uint64Value(&lossless);
+  return lossless;
+#else
+  return false;
+#endif
+}
+
+extern "C" bool Neon_TypedArray_NewBigInt64Array(v8::Local<v8::Object> *out, v8::Isolate *isolate, uint32_t len) {
+#if NEON_HAS_BIGINT_ARRAYS
+  v8::Local<v8::ArrayBuffer> buffer = v8::ArrayBuffer::New(isolate, static_cast<size_t>(len) * sizeof(int64_t));
+  *out = v8::BigInt64Array::New(buffer, 0, len);
+  return true;
+#else
+  Nan::ThrowTypeError("BigInt64Array is not supported by this version of Node");
+  return false;
+#endif
+}
+
+extern "C" bool Neon_TypedArray_NewBigUint64Array(v8::Local<v8::Object> *out, v8::Isolate *isolate, uint32_t len) {
+#if NEON_HAS_BIGINT_ARRAYS
+  v8::Local<v8::ArrayBuffer> buffer = v8::ArrayBuffer::New(isolate, static_cast<size_t>(len) * sizeof(uint64_t));
+  *out = v8::BigUint64Array::New(buffer, 0, len);
+  return true;
+#else
+  Nan::ThrowTypeError("BigUint64Array is not supported by this version of Node");
+  return false;
+#endif
+}
+
+extern "C" void Neon_TypedArray_Data(buf_t *out, v8::Local<v8::TypedArray> array) {
+  v8::ArrayBuffer::Contents contents = array->Buffer()->GetContents();
+  out->data = static_cast<char *>(contents.Data()) + array->ByteOffset();
+  out->len = array->ByteLength();
+}
+
+extern "C" bool Neon_Date_New(v8::Local<v8::Date> *out, double time) {
+  Nan::MaybeLocal<v8::Date> maybe = Nan::New<v8::Date>(time);
+  return maybe.ToLocal(out);
+}
+
+extern "C" double Neon_Date_Value(v8::Local<v8::Date> date) {
+  return date->ValueOf();
+}
+
 extern "C" void Neon_Scope_Escape(v8::Local<v8::Value> *out, Nan::EscapableHandleScope *scope, v8::Local<v8::Value> value) {
   *out = scope->Escape(value);
 }
@@ -263,6 +522,26 @@ extern "C" void Neon_Scope_GetGlobal(v8::Isolate *isolate, v8::Local<v8::Value>
   *out = ctx->Global();
 }
 
+extern "C" void Neon_Scope_RunMicrotasks(v8::Isolate *isolate) {
+#if V8_MAJOR_VERSION > 7 || (V8_MAJOR_VERSION == 7 && V8_MINOR_VERSION >= 5)
+  isolate->PerformMicrotaskCheckpoint();
+#else
+  isolate->RunMicrotasks();
+#endif
+}
+
+extern "C" void Neon_Scope_EnqueueMicrotask(v8::Isolate *isolate, v8::Local<v8::Function> microtask) {
+  isolate->EnqueueMicrotask(microtask);
+}
+
+extern "C" size_t Neon_Scope_NumberOfHandles(v8::Isolate *isolate) {
+  return static_cast<size_t>(v8::HandleScope::NumberOfHandles(isolate));
+}
+
+extern "C" int32_t Neon_Scope_GetMicrotasksPolicy(v8::Isolate *isolate) {
+  return static_cast<int32_t>(isolate->GetMicrotasksPolicy());
+}
+
 extern "C" void Neon_Fun_ExecKernel(void *kernel, Neon_RootScopeCallback callback, v8::FunctionCallbackInfo<v8::Value> *info, void *scope) {
   Nan::HandleScope v8_scope;
   callback(info, kernel, scope);
@@ -277,6 +556,22 @@ extern "C" uint32_t Neon_Module_GetVersion() {
   return NODE_MODULE_VERSION;
 }
 
+static Neon_ModuleInitializer neon_module_initializer = NULL;
+
+extern "C" void Neon_Module_SetInitializer(Neon_ModuleInitializer init) {
+  neon_module_initializer = init;
+}
+
+#ifdef NODE_MODULE_INITIALIZER
+// The module's static constructor only runs the first time the library is loaded, so when
+// another context, such as a worker thread, loads it again Node finds this symbol instead.
+extern "C" NODE_MODULE_EXPORT void NODE_MODULE_INITIALIZER(v8::Local<v8::Object> exports, v8::Local<v8::Value> module, v8::Local<v8::Context> context) {
+  if (neon_module_initializer) {
+    neon_module_initializer(exports, module, context, NULL);
+  }
+}
+#endif
+
 extern "C" void Neon_Class_ConstructBaseCallback(const v8::FunctionCallbackInfo<v8::Value>& info) {
   Nan::HandleScope scope;
   v8::Local<v8::External> wrapper = v8::Local<v8::External>::Cast(info.Data());
@@ -307,7 +602,7 @@ extern "C" void *Neon_Class_CreateBase(v8::Isolate *isolate,
 }
 
 extern "C" void *Neon_Class_GetClassMap(v8::Isolate *isolate) {
-  neon::ClassMapHolder *holder = static_cast<neon::ClassMapHolder *>(isolate->GetData(NEON_ISOLATE_SLOT));
+  neon::ClassMapHolder *holder = neon::ClassMapHolder::ForContext(isolate->GetCurrentContext());
   return (holder == nullptr)
        ? nullptr
        : holder->GetMap();
@@ -320,9 +615,13 @@ void cleanup_class_map(void *arg) {
 
 extern "C" void Neon_Class_SetClassMap(v8::Isolate *isolate, void *map, Neon_DropCallback drop_map) {
   neon::ClassMapHolder *holder = new neon::ClassMapHolder(map, drop_map);
-  isolate->SetData(NEON_ISOLATE_SLOT, holder);
-  // ISSUE(#77): When workers land in node, this will need to be generalized to a per-worker version.
+  neon::ClassMapHolder::SetForContext(isolate->GetCurrentContext(), holder);
+#if NODE_MODULE_VERSION >= 67 // Node 11
+  // Clean up when this module instance's environment (e.g. a worker thread) exits.
+  node::AddEnvironmentCleanupHook(isolate, cleanup_class_map, holder);
+#else
   node::AtExit(cleanup_class_map, holder);
+#endif
 }
 
 extern "C" void *Neon_Class_GetCallKernel(v8::Local<v8::External> wrapper) {
@@ -392,6 +691,35 @@ extern "C" bool Neon_Class_AddMethod(v8::Isolate *isolate, void *metadata_pointe
   return true;
 }
 
+extern "C" bool Neon_Class_AddIterator(v8::Isolate *isolate, void *metadata_pointer, bool is_async, v8::Local<v8::FunctionTemplate> method) {
+  neon::ClassMetadata *metadata = static_cast<neon::ClassMetadata *>(metadata_pointer);
+  v8::Local<v8::FunctionTemplate> ft = metadata->GetTemplate(isolate);
+  v8::Local<v8::ObjectTemplate> pt = ft->PrototypeTemplate();
+  Nan::HandleScope scope;
+  v8::Local<v8::Symbol> key;
+  if (!is_async) {
+    key = v8::Symbol::GetIterator(isolate);
+  } else {
+    // Symbol.asyncIterator isn't exposed through the V8 API on every version of Node
+    // we support, so look it up on the global Symbol constructor instead.
+    v8::Local<v8::Value> symbol;
+    if (!Nan::Get(isolate->GetCurrentContext()->Global(), Nan::New("Symbol").ToLocalChecked()).ToLocal(&symbol)) {
+      return false;
+    }
+    v8::Local<v8::Value> async_iterator;
+    if (!symbol->IsObject() || !Nan::Get(symbol.As<v8::Object>(), Nan::New("asyncIterator").ToLocalChecked()).ToLocal(&async_iterator)) {
+      return false;
+    }
+    // Without Symbol.asyncIterator there's no `for await` either, so there's nothing to hook into.
+    if (!async_iterator->IsSymbol()) {
+      return true;
+    }
+    key = async_iterator.As<v8::Symbol>();
+  }
+  pt->Set(key, method);
+  return true;
+}
+
 extern "C" void Neon_Class_MetadataToClass(v8::Local<v8::FunctionTemplate> *out, v8::Isolate *isolate, void *metadata) {
   *out = static_cast<neon::ClassMetadata *>(metadata)->GetTemplate(isolate);
 }
@@ -420,6 +748,110 @@ extern "C" bool Neon_Fun_New(v8::Local<v8::Function> *out, v8::Isolate *isolate,
   return maybe_result.ToLocal(out);
 }
 
+extern "C" bool Neon_Fun_NewClosure(v8::Local<v8::Function> *out, v8::Isolate *isolate, v8::FunctionCallback callback, void *closure, Neon_DropCallback drop) {
+  if (!Neon_Fun_New(out, isolate, callback, closure)) {
+    return false;
+  }
+  new neon::Finalizer(isolate, *out, closure, drop);
+  return true;
+}
+
+extern "C" bool Neon_Fun_NewCached(v8::Local<v8::Function> *out, v8::Isolate *isolate, v8::FunctionCallback callback, void *kernel, void **cache) {
+  Nan::EscapableHandleScope scope;
+  v8::Local<v8::FunctionTemplate> tmpl;
+  if (*cache) {
+    tmpl = v8::Local<v8::FunctionTemplate>::New(isolate, *static_cast<v8::Persistent<v8::FunctionTemplate> *>(*cache));
+  } else {
+    v8::Local<v8::External> wrapper = v8::External::New(isolate, kernel);
+    if (wrapper.IsEmpty()) {
+      return false;
+    }
+    tmpl = v8::FunctionTemplate::New(isolate, callback, wrapper);
+    if (tmpl.IsEmpty()) {
+      return false;
+    }
+    *cache = new v8::Persistent<v8::FunctionTemplate>(isolate, tmpl);
+  }
+
+  v8::Local<v8::Function> result;
+  if (!tmpl->GetFunction(isolate->GetCurrentContext()).ToLocal(&result)) {
+    return false;
+  }
+  *out = scope.Escape(result);
+  return true;
+}
+
+extern "C" void Neon_Fun_SetName(v8::Local<v8::Function> fun, v8::Local<v8::String> name) {
+  fun->SetName(name);
+}
+
+extern "C" void Neon_Fun_GetName(v8::Local<v8::Value> *out, v8::Local<v8::Function> fun) {
+  *out = fun->GetName();
+}
+
+extern "C" bool Neon_Fun_GetScriptLocation(v8::Local<v8::Value> *resource, int32_t *line, int32_t *column, v8::Local<v8::Function> fun) {
+  int line_number = fun->GetScriptLineNumber();
+  if (line_number == v8::Function::kLineOffsetNotFound) {
+    return false;
+  }
+  *resource = fun->GetScriptOrigin().ResourceName();
+  *line = line_number;
+  *column = fun->GetScriptColumnNumber();
+  return true;
+}
+
+extern "C" void *Neon_Profiler_Start(v8::Isolate *isolate, v8::Local<v8::String> title) {
+#if NODE_MODULE_VERSION >= 57 // Node 8
+  v8::CpuProfiler *profiler = v8::CpuProfiler::New(isolate);
+  profiler->StartProfiling(title, true);
+  return profiler;
+#else
+  return nullptr;
+#endif
+}
+
+extern "C" bool Neon_Profiler_Stop(v8::Local<v8::Object> *out, void *profiler, v8::Local<v8::String> title) {
+#if NODE_MODULE_VERSION >= 57 // Node 8
+  Nan::EscapableHandleScope scope;
+  v8::CpuProfile *profile = static_cast<v8::CpuProfiler *>(profiler)->StopProfiling(title);
+  if (profile == nullptr) {
+    return false;
+  }
+  v8::Local<v8::Object> result = neon::profile_object(profile);
+  profile->Delete();
+  *out = scope.Escape(result);
+  return true;
+#else
+  return false;
+#endif
+}
+
+extern "C" void Neon_Profiler_Dispose(void *profiler) {
+#if NODE_MODULE_VERSION >= 57 // Node 8
+  static_cast<v8::CpuProfiler *>(profiler)->Dispose();
+#endif
+}
+
+extern "C" bool Neon_Profiler_WriteHeapSnapshot(v8::Isolate *isolate, const uint8_t *path, size_t len) {
+  std::string filename(reinterpret_cast<const char *>(path), len);
+  FILE *file = fopen(filename.c_str(), "w");
+  if (file == nullptr) {
+    return false;
+  }
+  const v8::HeapSnapshot *snapshot = isolate->GetHeapProfiler()->TakeHeapSnapshot();
+  neon::FileOutputStream stream(file);
+  snapshot->Serialize(&stream, v8::HeapSnapshot::kJSON);
+  const_cast<v8::HeapSnapshot *>(snapshot)->Delete();
+  bool written = !stream.failed();
+  return fclose(file) == 0 && written;
+}
+
+extern "C" void Neon_Fun_DropTemplate(void *cache) {
+  v8::Persistent<v8::FunctionTemplate> *p = static_cast<v8::Persistent<v8::FunctionTemplate> *>(cache);
+  p->Reset();
+  delete p;
+}
+
 extern "C" void *Neon_Fun_GetKernel(v8::Local<v8::External> data) {
   return data->Value();
 }
@@ -481,6 +913,10 @@ extern "C" bool Neon_Tag_IsArray(v8::Local<v8::Value> val) {
   return val->IsArray();
 }
 
+extern "C" bool Neon_Tag_IsDate(v8::Local<v8::Value> val) {
+  return val->IsDate();
+}
+
 extern "C" bool Neon_Tag_IsFunction(v8::Local<v8::Value> val) {
   return val->IsFunction();
 }
@@ -513,6 +949,30 @@ extern "C" void Neon_Error_NewSyntaxError(v8::Local<v8::Value> *out, v8::Local<v
   *out = v8::Exception::SyntaxError(msg);
 }
 
+extern "C" void Neon_Error_AddFrames(v8::Local<v8::Value> error, const uint8_t *frames, int32_t len) {
+  Nan::HandleScope scope;
+  if (!error->IsObject()) {
+    return;
+  }
+  v8::Local<v8::Object> object = error.As<v8::Object>();
+  v8::Local<v8::String> stack_key = Nan::New("stack").ToLocalChecked();
+  v8::Local<v8::Value> stack;
+  v8::Local<v8::String> header;
+  if (!Nan::Get(object, stack_key).ToLocal(&stack) || !stack->IsString() ||
+      !Nan::To<v8::String>(error).ToLocal(&header)) {
+    return;
+  }
+  // V8 formats the stack as the error's string conversion followed by one line per
+  // frame, so the new frames go right after that header.
+  std::string stack_str(*Nan::Utf8String(stack));
+  std::string header_str(*Nan::Utf8String(header));
+  if (stack_str.compare(0, header_str.size(), header_str) != 0) {
+    return;
+  }
+  std::string result = header_str + std::string(reinterpret_cast<const char *>(frames), len) + stack_str.substr(header_str.size());
+  Nan::Set(object, stack_key, Nan::New(result).ToLocalChecked());
+}
+
 extern "C" void Neon_Error_ThrowErrorFromCString(const char *msg) {
   Nan::ThrowError(msg);
 }
@@ -537,8 +997,121 @@ extern "C" bool Neon_Mem_SameHandle(v8::Local<v8::Value> v1, v8::Local<v8::Value
   return v1 == v2;
 }
 
-extern "C" void Neon_Task_Schedule(void *task, Neon_TaskPerformCallback perform, Neon_TaskCompleteCallback complete, v8::Local<v8::Function> callback) {
+extern "C" bool Neon_Mem_StrictEquals(v8::Local<v8::Value> v1, v8::Local<v8::Value> v2) {
+  return v1->StrictEquals(v2);
+}
+
+extern "C" bool Neon_Mem_SameValue(v8::Local<v8::Value> v1, v8::Local<v8::Value> v2) {
+  return v1->SameValue(v2);
+}
+
+extern "C" void Neon_Mem_TypeName(v8::Local<v8::String> *out, v8::Isolate *isolate, v8::Local<v8::Value> value) {
+  if (value->IsNull()) {
+    *out = Nan::New("null").ToLocalChecked();
+  } else if (value->IsObject()) {
+    *out = value.As<v8::Object>()->GetConstructorName();
+  } else {
+    *out = value->TypeOf(isolate);
+  }
+}
+
+extern "C" void *Neon_Mem_NewPersistent(v8::Isolate *isolate, v8::Local<v8::Value> value) {
+  return new v8::Persistent<v8::Value>(isolate, value);
+}
+
+extern "C" void Neon_Mem_ReadPersistent(v8::Local<v8::Value> *out, v8::Isolate *isolate, void *persistent) {
+  *out = v8::Local<v8::Value>::New(isolate, *static_cast<v8::Persistent<v8::Value> *>(persistent));
+}
+
+extern "C" void Neon_Mem_DropPersistent(void *persistent) {
+  v8::Persistent<v8::Value> *p = static_cast<v8::Persistent<v8::Value> *>(persistent);
+  p->Reset();
+  delete p;
+}
+
+extern "C" void *Neon_Mem_NewWeak(v8::Isolate *isolate, v8::Local<v8::Value> value, void *data, Neon_DropCallback callback, Neon_DropCallback drop) {
+  return new neon::WeakRef(isolate, value, data, callback, drop);
+}
+
+extern "C" bool Neon_Mem_ReadWeak(v8::Local<v8::Value> *out, void *weak) {
+  return static_cast<neon::WeakRef *>(weak)->Read(out);
+}
+
+extern "C" void Neon_Mem_DropWeak(void *weak) {
+  static_cast<neon::WeakRef *>(weak)->Release();
+}
+
+extern "C" int64_t Neon_Mem_AdjustExternalMemory(int64_t change) {
   v8::Isolate *isolate = v8::Isolate::GetCurrent();
-  neon::Task *internal_task = new neon::Task(isolate, task, perform, complete, callback);
+  if (isolate == nullptr) {
+    return 0;
+  }
+  return isolate->AdjustAmountOfExternalAllocatedMemory(change);
+}
+
+extern "C" void *Neon_Gc_AddCallbacks(v8::Isolate *isolate, void *data, Neon_GcCallback callback) {
+#if NODE_MODULE_VERSION >= 64 // Node 10
+  return new neon::GcCallbacks(isolate, data, callback);
+#else
+  return nullptr;
+#endif
+}
+
+extern "C" void Neon_Gc_RemoveCallbacks(void *callbacks) {
+#if NODE_MODULE_VERSION >= 64 // Node 10
+  delete static_cast<neon::GcCallbacks *>(callbacks);
+#endif
+}
+
+extern "C" void Neon_Task_Schedule(void *task, const char *name, size_t name_len, Neon_TaskPerformCallback perform, Neon_TaskCompleteCallback complete, v8::Local<v8::Function> callback) {
+  v8::Isolate *isolate = v8::Isolate::GetCurrent();
+  neon::Task *internal_task = new neon::Task(isolate, std::string(name, name_len), task, perform, complete, callback);
   neon::queue_task(internal_task);
 }
+
+extern "C" void Neon_Task_RunNow(void *task, const char *name, size_t name_len, Neon_TaskPerformCallback perform, Neon_TaskCompleteCallback complete, v8::Local<v8::Function> callback) {
+  v8::Isolate *isolate = v8::Isolate::GetCurrent();
+  neon::Task internal_task(isolate, std::string(name, name_len), task, perform, complete, callback);
+  internal_task.execute();
+  internal_task.complete();
+}
+
+extern "C" void *Neon_Async_New(v8::Isolate *isolate, const char *name, size_t name_len, void *data, Neon_AsyncCallback callback, Neon_DropCallback drop) {
+  return new neon::AsyncSignal(isolate, std::string(name, name_len), data, callback, drop);
+}
+
+extern "C" void Neon_Async_Send(void *signal) {
+  static_cast<neon::AsyncSignal *>(signal)->send();
+}
+
+extern "C" void Neon_Async_Retain(void *signal) {
+  static_cast<neon::AsyncSignal *>(signal)->retain();
+}
+
+extern "C" void Neon_Async_Release(void *signal) {
+  static_cast<neon::AsyncSignal *>(signal)->release();
+}
+
+extern "C" void Neon_Async_Unref(void *signal) {
+  static_cast<neon::AsyncSignal *>(signal)->unref();
+}
+
+extern "C" bool Neon_Fun_NewOnce(v8::Local<v8::Function> *out, v8::Isolate *isolate, void *data, Neon_AsyncCallback callback, Neon_DropCallback drop) {
+  return neon::OnceFunction::New(out, isolate, data, callback, drop);
+}
+
+extern "C" void *Neon_Timer_New(v8::Isolate *isolate, const char *name, size_t name_len, void *data, Neon_AsyncCallback callback, Neon_DropCallback drop, uint64_t timeout, uint64_t repeat) {
+  return new neon::Timer(isolate, std::string(name, name_len), data, callback, drop, timeout, repeat);
+}
+
+extern "C" void Neon_Timer_Stop(void *timer) {
+  static_cast<neon::Timer *>(timer)->stop();
+}
+
+extern "C" void Neon_Timer_Close(void *timer) {
+  static_cast<neon::Timer *>(timer)->close();
+}
+
+extern "C" void Neon_Timer_Detach(void *timer) {
+  static_cast<neon::Timer *>(timer)->detach();
+}
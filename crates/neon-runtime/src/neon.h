@@ -36,6 +36,7 @@ extern "C" {
   void Neon_Call_Data(v8::FunctionCallbackInfo<v8::Value> *info, v8::Local<v8::Value> *out);
   int32_t Neon_Call_Length(v8::FunctionCallbackInfo<v8::Value> *info);
   void Neon_Call_Get(v8::FunctionCallbackInfo<v8::Value> *info, int32_t i, v8::Local<v8::Value> *out);
+  void Neon_Call_GetAll(v8::FunctionCallbackInfo<v8::Value> *info, v8::Local<v8::Value> *out, int32_t len);
 
   // DEPRECATE(0.2)
   void Neon_Primitive_Integer(v8::Local<v8::Integer> *out, v8::Isolate *isolate, int32_t x);
@@ -55,29 +56,58 @@ extern "C" {
   bool Neon_Object_Set_Index(bool *out, v8::Local<v8::Object> object, uint32_t index, v8::Local<v8::Value> val);
   bool Neon_Object_Get_String(v8::Local<v8::Value> *out, v8::Local<v8::Object> object, const uint8_t *key, int32_t len);
   bool Neon_Object_Set_String(bool *out, v8::Local<v8::Object> object, const uint8_t *key, int32_t len, v8::Local<v8::Value> val);
+  bool Neon_Object_GetHidden(v8::Local<v8::Value> *out, v8::Local<v8::Object> object, const uint8_t *key, int32_t len);
+  bool Neon_Object_SetHidden(bool *out, v8::Local<v8::Object> object, const uint8_t *key, int32_t len, v8::Local<v8::Value> val);
   bool Neon_Object_Get(v8::Local<v8::Value> *out, v8::Local<v8::Object> object, v8::Local<v8::Value> key);
   bool Neon_Object_Set(bool *out, v8::Local<v8::Object> obj, v8::Local<v8::Value> key, v8::Local<v8::Value> val);
 
   void Neon_Array_New(v8::Local<v8::Array> *out, v8::Isolate *isolate, uint32_t length);
+  bool Neon_Array_FromValues(v8::Local<v8::Array> *out, v8::Isolate *isolate, v8::Local<v8::Value> *elements, uint32_t len);
+  bool Neon_Array_FromNumbers(v8::Local<v8::Array> *out, v8::Isolate *isolate, const double *elements, uint32_t len);
   uint32_t Neon_Array_Length(v8::Local<v8::Array> array);
 
   bool Neon_String_New(v8::Local<v8::String> *out, v8::Isolate *isolate, const uint8_t *data, int32_t len);
+  bool Neon_String_NewInternalized(v8::Local<v8::String> *out, v8::Isolate *isolate, const uint8_t *data, int32_t len);
   int32_t Neon_String_Utf8Length(v8::Local<v8::String> str);
   size_t Neon_String_Data(char *out, size_t len, v8::Local<v8::Value> str);
+  bool Neon_String_NewFromUtf16(v8::Local<v8::String> *out, v8::Isolate *isolate, const uint16_t *data, int32_t len);
+  bool Neon_String_NewFromLatin1(v8::Local<v8::String> *out, v8::Isolate *isolate, const uint8_t *data, int32_t len);
+  int32_t Neon_String_Length(v8::Local<v8::String> str);
+  int32_t Neon_String_WriteUtf16(v8::Local<v8::String> str, uint16_t *out, int32_t len);
+  int32_t Neon_String_WriteLatin1(v8::Local<v8::String> str, uint8_t *out, int32_t len);
+  int32_t Neon_String_WriteUtf8(v8::Local<v8::String> str, char *out, int32_t len);
 
   bool Neon_Convert_ToString(v8::Local<v8::String> *out, v8::Local<v8::Value> value);
+  bool Neon_Convert_ToNumber(v8::Local<v8::Number> *out, v8::Local<v8::Value> value);
+  void Neon_Convert_ToBoolean(v8::Local<v8::Boolean> *out, v8::Local<v8::Value> value);
   bool Neon_Convert_ToObject(v8::Local<v8::Object> *out, v8::Local<v8::Value> *value);
 
   bool Neon_Buffer_New(v8::Local<v8::Object> *out, uint32_t size);
+  bool Neon_Buffer_Uninitialized(v8::Local<v8::Object> *out, uint32_t size);
+  bool Neon_Buffer_FromSlice(v8::Local<v8::Object> *out, const char *data, uint32_t size);
+  bool Neon_Buffer_External(v8::Local<v8::Object> *out, char *data, uint32_t size, Nan::FreeCallback free, void *hint);
   void Neon_Buffer_Data(buf_t *out, v8::Local<v8::Object> obj);
 
   bool Neon_ArrayBuffer_New(v8::Local<v8::ArrayBuffer> *out, v8::Isolate *isolate, uint32_t size);
   void Neon_ArrayBuffer_Data(buf_t *out, v8::Local<v8::ArrayBuffer> buffer);
+  bool Neon_ArrayBuffer_Detach(v8::Isolate *isolate, v8::Local<v8::ArrayBuffer> buffer);
+  bool Neon_ArrayBuffer_IsDetached(v8::Local<v8::ArrayBuffer> buffer);
+
+  bool Neon_TypedArray_NewBigInt64Array(v8::Local<v8::Object> *out, v8::Isolate *isolate, uint32_t len);
+  bool Neon_TypedArray_NewBigUint64Array(v8::Local<v8::Object> *out, v8::Isolate *isolate, uint32_t len);
+  void Neon_TypedArray_Data(buf_t *out, v8::Local<v8::TypedArray> array);
+
+  bool Neon_Date_New(v8::Local<v8::Date> *out, double time);
+  double Neon_Date_Value(v8::Local<v8::Date> date);
+
+  bool Neon_BigInt_ToInt64(int64_t *out, v8::Local<v8::Value> bigint);
+  bool Neon_BigInt_ToUint64(uint64_t *out, v8::Local<v8::Value> bigint);
 
   typedef void(*Neon_ChainedScopeCallback)(void *, void *, void *, void *);
   typedef void(*Neon_NestedScopeCallback)(void *, void *, void *);
   typedef void(*Neon_RootScopeCallback)(void *, void *, void *);
   typedef void(*Neon_ModuleScopeCallback)(void *, v8::Local<v8::Object>, void *);
+  typedef void(*Neon_ModuleInitializer)(v8::Local<v8::Object>, v8::Local<v8::Value>, v8::Local<v8::Context>, void *);
 
   void Neon_Scope_Escape(v8::Local<v8::Value> *out, Nan::EscapableHandleScope *scope, v8::Local<v8::Value> value);
   void Neon_Scope_Nested(void *out, void *closure, Neon_NestedScopeCallback callback, void *realm);
@@ -89,8 +119,25 @@ extern "C" {
   size_t Neon_Scope_SizeofEscapable();
   size_t Neon_Scope_AlignofEscapable();
   void Neon_Scope_GetGlobal(v8::Isolate *isolate, v8::Local<v8::Value> *out);
+  void Neon_Scope_RunMicrotasks(v8::Isolate *isolate);
+  int32_t Neon_Scope_GetMicrotasksPolicy(v8::Isolate *isolate);
+  void Neon_Scope_EnqueueMicrotask(v8::Isolate *isolate, v8::Local<v8::Function> microtask);
+  size_t Neon_Scope_NumberOfHandles(v8::Isolate *isolate);
+
+  typedef void (*Neon_DropCallback)(void *);
 
   bool Neon_Fun_New(v8::Local<v8::Function> *out, v8::Isolate *isolate, v8::FunctionCallback callback, void *kernel);
+  bool Neon_Fun_NewClosure(v8::Local<v8::Function> *out, v8::Isolate *isolate, v8::FunctionCallback callback, void *closure, Neon_DropCallback drop);
+  bool Neon_Fun_NewCached(v8::Local<v8::Function> *out, v8::Isolate *isolate, v8::FunctionCallback callback, void *kernel, void **cache);
+  void Neon_Fun_DropTemplate(void *cache);
+  void Neon_Fun_SetName(v8::Local<v8::Function> fun, v8::Local<v8::String> name);
+  void Neon_Fun_GetName(v8::Local<v8::Value> *out, v8::Local<v8::Function> fun);
+  bool Neon_Fun_GetScriptLocation(v8::Local<v8::Value> *resource, int32_t *line, int32_t *column, v8::Local<v8::Function> fun);
+
+  void *Neon_Profiler_Start(v8::Isolate *isolate, v8::Local<v8::String> title);
+  bool Neon_Profiler_Stop(v8::Local<v8::Object> *out, void *profiler, v8::Local<v8::String> title);
+  void Neon_Profiler_Dispose(void *profiler);
+  bool Neon_Profiler_WriteHeapSnapshot(v8::Isolate *isolate, const uint8_t *path, size_t len);
   void Neon_Fun_ExecKernel(void *kernel, Neon_RootScopeCallback callback, v8::FunctionCallbackInfo<v8::Value> *info, void *scope);
   void *Neon_Fun_GetKernel(v8::Local<v8::External> obj);
   bool Neon_Fun_Call(v8::Local<v8::Value> *out, v8::Isolate *isolate, v8::Local<v8::Function> fun, v8::Local<v8::Value> self, int32_t argc, v8::Local<v8::Value> argv[]);
@@ -102,8 +149,6 @@ extern "C" {
   void Neon_Class_ForConstructor(v8::FunctionCallbackInfo<v8::Value> *info, v8::Local<v8::FunctionTemplate> *out);
   void Neon_Class_ForMethod(v8::FunctionCallbackInfo<v8::Value> *info, v8::Local<v8::FunctionTemplate> *out);
 
-  typedef void (*Neon_DropCallback)(void *);
-
   void *Neon_Class_GetClassMap(v8::Isolate *isolate);
   void Neon_Class_SetClassMap(v8::Isolate *isolate, void *map, Neon_DropCallback free_map);
   void *Neon_Class_CreateBase(v8::Isolate *isolate,
@@ -123,10 +168,12 @@ extern "C" {
   bool Neon_Class_SetName(v8::Isolate *isolate, void *metadata, const char *name, uint32_t byte_length);
   void Neon_Class_ThrowThisError(v8::Isolate *isolate, void *metadata_pointer);
   bool Neon_Class_AddMethod(v8::Isolate *isolate, void *metadata, const char *name, uint32_t byte_length, v8::Local<v8::FunctionTemplate> method);
+  bool Neon_Class_AddIterator(v8::Isolate *isolate, void *metadata, bool is_async, v8::Local<v8::FunctionTemplate> method);
   void Neon_Class_MetadataToClass(v8::Local<v8::FunctionTemplate> *out, v8::Isolate *isolate, void *metadata);
   void *Neon_Class_GetInstanceInternals(v8::Local<v8::Object> obj);
 
   void Neon_Module_ExecKernel(void *kernel, Neon_ModuleScopeCallback callback, v8::Local<v8::Object> exports, void *scope);
+  void Neon_Module_SetInitializer(Neon_ModuleInitializer init);
 
   tag_t Neon_Tag_Of(v8::Local<v8::Value> val);
   bool Neon_Tag_IsUndefined(v8::Local<v8::Value> val);
@@ -138,9 +185,13 @@ extern "C" {
   bool Neon_Tag_IsString(v8::Local<v8::Value> val);
   bool Neon_Tag_IsObject(v8::Local<v8::Value> val);
   bool Neon_Tag_IsArray(v8::Local<v8::Value> val);
+  bool Neon_Tag_IsDate(v8::Local<v8::Value> val);
   bool Neon_Tag_IsFunction(v8::Local<v8::Value> val);
   bool Neon_Tag_IsBuffer(v8::Local<v8::Value> obj);
   bool Neon_Tag_IsArrayBuffer(v8::Local<v8::Value> obj);
+  bool Neon_Tag_IsBigInt64Array(v8::Local<v8::Value> obj);
+  bool Neon_Tag_IsBigUint64Array(v8::Local<v8::Value> obj);
+  bool Neon_Tag_IsBigInt(v8::Local<v8::Value> val);
   bool Neon_Tag_IsError(v8::Local<v8::Value> val);
 
   void Neon_Error_NewError(v8::Local<v8::Value> *out, v8::Local<v8::String> msg);
@@ -149,6 +200,7 @@ extern "C" {
   void Neon_Error_NewRangeError(v8::Local<v8::Value> *out, v8::Local<v8::String> msg);
   void Neon_Error_NewSyntaxError(v8::Local<v8::Value> *out, v8::Local<v8::String> msg);
   void Neon_Error_Throw(v8::Local<v8::Value> val);
+  void Neon_Error_AddFrames(v8::Local<v8::Value> error, const uint8_t *frames, int32_t len);
   void Neon_Error_ThrowErrorFromCString(const char *msg);
   void Neon_Error_ThrowTypeErrorFromCString(const char *msg);
   void Neon_Error_ThrowReferenceErrorFromCString(const char *msg);
@@ -156,11 +208,41 @@ extern "C" {
   void Neon_Error_ThrowSyntaxErrorFromCString(const char *msg);
 
   bool Neon_Mem_SameHandle(v8::Local<v8::Value> v1, v8::Local<v8::Value> v2);
+  bool Neon_Mem_StrictEquals(v8::Local<v8::Value> v1, v8::Local<v8::Value> v2);
+  bool Neon_Mem_SameValue(v8::Local<v8::Value> v1, v8::Local<v8::Value> v2);
+  void Neon_Mem_TypeName(v8::Local<v8::String> *out, v8::Isolate *isolate, v8::Local<v8::Value> value);
+  void *Neon_Mem_NewPersistent(v8::Isolate *isolate, v8::Local<v8::Value> value);
+  void Neon_Mem_ReadPersistent(v8::Local<v8::Value> *out, v8::Isolate *isolate, void *persistent);
+  void Neon_Mem_DropPersistent(void *persistent);
+  void *Neon_Mem_NewWeak(v8::Isolate *isolate, v8::Local<v8::Value> value, void *data, Neon_DropCallback callback, Neon_DropCallback drop);
+  bool Neon_Mem_ReadWeak(v8::Local<v8::Value> *out, void *weak);
+  void Neon_Mem_DropWeak(void *weak);
+  int64_t Neon_Mem_AdjustExternalMemory(int64_t change);
+
+  typedef void (*Neon_GcCallback)(void *data, bool epilogue, uint32_t type);
+
+  void *Neon_Gc_AddCallbacks(v8::Isolate *isolate, void *data, Neon_GcCallback callback);
+  void Neon_Gc_RemoveCallbacks(void *callbacks);
 
   typedef void* (*Neon_TaskPerformCallback)(void *);
-  typedef void (*Neon_TaskCompleteCallback)(void *, void *, v8::Local<v8::Value> *out);
+  typedef void (*Neon_TaskCompleteCallback)(v8::Isolate *isolate, void *, void *, v8::Local<v8::Value> *out);
+
+  void Neon_Task_Schedule(void *task, const char *name, size_t name_len, Neon_TaskPerformCallback perform, Neon_TaskCompleteCallback complete, v8::Local<v8::Function> callback);
+  void Neon_Task_RunNow(void *task, const char *name, size_t name_len, Neon_TaskPerformCallback perform, Neon_TaskCompleteCallback complete, v8::Local<v8::Function> callback);
+
+  typedef void (*Neon_AsyncCallback)(v8::Isolate *isolate, void *data);
+
+  void *Neon_Async_New(v8::Isolate *isolate, const char *name, size_t name_len, void *data, Neon_AsyncCallback callback, Neon_DropCallback drop);
+  void Neon_Async_Send(void *signal);
+  void Neon_Async_Retain(void *signal);
+  void Neon_Async_Release(void *signal);
+  void Neon_Async_Unref(void *signal);
+  bool Neon_Fun_NewOnce(v8::Local<v8::Function> *out, v8::Isolate *isolate, void *data, Neon_AsyncCallback callback, Neon_DropCallback drop);
 
-  void Neon_Task_Schedule(void *task, Neon_TaskPerformCallback perform, Neon_TaskCompleteCallback complete, v8::Local<v8::Function> callback);
+  void *Neon_Timer_New(v8::Isolate *isolate, const char *name, size_t name_len, void *data, Neon_AsyncCallback callback, Neon_DropCallback drop, uint64_t timeout, uint64_t repeat);
+  void Neon_Timer_Stop(void *timer);
+  void Neon_Timer_Close(void *timer);
+  void Neon_Timer_Detach(void *timer);
 }
 
 #endif
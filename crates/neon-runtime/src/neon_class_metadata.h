@@ -4,32 +4,28 @@
 #include <stdio.h>
 #include <stdint.h>
 #include <cstring>
+#include <unordered_set>
+#include <nan.h>
 #include "v8.h"
 #include "neon.h"
 #include "neon_string.h"
 
-// Currently, Node only ever has one isolate so we could get away with storing
-// Neon metadata in a global variable. But when workers land in Node, they will
-// each have a separate isolate.
+// A single isolate can host the same addon several times over: once per
+// vm context, and once per module instance for context-aware modules loaded in
+// worker threads. Class metadata is therefore stored per context rather than
+// per isolate, so that classes registered in one instance of the module never
+// collide with another.
 //
-// See: https://github.com/nodejs/node/pull/2133
-//
-// So instead we have to store per-isolate metadata in one of the isolate's
-// extensible data slots.
-//
-// Slots 0 and 1 appear to be reserved by Chrome, and slot 3 is reserved by Node.
-// That apparently leaves only slot 2 available for storing Neon metadata.
-//
-// See: https://github.com/nodejs/node/blob/master/src/env.h#L33-L39
-//
-// If this causes clashes with some other consumer of V8 in the future, we try
-// proposing to Node to make node::Environment extensible instead.
+// The ClassMapHolder for a context lives in a private property of the context's
+// global object, where it can't be observed or tampered with from JavaScript.
 
-#define NEON_ISOLATE_SLOT 2
+#define NEON_CLASS_MAP_KEY "neon::ClassMap"
 
 
 namespace neon {
 
+class BaseClassInstanceMetadata;
+
 class ClassMapHolder {
 public:
   ClassMapHolder(void *map, Neon_DropCallback drop_map)
@@ -37,18 +33,44 @@ public:
   {
   }
 
-  ~ClassMapHolder() {
-    drop_map_(map_);
-    map_ = nullptr;
+  // Defined below, since it needs the full definition of BaseClassInstanceMetadata.
+  ~ClassMapHolder();
+
+  // Returns the holder for the given context, or nullptr if no classes have
+  // been created in that context yet.
+  static ClassMapHolder *ForContext(v8::Local<v8::Context> context) {
+    Nan::HandleScope scope;
+    v8::Local<v8::Value> value;
+    if (!Nan::GetPrivate(context->Global(), Nan::New(NEON_CLASS_MAP_KEY).ToLocalChecked()).ToLocal(&value) || !value->IsExternal()) {
+      return nullptr;
+    }
+    return static_cast<ClassMapHolder *>(value.As<v8::External>()->Value());
+  }
+
+  static bool SetForContext(v8::Local<v8::Context> context, ClassMapHolder *holder) {
+    Nan::HandleScope scope;
+    return Nan::SetPrivate(context->Global(), Nan::New(NEON_CLASS_MAP_KEY).ToLocalChecked(), Nan::New<v8::External>(holder)).FromMaybe(false);
   }
 
   void *GetMap() {
     return map_;
   }
 
+  // The holder keeps track of every live class instance so that any instances
+  // that were never collected by the GC still get their internals dropped when
+  // the module instance is torn down.
+  void AddInstance(BaseClassInstanceMetadata *instance) {
+    instances_.insert(instance);
+  }
+
+  void RemoveInstance(BaseClassInstanceMetadata *instance) {
+    instances_.erase(instance);
+  }
+
 private:
   void *map_;
   Neon_DropCallback drop_map_;
+  std::unordered_set<BaseClassInstanceMetadata *> instances_;
 };
 
 
@@ -142,14 +164,29 @@ public:
     instance_.SetWeak(this, FinalizeInstance, v8::WeakCallbackType::kParameter);
     internals_ = internals;
     drop_ = drop;
+    holder_ = ClassMapHolder::ForContext(instance->CreationContext());
+    if (holder_) {
+      holder_->AddInstance(this);
+    }
   }
 
   void *GetInternals() {
     return internals_;
   }
 
+  // Drops the internals of an instance that is still alive when the module
+  // instance is being torn down. The holder is already being destroyed, so there is
+  // no need to unregister from it.
+  void Sweep() {
+    holder_ = nullptr;
+    delete this;
+  }
+
 protected:
   ~BaseClassInstanceMetadata() {
+    if (holder_) {
+      holder_->RemoveInstance(this);
+    }
     instance_.Reset();
     drop_(internals_);
     internals_ = nullptr;
@@ -157,7 +194,21 @@ protected:
 
 private:
 
+  // The first pass of a weak callback may only reset the handle, so the
+  // internals are dropped in the second pass, where it's safe for the Rust
+  // destructor to run arbitrary code. The instance leaves the holder right away,
+  // so that a module instance torn down in between doesn't sweep it as well.
   static void FinalizeInstance(const v8::WeakCallbackInfo<BaseClassInstanceMetadata>& data) {
+    BaseClassInstanceMetadata *metadata = data.GetParameter();
+    metadata->instance_.Reset();
+    if (metadata->holder_) {
+      metadata->holder_->RemoveInstance(metadata);
+      metadata->holder_ = nullptr;
+    }
+    data.SetSecondPassCallback(DropInstance);
+  }
+
+  static void DropInstance(const v8::WeakCallbackInfo<BaseClassInstanceMetadata>& data) {
     BaseClassInstanceMetadata *metadata = data.GetParameter();
     delete metadata;
   }
@@ -165,9 +216,22 @@ private:
   void *internals_;
   v8::Global<v8::Object> instance_;
   Neon_DropCallback drop_;
+  ClassMapHolder *holder_;
 };
 
 
+inline ClassMapHolder::~ClassMapHolder() {
+  // Each instance detaches itself from the holder before being deleted, so
+  // sweeping doesn't invalidate the iteration.
+  for (BaseClassInstanceMetadata *instance : instances_) {
+    instance->Sweep();
+  }
+  instances_.clear();
+  drop_map_(map_);
+  map_ = nullptr;
+}
+
+
 class BaseClassMetadata: public ClassMetadata {
 public:
 
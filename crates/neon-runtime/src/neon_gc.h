@@ -0,0 +1,45 @@
+#ifndef NEON_GC_H_
+#define NEON_GC_H_
+
+#include "neon.h"
+#include "v8.h"
+
+namespace neon {
+
+#if NODE_MODULE_VERSION >= 64 // Node 10
+// Registers a Rust callback with `data` to run before and after every garbage
+// collection, until this object is deleted.
+class GcCallbacks {
+public:
+  GcCallbacks(v8::Isolate *isolate, void *data, Neon_GcCallback callback)
+    : isolate_(isolate), data_(data), callback_(callback)
+  {
+    isolate->AddGCPrologueCallback(prologue, this);
+    isolate->AddGCEpilogueCallback(epilogue, this);
+  }
+
+  ~GcCallbacks() {
+    isolate_->RemoveGCPrologueCallback(prologue, this);
+    isolate_->RemoveGCEpilogueCallback(epilogue, this);
+  }
+
+private:
+  static void prologue(v8::Isolate *isolate, v8::GCType type, v8::GCCallbackFlags flags, void *data) {
+    GcCallbacks *self = static_cast<GcCallbacks *>(data);
+    self->callback_(self->data_, false, static_cast<uint32_t>(type));
+  }
+
+  static void epilogue(v8::Isolate *isolate, v8::GCType type, v8::GCCallbackFlags flags, void *data) {
+    GcCallbacks *self = static_cast<GcCallbacks *>(data);
+    self->callback_(self->data_, true, static_cast<uint32_t>(type));
+  }
+
+  v8::Isolate *isolate_;
+  void *data_;
+  Neon_GcCallback callback_;
+};
+#endif
+
+}
+
+#endif
@@ -0,0 +1,65 @@
+#ifndef NEON_PROFILER_H_
+#define NEON_PROFILER_H_
+
+#include <nan.h>
+#include <stdio.h>
+#include <v8-profiler.h>
+
+namespace neon {
+
+// Converts a node of a CPU profile's call tree, and all of its descendants, into the
+// same shape of object that the inspector's Profiler domain uses for nodes.
+v8::Local<v8::Object> profile_node(const v8::CpuProfileNode *node) {
+  Nan::EscapableHandleScope scope;
+  v8::Local<v8::Object> result = Nan::New<v8::Object>();
+  Nan::Set(result, Nan::New("functionName").ToLocalChecked(), node->GetFunctionName());
+  Nan::Set(result, Nan::New("url").ToLocalChecked(), node->GetScriptResourceName());
+  Nan::Set(result, Nan::New("lineNumber").ToLocalChecked(), Nan::New(node->GetLineNumber()));
+  Nan::Set(result, Nan::New("columnNumber").ToLocalChecked(), Nan::New(node->GetColumnNumber()));
+  Nan::Set(result, Nan::New("hitCount").ToLocalChecked(), Nan::New(node->GetHitCount()));
+
+  int count = node->GetChildrenCount();
+  v8::Local<v8::Array> children = Nan::New<v8::Array>(count);
+  for (int i = 0; i < count; i++) {
+    Nan::Set(children, i, profile_node(node->GetChild(i)));
+  }
+  Nan::Set(result, Nan::New("children").ToLocalChecked(), children);
+  return scope.Escape(result);
+}
+
+v8::Local<v8::Object> profile_object(const v8::CpuProfile *profile) {
+  Nan::EscapableHandleScope scope;
+  v8::Local<v8::Object> result = Nan::New<v8::Object>();
+  Nan::Set(result, Nan::New("title").ToLocalChecked(), profile->GetTitle());
+  // V8 reports times in microseconds.
+  Nan::Set(result, Nan::New("startTime").ToLocalChecked(), Nan::New<v8::Number>(profile->GetStartTime() / 1000.0));
+  Nan::Set(result, Nan::New("endTime").ToLocalChecked(), Nan::New<v8::Number>(profile->GetEndTime() / 1000.0));
+  Nan::Set(result, Nan::New("root").ToLocalChecked(), profile_node(profile->GetTopDownRoot()));
+  return scope.Escape(result);
+}
+
+// Writes a serialized heap snapshot to a file as V8 produces it, chunk by chunk.
+class FileOutputStream : public v8::OutputStream {
+public:
+  explicit FileOutputStream(FILE *file) : file_(file), failed_(false) { }
+
+  void EndOfStream() override { }
+
+  WriteResult WriteAsciiChunk(char *data, int size) override {
+    if (fwrite(data, 1, size, file_) != static_cast<size_t>(size)) {
+      failed_ = true;
+      return kAbort;
+    }
+    return kContinue;
+  }
+
+  bool failed() const { return failed_; }
+
+private:
+  FILE *file_;
+  bool failed_;
+};
+
+}
+
+#endif
@@ -1,20 +1,123 @@
 #ifndef NEON_TASK_H_
 #define NEON_TASK_H_
 
+#include <atomic>
+#include <string>
 #include <uv.h>
 #include "neon.h"
 #include "v8.h"
 
 namespace neon {
 
+// Each worker thread runs its own event loop, so work has to complete on the loop
+// of the thread that started it rather than on the main thread's default loop.
+uv_loop_t *current_loop(v8::Isolate *isolate) {
+#if NODE_MODULE_VERSION >= 59 // Node 9.3
+  uv_loop_t *loop = node::GetCurrentEventLoop(isolate);
+  if (loop != nullptr) {
+    return loop;
+  }
+#endif
+  return uv_default_loop();
+}
+
+// The async_hooks identity of native work that calls back into JavaScript later, such as
+// a task or a timer. Entering it with a `Scope` restores the context (aka realm) the work
+// was started in along with its async context, so that AsyncLocalStorage and tracing
+// tools see the callback as a continuation of the code that started the work.
+class AsyncResource {
+public:
+  AsyncResource(v8::Isolate *isolate, const std::string &name)
+    : isolate_(isolate)
+  {
+    v8::HandleScope handle_scope(isolate);
+    v8::Local<v8::Object> resource = v8::Object::New(isolate);
+    context_.Reset(isolate, isolate->GetCurrentContext());
+    resource_.Reset(isolate, resource);
+#if NODE_MODULE_VERSION >= 57 // Node 8
+    async_context_ = node::EmitAsyncInit(isolate, resource, name.c_str());
+#endif
+  }
+
+  ~AsyncResource() {
+#if NODE_MODULE_VERSION >= 57 // Node 8
+    v8::Isolate::Scope isolate_scope(isolate_);
+    v8::HandleScope handle_scope(isolate_);
+    v8::Context::Scope context_scope(v8::Local<v8::Context>::New(isolate_, context_));
+    node::EmitAsyncDestroy(isolate_, async_context_);
+#endif
+    resource_.Reset();
+    context_.Reset();
+  }
+
+  // Installs every scope needed to call into JavaScript on behalf of the resource.
+  // Once the outermost scope exits, Node drains the nextTick and microtask queues
+  // and reports any exception left behind as uncaught.
+  class Scope {
+  public:
+    Scope(AsyncResource *async)
+      : isolate_scope_(async->isolate_),
+        handle_scope_(async->isolate_),
+        context_(v8::Local<v8::Context>::New(async->isolate_, async->context_)),
+        context_scope_(context_)
+#if NODE_MAJOR_VERSION >= 10
+        , callback_scope_(async->isolate_,
+                          v8::Local<v8::Object>::New(async->isolate_, async->resource_),
+                          async->async_context_)
+#endif
+    {
+    }
+
+    v8::Local<v8::Context> context() {
+      return context_;
+    }
+
+  private:
+    v8::Isolate::Scope isolate_scope_;
+    v8::HandleScope handle_scope_;
+    v8::Local<v8::Context> context_;
+    v8::Context::Scope context_scope_;
+#if NODE_MAJOR_VERSION >= 10
+    node::CallbackScope callback_scope_;
+#endif
+  };
+
+  // Calls a function on behalf of the resource from within its `Scope`. On Node versions
+  // without node::CallbackScope, node::MakeCallback enters the async context instead.
+  void MakeCallback(v8::Local<v8::Context> context, v8::Local<v8::Function> callback, int argc, v8::Local<v8::Value> *argv) {
+#if NODE_MAJOR_VERSION >= 10
+    v8::TryCatch trycatch(isolate_);
+    v8::MaybeLocal<v8::Value> result = callback->Call(context, context->Global(), argc, argv);
+    if (result.IsEmpty() && trycatch.HasCaught()) {
+      Nan::FatalException(trycatch);
+    }
+#elif NODE_MODULE_VERSION >= 57 // Node 8
+    node::MakeCallback(isolate_, context->Global(), callback, argc, argv, async_context_);
+#else
+    node::MakeCallback(isolate_, context->Global(), callback, argc, argv);
+#endif
+  }
+
+private:
+  v8::Isolate *isolate_;
+  v8::Persistent<v8::Context> context_;
+  v8::Persistent<v8::Object> resource_;
+#if NODE_MODULE_VERSION >= 57 // Node 8
+  node::async_context async_context_;
+#endif
+};
+
 class Task {
 public:
   Task(v8::Isolate *isolate,
+       const std::string &name,
        void *rust_task,
        Neon_TaskPerformCallback perform,
        Neon_TaskCompleteCallback complete,
        v8::Local<v8::Function> callback)
     : isolate_(isolate),
+      loop_(current_loop(isolate)),
+      async_(isolate, name),
       rust_task_(rust_task),
       perform_(perform),
       complete_(complete)
@@ -23,8 +126,6 @@ public:
     result_ = nullptr;
     // Save the callback to be invoked when the task completes.
     callback_.Reset(isolate, callback);
-    // Save the context (aka realm) to be used when invoking the callback.
-    context_.Reset(isolate, isolate->GetCurrentContext());
   }
 
   void execute() {
@@ -33,11 +134,10 @@ public:
 
   void complete() {
     // Ensure that we have all the proper scopes installed on the C++ stack before
-    // invoking the callback, and use the context (i.e. realm) we saved with the task.
-    v8::Isolate::Scope isolate_scope(isolate_);
-    v8::HandleScope handle_scope(isolate_);
-    v8::Local<v8::Context> context = v8::Local<v8::Context>::New(isolate_, context_);
-    v8::Context::Scope context_scope(context);
+    // completing the task, so that both the Rust completion and the callback run in
+    // the context and async context the task was scheduled from.
+    AsyncResource::Scope scope(&async_);
+    v8::Local<v8::Context> context = scope.context();
 
     v8::Local<v8::Value> argv[2];
 
@@ -49,7 +149,7 @@ public:
 
       v8::Local<v8::Value> completion;
 
-      complete_(rust_task_, result_, &completion);
+      complete_(isolate_, rust_task_, result_, &completion);
 
       if (trycatch.HasCaught()) {
         argv[0] = trycatch.Exception();
@@ -59,25 +159,29 @@ public:
     }
 
     v8::Local<v8::Function> callback = v8::Local<v8::Function>::New(isolate_, callback_);
-    node::MakeCallback(isolate_, context->Global(), callback, 2, argv);
+    async_.MakeCallback(context, callback, 2, argv);
     callback_.Reset();
-    context_.Reset();
   }
 
   void *get_result() {
     return result_;
   }
 
+  uv_loop_t *get_loop() {
+    return loop_;
+  }
+
   uv_work_t request_;
 
 private:
   v8::Isolate *isolate_;
+  uv_loop_t *loop_;
+  AsyncResource async_;
   void *rust_task_;
   Neon_TaskPerformCallback perform_;
   Neon_TaskCompleteCallback complete_;
   void *result_;
   v8::Persistent<v8::Function> callback_;
-  v8::Persistent<v8::Context> context_;
 };
 
 void execute_task(uv_work_t *request) {
@@ -92,12 +196,230 @@ void complete_task(uv_work_t *request) {
 }
 
 void queue_task(Task *task) {
-  uv_queue_work(uv_default_loop(),
+  uv_queue_work(task->get_loop(),
                 &task->request_,
                 execute_task,
                 (uv_after_work_cb)complete_task);
 }
 
+// Runs a Rust callback from the event loop on behalf of an async resource. Exceptions
+// it leaves behind are reported as uncaught.
+void run_loop_callback(v8::Isolate *isolate,
+                       AsyncResource *async,
+                       Neon_AsyncCallback callback,
+                       void *data)
+{
+  AsyncResource::Scope scope(async);
+  v8::TryCatch trycatch(isolate);
+  callback(isolate, data);
+  if (trycatch.HasCaught()) {
+    Nan::FatalException(trycatch);
+  }
+}
+
+// A `uv_async_t` that runs a Rust callback on the loop thread whenever it's sent from
+// any thread. Rust holds counted references to it; once the last one is released the
+// handle is closed from the loop thread, after running any send that's still pending.
+class AsyncSignal {
+public:
+  AsyncSignal(v8::Isolate *isolate,
+              const std::string &name,
+              void *data,
+              Neon_AsyncCallback callback,
+              Neon_DropCallback drop)
+    : isolate_(isolate),
+      async_(isolate, name),
+      data_(data),
+      callback_(callback),
+      drop_(drop),
+      refs_(1),
+      pending_(false),
+      closing_(false)
+  {
+    handle_.data = this;
+    uv_async_init(current_loop(isolate), &handle_, on_async);
+  }
+
+  void send() {
+    pending_ = true;
+    uv_async_send(&handle_);
+  }
+
+  void retain() {
+    refs_++;
+  }
+
+  void release() {
+    if (--refs_ == 0) {
+      closing_ = true;
+      uv_async_send(&handle_);
+    }
+  }
+
+  void unref() {
+    uv_unref(reinterpret_cast<uv_handle_t*>(&handle_));
+  }
+
+private:
+  static void on_async(uv_async_t *handle) {
+    AsyncSignal *self = static_cast<AsyncSignal*>(handle->data);
+    // Sends are coalesced, so a single wakeup can stand for both a pending send
+    // and the release of the last reference.
+    if (self->pending_.exchange(false)) {
+      self->fire();
+    }
+    if (self->closing_) {
+      uv_close(reinterpret_cast<uv_handle_t*>(handle), on_close);
+    }
+  }
+
+  static void on_close(uv_handle_t *handle) {
+    AsyncSignal *self = static_cast<AsyncSignal*>(handle->data);
+    self->drop_(self->data_);
+    delete self;
+  }
+
+  void fire() {
+    run_loop_callback(isolate_, &async_, callback_, data_);
+  }
+
+  uv_async_t handle_;
+  v8::Isolate *isolate_;
+  AsyncResource async_;
+  void *data_;
+  Neon_AsyncCallback callback_;
+  Neon_DropCallback drop_;
+  std::atomic<size_t> refs_;
+  std::atomic<bool> pending_;
+  std::atomic<bool> closing_;
+};
+
+// A `uv_timer_t` that runs a Rust callback after a timeout, and then repeatedly if it has
+// a repeat interval. It's owned by a guard in Rust: dropping the guard closes the timer,
+// and detaching it leaves the timer to close itself once it's no longer active.
+class Timer {
+public:
+  Timer(v8::Isolate *isolate,
+        const std::string &name,
+        void *data,
+        Neon_AsyncCallback callback,
+        Neon_DropCallback drop,
+        uint64_t timeout,
+        uint64_t repeat)
+    : isolate_(isolate),
+      async_(isolate, name),
+      data_(data),
+      callback_(callback),
+      drop_(drop),
+      detached_(false)
+  {
+    handle_.data = this;
+    uv_timer_init(current_loop(isolate), &handle_);
+    uv_timer_start(&handle_, on_timer, timeout, repeat);
+  }
+
+  void stop() {
+    uv_timer_stop(&handle_);
+  }
+
+  void close() {
+    uv_timer_stop(&handle_);
+    uv_close(reinterpret_cast<uv_handle_t*>(&handle_), on_close);
+  }
+
+  void detach() {
+    detached_ = true;
+    if (!uv_is_active(reinterpret_cast<uv_handle_t*>(&handle_))) {
+      close();
+    }
+  }
+
+private:
+  static void on_timer(uv_timer_t *handle) {
+    Timer *self = static_cast<Timer*>(handle->data);
+    run_loop_callback(self->isolate_, &self->async_, self->callback_, self->data_);
+    if (self->detached_ && !uv_is_active(reinterpret_cast<uv_handle_t*>(handle))) {
+      self->close();
+    }
+  }
+
+  static void on_close(uv_handle_t *handle) {
+    Timer *self = static_cast<Timer*>(handle->data);
+    self->drop_(self->data_);
+    delete self;
+  }
+
+  uv_timer_t handle_;
+  v8::Isolate *isolate_;
+  AsyncResource async_;
+  void *data_;
+  Neon_AsyncCallback callback_;
+  Neon_DropCallback drop_;
+  bool detached_;
+};
+
+// A function that runs a Rust callback the first time it's called, for handing Rust
+// closures to JavaScript's own schedulers, such as `process.nextTick`. The callback is
+// dropped once it has run, or once the function is collected if it never does.
+class OnceFunction {
+public:
+  static bool New(v8::Local<v8::Function> *out,
+                  v8::Isolate *isolate,
+                  void *data,
+                  Neon_AsyncCallback callback,
+                  Neon_DropCallback drop)
+  {
+    OnceFunction *self = new OnceFunction(data, callback, drop);
+    v8::Local<v8::External> wrapper = v8::External::New(isolate, self);
+    v8::Local<v8::Function> function;
+    if (wrapper.IsEmpty() || !v8::Function::New(isolate->GetCurrentContext(), call, wrapper).ToLocal(&function)) {
+      delete self;
+      return false;
+    }
+    self->function_.Reset(isolate, function);
+    self->function_.SetWeak(self, on_collected, v8::WeakCallbackType::kParameter);
+    *out = function;
+    return true;
+  }
+
+private:
+  OnceFunction(void *data, Neon_AsyncCallback callback, Neon_DropCallback drop)
+    : data_(data), callback_(callback), drop_(drop) { }
+
+  ~OnceFunction() {
+    if (callback_) {
+      drop_(data_);
+    }
+  }
+
+  static void call(const v8::FunctionCallbackInfo<v8::Value> &info) {
+    OnceFunction *self = static_cast<OnceFunction *>(info.Data().As<v8::External>()->Value());
+    Neon_AsyncCallback callback = self->callback_;
+    if (!callback) {
+      return;
+    }
+    self->callback_ = nullptr;
+    callback(info.GetIsolate(), self->data_);
+    self->drop_(self->data_);
+  }
+
+  static void on_collected(const v8::WeakCallbackInfo<OnceFunction> &data) {
+    data.GetParameter()->function_.Reset();
+    // Dropping the Rust callback can free persistent handles, which isn't allowed
+    // until the second pass.
+    data.SetSecondPassCallback(drop_collected);
+  }
+
+  static void drop_collected(const v8::WeakCallbackInfo<OnceFunction> &data) {
+    delete data.GetParameter();
+  }
+
+  v8::Global<v8::Function> function_;
+  void *data_;
+  Neon_AsyncCallback callback_;
+  Neon_DropCallback drop_;
+};
+
 }
 
 #endif
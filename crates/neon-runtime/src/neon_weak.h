@@ -0,0 +1,115 @@
+#ifndef NEON_WEAK_H_
+#define NEON_WEAK_H_
+
+#include "neon.h"
+#include "v8.h"
+
+namespace neon {
+
+// A weak reference to an object, owned by Rust. If it has a callback, the callback
+// is called with `data` once the object is collected, unless Rust releases the
+// reference first. `drop` is called with `data` either way.
+class WeakRef {
+public:
+  WeakRef(v8::Isolate *isolate,
+          v8::Local<v8::Value> value,
+          void *data,
+          Neon_DropCallback callback,
+          Neon_DropCallback drop)
+    : isolate_(isolate), data_(data), callback_(callback), drop_(drop), collecting_(false), released_(false)
+  {
+    handle_.Reset(isolate, value);
+    handle_.SetWeak(this, on_collected, v8::WeakCallbackType::kParameter);
+  }
+
+  bool Read(v8::Local<v8::Value> *out) {
+    if (handle_.IsEmpty()) {
+      return false;
+    }
+    *out = v8::Local<v8::Value>::New(isolate_, handle_);
+    return true;
+  }
+
+  // The second pass of the weak callback can be deferred to a later task, so a
+  // reference released in between is left for it to delete.
+  void Release() {
+    if (collecting_) {
+      released_ = true;
+      return;
+    }
+    delete this;
+  }
+
+private:
+  ~WeakRef() {
+    handle_.Reset();
+    if (data_) {
+      drop_(data_);
+    }
+  }
+
+  static void on_collected(const v8::WeakCallbackInfo<WeakRef> &info) {
+    WeakRef *self = info.GetParameter();
+    self->handle_.Reset();
+    self->collecting_ = true;
+    // The Rust callback can free persistent handles, which isn't allowed until the
+    // second pass.
+    info.SetSecondPassCallback(notify_collected);
+  }
+
+  static void notify_collected(const v8::WeakCallbackInfo<WeakRef> &info) {
+    WeakRef *self = info.GetParameter();
+    self->collecting_ = false;
+    if (self->released_) {
+      delete self;
+      return;
+    }
+    if (self->data_) {
+      void *data = self->data_;
+      self->data_ = nullptr;
+      self->callback_(data);
+      self->drop_(data);
+    }
+  }
+
+  v8::Global<v8::Value> handle_;
+  v8::Isolate *isolate_;
+  void *data_;
+  Neon_DropCallback callback_;
+  Neon_DropCallback drop_;
+  bool collecting_;
+  bool released_;
+};
+
+// Calls `drop` with `data` once an object is collected, then deletes itself.
+class Finalizer {
+public:
+  Finalizer(v8::Isolate *isolate, v8::Local<v8::Value> value, void *data, Neon_DropCallback drop)
+    : data_(data), drop_(drop)
+  {
+    handle_.Reset(isolate, value);
+    handle_.SetWeak(this, on_collected, v8::WeakCallbackType::kParameter);
+  }
+
+private:
+  static void on_collected(const v8::WeakCallbackInfo<Finalizer> &info) {
+    info.GetParameter()->handle_.Reset();
+    // Dropping Rust data can free persistent handles, which isn't allowed until the
+    // second pass.
+    info.SetSecondPassCallback(finalize);
+  }
+
+  static void finalize(const v8::WeakCallbackInfo<Finalizer> &info) {
+    Finalizer *self = info.GetParameter();
+    self->drop_(self->data_);
+    delete self;
+  }
+
+  v8::Global<v8::Value> handle_;
+  void *data_;
+  Neon_DropCallback drop_;
+};
+
+}
+
+#endif
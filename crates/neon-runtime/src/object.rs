@@ -38,6 +38,17 @@ extern "C" {
     #[link_name = "Neon_Object_Set_String"]
     pub fn set_string(out: &mut bool, object: Local, key: *const u8, len: i32, val: Local) -> bool;
 
+    /// Mutates the `out` argument provided to refer to the hidden value stored on the
+    /// `v8::Object` under the private symbol named by `key`, or `undefined` if there is none.
+    /// Returns `false` if the value couldn't be retrieved.
+    #[link_name = "Neon_Object_GetHidden"]
+    pub fn get_hidden(out: &mut Local, object: Local, key: *const u8, len: i32) -> bool;
+
+    /// Stores a hidden value on the `v8::Object` under the private symbol named by `key`. Also
+    /// mutates the `out` argument provided to `true` if the set was successful.
+    #[link_name = "Neon_Object_SetHidden"]
+    pub fn set_hidden(out: &mut bool, object: Local, key: *const u8, len: i32, val: Local) -> bool;
+
     /// Mutates the `out` argument provided to refer to the `v8::Local` value at the `key`
     /// provided. Returns `false` if the result couldn't be retrieved.
     #[link_name = "Neon_Object_Get"]
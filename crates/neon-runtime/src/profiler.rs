@@ -0,0 +1,27 @@
+//! Facilities for running V8's CPU and heap profilers.
+
+use std::os::raw::c_void;
+use raw::{Isolate, Local};
+
+extern "C" {
+
+    /// Creates a `v8::CpuProfiler` and starts a profile named `title` with it. Returns null if
+    /// the profiler isn't available, which is the case before Node 8.
+    #[link_name = "Neon_Profiler_Start"]
+    pub fn start(isolate: *mut Isolate, title: Local) -> *mut c_void;
+
+    /// Stops the profile named `title` and mutates the `out` argument provided to refer to an
+    /// object describing it. Returns `false` if there was no such profile.
+    #[link_name = "Neon_Profiler_Stop"]
+    pub fn stop(out: &mut Local, profiler: *mut c_void, title: Local) -> bool;
+
+    /// Stops any profiles still running and frees a profiler created by `start`.
+    #[link_name = "Neon_Profiler_Dispose"]
+    pub fn dispose(profiler: *mut c_void);
+
+    /// Takes a snapshot of the isolate's heap and writes it as JSON to the file at the UTF-8
+    /// path `path`, of length `len`. Returns `false` if the file couldn't be written.
+    #[link_name = "Neon_Profiler_WriteHeapSnapshot"]
+    pub fn write_heap_snapshot(isolate: *mut Isolate, path: *const u8, len: usize) -> bool;
+
+}
@@ -48,4 +48,21 @@ extern "C" {
     #[link_name = "Neon_Scope_GetGlobal"]
     pub fn get_global(isolate: *mut c_void, out: &mut Local);
 
+    /// Runs the isolate's pending microtasks, such as promise reactions, to completion.
+    #[link_name = "Neon_Scope_RunMicrotasks"]
+    pub fn run_microtasks(isolate: *mut c_void);
+
+    /// Adds a function to the isolate's microtask queue, after any promise reactions
+    /// already queued.
+    #[link_name = "Neon_Scope_EnqueueMicrotask"]
+    pub fn enqueue_microtask(isolate: *mut c_void, microtask: Local);
+
+    /// Gets the isolate's `v8::MicrotasksPolicy`: 0 for explicit, 1 for scoped, or 2 for auto.
+    #[link_name = "Neon_Scope_GetMicrotasksPolicy"]
+    pub fn microtasks_policy(isolate: *mut c_void) -> i32;
+
+    /// Gets the number of handles held by all of the isolate's open `v8::HandleScope`s.
+    #[link_name = "Neon_Scope_NumberOfHandles"]
+    pub fn number_of_handles(isolate: *mut c_void) -> usize;
+
 }
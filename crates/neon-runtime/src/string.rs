@@ -9,6 +9,12 @@ extern "C" {
     #[link_name = "Neon_String_New"]
     pub fn new(out: &mut Local, isolate: *mut Isolate, data: *const u8, len: i32) -> bool;
 
+    /// Mutates the `out` argument provided to refer to an internalized `v8::String`, which
+    /// is shared with any equal string used as a property key. Returns `false` if the value
+    /// couldn't be created.
+    #[link_name = "Neon_String_NewInternalized"]
+    pub fn new_internalized(out: &mut Local, isolate: *mut Isolate, data: *const u8, len: i32) -> bool;
+
     /// Gets the length of a `v8::String`.
     #[link_name = "Neon_String_Utf8Length"]
     pub fn utf8_len(str: Local) -> isize;
@@ -17,4 +23,32 @@ extern "C" {
     #[link_name = "Neon_String_Data"]
     pub fn data(out: *mut u8, len: isize, str: Local) -> isize;
 
+    /// Mutates the `out` argument provided to refer to a newly created `v8::String` from
+    /// UTF-16 code units. Returns `false` if the value couldn't be created.
+    #[link_name = "Neon_String_NewFromUtf16"]
+    pub fn new_from_utf16(out: &mut Local, isolate: *mut Isolate, data: *const u16, len: i32) -> bool;
+
+    /// Mutates the `out` argument provided to refer to a newly created `v8::String` from
+    /// Latin-1 bytes. Returns `false` if the value couldn't be created.
+    #[link_name = "Neon_String_NewFromLatin1"]
+    pub fn new_from_latin1(out: &mut Local, isolate: *mut Isolate, data: *const u8, len: i32) -> bool;
+
+    /// Gets the length of a `v8::String` in UTF-16 code units.
+    #[link_name = "Neon_String_Length"]
+    pub fn len(str: Local) -> i32;
+
+    /// Writes up to `len` UTF-16 code units of a `v8::String` and returns the number written.
+    #[link_name = "Neon_String_WriteUtf16"]
+    pub fn write_utf16(str: Local, out: *mut u16, len: i32) -> i32;
+
+    /// Writes up to `len` characters of a `v8::String` as Latin-1 bytes, keeping only the low
+    /// byte of each UTF-16 code unit, and returns the number written.
+    #[link_name = "Neon_String_WriteLatin1"]
+    pub fn write_latin1(str: Local, out: *mut u8, len: i32) -> i32;
+
+    /// Writes up to `len` bytes of a `v8::String` as UTF-8, without splitting characters
+    /// and replacing unpaired surrogates, and returns the number of bytes written.
+    #[link_name = "Neon_String_WriteUtf8"]
+    pub fn write_utf8(str: Local, out: *mut u8, len: i32) -> i32;
+
 }
@@ -54,6 +54,10 @@ extern "C" {
     #[link_name = "Neon_Tag_IsObject"]
     pub fn is_object(val: Local) -> bool;
 
+    /// Indicates if the value type is `Date`.
+    #[link_name = "Neon_Tag_IsDate"]
+    pub fn is_date(val: Local) -> bool;
+
     /// Indicates if the value type is `Array`.
     #[link_name = "Neon_Tag_IsArray"]
     pub fn is_array(val: Local) -> bool;
@@ -74,4 +78,16 @@ extern "C" {
     #[link_name = "Neon_Tag_IsArrayBuffer"]
     pub fn is_arraybuffer(obj: Local) -> bool;
 
+    /// Indicates if the value type is `BigInt64Array`. Always `false` before V8 6.7.
+    #[link_name = "Neon_Tag_IsBigInt64Array"]
+    pub fn is_bigint64array(obj: Local) -> bool;
+
+    /// Indicates if the value type is `BigUint64Array`. Always `false` before V8 6.7.
+    #[link_name = "Neon_Tag_IsBigUint64Array"]
+    pub fn is_biguint64array(obj: Local) -> bool;
+
+    /// Indicates if the value type is `BigInt`. Always `false` before V8 6.8.
+    #[link_name = "Neon_Tag_IsBigInt"]
+    pub fn is_bigint(val: Local) -> bool;
+
 }
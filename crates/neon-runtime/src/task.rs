@@ -1,15 +1,97 @@
-//! Facilities for running background tasks in the libuv thread pool.
+//! Facilities for running background tasks in the libuv thread pool and for waking the
+//! event loop from other threads.
 
-use raw::Local;
+use raw::{Local, Isolate};
 use std::os::raw::c_void;
 
 extern "C" {
 
-    /// Schedules a background task.
+    /// Schedules a background task. The task completes on the event loop of the thread
+    /// that scheduled it, and `complete` is passed that thread's isolate. The UTF-8
+    /// `name` is the task's resource type for `async_hooks`.
     #[link_name = "Neon_Task_Schedule"]
     pub fn schedule(task: *mut c_void,
+                    name: *const u8,
+                    name_len: usize,
                     perform: unsafe extern fn(*mut c_void) -> *mut c_void,
-                    complete: unsafe extern fn(*mut c_void, *mut c_void, &mut Local),
+                    complete: unsafe extern fn(*mut Isolate, *mut c_void, *mut c_void, &mut Local),
                     callback: Local);
 
+    /// Runs a task the way `schedule` would, but synchronously: `perform` and `complete`
+    /// are called on the current thread, followed by the callback, before this returns.
+    #[link_name = "Neon_Task_RunNow"]
+    pub fn run_now(task: *mut c_void,
+                   name: *const u8,
+                   name_len: usize,
+                   perform: unsafe extern fn(*mut c_void) -> *mut c_void,
+                   complete: unsafe extern fn(*mut Isolate, *mut c_void, *mut c_void, &mut Local),
+                   callback: Local);
+
+    /// Creates a `uv_async_t` on the current thread's event loop, holding one reference.
+    /// Each send runs `callback` with `data` on that loop; once the last reference is
+    /// released the handle is closed and `drop` is called with `data`. The UTF-8 `name` is
+    /// the signal's resource type for `async_hooks`.
+    #[link_name = "Neon_Async_New"]
+    pub fn new_async(isolate: *mut Isolate,
+                     name: *const u8,
+                     name_len: usize,
+                     data: *mut c_void,
+                     callback: unsafe extern fn(*mut Isolate, *mut c_void),
+                     drop: unsafe extern fn(*mut c_void)) -> *mut c_void;
+
+    /// Wakes the event loop to run the signal's callback. Safe to call from any thread.
+    #[link_name = "Neon_Async_Send"]
+    pub fn send_async(signal: *mut c_void);
+
+    /// Adds a reference to the signal. Safe to call from any thread.
+    #[link_name = "Neon_Async_Retain"]
+    pub fn retain_async(signal: *mut c_void);
+
+    /// Releases a reference to the signal. Safe to call from any thread.
+    #[link_name = "Neon_Async_Release"]
+    pub fn release_async(signal: *mut c_void);
+
+    /// Stops the signal from keeping its event loop alive. Must be called on the loop's
+    /// thread.
+    #[link_name = "Neon_Async_Unref"]
+    pub fn unref_async(signal: *mut c_void);
+
+    /// Mutates the `out` argument provided to refer to a new `v8::Function` that runs
+    /// `callback` with `data` the first time it's called, and does nothing after that.
+    /// `drop` is called with `data` once the callback has run, or once the function is
+    /// collected if it never ran. Returns `false` if the function couldn't be created.
+    #[link_name = "Neon_Fun_NewOnce"]
+    pub fn new_once_function(out: &mut Local,
+                             isolate: *mut Isolate,
+                             data: *mut c_void,
+                             callback: unsafe extern fn(*mut Isolate, *mut c_void),
+                             drop: unsafe extern fn(*mut c_void)) -> bool;
+
+    /// Starts a `uv_timer_t` on the current thread's event loop that runs `callback` with
+    /// `data` after `timeout` milliseconds, and then every `repeat` milliseconds unless
+    /// `repeat` is zero. Once the timer is closed, `drop` is called with `data`. The UTF-8
+    /// `name` is the timer's resource type for `async_hooks`.
+    #[link_name = "Neon_Timer_New"]
+    pub fn new_timer(isolate: *mut Isolate,
+                     name: *const u8,
+                     name_len: usize,
+                     data: *mut c_void,
+                     callback: unsafe extern fn(*mut Isolate, *mut c_void),
+                     drop: unsafe extern fn(*mut c_void),
+                     timeout: u64,
+                     repeat: u64) -> *mut c_void;
+
+    /// Stops a timer without closing it.
+    #[link_name = "Neon_Timer_Stop"]
+    pub fn stop_timer(timer: *mut c_void);
+
+    /// Stops and closes a timer. The timer must not be used afterwards.
+    #[link_name = "Neon_Timer_Close"]
+    pub fn close_timer(timer: *mut c_void);
+
+    /// Gives up ownership of a timer, which closes itself once it has stopped. The timer
+    /// must not be used afterwards.
+    #[link_name = "Neon_Timer_Detach"]
+    pub fn detach_timer(timer: *mut c_void);
+
 }
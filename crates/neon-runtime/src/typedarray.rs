@@ -0,0 +1,28 @@
+//! Facilities for working with `v8::TypedArray`s.
+
+use raw::Local;
+use cslice::CMutSlice;
+use std::os::raw::c_void;
+
+// Suppress a spurious rustc warning about the use of CMutSlice.
+#[allow(improper_ctypes)]
+extern "C" {
+
+    /// Mutates the `out` argument provided to refer to a newly created `v8::BigInt64Array`
+    /// of `len` elements. Returns `false` and throws if the value couldn't be created, which
+    /// is always the case before V8 6.7.
+    #[link_name = "Neon_TypedArray_NewBigInt64Array"]
+    pub fn new_bigint64array(out: &mut Local, isolate: *mut c_void, len: u32) -> bool;
+
+    /// Mutates the `out` argument provided to refer to a newly created `v8::BigUint64Array`
+    /// of `len` elements. Returns `false` and throws if the value couldn't be created, which
+    /// is always the case before V8 6.7.
+    #[link_name = "Neon_TypedArray_NewBigUint64Array"]
+    pub fn new_biguint64array(out: &mut Local, isolate: *mut c_void, len: u32) -> bool;
+
+    /// Mutates the `out` argument provided populating the `data` and `len` properties with
+    /// the region of the underlying buffer that the typed array views, in bytes.
+    #[link_name = "Neon_TypedArray_Data"]
+    pub fn data<'a, 'b>(out: &'a mut CMutSlice<'b, u8>, obj: Local);
+
+}
@@ -0,0 +1,205 @@
+//! A harness for running a Neon addon's Rust tests inside Node.
+//!
+//! An addon registers its tests with `Module::test` and `Module::test_module`. A
+//! `cargo test` integration test then points the harness at the built addon, which
+//! loads it into a `node` process, runs each test there against the real JavaScript
+//! engine, and reports the results back:
+//!
+//! ```rust,no_run
+//! extern crate neon_test;
+//!
+//! #[test]
+//! fn native() {
+//!     neon_test::run("native/index.node");
+//! }
+//! ```
+//!
+//! The addon is loaded with `process.dlopen`, so the harness can also be pointed at
+//! the shared library that Cargo builds, without copying it to a `.node` file. It has
+//! to be built before the tests run, e.g. with `neon build`.
+
+use std::env;
+use std::ffi::{OsStr, OsString};
+use std::fmt::{self, Display, Formatter};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::str;
+
+// Loads the addon and runs each of its tests, printing a line per test with the
+// test's name and any failure percent-encoded.
+const RUNNER: &'static str = r#"
+var path = require('path');
+var addon = { exports: {}, filename: path.resolve(process.argv[1]) };
+process.dlopen(addon, addon.filename);
+var tests = addon.exports.__neon_tests || {};
+var filter = process.argv[2] || '';
+Object.keys(tests).forEach(function (name) {
+  if (name.indexOf(filter) < 0) {
+    return;
+  }
+  try {
+    tests[name]();
+    console.log('ok ' + encodeURIComponent(name));
+  } catch (e) {
+    var message = e && e.stack ? e.stack : String(e);
+    console.log('failed ' + encodeURIComponent(name) + ' ' + encodeURIComponent(message));
+  }
+});
+"#;
+
+/// A run of an addon's tests, which can be configured before it starts.
+pub struct Harness {
+    addon: PathBuf,
+    node: OsString,
+    filter: String
+}
+
+impl Harness {
+    /// Prepares to run the tests of the addon at `addon`, with the `node` found on the
+    /// `PATH`, or the one named by the `NEON_TEST_NODE` environment variable.
+    pub fn new<P: AsRef<Path>>(addon: P) -> Harness {
+        Harness {
+            addon: addon.as_ref().to_path_buf(),
+            node: env::var_os("NEON_TEST_NODE").unwrap_or_else(|| OsString::from("node")),
+            filter: String::new()
+        }
+    }
+
+    /// Runs the tests with a different `node` executable.
+    pub fn node<S: AsRef<OsStr>>(mut self, node: S) -> Harness {
+        self.node = node.as_ref().to_os_string();
+        self
+    }
+
+    /// Only runs the tests whose names contain `filter`.
+    pub fn filter(mut self, filter: &str) -> Harness {
+        self.filter = filter.to_string();
+        self
+    }
+
+    /// Runs the tests, failing if `node` can't be started or the addon can't be loaded.
+    pub fn run(&self) -> io::Result<Report> {
+        let output = Command::new(&self.node)
+            .arg("-e")
+            .arg(RUNNER)
+            .arg(&self.addon)
+            .arg(&self.filter)
+            .output()?;
+        if !output.status.success() {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            return Err(io::Error::new(io::ErrorKind::Other,
+                                      format!("couldn't run the tests of {}: {}", self.addon.display(), stderr.trim())));
+        }
+        let mut report = Report { passed: Vec::new(), failed: Vec::new() };
+        for line in String::from_utf8_lossy(&output.stdout).lines() {
+            let mut parts = line.split(' ');
+            match (parts.next(), parts.next(), parts.next()) {
+                (Some("ok"), Some(name), None) => {
+                    report.passed.push(decode(name));
+                }
+                (Some("failed"), Some(name), Some(message)) => {
+                    report.failed.push(Failure { name: decode(name), message: decode(message) });
+                }
+                // Anything else was printed by a test.
+                _ => println!("{}", line)
+            }
+        }
+        Ok(report)
+    }
+}
+
+/// The results of a run of an addon's tests.
+#[derive(Debug, Clone)]
+pub struct Report {
+    /// The names of the tests that passed.
+    pub passed: Vec<String>,
+    /// The tests that failed.
+    pub failed: Vec<Failure>
+}
+
+impl Report {
+    /// Whether every test passed.
+    pub fn success(&self) -> bool {
+        self.failed.is_empty()
+    }
+}
+
+impl Display for Report {
+    fn fmt(&self, fmt: &mut Formatter) -> fmt::Result {
+        for name in &self.passed {
+            writeln!(fmt, "test {} ... ok", name)?;
+        }
+        for failure in &self.failed {
+            writeln!(fmt, "test {} ... FAILED", failure.name)?;
+        }
+        for failure in &self.failed {
+            writeln!(fmt, "\n---- {} ----\n{}", failure.name, failure.message)?;
+        }
+        write!(fmt, "\ntest result: {}. {} passed; {} failed",
+               if self.success() { "ok" } else { "FAILED" },
+               self.passed.len(),
+               self.failed.len())
+    }
+}
+
+/// A test that threw or panicked.
+#[derive(Debug, Clone)]
+pub struct Failure {
+    /// The name the test was registered with.
+    pub name: String,
+    /// The exception's stack, or the exception itself if it has none. A panic is
+    /// reported as an `Error` with the panic's message.
+    pub message: String
+}
+
+/// Runs the tests of the addon at `addon`, printing a report, and panics if any of
+/// them fail or if none were found. This is meant to be called from a `#[test]`.
+pub fn run<P: AsRef<Path>>(addon: P) {
+    let report = match Harness::new(addon).run() {
+        Ok(report) => report,
+        Err(err) => panic!("{}", err)
+    };
+    println!("{}", report);
+    assert!(!report.passed.is_empty() || !report.failed.is_empty(),
+            "the addon has no tests; register them with `Module::test`");
+    assert!(report.success(), "{} native test(s) failed", report.failed.len());
+}
+
+// Reverses `encodeURIComponent`.
+fn decode(s: &str) -> String {
+    let bytes = s.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let byte = if bytes[i] == b'%' && i + 2 < bytes.len() {
+            str::from_utf8(&bytes[i + 1..i + 3]).ok().and_then(|hex| u8::from_str_radix(hex, 16).ok())
+        } else {
+            None
+        };
+        match byte {
+            Some(byte) => {
+                decoded.push(byte);
+                i += 3;
+            }
+            None => {
+                decoded.push(bytes[i]);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::decode;
+
+    #[test]
+    fn decodes_uri_components() {
+        assert_eq!(decode("plain"), "plain");
+        assert_eq!(decode("a%20b%0Ac"), "a b\nc");
+        assert_eq!(decode("caf%C3%A9"), "café");
+        assert_eq!(decode("100%"), "100%");
+    }
+}
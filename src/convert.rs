@@ -0,0 +1,529 @@
+//! Conversions between common Rust types and JavaScript values.
+//!
+//! Rust values are converted to JavaScript with `TryIntoJs`, and JavaScript values
+//! are read back into Rust with `TryFromJs`:
+//!
+//! - numbers convert to and from `f64`, `f32` and the integer types (integers must be
+//!   integral and in range);
+//! - booleans convert to and from `bool`, strings to and from `String`;
+//! - `Option<T>` reads `null` and `undefined` as `None` and writes `None` as `null`;
+//! - arrays convert to and from `Vec<T>`, fixed-size arrays of up to 32 elements, and
+//!   tuples of up to eight elements;
+//! - objects convert to and from `HashMap<String, T>`;
+//! - dates convert to and from `SystemTime`, and to and from `chrono::DateTime<Utc>` with
+//!   the `chrono` feature, while `Duration`s convert to and from milliseconds.
+//!
+//! Plain structs can be converted to and from objects with the `ToJsObject` and
+//! `FromJsObject` derives from the `neon-derive` crate, and enums to and from strings
+//! or tagged objects with `ToJsEnum` and `FromJsEnum`.
+//!
+//! A function's arguments can be read one at a time with `FunctionCall::argument`, or
+//! all at once into a tuple with `FunctionCall::args`. A trailing options object can be
+//! read into a struct of settings with `FunctionCall::options`, given a `FromOptions`
+//! implementation, which the `FromJsOptions` derive provides.
+
+use std::collections::HashMap;
+use std::hash::BuildHasher;
+use std::time::{Duration, SystemTime};
+#[cfg(feature = "chrono")]
+use chrono::{DateTime, TimeZone, Utc};
+use mem::Handle;
+use scope::Scope;
+use vm::{JsResult, VmResult, FunctionCall, This};
+use js::{Value, Object, JsValue, JsObject, JsArray, JsString, JsNumber, JsBoolean, JsNull, JsUndefined, JsFunction};
+use js::error::{JsError, Kind};
+use js::date::{self, JsDate};
+
+/// A Rust type that can be read from a JavaScript value.
+pub trait TryFromJs: Sized {
+    /// Reads a value of this type, producing `None` if the JavaScript value has the wrong
+    /// type or shape. Errors are reserved for JavaScript exceptions, e.g. from getters.
+    fn from_js<'a, 'b, S: Scope<'a>>(scope: &mut S, value: Handle<'b, JsValue>) -> VmResult<Option<Self>>;
+
+    /// A description of the expected JavaScript value, used in error messages.
+    fn describe() -> String;
+
+    /// Reads a value of this type, throwing a `TypeError` if the JavaScript value has the
+    /// wrong type or shape.
+    fn try_from_js<'a, 'b, S: Scope<'a>>(scope: &mut S, value: Handle<'b, JsValue>) -> VmResult<Self> {
+        match Self::from_js(scope, value)? {
+            Some(result) => Ok(result),
+            None => JsError::throw(Kind::TypeError, &format!("expected {}", Self::describe()))
+        }
+    }
+}
+
+/// A struct of settings that can be read from an options object, in the style of the
+/// `{ ... }` argument that ends many Node APIs. Each setting starts out with its
+/// default and is overridden by the matching property, if the object has one.
+pub trait FromOptions: Default {
+    /// The names of all of the properties that the options object may have.
+    fn keys() -> &'static [&'static str];
+
+    /// Overrides each setting whose property is present and not `undefined`, throwing a
+    /// `TypeError` naming the first property that has the wrong type.
+    fn read_options<'a, 'b, S: Scope<'a>>(&mut self, scope: &mut S, object: Handle<'b, JsObject>) -> VmResult<()>;
+}
+
+/// A Rust type that can be converted to a JavaScript value.
+pub trait TryIntoJs {
+    /// The type of the resulting JavaScript value.
+    type Value: Value + 'static;
+
+    fn try_into_js<'a, S: Scope<'a>>(self, scope: &mut S) -> JsResult<'a, Self::Value>;
+}
+
+impl TryFromJs for f64 {
+    fn from_js<'a, 'b, S: Scope<'a>>(_: &mut S, value: Handle<'b, JsValue>) -> VmResult<Option<f64>> {
+        Ok(value.downcast::<JsNumber>().map(|n| n.value()))
+    }
+
+    fn describe() -> String { String::from("a number") }
+}
+
+impl TryIntoJs for f64 {
+    type Value = JsNumber;
+
+    fn try_into_js<'a, S: Scope<'a>>(self, scope: &mut S) -> JsResult<'a, JsNumber> {
+        Ok(JsNumber::new(scope, self))
+    }
+}
+
+impl TryFromJs for f32 {
+    fn from_js<'a, 'b, S: Scope<'a>>(scope: &mut S, value: Handle<'b, JsValue>) -> VmResult<Option<f32>> {
+        Ok(f64::from_js(scope, value)?.map(|n| n as f32))
+    }
+
+    fn describe() -> String { String::from("a number") }
+}
+
+impl TryIntoJs for f32 {
+    type Value = JsNumber;
+
+    fn try_into_js<'a, S: Scope<'a>>(self, scope: &mut S) -> JsResult<'a, JsNumber> {
+        Ok(JsNumber::new(scope, self as f64))
+    }
+}
+
+// The bounds are a type's range as an `f64`, with an exclusive upper bound so that
+// the 64-bit types, whose maximum isn't representable, don't round up into range.
+macro_rules! integer_conversions {
+    ($($ty:ident: $min:expr, $max:expr;)*) => {
+        $(
+            impl TryFromJs for $ty {
+                fn from_js<'a, 'b, S: Scope<'a>>(scope: &mut S, value: Handle<'b, JsValue>) -> VmResult<Option<$ty>> {
+                    Ok(f64::from_js(scope, value)?.and_then(|n| {
+                        if n.fract() == 0.0 && n >= $min && n < $max {
+                            Some(n as $ty)
+                        } else {
+                            None
+                        }
+                    }))
+                }
+
+                fn describe() -> String {
+                    format!("an integer in the range of {}", stringify!($ty))
+                }
+            }
+
+            impl TryIntoJs for $ty {
+                type Value = JsNumber;
+
+                fn try_into_js<'a, S: Scope<'a>>(self, scope: &mut S) -> JsResult<'a, JsNumber> {
+                    Ok(JsNumber::new(scope, self as f64))
+                }
+            }
+        )*
+    }
+}
+
+integer_conversions! {
+    i8: -128.0, 128.0;
+    i16: -32768.0, 32768.0;
+    i32: -2147483648.0, 2147483648.0;
+    i64: -9223372036854775808.0, 9223372036854775808.0;
+    u8: 0.0, 256.0;
+    u16: 0.0, 65536.0;
+    u32: 0.0, 4294967296.0;
+    u64: 0.0, 18446744073709551616.0;
+}
+
+impl TryFromJs for bool {
+    fn from_js<'a, 'b, S: Scope<'a>>(_: &mut S, value: Handle<'b, JsValue>) -> VmResult<Option<bool>> {
+        Ok(value.downcast::<JsBoolean>().map(|b| b.value()))
+    }
+
+    fn describe() -> String { String::from("a boolean") }
+}
+
+impl TryIntoJs for bool {
+    type Value = JsBoolean;
+
+    fn try_into_js<'a, S: Scope<'a>>(self, scope: &mut S) -> JsResult<'a, JsBoolean> {
+        Ok(JsBoolean::new(scope, self))
+    }
+}
+
+impl TryFromJs for String {
+    fn from_js<'a, 'b, S: Scope<'a>>(_: &mut S, value: Handle<'b, JsValue>) -> VmResult<Option<String>> {
+        Ok(value.downcast::<JsString>().map(|s| s.value()))
+    }
+
+    fn describe() -> String { String::from("a string") }
+}
+
+impl TryIntoJs for String {
+    type Value = JsString;
+
+    fn try_into_js<'a, S: Scope<'a>>(self, scope: &mut S) -> JsResult<'a, JsString> {
+        JsString::new_or_throw(scope, &self)
+    }
+}
+
+impl<'c> TryIntoJs for &'c str {
+    type Value = JsString;
+
+    fn try_into_js<'a, S: Scope<'a>>(self, scope: &mut S) -> JsResult<'a, JsString> {
+        JsString::new_or_throw(scope, self)
+    }
+}
+
+impl<T: TryFromJs> TryFromJs for Option<T> {
+    fn from_js<'a, 'b, S: Scope<'a>>(scope: &mut S, value: Handle<'b, JsValue>) -> VmResult<Option<Option<T>>> {
+        if value.is_a::<JsNull>() || value.is_a::<JsUndefined>() {
+            return Ok(Some(None));
+        }
+        Ok(T::from_js(scope, value)?.map(Some))
+    }
+
+    fn describe() -> String {
+        format!("{}, null or undefined", T::describe())
+    }
+}
+
+impl<T: TryIntoJs> TryIntoJs for Option<T> {
+    type Value = JsValue;
+
+    fn try_into_js<'a, S: Scope<'a>>(self, scope: &mut S) -> JsResult<'a, JsValue> {
+        match self {
+            Some(value) => Ok(value.try_into_js(scope)?.upcast()),
+            None => Ok(JsNull::new().upcast())
+        }
+    }
+}
+
+impl<T: TryFromJs> TryFromJs for Vec<T> {
+    fn from_js<'a, 'b, S: Scope<'a>>(scope: &mut S, value: Handle<'b, JsValue>) -> VmResult<Option<Vec<T>>> {
+        let array = match value.downcast::<JsArray>() {
+            Some(array) => array,
+            None => return Ok(None)
+        };
+        let mut result = Vec::with_capacity(array.len() as usize);
+        for element in array.to_vec(scope)? {
+            match T::from_js(scope, element)? {
+                Some(element) => result.push(element),
+                None => return Ok(None)
+            }
+        }
+        Ok(Some(result))
+    }
+
+    fn describe() -> String {
+        format!("an array of {}", plural(T::describe()))
+    }
+}
+
+impl<T: TryIntoJs> TryIntoJs for Vec<T> {
+    type Value = JsArray;
+
+    fn try_into_js<'a, S: Scope<'a>>(self, scope: &mut S) -> JsResult<'a, JsArray> {
+        JsArray::from_iter(scope, self)
+    }
+}
+
+impl TryFromJs for SystemTime {
+    fn from_js<'a, 'b, S: Scope<'a>>(_: &mut S, value: Handle<'b, JsValue>) -> VmResult<Option<SystemTime>> {
+        Ok(value.downcast::<JsDate>().and_then(|date| date.to_system_time()))
+    }
+
+    fn describe() -> String { String::from("a valid Date") }
+}
+
+impl TryIntoJs for SystemTime {
+    type Value = JsDate;
+
+    fn try_into_js<'a, S: Scope<'a>>(self, scope: &mut S) -> JsResult<'a, JsDate> {
+        JsDate::from_system_time(scope, self)
+    }
+}
+
+impl TryFromJs for Duration {
+    fn from_js<'a, 'b, S: Scope<'a>>(scope: &mut S, value: Handle<'b, JsValue>) -> VmResult<Option<Duration>> {
+        Ok(f64::from_js(scope, value)?.and_then(|millis| {
+            if millis >= 0.0 && millis.is_finite() {
+                Some(date::duration_from_millis(millis))
+            } else {
+                None
+            }
+        }))
+    }
+
+    fn describe() -> String { String::from("a non-negative number of milliseconds") }
+}
+
+impl TryIntoJs for Duration {
+    type Value = JsNumber;
+
+    fn try_into_js<'a, S: Scope<'a>>(self, scope: &mut S) -> JsResult<'a, JsNumber> {
+        Ok(JsNumber::new(scope, date::millis(self)))
+    }
+}
+
+#[cfg(feature = "chrono")]
+impl TryFromJs for DateTime<Utc> {
+    fn from_js<'a, 'b, S: Scope<'a>>(_: &mut S, value: Handle<'b, JsValue>) -> VmResult<Option<DateTime<Utc>>> {
+        let time = match value.downcast::<JsDate>() {
+            Some(date) if date.is_valid() => date.value(),
+            _ => return Ok(None)
+        };
+        let secs = (time / 1000.0).floor();
+        let nanos = ((time - secs * 1000.0) * 1_000_000.0) as u32;
+        Ok(Utc.timestamp_opt(secs as i64, nanos).single())
+    }
+
+    fn describe() -> String { String::from("a valid Date") }
+}
+
+#[cfg(feature = "chrono")]
+impl TryIntoJs for DateTime<Utc> {
+    type Value = JsDate;
+
+    fn try_into_js<'a, S: Scope<'a>>(self, scope: &mut S) -> JsResult<'a, JsDate> {
+        let time = (self.timestamp() as f64) * 1000.0 + (self.timestamp_subsec_nanos() as f64) / 1_000_000.0;
+        JsDate::new(scope, time)
+    }
+}
+
+macro_rules! array_conversions {
+    ($($len:expr)*) => {
+        $(
+            impl<T: TryFromJs> TryFromJs for [T; $len] {
+                fn from_js<'a, 'b, S: Scope<'a>>(scope: &mut S, value: Handle<'b, JsValue>) -> VmResult<Option<[T; $len]>> {
+                    match value.downcast::<JsArray>() {
+                        Some(array) if array.len() == $len => { }
+                        _ => return Ok(None)
+                    }
+                    let elements = match Vec::<T>::from_js(scope, value)? {
+                        // The array can be resized by a getter while it's being read.
+                        Some(ref elements) if elements.len() != $len => return Ok(None),
+                        Some(elements) => elements.into_boxed_slice(),
+                        None => return Ok(None)
+                    };
+                    let elements: Box<[T; $len]> = unsafe {
+                        Box::from_raw(Box::into_raw(elements) as *mut [T; $len])
+                    };
+                    Ok(Some(*elements))
+                }
+
+                fn describe() -> String {
+                    format!("an array of {} {}", $len, plural(T::describe()))
+                }
+            }
+
+            impl<T: TryIntoJs> TryIntoJs for [T; $len] {
+                type Value = JsArray;
+
+                fn try_into_js<'a, S: Scope<'a>>(self, scope: &mut S) -> JsResult<'a, JsArray> {
+                    let elements: Box<[T]> = Box::new(self);
+                    elements.into_vec().try_into_js(scope)
+                }
+            }
+        )*
+    }
+}
+
+array_conversions! {
+    0 1 2 3 4 5 6 7 8 9 10 11 12 13 14 15 16
+    17 18 19 20 21 22 23 24 25 26 27 28 29 30 31 32
+}
+
+impl<T: TryFromJs, H: BuildHasher + Default> TryFromJs for HashMap<String, T, H> {
+    fn from_js<'a, 'b, S: Scope<'a>>(scope: &mut S, value: Handle<'b, JsValue>) -> VmResult<Option<HashMap<String, T, H>>> {
+        let object = match plain_object(value) {
+            Some(object) => object,
+            None => return Ok(None)
+        };
+        let keys = object.get_own_property_names(scope)?.to_vec(scope)?;
+        let mut result = HashMap::with_capacity_and_hasher(keys.len(), H::default());
+        for key in keys {
+            let value = object.get(scope, key)?;
+            let value = match T::from_js(scope, value)? {
+                Some(value) => value,
+                None => return Ok(None)
+            };
+            result.insert(key.to_string(scope)?.value(), value);
+        }
+        Ok(Some(result))
+    }
+
+    fn describe() -> String {
+        format!("an object whose values are {}", plural(T::describe()))
+    }
+}
+
+impl<T: TryIntoJs, H: BuildHasher> TryIntoJs for HashMap<String, T, H> {
+    type Value = JsObject;
+
+    fn try_into_js<'a, S: Scope<'a>>(self, scope: &mut S) -> JsResult<'a, JsObject> {
+        let object = JsObject::new(scope);
+        for (key, value) in self {
+            let value = value.try_into_js(scope)?;
+            object.set(key.as_str(), value)?;
+        }
+        Ok(object)
+    }
+}
+
+// Objects other than arrays and functions, which would otherwise be read as maps or
+// structs with no properties.
+pub(crate) fn plain_object<'b>(value: Handle<'b, JsValue>) -> Option<Handle<'b, JsObject>> {
+    if value.is_a::<JsArray>() || value.is_a::<JsFunction>() {
+        return None;
+    }
+    value.downcast::<JsObject>()
+}
+
+#[doc(hidden)]
+pub mod derive_internal {
+    use mem::Handle;
+    use scope::Scope;
+    use vm::VmResult;
+    use js::{Object, JsValue, JsObject, JsUndefined};
+    use js::error::{JsError, Kind};
+    use super::{TryFromJs, TryIntoJs};
+
+    pub fn object<'b>(value: Handle<'b, JsValue>) -> Option<Handle<'b, JsObject>> {
+        super::plain_object(value)
+    }
+
+    pub fn read<'a, 'b, S: Scope<'a>, T: TryFromJs>(scope: &mut S, object: Handle<'b, JsObject>, key: &str) -> VmResult<Option<T>> {
+        let value = object.get(scope, key)?;
+        T::from_js(scope, value)
+    }
+
+    pub fn read_option<'a, 'b, S: Scope<'a>, T: TryFromJs>(scope: &mut S, object: Handle<'b, JsObject>, key: &str, setting: &mut T) -> VmResult<()> {
+        let value = object.get(scope, key)?;
+        if value.is_a::<JsUndefined>() {
+            return Ok(());
+        }
+        match T::from_js(scope, value)? {
+            Some(value) => {
+                *setting = value;
+                Ok(())
+            }
+            None => JsError::throw(Kind::TypeError, &format!("option {:?}: expected {}", key, T::describe()))
+        }
+    }
+
+    pub fn write<'a, 'b, S: Scope<'a>, T: TryIntoJs>(scope: &mut S, object: Handle<'b, JsObject>, key: &str, value: T) -> VmResult<()> {
+        let value = value.try_into_js(scope)?;
+        object.set(key, value)?;
+        Ok(())
+    }
+}
+
+/// A tuple of Rust types that a function's arguments can be read into all at once
+/// with `FunctionCall::args`.
+pub trait FromArguments: Sized {
+    fn from_arguments<'a, T: This>(call: &mut FunctionCall<'a, T>) -> VmResult<Self>;
+}
+
+impl FromArguments for () {
+    fn from_arguments<'a, T: This>(_: &mut FunctionCall<'a, T>) -> VmResult<()> {
+        Ok(())
+    }
+}
+
+// Turns a description like "an array of numbers" into "arrays of numbers" for use in
+// compound descriptions.
+fn plural(description: String) -> String {
+    let noun = if description.starts_with("an ") {
+        &description[3..]
+    } else if description.starts_with("a ") {
+        &description[2..]
+    } else {
+        return description;
+    };
+    match noun.find(|c| c == ' ' || c == ',') {
+        Some(i) => format!("{}s{}", &noun[..i], &noun[i..]),
+        None => format!("{}s", noun)
+    }
+}
+
+macro_rules! tuple_conversions {
+    ($(($len:expr; $($name:ident: $index:expr),*))*) => {
+        $(
+            impl<$($name: TryFromJs),*> TryFromJs for ($($name,)*) {
+                #[allow(non_snake_case)]
+                fn from_js<'a, 'b, S: Scope<'a>>(scope: &mut S, value: Handle<'b, JsValue>) -> VmResult<Option<Self>> {
+                    let array = match value.downcast::<JsArray>() {
+                        Some(array) => array,
+                        None => return Ok(None)
+                    };
+                    if array.len() != $len {
+                        return Ok(None);
+                    }
+                    $(
+                        let element = array.get(scope, $index as u32)?;
+                        let $name = match $name::from_js(scope, element)? {
+                            Some(element) => element,
+                            None => return Ok(None)
+                        };
+                    )*
+                    Ok(Some(($($name,)*)))
+                }
+
+                fn describe() -> String {
+                    let elements: Vec<String> = vec![$($name::describe()),*];
+                    format!("an array of the form [{}]", elements.join(", "))
+                }
+            }
+
+            impl<$($name: TryIntoJs),*> TryIntoJs for ($($name,)*) {
+                type Value = JsArray;
+
+                #[allow(non_snake_case)]
+                fn try_into_js<'a, S: Scope<'a>>(self, scope: &mut S) -> JsResult<'a, JsArray> {
+                    let ($($name,)*) = self;
+                    let array = JsArray::new(scope, $len);
+                    $(
+                        let element = $name.try_into_js(scope)?;
+                        array.set($index as u32, element)?;
+                    )*
+                    Ok(array)
+                }
+            }
+
+            impl<$($name: TryFromJs),*> FromArguments for ($($name,)*) {
+                #[allow(non_snake_case)]
+                fn from_arguments<'a, T: This>(call: &mut FunctionCall<'a, T>) -> VmResult<Self> {
+                    $(
+                        let $name = call.argument::<$name>($index)?;
+                    )*
+                    Ok(($($name,)*))
+                }
+            }
+        )*
+    }
+}
+
+tuple_conversions! {
+    (1; A: 0)
+    (2; A: 0, B: 1)
+    (3; A: 0, B: 1, C: 2)
+    (4; A: 0, B: 1, C: 2, D: 3)
+    (5; A: 0, B: 1, C: 2, D: 3, E: 4)
+    (6; A: 0, B: 1, C: 2, D: 3, E: 4, F: 5)
+    (7; A: 0, B: 1, C: 2, D: 3, E: 4, F: 5, G: 6)
+    (8; A: 0, B: 1, C: 2, D: 3, E: 4, F: 5, G: 6, H: 7)
+}
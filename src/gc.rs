@@ -0,0 +1,163 @@
+//! Callbacks around the garbage collector's cycles, e.g. for flushing a cache before
+//! a collection or recording how often collections happen.
+//!
+//! Callbacks run while the garbage collector has the heap to itself, so they can't
+//! use JavaScript. They're registered per context and dropped along with it.
+
+use std::cell::{Cell, RefCell};
+use std::mem;
+use std::os::raw::c_void;
+use std::ptr;
+use neon_runtime;
+use scope::Scope;
+
+/// Whether a callback is running before or after a collection.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum GcPhase {
+    Prologue,
+    Epilogue
+}
+
+/// The kind of collection.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum GcKind {
+    /// A collection of the young generation only.
+    Scavenge,
+    /// A full collection.
+    MarkSweepCompact,
+    /// A step of an incremental full collection.
+    IncrementalMarking,
+    /// The processing of weak references' callbacks.
+    ProcessWeakCallbacks,
+    /// A kind of collection this version of Neon doesn't know about.
+    Other
+}
+
+impl GcKind {
+    fn from_raw(kind: u32) -> GcKind {
+        match kind {
+            1 => GcKind::Scavenge,
+            4 => GcKind::MarkSweepCompact,
+            8 => GcKind::IncrementalMarking,
+            16 => GcKind::ProcessWeakCallbacks,
+            _ => GcKind::Other
+        }
+    }
+}
+
+/// A garbage collection event delivered to a callback.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct GcEvent {
+    pub phase: GcPhase,
+    pub kind: GcKind
+}
+
+/// Identifies a callback registered with `add_callback`, for removing it.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct GcCallbackId(usize);
+
+/// Registers `callback` to run before and after every garbage collection in the
+/// current context, until it's removed with `remove_callback`. Callbacks are only
+/// delivered on Node 10 and later.
+///
+/// Callbacks can be added and removed while callbacks are running: a removed callback
+/// isn't called again, and an added one is first called for the next event.
+pub fn add_callback<'a, S, F>(scope: &mut S, callback: F) -> GcCallbackId
+    where S: Scope<'a>,
+          F: FnMut(GcEvent) + 'static
+{
+    let mut isolate = scope.isolate();
+    let raw_isolate = isolate.to_raw();
+    let map = isolate.class_map();
+    if map.gc_registry().is_none() {
+        let mut registry = Box::new(GcRegistry {
+            raw: ptr::null_mut(),
+            callbacks: RefCell::new(Vec::new()),
+            running: RefCell::new(None),
+            removed: RefCell::new(Vec::new()),
+            next_id: Cell::new(0)
+        });
+        registry.raw = unsafe {
+            let data = &mut *registry as *mut GcRegistry as *mut c_void;
+            neon_runtime::gc::add_callbacks(raw_isolate, data, run_callbacks)
+        };
+        map.set_gc_registry(registry);
+    }
+    let registry = map.gc_registry().unwrap();
+    let id = registry.next_id.get();
+    registry.next_id.set(id + 1);
+    registry.callbacks.borrow_mut().push((id, Box::new(callback)));
+    GcCallbackId(id)
+}
+
+/// Removes a callback registered with `add_callback`, returning `false` if it had
+/// already been removed.
+pub fn remove_callback<'a, S: Scope<'a>>(scope: &mut S, id: GcCallbackId) -> bool {
+    let mut isolate = scope.isolate();
+    let registry = match isolate.class_map().gc_registry() {
+        Some(registry) => registry,
+        None => return false
+    };
+    let mut callbacks = registry.callbacks.borrow_mut();
+    let len = callbacks.len();
+    callbacks.retain(|&(other, _)| other != id.0);
+    if callbacks.len() < len {
+        return true;
+    }
+    if let Some(ref running) = *registry.running.borrow() {
+        let mut removed = registry.removed.borrow_mut();
+        if running.contains(&id.0) && !removed.contains(&id.0) {
+            removed.push(id.0);
+            return true;
+        }
+    }
+    false
+}
+
+// A context's garbage collection callbacks, which are registered with the isolate
+// as long as the registry is alive. While the callbacks run they're moved out of
+// `callbacks`, so the callbacks themselves can add to it, and `running` holds their
+// ids so they can be removed through `removed`.
+pub(crate) struct GcRegistry {
+    raw: *mut c_void,
+    callbacks: RefCell<Vec<(usize, Box<dyn FnMut(GcEvent)>)>>,
+    running: RefCell<Option<Vec<usize>>>,
+    removed: RefCell<Vec<usize>>,
+    next_id: Cell<usize>
+}
+
+impl Drop for GcRegistry {
+    fn drop(&mut self) {
+        if !self.raw.is_null() {
+            unsafe {
+                neon_runtime::gc::remove_callbacks(self.raw);
+            }
+        }
+    }
+}
+
+unsafe extern "C" fn run_callbacks(registry: *mut c_void, epilogue: bool, kind: u32) {
+    let registry = &*(registry as *const GcRegistry);
+    let event = GcEvent {
+        phase: if epilogue { GcPhase::Epilogue } else { GcPhase::Prologue },
+        kind: GcKind::from_raw(kind)
+    };
+    // A callback that allocates enough could trigger a collection while the callbacks
+    // are running, whose events are skipped.
+    if registry.running.borrow().is_some() {
+        return;
+    }
+    let mut callbacks = mem::replace(&mut *registry.callbacks.borrow_mut(), Vec::new());
+    *registry.running.borrow_mut() = Some(callbacks.iter().map(|&(id, _)| id).collect());
+    for &mut (id, ref mut callback) in callbacks.iter_mut() {
+        if !registry.removed.borrow().contains(&id) {
+            callback(event);
+        }
+    }
+    *registry.running.borrow_mut() = None;
+    let removed = mem::replace(&mut *registry.removed.borrow_mut(), Vec::new());
+    callbacks.retain(|&(id, _)| !removed.contains(&id));
+    let mut current = registry.callbacks.borrow_mut();
+    let added = mem::replace(&mut *current, callbacks);
+    current.extend(added);
+}
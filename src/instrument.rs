@@ -0,0 +1,202 @@
+//! Counters and timers for the boundary between Rust and JavaScript.
+//!
+//! With the `instrumentation` feature enabled, Neon records how many scopes, handles,
+//! background tasks, and native callbacks a thread creates, along with the time spent
+//! in each. Without the feature, recording compiles away to nothing.
+//!
+//! Debug builds also check each call to a native function for creating more handles
+//! than a limit set with `set_handle_limit`, which usually means a loop that should be
+//! allocating in a nested scope.
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+#[cfg(feature = "instrumentation")]
+use std::cell::Cell;
+#[cfg(feature = "instrumentation")]
+use std::time::{Duration, Instant};
+#[cfg(debug_assertions)]
+use std::thread;
+#[cfg(debug_assertions)]
+use neon_runtime;
+#[cfg(debug_assertions)]
+use vm::internal::Isolate;
+use scope::Scope;
+#[cfg(feature = "instrumentation")]
+use vm::JsResult;
+#[cfg(feature = "instrumentation")]
+use js::{JsObject, JsNumber, Object};
+#[cfg(feature = "instrumentation")]
+use js::date::millis;
+
+#[derive(Clone, Copy)]
+pub(crate) enum Event {
+    Scope,
+    Handle,
+    Task,
+    Callback
+}
+
+/// The number of times an event occurred and the total time spent in it. Time spent
+/// in a scope or callback includes everything that happens inside of it.
+#[cfg(feature = "instrumentation")]
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Counter {
+    pub count: u64,
+    pub time: Duration
+}
+
+/// A snapshot of the events recorded on the current thread.
+#[cfg(feature = "instrumentation")]
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Stats {
+    pub scopes: Counter,
+    pub handles: Counter,
+    pub tasks: Counter,
+    pub callbacks: Counter
+}
+
+#[cfg(feature = "instrumentation")]
+impl Stats {
+    fn counter(&mut self, event: Event) -> &mut Counter {
+        match event {
+            Event::Scope => &mut self.scopes,
+            Event::Handle => &mut self.handles,
+            Event::Task => &mut self.tasks,
+            Event::Callback => &mut self.callbacks
+        }
+    }
+}
+
+#[cfg(feature = "instrumentation")]
+thread_local! {
+    static STATS: Cell<Stats> = Cell::new(Stats::default());
+}
+
+/// Returns the events recorded on the current thread since it started or since the
+/// last call to `reset`.
+#[cfg(feature = "instrumentation")]
+pub fn stats() -> Stats {
+    STATS.with(|stats| stats.get())
+}
+
+/// Clears the events recorded on the current thread.
+#[cfg(feature = "instrumentation")]
+pub fn reset() {
+    STATS.with(|stats| stats.set(Stats::default()));
+}
+
+/// Creates a JavaScript object describing the current thread's events, with a
+/// `{ count, time }` object for each of `scopes`, `handles`, `tasks`, and `callbacks`.
+/// Times are in milliseconds.
+#[cfg(feature = "instrumentation")]
+pub fn diagnostics<'a, T: Scope<'a>>(scope: &mut T) -> JsResult<'a, JsObject> {
+    let stats = stats();
+    let object = JsObject::new(scope);
+    for &(name, counter) in &[("scopes", stats.scopes), ("handles", stats.handles),
+                              ("tasks", stats.tasks), ("callbacks", stats.callbacks)] {
+        let entry = JsObject::new(scope);
+        entry.set("count", JsNumber::new(scope, counter.count as f64))?;
+        entry.set("time", JsNumber::new(scope, millis(counter.time)))?;
+        object.set(name, entry)?;
+    }
+    Ok(object)
+}
+
+// Records an event when dropped, timing it from the call to `start`.
+#[cfg(feature = "instrumentation")]
+pub(crate) struct Timer {
+    event: Event,
+    start: Instant
+}
+
+#[cfg(feature = "instrumentation")]
+impl Drop for Timer {
+    fn drop(&mut self) {
+        let elapsed = self.start.elapsed();
+        let event = self.event;
+        STATS.with(|cell| {
+            let mut stats = cell.get();
+            {
+                let counter = stats.counter(event);
+                counter.count += 1;
+                counter.time += elapsed;
+            }
+            cell.set(stats);
+        });
+    }
+}
+
+#[cfg(feature = "instrumentation")]
+pub(crate) fn start(event: Event) -> Timer {
+    Timer {
+        event: event,
+        start: Instant::now()
+    }
+}
+
+#[cfg(not(feature = "instrumentation"))]
+pub(crate) struct Timer;
+
+#[cfg(not(feature = "instrumentation"))]
+#[inline(always)]
+pub(crate) fn start(_: Event) -> Timer {
+    Timer
+}
+
+static HANDLE_LIMIT: AtomicUsize = AtomicUsize::new(10_000);
+
+/// Sets how many handles a single call to a native function can create before Neon
+/// reports it, or turns the check off with zero. The limit is 10,000 by default.
+///
+/// The check only runs in debug builds. It prints a warning to stderr, or panics with
+/// the `panic-on-handle-limit` feature, which the calling function throws as an error.
+pub fn set_handle_limit(limit: usize) {
+    HANDLE_LIMIT.store(limit, Ordering::Relaxed);
+}
+
+/// The current limit set with `set_handle_limit`.
+pub fn handle_limit() -> usize {
+    HANDLE_LIMIT.load(Ordering::Relaxed)
+}
+
+// Checks the number of handles created since the call to `check_handles` when dropped.
+#[cfg(debug_assertions)]
+pub(crate) struct HandleCheck {
+    isolate: Isolate,
+    start: usize
+}
+
+#[cfg(debug_assertions)]
+impl Drop for HandleCheck {
+    fn drop(&mut self) {
+        let limit = handle_limit();
+        let live = unsafe { neon_runtime::scope::number_of_handles(self.isolate.to_raw()) };
+        let created = live.saturating_sub(self.start);
+        if limit == 0 || created <= limit || thread::panicking() {
+            return;
+        }
+        let message = format!("a native function created {} handles in one call, more than the limit of {}; \
+                               allocating in a nested scope frees handles that are no longer needed",
+                              created, limit);
+        if cfg!(feature = "panic-on-handle-limit") {
+            panic!("{}", message);
+        }
+        eprintln!("neon: warning: {}", message);
+    }
+}
+
+#[cfg(debug_assertions)]
+pub(crate) fn check_handles<'a, S: Scope<'a>>(scope: &S) -> HandleCheck {
+    HandleCheck {
+        isolate: scope.isolate(),
+        start: scope.live_handles()
+    }
+}
+
+#[cfg(not(debug_assertions))]
+pub(crate) struct HandleCheck;
+
+#[cfg(not(debug_assertions))]
+#[inline(always)]
+pub(crate) fn check_handles<'a, S: Scope<'a>>(_: &S) -> HandleCheck {
+    HandleCheck
+}
@@ -1,7 +1,15 @@
 use std::mem;
-use vm::VmResult;
+use std::slice;
+use std::ptr;
+use std::ops::Range;
+use std::os::raw::c_void;
+use std::marker::PhantomData;
+use std::rc::{Rc, Weak};
+use std::cell::RefCell;
+use vm::{VmResult, JsResult, Throw};
 use js::{Value, Object, build};
 use js::internal::ValueInternal;
+use js::error::{JsError, Kind};
 use mem::{Handle, Managed};
 use vm::Lock;
 use vm::internal::LockState;
@@ -10,14 +18,57 @@ use cslice::CMutSlice;
 use neon_runtime;
 use neon_runtime::raw;
 
-#[repr(C)]
+/// A Node `Buffer` object.
+#[repr(transparent)]
 #[derive(Clone, Copy)]
 pub struct JsBuffer(raw::Local);
 
 impl JsBuffer {
+    /// Creates a new zero-filled `Buffer` of `size` bytes. A size passed in from
+    /// JavaScript can be checked with `JsNumber::to_u32_strict` rather than cast with `as`.
     pub fn new<'a, T: Scope<'a>>(_: &mut T, size: u32) -> VmResult<Handle<'a, JsBuffer>> {
         build(|out| { unsafe { neon_runtime::buffer::new(out, size) } })
     }
+
+    /// Creates a new `Buffer` of `size` bytes without initializing its contents.
+    ///
+    /// This is unsafe because the contents are whatever happened to be in memory, which
+    /// must not leak back to JavaScript before being overwritten.
+    pub unsafe fn uninitialized<'a, T: Scope<'a>>(_: &mut T, size: u32) -> VmResult<Handle<'a, JsBuffer>> {
+        build(|out| { neon_runtime::buffer::uninitialized(out, size) })
+    }
+
+    /// Creates a new `Buffer` containing a copy of `data`.
+    pub fn from_slice<'a, T: Scope<'a>>(_: &mut T, data: &[u8]) -> VmResult<Handle<'a, JsBuffer>> {
+        if data.len() > u32::max_value() as usize {
+            return JsError::throw(Kind::RangeError, "slice is too large for a Buffer");
+        }
+        build(|out| { unsafe { neon_runtime::buffer::from_slice(out, data.as_ptr(), data.len() as u32) } })
+    }
+
+    /// Creates a new `Buffer` that takes ownership of `data` without copying it. The vector
+    /// is dropped once the buffer is garbage collected.
+    pub fn external<'a, T: Scope<'a>>(_: &mut T, data: Vec<u8>) -> VmResult<Handle<'a, JsBuffer>> {
+        if data.len() > u32::max_value() as usize {
+            return JsError::throw(Kind::RangeError, "vector is too large for a Buffer");
+        }
+        let mut data = Box::new(data);
+        let ptr = data.as_mut_ptr();
+        let size = data.len() as u32;
+        let hint = Box::into_raw(data);
+        let result = build(|out| {
+            unsafe { neon_runtime::buffer::external(out, ptr, size, drop_external, hint as *mut c_void) }
+        });
+        if result.is_err() {
+            drop_external(ptr, hint as *mut c_void);
+        }
+        result
+    }
+}
+
+extern "C" fn drop_external(_: *mut u8, hint: *mut c_void) {
+    let data: Box<Vec<u8>> = unsafe { Box::from_raw(hint as *mut Vec<u8>) };
+    mem::drop(data);
 }
 
 impl Managed for JsBuffer {
@@ -27,6 +78,8 @@ impl Managed for JsBuffer {
 }
 
 impl ValueInternal for JsBuffer {
+    fn name() -> String { "JsBuffer".to_string() }
+
     fn is_typeof<Other: Value>(other: Other) -> bool {
         unsafe { neon_runtime::tag::is_buffer(other.to_raw()) }
     }
@@ -37,23 +90,109 @@ impl Value for JsBuffer { }
 impl Object for JsBuffer { }
 
 impl<'a> Lock for &'a mut JsBuffer {
-    type Internals = CMutSlice<'a, u8>;
+    type Internals = BinaryData<'a>;
 
     unsafe fn expose(self, state: &mut LockState) -> Self::Internals {
-        let mut result = mem::uninitialized();
+        let mut result: CMutSlice<u8> = mem::uninitialized();
         neon_runtime::buffer::data(&mut result, self.to_raw());
-        state.use_buffer(result);
+        BinaryData::new(state.use_buffer("JsBuffer", result))
+    }
+}
+
+/// A pool of fixed-size chunks of memory for `Buffer`s, for modules that produce many
+/// short-lived binary messages. Each chunk is handed to JavaScript without copying, and
+/// returned to the pool instead of being freed once its buffer is garbage collected.
+pub struct BufferPool {
+    chunk_size: u32,
+    capacity: usize,
+    free: Rc<RefCell<Vec<Vec<u8>>>>
+}
+
+struct PooledChunk {
+    data: Vec<u8>,
+    pool: Weak<RefCell<Vec<Vec<u8>>>>,
+    capacity: usize
+}
+
+impl BufferPool {
+    /// Creates a pool of `chunk_size`-byte buffers that keeps up to `capacity` unused
+    /// chunks around for reuse.
+    pub fn new<'a, T: Scope<'a>>(_: &mut T, chunk_size: u32, capacity: usize) -> BufferPool {
+        BufferPool {
+            chunk_size: chunk_size,
+            capacity: capacity,
+            free: Rc::new(RefCell::new(Vec::with_capacity(capacity)))
+        }
+    }
+
+    /// Creates a zero-filled `Buffer` backed by a recycled chunk if one is available.
+    pub fn get<'a, T: Scope<'a>>(&self, _: &mut T) -> JsResult<'a, JsBuffer> {
+        let mut data = match self.free.borrow_mut().pop() {
+            Some(mut data) => {
+                for byte in data.iter_mut() {
+                    *byte = 0;
+                }
+                data
+            }
+            None => vec![0; self.chunk_size as usize]
+        };
+        let ptr = data.as_mut_ptr();
+        let hint = Box::into_raw(Box::new(PooledChunk {
+            data: data,
+            pool: Rc::downgrade(&self.free),
+            capacity: self.capacity
+        }));
+        let result = build(|out| {
+            unsafe { neon_runtime::buffer::external(out, ptr, self.chunk_size, recycle_chunk, hint as *mut c_void) }
+        });
+        if result.is_err() {
+            recycle_chunk(ptr, hint as *mut c_void);
+        }
         result
     }
+
+    /// The number of unused chunks currently held by the pool.
+    pub fn available(&self) -> usize {
+        self.free.borrow().len()
+    }
+}
+
+extern "C" fn recycle_chunk(_: *mut u8, hint: *mut c_void) {
+    let chunk: Box<PooledChunk> = unsafe { Box::from_raw(hint as *mut PooledChunk) };
+    let chunk = *chunk;
+    if let Some(pool) = chunk.pool.upgrade() {
+        let mut free = pool.borrow_mut();
+        if free.len() < chunk.capacity {
+            free.push(chunk.data);
+        }
+    }
 }
 
-#[repr(C)]
+/// A JavaScript `ArrayBuffer` object.
+#[repr(transparent)]
 #[derive(Clone, Copy)]
 pub struct JsArrayBuffer(raw::Local);
 
 impl JsArrayBuffer {
     pub fn new<'a, T: Scope<'a>>(scope: &mut T, size: u32) -> VmResult<Handle<'a, JsArrayBuffer>> {
-        build(|out| { unsafe { neon_runtime::arraybuffer::new(out, mem::transmute(scope.isolate()), size) } })
+        build(|out| { unsafe { neon_runtime::arraybuffer::new(out, scope.isolate().to_raw(), size) } })
+    }
+
+    /// Detaches the buffer from its contents, which are freed, leaving it and any views
+    /// of it empty. Throws a `TypeError` if the buffer can't be detached, e.g. because it
+    /// backs a WebAssembly memory.
+    pub fn detach<'a, T: Scope<'a>>(self, scope: &mut T) -> VmResult<()> {
+        if unsafe { neon_runtime::arraybuffer::detach(scope.isolate().to_raw(), self.to_raw()) } {
+            Ok(())
+        } else {
+            Err(Throw)
+        }
+    }
+
+    /// Indicates whether the buffer has been detached. Before Node 16, this only
+    /// recognizes buffers detached with `detach`.
+    pub fn is_detached(self) -> bool {
+        unsafe { neon_runtime::arraybuffer::is_detached(self.to_raw()) }
     }
 }
 
@@ -64,6 +203,8 @@ impl Managed for JsArrayBuffer {
 }
 
 impl ValueInternal for JsArrayBuffer {
+    fn name() -> String { "JsArrayBuffer".to_string() }
+
     fn is_typeof<Other: Value>(other: Other) -> bool {
         unsafe { neon_runtime::tag::is_arraybuffer(other.to_raw()) }
     }
@@ -74,12 +215,293 @@ impl Value for JsArrayBuffer { }
 impl Object for JsArrayBuffer { }
 
 impl<'a> Lock for &'a mut JsArrayBuffer {
-    type Internals = CMutSlice<'a, u8>;
+    type Internals = BinaryData<'a>;
 
     unsafe fn expose(self, state: &mut LockState) -> Self::Internals {
-        let mut result = mem::uninitialized();
+        if self.is_detached() {
+            panic!("attempt to lock a detached ArrayBuffer");
+        }
+        let mut result: CMutSlice<u8> = mem::uninitialized();
         neon_runtime::arraybuffer::data(&mut result, self.to_raw());
-        state.use_buffer(result);
-        result
+        BinaryData::new(state.use_buffer("JsArrayBuffer", result))
+    }
+}
+
+macro_rules! bigint_array {
+    ($(#[$attr:meta])* $name:ident, $elem:ty, $new:ident, $is:ident) => {
+        $(#[$attr])*
+        #[repr(transparent)]
+        #[derive(Clone, Copy)]
+        pub struct $name(raw::Local);
+
+        impl $name {
+            /// Creates a new zero-filled array of `len` elements. Throws a `TypeError` on
+            /// versions of Node without BigInt typed arrays.
+            pub fn new<'a, T: Scope<'a>>(scope: &mut T, len: u32) -> JsResult<'a, $name> {
+                build(|out| { unsafe { neon_runtime::typedarray::$new(out, scope.isolate().to_raw(), len) } })
+            }
+        }
+
+        impl Managed for $name {
+            fn to_raw(self) -> raw::Local { self.0 }
+
+            fn from_raw(h: raw::Local) -> Self { $name(h) }
+        }
+
+        impl ValueInternal for $name {
+            fn name() -> String { stringify!($name).to_string() }
+
+            fn is_typeof<Other: Value>(other: Other) -> bool {
+                unsafe { neon_runtime::tag::$is(other.to_raw()) }
+            }
+        }
+
+        impl Value for $name { }
+
+        impl Object for $name { }
+
+        impl<'a> Lock for &'a mut $name {
+            type Internals = &'a mut [$elem];
+
+            unsafe fn expose(self, state: &mut LockState) -> Self::Internals {
+                let mut result: CMutSlice<u8> = mem::uninitialized();
+                neon_runtime::typedarray::data(&mut result, self.to_raw());
+                let result = state.use_buffer(stringify!($name), result);
+                let len = result.len() / mem::size_of::<$elem>();
+                if len == 0 {
+                    &mut []
+                } else {
+                    slice::from_raw_parts_mut(result.as_ptr() as *mut $elem, len)
+                }
+            }
+        }
+    }
+}
+
+bigint_array! {
+    /// A JavaScript `BigInt64Array`, which can be locked as a `&mut [i64]`.
+    JsBigInt64Array, i64, new_bigint64array, is_bigint64array
+}
+
+bigint_array! {
+    /// A JavaScript `BigUint64Array`, which can be locked as a `&mut [u64]`.
+    JsBigUint64Array, u64, new_biguint64array, is_biguint64array
+}
+
+/// The contents of a locked `JsBuffer` or `JsArrayBuffer`, which can be viewed as a
+/// slice of any plain-old-data element type.
+pub struct BinaryData<'a> {
+    base: *mut u8,
+    size: usize,
+    phantom: PhantomData<&'a mut [u8]>
+}
+
+/// The element types that binary data can be viewed as: types for which every bit
+/// pattern is a valid value.
+pub unsafe trait BinaryViewType: Sized { }
+
+unsafe impl BinaryViewType for u8 { }
+unsafe impl BinaryViewType for i8 { }
+unsafe impl BinaryViewType for u16 { }
+unsafe impl BinaryViewType for i16 { }
+unsafe impl BinaryViewType for u32 { }
+unsafe impl BinaryViewType for i32 { }
+unsafe impl BinaryViewType for u64 { }
+unsafe impl BinaryViewType for i64 { }
+unsafe impl BinaryViewType for f32 { }
+unsafe impl BinaryViewType for f64 { }
+
+/// Plain-old-data types that can be viewed in place over binary data: binary view types,
+/// arrays of them, and `#[repr(C)]` structs of them without padding, which can implement
+/// this trait with `#[derive(BufferLayout)]` from the `neon-derive` crate.
+pub unsafe trait BufferLayout: Copy {
+    #[doc(hidden)]
+    fn assert_fields() { }
+}
+
+macro_rules! buffer_layouts {
+    ($($ty:ty)*) => { $(unsafe impl BufferLayout for $ty { })* }
+}
+
+macro_rules! buffer_layout_arrays {
+    ($($n:expr)*) => { $(unsafe impl<T: BufferLayout> BufferLayout for [T; $n] { })* }
+}
+
+buffer_layouts! { u8 i8 u16 i16 u32 i32 u64 i64 f32 f64 }
+
+buffer_layout_arrays! {
+    0 1 2 3 4 5 6 7 8 9 10 11 12 13 14 15 16
+    17 18 19 20 21 22 23 24 25 26 27 28 29 30 31 32
+}
+
+impl<'a> BinaryData<'a> {
+    fn new(slice: CMutSlice<'a, u8>) -> BinaryData<'a> {
+        BinaryData {
+            base: slice.as_ptr() as *mut u8,
+            size: slice.len(),
+            phantom: PhantomData
+        }
+    }
+
+    /// The size of the data in bytes.
+    pub fn len(&self) -> usize {
+        self.size
+    }
+
+    /// Views the data as a slice of `T`.
+    ///
+    /// Panics if the data is not aligned for `T` or its size is not a multiple of the
+    /// size of `T`.
+    pub fn as_slice<T: BinaryViewType>(&self) -> &[T] {
+        match self.view_len::<T>() {
+            0 => &[],
+            len => unsafe { slice::from_raw_parts(self.base as *const T, len) }
+        }
+    }
+
+    /// Views the data as a mutable slice of `T`.
+    ///
+    /// Panics if the data is not aligned for `T` or its size is not a multiple of the
+    /// size of `T`.
+    pub fn as_mut_slice<T: BinaryViewType>(&mut self) -> &mut [T] {
+        match self.view_len::<T>() {
+            0 => &mut [],
+            len => unsafe { slice::from_raw_parts_mut(self.base as *mut T, len) }
+        }
+    }
+
+    /// Views the data at the byte offset `offset` as a `T`.
+    ///
+    /// Panics if `T` doesn't fit within the data at `offset`, or if the data at `offset` is
+    /// not aligned for `T`.
+    pub fn view<T: BufferLayout>(&self, offset: usize) -> &T {
+        unsafe { &*(self.layout_ptr::<T>(offset) as *const T) }
+    }
+
+    /// Views the data at the byte offset `offset` as a mutable `T`.
+    ///
+    /// Panics if `T` doesn't fit within the data at `offset`, or if the data at `offset` is
+    /// not aligned for `T`.
+    pub fn view_mut<T: BufferLayout>(&mut self, offset: usize) -> &mut T {
+        unsafe { &mut *(self.layout_ptr::<T>(offset) as *mut T) }
+    }
+
+    fn layout_ptr<T: BufferLayout>(&self, offset: usize) -> *mut u8 {
+        self.check_bounds(offset, mem::size_of::<T>());
+        let ptr = self.base.wrapping_offset(offset as isize);
+        if (ptr as usize) % mem::align_of::<T>() != 0 {
+            panic!("binary data at offset {} (0x{:x}) is not aligned for a {}-byte aligned view", offset, ptr as usize, mem::align_of::<T>());
+        }
+        ptr
+    }
+
+    fn view_len<T: BinaryViewType>(&self) -> usize {
+        if self.size == 0 {
+            return 0;
+        }
+        let element_size = mem::size_of::<T>();
+        if (self.base as usize) % mem::align_of::<T>() != 0 {
+            panic!("binary data (0x{:x}) is not aligned for {}-byte elements", self.base as usize, element_size);
+        }
+        if self.size % element_size != 0 {
+            panic!("binary data of {} bytes is not a multiple of {}-byte elements", self.size, element_size);
+        }
+        self.size / element_size
+    }
+}
+
+macro_rules! binary_accessors {
+    ($( $ty:ident as $bits:ident : $read_le:ident, $read_be:ident, $write_le:ident, $write_be:ident; )*) => {
+        impl<'a> BinaryData<'a> {
+            $(
+                /// Reads a little-endian value at the byte offset `offset`, panicking if it is out of bounds.
+                pub fn $read_le(&self, offset: usize) -> $ty {
+                    $ty::from_bits($bits::from_le(self.read(offset)))
+                }
+
+                /// Reads a big-endian value at the byte offset `offset`, panicking if it is out of bounds.
+                pub fn $read_be(&self, offset: usize) -> $ty {
+                    $ty::from_bits($bits::from_be(self.read(offset)))
+                }
+
+                /// Writes a little-endian value at the byte offset `offset`, panicking if it is out of bounds.
+                pub fn $write_le(&mut self, offset: usize, value: $ty) {
+                    self.write(offset, value.to_bits().to_le())
+                }
+
+                /// Writes a big-endian value at the byte offset `offset`, panicking if it is out of bounds.
+                pub fn $write_be(&mut self, offset: usize, value: $ty) {
+                    self.write(offset, value.to_bits().to_be())
+                }
+            )*
+        }
+    }
+}
+
+// Lets integers and floats share the accessor definitions above.
+trait Bits<T> {
+    fn from_bits(bits: T) -> Self;
+    fn to_bits(self) -> T;
+}
+
+macro_rules! identity_bits {
+    ($($ty:ident)*) => {
+        $(
+            impl Bits<$ty> for $ty {
+                fn from_bits(bits: $ty) -> $ty { bits }
+                fn to_bits(self) -> $ty { self }
+            }
+        )*
+    }
+}
+
+identity_bits! { u16 i16 u32 i32 u64 i64 }
+
+binary_accessors! {
+    u16 as u16: read_u16_le, read_u16_be, write_u16_le, write_u16_be;
+    i16 as i16: read_i16_le, read_i16_be, write_i16_le, write_i16_be;
+    u32 as u32: read_u32_le, read_u32_be, write_u32_le, write_u32_be;
+    i32 as i32: read_i32_le, read_i32_be, write_i32_le, write_i32_be;
+    u64 as u64: read_u64_le, read_u64_be, write_u64_le, write_u64_be;
+    i64 as i64: read_i64_le, read_i64_be, write_i64_le, write_i64_be;
+    f32 as u32: read_f32_le, read_f32_be, write_f32_le, write_f32_be;
+    f64 as u64: read_f64_le, read_f64_be, write_f64_le, write_f64_be;
+}
+
+impl<'a> BinaryData<'a> {
+    /// Sets every byte of the data to `value`.
+    pub fn fill(&mut self, value: u8) {
+        for byte in self.as_mut_slice::<u8>() {
+            *byte = value;
+        }
+    }
+
+    /// Copies the bytes in `src` to the byte offset `dest`, which may overlap with `src`.
+    /// Panics if either range is out of bounds.
+    pub fn copy_within(&mut self, src: Range<usize>, dest: usize) {
+        if src.start > src.end || src.end > self.size {
+            panic!("source range {}..{} is out of bounds for binary data of {} bytes", src.start, src.end, self.size);
+        }
+        let count = src.end - src.start;
+        self.check_bounds(dest, count);
+        unsafe {
+            ptr::copy(self.base.offset(src.start as isize), self.base.offset(dest as isize), count);
+        }
+    }
+
+    fn check_bounds(&self, offset: usize, size: usize) {
+        if offset > self.size || size > self.size - offset {
+            panic!("offset {} is out of bounds for {} bytes of binary data of {} bytes", offset, size, self.size);
+        }
+    }
+
+    fn read<T: Copy>(&self, offset: usize) -> T {
+        self.check_bounds(offset, mem::size_of::<T>());
+        unsafe { ptr::read_unaligned(self.base.offset(offset as isize) as *const T) }
+    }
+
+    fn write<T: Copy>(&mut self, offset: usize, value: T) {
+        self.check_bounds(offset, mem::size_of::<T>());
+        unsafe { ptr::write_unaligned(self.base.offset(offset as isize) as *mut T, value) }
     }
 }
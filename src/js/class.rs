@@ -7,14 +7,17 @@ use neon_runtime;
 use neon_runtime::raw;
 use mem::{Handle, Managed};
 use scope::Scope;
-use vm::{JsResult, VmResult, Lock, Throw, This, Kernel};
+use scope::internal::ScopeInternal;
+use vm::{JsResult, VmResult, Lock, Throw, This, Kernel, FunctionCall};
 use vm::internal::{Isolate, LockState};
-use js::{Value, JsFunction, Object, JsValue, build};
+use js::{Value, JsFunction, Object, JsObject, JsValue, build};
 use js::internal::ValueInternal;
 use js::error::{JsError, Kind};
-use self::internal::{ClassMetadata, MethodKernel, ConstructorCallKernel, AllocateKernel, ConstructKernel};
+use self::internal::{ClassMetadata, MethodKernel, ConstructorCallKernel, AllocateKernel, ConstructKernel, forget_external_size};
 
 pub(crate) mod internal {
+    use std::cell::RefCell;
+    use std::collections::HashMap;
     use std::mem;
     use std::marker::PhantomData;
     use std::os::raw::c_void;
@@ -26,8 +29,48 @@ pub(crate) mod internal {
     use scope::internal::ScopeInternal;
     use js::{JsValue, JsObject, JsUndefined};
     use vm::{JsResult, VmResult, FunctionCall, CallbackInfo, Kernel};
+    #[cfg_attr(feature = "unstable-raw", allow(unused_imports))]
     use mem::{Handle, Managed};
     use js::error::convert_panics;
+    use instrument::{self, Event};
+
+    thread_local! {
+        // The number of bytes last reported to V8 for each instance of a class with a
+        // size hint, keyed by the address of the instance's internals.
+        static EXTERNAL_SIZES: RefCell<HashMap<usize, usize>> = RefCell::new(HashMap::new());
+    }
+
+    fn adjust_external_memory(old: usize, new: usize) {
+        if old != new {
+            unsafe {
+                neon_runtime::mem::adjust_external_memory(new as i64 - old as i64);
+            }
+        }
+    }
+
+    /// Starts reporting the size of newly allocated internals to V8.
+    pub fn track_external_size(internals: *mut c_void, size: usize) {
+        EXTERNAL_SIZES.with(|sizes| sizes.borrow_mut().insert(internals as usize, size));
+        adjust_external_memory(0, size);
+    }
+
+    /// Reports any change in the size of internals since they were last measured.
+    pub fn update_external_size(internals: *mut c_void, size: usize) {
+        let old = EXTERNAL_SIZES.with(|sizes| {
+            sizes.borrow_mut().get_mut(&(internals as usize)).map(|entry| mem::replace(entry, size))
+        });
+        if let Some(old) = old {
+            adjust_external_memory(old, size);
+        }
+    }
+
+    /// Stops reporting the size of internals that are about to be freed.
+    pub fn forget_external_size(internals: *mut c_void) {
+        let entry = EXTERNAL_SIZES.with(|sizes| sizes.borrow_mut().remove(&(internals as usize)));
+        if let Some(size) = entry {
+            adjust_external_memory(size, 0);
+        }
+    }
 
     #[repr(C)]
     pub struct MethodKernel<T: Class>(fn(FunctionCall<T>) -> JsResult<JsValue>);
@@ -40,6 +83,7 @@ pub(crate) mod internal {
 
     impl<T: Class> Kernel<()> for MethodKernel<T> {
         extern "C" fn callback(info: &CallbackInfo) {
+            let _timer = instrument::start(Event::Callback);
             info.scope().with(|scope| {
                 let data = info.data();
                 let call = info.as_call(scope);
@@ -49,13 +93,22 @@ pub(crate) mod internal {
                 if !this.is_a::<T>() {
                     if let Ok(metadata) = T::metadata(call.scope) {
                         unsafe {
-                            neon_runtime::class::throw_this_error(mem::transmute(call.scope.isolate()), metadata.pointer);
+                            neon_runtime::class::throw_this_error(call.scope.isolate().to_raw(), metadata.pointer);
                         }
                     }
                     return;
                 }
+                let internals = unsafe { neon_runtime::class::get_instance_internals(this.to_raw()) };
                 let MethodKernel(kernel) = unsafe { Self::from_wrapper(data.to_raw()) };
-                if let Ok(value) = convert_panics(|| { kernel(call) }) {
+                let result = convert_panics(|| {
+                    let _handles = instrument::check_handles(call.scope);
+                    kernel(call)
+                });
+                // The method may have grown or shrunk the instance's internals.
+                if let Some(hint) = T::size_hint() {
+                    update_external_size(internals, hint(unsafe { &*(internals as *const T::Internals) }));
+                }
+                if let Ok(value) = result {
                     info.set_return(value);
                 }
             })
@@ -82,7 +135,7 @@ pub(crate) mod internal {
             let mut scope = info.scope();
             if let Ok(metadata) = T::metadata(&mut scope) {
                 unsafe {
-                    neon_runtime::class::throw_call_error(mem::transmute(scope.isolate()), metadata.pointer);
+                    neon_runtime::class::throw_call_error(scope.isolate().to_raw(), metadata.pointer);
                 }
             }
             return;
@@ -91,6 +144,7 @@ pub(crate) mod internal {
 
     impl Kernel<()> for ConstructorCallKernel {
         extern "C" fn callback(info: &CallbackInfo) {
+            let _timer = instrument::start(Event::Callback);
             info.scope().with(|scope| {
                 let data = info.data();
                 let ConstructorCallKernel(kernel) = unsafe { Self::from_wrapper(data.to_raw()) };
@@ -126,8 +180,12 @@ pub(crate) mod internal {
                 let AllocateKernel(kernel) = unsafe { Self::from_wrapper(data.to_raw()) };
                 let call = info.as_call(scope);
                 if let Ok(value) = convert_panics(|| { kernel(call) }) {
-                    let p = Box::into_raw(Box::new(value));
-                    unsafe { mem::transmute(p) }
+                    let size = T::size_hint().map(|hint| hint(&value));
+                    let p = Box::into_raw(Box::new(value)) as *mut c_void;
+                    if let Some(size) = size {
+                        track_external_size(p, size);
+                    }
+                    p
                 } else {
                     null_mut()
                 }
@@ -154,6 +212,7 @@ pub(crate) mod internal {
 
     impl<T: Class> Kernel<bool> for ConstructKernel<T> {
         extern "C" fn callback(info: &CallbackInfo) -> bool {
+            let _timer = instrument::start(Event::Callback);
             info.scope().with(|scope| {
                 let data = info.data();
                 let ConstructKernel(kernel) = unsafe { Self::from_wrapper(data.to_raw()) };
@@ -187,7 +246,7 @@ pub(crate) mod internal {
     impl ClassMetadata {
         pub unsafe fn class<'a, T: Class, U: Scope<'a>>(&self, scope: &mut U) -> Handle<'a, JsClass<T>> {
             let mut local: raw::Local = mem::zeroed();
-            neon_runtime::class::metadata_to_class(&mut local, mem::transmute(scope.isolate()), self.pointer);
+            neon_runtime::class::metadata_to_class(&mut local, scope.isolate().to_raw(), self.pointer);
             Handle::new_internal(JsClass {
                 handle: local,
                 phantom: PhantomData
@@ -205,7 +264,9 @@ pub struct ClassDescriptor<'a, T: Class> {
     allocate: AllocateKernel<T>,
     call: Option<ConstructorCallKernel>,
     construct: Option<ConstructKernel<T>>,
-    methods: Vec<(&'a str, MethodKernel<T>)>
+    methods: Vec<(&'a str, MethodKernel<T>)>,
+    iterator: Option<MethodKernel<T>>,
+    async_iterator: Option<MethodKernel<T>>
 }
 
 impl<'a, T: Class> ClassDescriptor<'a, T> {
@@ -215,7 +276,9 @@ impl<'a, T: Class> ClassDescriptor<'a, T> {
             allocate: allocate,
             call: None,
             construct: None,
-            methods: Vec::new()
+            methods: Vec::new(),
+            iterator: None,
+            async_iterator: None
         }
     }
 
@@ -233,10 +296,28 @@ impl<'a, T: Class> ClassDescriptor<'a, T> {
         self.methods.push((name, kernel));
         self
     }
+
+    /// Installs `kernel` as the class's `Symbol.iterator` method, making instances
+    /// usable with `for...of`. See `js::iter::JsIterator` for returning a Rust iterator.
+    pub fn iterator(mut self, kernel: MethodKernel<T>) -> Self {
+        self.iterator = Some(kernel);
+        self
+    }
+
+    /// Installs `kernel` as the class's `Symbol.asyncIterator` method, making instances
+    /// usable with `for await...of`. This is ignored on versions of Node without
+    /// `Symbol.asyncIterator`.
+    pub fn async_iterator(mut self, kernel: MethodKernel<T>) -> Self {
+        self.async_iterator = Some(kernel);
+        self
+    }
 }
 
-extern "C" fn drop_internals<T>(internals: *mut c_void) {
-    let p: Box<T> = unsafe { Box::from_raw(mem::transmute(internals)) };
+extern "C" fn drop_internals<T: Class>(internals: *mut c_void) {
+    if T::size_hint().is_some() {
+        forget_external_size(internals);
+    }
+    let p: Box<T::Internals> = unsafe { Box::from_raw(internals as *mut T::Internals) };
     mem::drop(p);
 }
 
@@ -245,6 +326,14 @@ pub trait Class: Managed + Any {
 
     fn setup<'a, T: Scope<'a>>(_: &mut T) -> VmResult<ClassDescriptor<'a, Self>>;
 
+    /// A function measuring the number of bytes an instance's internals keep alive on
+    /// the Rust heap, which is reported to the garbage collector as external memory so
+    /// that instances owning large buffers are collected promptly. The size is measured
+    /// when an instance is allocated and again after each call to one of its methods.
+    fn size_hint() -> Option<fn(&Self::Internals) -> usize> {
+        None
+    }
+
     fn class<'a, T: Scope<'a>>(scope: &mut T) -> JsResult<'a, JsClass<Self>> {
         let metadata = Self::metadata(scope)?;
         Ok(unsafe { metadata.class(scope) })
@@ -253,6 +342,14 @@ pub trait Class: Managed + Any {
     fn describe<'a>(name: &'a str, allocate: AllocateKernel<Self>) -> ClassDescriptor<'a, Self> {
         ClassDescriptor::<Self>::new(name, allocate)
     }
+
+    /// Returns the class's prototype object, so that additional methods defined at runtime
+    /// (for example in JavaScript, or as mixins) can be attached to every instance.
+    fn prototype<'a, T: Scope<'a>>(scope: &mut T) -> JsResult<'a, JsObject> {
+        let class = Self::class(scope)?;
+        let constructor = class.constructor(scope)?;
+        constructor.get(scope, "prototype")?.check::<JsObject>()
+    }
 }
 
 unsafe impl<T: Class> This for T {
@@ -281,7 +378,7 @@ pub(crate) trait ClassInternal: Class {
     fn create<'a, T: Scope<'a>>(scope: &mut T) -> VmResult<ClassMetadata> {
         let descriptor = Self::setup(scope)?;
         unsafe {
-            let isolate: *mut c_void = mem::transmute(scope.isolate());
+            let isolate: *mut c_void = scope.isolate().to_raw();
 
             let (allocate_callback, allocate_kernel) = descriptor.allocate.export();
 
@@ -299,7 +396,7 @@ pub(crate) trait ClassInternal: Class {
                                                                     allocate_callback, allocate_kernel,
                                                                     construct_callback, construct_kernel,
                                                                     call_callback, call_kernel,
-                                                                    drop_internals::<Self::Internals>);
+                                                                    drop_internals::<Self>);
 
             if metadata_pointer.is_null() {
                 return Err(Throw);
@@ -323,11 +420,24 @@ pub(crate) trait ClassInternal: Class {
                 }
             }
 
+            for (is_async, method) in vec![(false, descriptor.iterator), (true, descriptor.async_iterator)] {
+                if let Some(method) = method {
+                    let method: Handle<JsValue> = build(|out| {
+                        let (method_callback, method_kernel) = method.export();
+                        neon_runtime::fun::new_template(out, isolate, method_callback, method_kernel)
+                    })?;
+                    if !neon_runtime::class::add_iterator(isolate, metadata_pointer, is_async, method.to_raw()) {
+                        return Err(Throw);
+                    }
+                }
+            }
+
             let metadata = ClassMetadata {
                 pointer: metadata_pointer
             };
 
             scope.isolate().class_map().set(TypeId::of::<Self>(), metadata);
+            scope.isolate().class_map().set_class_name(TypeId::of::<Self>(), class_name);
 
             Ok(metadata)
         }
@@ -337,10 +447,16 @@ pub(crate) trait ClassInternal: Class {
 impl<T: Class> ClassInternal for T { }
 
 impl<T: Class> ValueInternal for T {
+    fn name() -> String {
+        let mut isolate = Isolate::current();
+        match isolate.class_map().class_name(&TypeId::of::<T>()) {
+            Some(name) => name.to_string(),
+            None => "an instance of a class".to_string()
+        }
+    }
+
     fn is_typeof<Other: Value>(value: Other) -> bool {
-        let mut isolate: Isolate = unsafe {
-            mem::transmute(neon_runtime::call::current_isolate())
-        };
+        let mut isolate = Isolate::current();
         let map = isolate.class_map();
         match map.get(&TypeId::of::<T>()) {
             None => false,
@@ -353,7 +469,7 @@ impl<T: Class> ValueInternal for T {
 
 impl<T: Class> Value for T { }
 
-#[repr(C)]
+#[repr(transparent)]
 #[derive(Clone, Copy)]
 pub struct JsClass<T: Class> {
     handle: raw::Local,
@@ -379,12 +495,51 @@ impl<T: Class> JsClass<T> {
     }
 }
 
+/// The call of a method of the class `T`, whose `this` binding is an instance of `T`.
+pub type MethodContext<'a, T> = FunctionCall<'a, T>;
+
+impl<'a, T: Class> FunctionCall<'a, T> {
+    /// The `this` binding, checked to be an instance of the class. Throws the class's
+    /// `TypeError` for a method called on another kind of object, e.g. with
+    /// `Function.prototype.call`.
+    pub fn this(&mut self) -> JsResult<'a, T> {
+        let this: Handle<T> = self.arguments.this(self.scope);
+        if !this.is_a::<T>() {
+            let metadata = T::metadata(self.scope)?;
+            unsafe {
+                neon_runtime::class::throw_this_error(self.scope.isolate().to_raw(), metadata.pointer);
+            }
+            return Err(Throw);
+        }
+        Ok(this)
+    }
+
+    /// Checks that the `this` binding is an instance of the class and
+    /// borrows its internals for the duration of `f`.
+    pub fn this_internals<F, U>(&mut self, f: F) -> VmResult<U>
+        where F: FnOnce(&T::Internals) -> U
+    {
+        self.this_internals_mut(|internals| f(internals))
+    }
+
+    /// Checks that the `this` binding is an instance of the class and
+    /// mutably borrows its internals for the duration of `f`.
+    pub fn this_internals_mut<F, U>(&mut self, f: F) -> VmResult<U>
+        where F: FnOnce(&mut T::Internals) -> U
+    {
+        let mut this = self.this()?;
+        let mut state = LockState::new();
+        let internals = unsafe { (&mut *this).expose(&mut state) };
+        Ok(f(internals))
+    }
+}
+
 impl<'a, T: Class> Lock for &'a mut T {
     type Internals = &'a mut T::Internals;
 
     unsafe fn expose(self, _: &mut LockState) -> Self::Internals {
         let ptr: *mut c_void = neon_runtime::class::get_instance_internals(self.to_raw());
-        mem::transmute(ptr)
+        &mut *(ptr as *mut T::Internals)
     }
 }
 
@@ -0,0 +1,98 @@
+//! Types and traits for working with JavaScript dates.
+
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use neon_runtime;
+use neon_runtime::raw;
+use mem::Managed;
+use scope::Scope;
+use vm::JsResult;
+use js::{Value, Object, build};
+use js::internal::ValueInternal;
+
+/// The largest time value, in milliseconds either side of the Unix epoch, that a
+/// JavaScript date can represent.
+pub const MAX_TIME: f64 = 8.64e15;
+
+/// A JavaScript `Date` object.
+#[repr(transparent)]
+#[derive(Clone, Copy)]
+pub struct JsDate(raw::Local);
+
+impl JsDate {
+    /// Creates a date from a time value in milliseconds since the Unix epoch. Values
+    /// outside of `MAX_TIME` produce an invalid date, as in JavaScript.
+    pub fn new<'a, T: Scope<'a>>(_: &mut T, time: f64) -> JsResult<'a, JsDate> {
+        build(|out| { unsafe { neon_runtime::date::new(out, time) } })
+    }
+
+    /// Creates a date from a `SystemTime`, truncated to the sub-millisecond precision
+    /// of an `f64`.
+    pub fn from_system_time<'a, T: Scope<'a>>(scope: &mut T, time: SystemTime) -> JsResult<'a, JsDate> {
+        JsDate::new(scope, millis_from_system_time(time))
+    }
+
+    /// The date's time value in milliseconds since the Unix epoch, or `NaN` for an
+    /// invalid date.
+    pub fn value(self) -> f64 {
+        unsafe {
+            neon_runtime::date::value(self.to_raw())
+        }
+    }
+
+    /// Indicates whether the date is valid, i.e. its time value isn't `NaN`.
+    pub fn is_valid(self) -> bool {
+        !self.value().is_nan()
+    }
+
+    /// Converts the date to a `SystemTime`, or `None` for an invalid date.
+    pub fn to_system_time(self) -> Option<SystemTime> {
+        system_time_from_millis(self.value())
+    }
+}
+
+pub(crate) fn millis(duration: Duration) -> f64 {
+    (duration.as_secs() as f64) * 1000.0 + (duration.subsec_nanos() as f64) / 1_000_000.0
+}
+
+pub(crate) fn millis_from_system_time(time: SystemTime) -> f64 {
+    match time.duration_since(UNIX_EPOCH) {
+        Ok(after) => millis(after),
+        Err(err) => -millis(err.duration())
+    }
+}
+
+pub(crate) fn system_time_from_millis(time: f64) -> Option<SystemTime> {
+    if time.is_nan() || time.abs() > MAX_TIME {
+        return None;
+    }
+    let offset = duration_from_millis(time.abs());
+    if time >= 0.0 {
+        UNIX_EPOCH.checked_add(offset)
+    } else {
+        UNIX_EPOCH.checked_sub(offset)
+    }
+}
+
+pub(crate) fn duration_from_millis(time: f64) -> Duration {
+    let secs = (time / 1000.0).trunc();
+    let nanos = ((time - secs * 1000.0) * 1_000_000.0) as u32;
+    Duration::new(secs as u64, nanos)
+}
+
+impl Value for JsDate { }
+
+impl Managed for JsDate {
+    fn to_raw(self) -> raw::Local { self.0 }
+
+    fn from_raw(h: raw::Local) -> Self { JsDate(h) }
+}
+
+impl ValueInternal for JsDate {
+    fn name() -> String { "JsDate".to_string() }
+
+    fn is_typeof<Other: Value>(other: Other) -> bool {
+        unsafe { neon_runtime::tag::is_date(other.to_raw()) }
+    }
+}
+
+impl Object for JsDate { }
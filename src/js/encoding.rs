@@ -0,0 +1,103 @@
+//! Native base64 and hex codecs backing the `Scope` encoding helpers.
+
+const BASE64_ALPHABET: &'static [u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+const HEX_DIGITS: &'static [u8; 16] = b"0123456789abcdef";
+
+pub(crate) fn encode_base64(data: &[u8]) -> String {
+    let mut out = Vec::with_capacity((data.len() + 2) / 3 * 4);
+    for chunk in data.chunks(3) {
+        let b = [chunk[0], *chunk.get(1).unwrap_or(&0), *chunk.get(2).unwrap_or(&0)];
+        let n = ((b[0] as u32) << 16) | ((b[1] as u32) << 8) | (b[2] as u32);
+        out.push(BASE64_ALPHABET[(n >> 18) as usize & 63]);
+        out.push(BASE64_ALPHABET[(n >> 12) as usize & 63]);
+        out.push(if chunk.len() > 1 { BASE64_ALPHABET[(n >> 6) as usize & 63] } else { b'=' });
+        out.push(if chunk.len() > 2 { BASE64_ALPHABET[n as usize & 63] } else { b'=' });
+    }
+    // The output only ever contains ASCII.
+    unsafe { String::from_utf8_unchecked(out) }
+}
+
+// Accepts both the standard and URL-safe alphabets, like Node's `Buffer`.
+fn base64_value(c: u8) -> Option<u32> {
+    match c {
+        b'A'..=b'Z' => Some((c - b'A') as u32),
+        b'a'..=b'z' => Some((c - b'a') as u32 + 26),
+        b'0'..=b'9' => Some((c - b'0') as u32 + 52),
+        b'+' | b'-' => Some(62),
+        b'/' | b'_' => Some(63),
+        _ => None
+    }
+}
+
+fn strip_base64_padding(s: &[u8]) -> &[u8] {
+    let mut end = s.len();
+    while end > 0 && s.len() - end < 2 && s[end - 1] == b'=' {
+        end -= 1;
+    }
+    &s[..end]
+}
+
+/// Returns the number of bytes encoded by `s`, or `None` if it can't be valid base64.
+pub(crate) fn base64_decoded_len(s: &[u8]) -> Option<usize> {
+    let s = strip_base64_padding(s);
+    match s.len() % 4 {
+        1 => None,
+        rem => Some(s.len() / 4 * 3 + rem.saturating_sub(1))
+    }
+}
+
+/// Decodes `s` into `out`, which must be exactly `base64_decoded_len(s)` bytes long.
+/// Returns `false` if `s` contains characters outside of the base64 alphabets.
+pub(crate) fn decode_base64_into(s: &[u8], out: &mut [u8]) -> bool {
+    let s = strip_base64_padding(s);
+    let mut i = 0;
+    for chunk in s.chunks(4) {
+        let mut n = 0;
+        for &c in chunk {
+            match base64_value(c) {
+                Some(v) => n = (n << 6) | v,
+                None => return false
+            }
+        }
+        n <<= 6 * (4 - chunk.len() as u32);
+        for k in 0..(chunk.len() - 1) {
+            out[i] = (n >> (16 - 8 * k)) as u8;
+            i += 1;
+        }
+    }
+    true
+}
+
+pub(crate) fn encode_hex(data: &[u8]) -> String {
+    let mut out = Vec::with_capacity(data.len() * 2);
+    for &b in data {
+        out.push(HEX_DIGITS[(b >> 4) as usize]);
+        out.push(HEX_DIGITS[(b & 15) as usize]);
+    }
+    // The output only ever contains ASCII.
+    unsafe { String::from_utf8_unchecked(out) }
+}
+
+fn hex_value(c: u8) -> Option<u8> {
+    match c {
+        b'0'..=b'9' => Some(c - b'0'),
+        b'a'..=b'f' => Some(c - b'a' + 10),
+        b'A'..=b'F' => Some(c - b'A' + 10),
+        _ => None
+    }
+}
+
+/// Decodes `s` into `out`, which must be exactly half as long as `s`. Returns `false`
+/// if `s` has an odd length or contains non-hex characters.
+pub(crate) fn decode_hex_into(s: &[u8], out: &mut [u8]) -> bool {
+    if s.len() % 2 != 0 {
+        return false;
+    }
+    for (pair, byte) in s.chunks(2).zip(out.iter_mut()) {
+        match (hex_value(pair[0]), hex_value(pair[1])) {
+            (Some(hi), Some(lo)) => *byte = (hi << 4) | lo,
+            _ => return false
+        }
+    }
+    true
+}
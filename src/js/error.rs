@@ -1,11 +1,12 @@
 use std::mem;
-use std::ffi::CString;
+use std::cell::RefCell;
 use std::panic::{UnwindSafe, catch_unwind};
 
 use neon_runtime;
 use neon_runtime::raw;
 
 use vm::{Throw, VmResult};
+use vm::internal::Isolate;
 use js::{Value, Object, ToJsString, build};
 use js::internal::ValueInternal;
 use mem::{Handle, Managed};
@@ -18,7 +19,7 @@ pub fn throw<'a, T: Value, U>(v: Handle<'a, T>) -> VmResult<U> {
     Err(Throw)
 }
 
-#[repr(C)]
+#[repr(transparent)]
 #[derive(Clone, Copy)]
 pub struct JsError(raw::Local);
 
@@ -29,6 +30,8 @@ impl Managed for JsError {
 }
 
 impl ValueInternal for JsError {
+    fn name() -> String { "JsError".to_string() }
+
     fn is_typeof<Other: Value>(other: Other) -> bool {
         unsafe { neon_runtime::tag::is_error(other.to_raw()) }
     }
@@ -46,44 +49,110 @@ pub enum Kind {
     SyntaxError
 }
 
-fn message(msg: &str) -> CString {
-    CString::new(msg).ok().unwrap_or_else(|| { CString::new("").ok().unwrap() })
+unsafe fn new_error(out: &mut raw::Local, kind: Kind, msg: raw::Local) {
+    match kind {
+        Kind::Error          => neon_runtime::error::new_error(out, msg),
+        Kind::TypeError      => neon_runtime::error::new_type_error(out, msg),
+        Kind::ReferenceError => neon_runtime::error::new_reference_error(out, msg),
+        Kind::RangeError     => neon_runtime::error::new_range_error(out, msg),
+        Kind::SyntaxError    => neon_runtime::error::new_syntax_error(out, msg)
+    }
+    add_native_frames(*out);
 }
 
 impl JsError {
+    /// Creates an error. When called from a function exported by a module, the error's
+    /// `stack` includes a frame for that function, which JavaScript's own stack traces
+    /// leave out.
     pub fn new<'a, T: Scope<'a>, U: ToJsString>(scope: &mut T, kind: Kind, msg: U) -> VmResult<Handle<'a, JsError>> {
         let msg = msg.to_js_string(scope);
         build(|out| {
             unsafe {
-                let raw = msg.to_raw();
-                match kind {
-                    Kind::Error          => neon_runtime::error::new_error(out, raw),
-                    Kind::TypeError      => neon_runtime::error::new_type_error(out, raw),
-                    Kind::ReferenceError => neon_runtime::error::new_reference_error(out, raw),
-                    Kind::RangeError     => neon_runtime::error::new_range_error(out, raw),
-                    Kind::SyntaxError    => neon_runtime::error::new_syntax_error(out, raw)
-                }
+                new_error(out, kind, msg.to_raw());
             }
             true
         })
     }
 
+    /// Throws a new error, with a `stack` like that of an error made with `new`.
     pub fn throw<T>(kind: Kind, msg: &str) -> VmResult<T> {
-        let msg = &message(msg);
         unsafe {
-            let ptr = mem::transmute(msg.as_ptr());
-            match kind {
-                Kind::Error          => neon_runtime::error::throw_error_from_cstring(ptr),
-                Kind::TypeError      => neon_runtime::error::throw_type_error_from_cstring(ptr),
-                Kind::ReferenceError => neon_runtime::error::throw_reference_error_from_cstring(ptr),
-                Kind::RangeError     => neon_runtime::error::throw_range_error_from_cstring(ptr),
-                Kind::SyntaxError    => neon_runtime::error::throw_syntax_error_from_cstring(ptr)
+            let isolate = Isolate::current().to_raw();
+            let mut js_msg: raw::Local = mem::zeroed();
+            if !neon_runtime::string::new(&mut js_msg, isolate, msg.as_ptr(), msg.len() as i32) {
+                neon_runtime::string::new(&mut js_msg, isolate, "".as_ptr(), 0);
             }
+            let mut error: raw::Local = mem::zeroed();
+            new_error(&mut error, kind, js_msg);
+            neon_runtime::error::throw(error);
         }
         Err(Throw)
     }
 }
 
+thread_local! {
+    // The Rust functions behind the exported functions currently running on this
+    // thread, innermost last.
+    static NATIVE_FRAMES: RefCell<Vec<usize>> = RefCell::new(Vec::new());
+}
+
+// Runs `f` as the body of the exported function backed by the Rust function at
+// address `function`, so errors created meanwhile are traced back to it.
+pub(crate) fn with_native_frame<T, F: FnOnce() -> T>(function: usize, f: F) -> T {
+    NATIVE_FRAMES.with(|frames| frames.borrow_mut().push(function));
+    let result = f();
+    NATIVE_FRAMES.with(|frames| frames.borrow_mut().pop());
+    result
+}
+
+unsafe fn add_native_frames(error: raw::Local) {
+    let function = match NATIVE_FRAMES.with(|frames| frames.borrow().last().cloned()) {
+        Some(function) => function,
+        None => { return; }
+    };
+    let mut isolate = Isolate::current();
+    let map = isolate.class_map();
+    let name = match map.function_name(function) {
+        Some(name) => name,
+        None => { return; }
+    };
+    let mut frames = rust_frames();
+    frames.push_str(&format!("\n    at {} ({})", name, map.origin().unwrap_or("native")));
+    neon_runtime::error::add_frames(error, frames.as_ptr(), frames.len() as i32);
+}
+
+// With the `backtrace` feature, the Rust frames between the exported function and
+// the creation of the error, innermost first.
+#[cfg(feature = "backtrace")]
+fn rust_frames() -> String {
+    let mut frames = String::new();
+    let trace = ::backtrace::Backtrace::new();
+    for frame in trace.frames() {
+        for symbol in frame.symbols() {
+            let name = match symbol.name() {
+                Some(name) => format!("{:#}", name),
+                None => continue
+            };
+            if name.contains("as neon::vm::Kernel") {
+                return frames;
+            }
+            if name.starts_with("backtrace::") || name.starts_with("neon::js::error::") {
+                continue;
+            }
+            match (symbol.filename(), symbol.lineno()) {
+                (Some(file), Some(line)) => frames.push_str(&format!("\n    at {} ({}:{})", name, file.display(), line)),
+                _ => frames.push_str(&format!("\n    at {}", name))
+            }
+        }
+    }
+    frames
+}
+
+#[cfg(not(feature = "backtrace"))]
+fn rust_frames() -> String {
+    String::new()
+}
+
 pub(crate) fn convert_panics<T, F: UnwindSafe + FnOnce() -> VmResult<T>>(f: F) -> VmResult<T> {
     match catch_unwind(|| { f() }) {
         Ok(result) => result,
@@ -0,0 +1,281 @@
+//! Support for the JavaScript iteration protocol: exposing Rust iterators to JavaScript,
+//! and consuming JavaScript iterables from Rust.
+
+use std::cell::RefCell;
+use neon_runtime;
+use neon_runtime::raw;
+use mem::{Handle, Managed};
+use scope::{Scope, RootScope};
+use vm::{JsResult, VmResult, FunctionCall};
+use js::{JsValue, JsObject, JsBoolean, JsUndefined, JsNull, JsNumber, JsString, JsFunction, Object};
+use js::class::{Class, ClassDescriptor};
+use js::class::internal::{AllocateKernel, MethodKernel};
+use js::error::{JsError, Kind, throw};
+use convert::TryIntoJs;
+
+/// Creates an iterator result object of the form `{ value, done }`. An exhausted
+/// iterator is represented by `None`.
+pub fn iter_result<'a, S: Scope<'a>>(scope: &mut S, value: Option<Handle<'a, JsValue>>) -> JsResult<'a, JsObject> {
+    let result = JsObject::new(scope);
+    let done = value.is_none();
+    let value = value.unwrap_or_else(|| JsUndefined::new().upcast());
+    result.set("value", value)?;
+    result.set("done", JsBoolean::new(scope, done))?;
+    Ok(result)
+}
+
+trait Source {
+    fn next<'a>(&mut self, scope: &mut RootScope<'a>) -> VmResult<Option<Handle<'a, JsValue>>>;
+}
+
+struct IteratorSource<I, F> {
+    iter: I,
+    convert: F
+}
+
+impl<I, F> Source for IteratorSource<I, F>
+    where I: Iterator,
+          F: for<'b> FnMut(&mut RootScope<'b>, I::Item) -> JsResult<'b, JsValue>
+{
+    fn next<'a>(&mut self, scope: &mut RootScope<'a>) -> VmResult<Option<Handle<'a, JsValue>>> {
+        match self.iter.next() {
+            Some(item) => (self.convert)(scope, item).map(Some),
+            None => Ok(None)
+        }
+    }
+}
+
+thread_local! {
+    // The source for the next `JsIterator` instance, handed from `JsIterator::new`
+    // to the class's allocator.
+    static PENDING: RefCell<Option<Box<dyn Source>>> = RefCell::new(None);
+}
+
+/// A JavaScript iterator object backed by a Rust `Iterator`.
+///
+/// Instances can only be created from Rust, and are themselves iterable, so they
+/// can be returned from a class's `iterator` method or consumed with `for...of`.
+/// Like a generator, an instance also has `return()` and `throw()` methods, which
+/// drop the Rust iterator immediately so that a loop that exits early releases it
+/// without waiting for garbage collection.
+#[repr(transparent)]
+#[derive(Clone, Copy)]
+pub struct JsIterator(raw::Local);
+
+impl JsIterator {
+    /// Wraps a Rust iterator, converting each item to a JavaScript value with `convert`
+    /// as it is requested.
+    pub fn new<'a, S, I, F>(scope: &mut S, iter: I, convert: F) -> JsResult<'a, JsIterator>
+        where S: Scope<'a>,
+              I: Iterator + 'static,
+              F: for<'b> FnMut(&mut RootScope<'b>, I::Item) -> JsResult<'b, JsValue> + 'static
+    {
+        let source: Box<dyn Source> = Box::new(IteratorSource { iter: iter, convert: convert });
+        PENDING.with(|pending| *pending.borrow_mut() = Some(source));
+        let class = JsIterator::class(scope)?;
+        let constructor = class.constructor(scope)?;
+        let args: Vec<Handle<JsValue>> = vec![];
+        let result = constructor.construct(scope, args);
+        PENDING.with(|pending| pending.borrow_mut().take());
+        result
+    }
+
+    /// Wraps a Rust iterator whose items can be converted to JavaScript values. The
+    /// sequence is produced lazily, one item per call to `next()`, so it may be
+    /// arbitrarily long or even infinite.
+    pub fn from_iter<'a, S, I>(scope: &mut S, iter: I) -> JsResult<'a, JsIterator>
+        where S: Scope<'a>,
+              I: IntoIterator,
+              I::IntoIter: 'static,
+              I::Item: TryIntoJs + 'static
+    {
+        JsIterator::new(scope, iter.into_iter(), convert_item::<I::Item>)
+    }
+}
+
+fn convert_item<'b, T: TryIntoJs>(scope: &mut RootScope<'b>, item: T) -> JsResult<'b, JsValue> {
+    Ok(item.try_into_js(scope)?.upcast())
+}
+
+impl Managed for JsIterator {
+    fn to_raw(self) -> raw::Local { self.0 }
+
+    fn from_raw(h: raw::Local) -> Self { JsIterator(h) }
+}
+
+/// The internals of a `JsIterator`. The source is dropped once it is exhausted or
+/// the iterator is closed with `return()` or `throw()`.
+pub struct IteratorInternals {
+    source: Option<Box<dyn Source>>
+}
+
+impl Class for JsIterator {
+    type Internals = IteratorInternals;
+
+    fn setup<'a, T: Scope<'a>>(_: &mut T) -> VmResult<ClassDescriptor<'a, Self>> {
+        Ok(Self::describe("NativeIterator", AllocateKernel::new(allocate))
+            .method("next", MethodKernel::new(next))
+            .method("return", MethodKernel::new(return_))
+            .method("throw", MethodKernel::new(throw_))
+            .iterator(MethodKernel::new(iterator)))
+    }
+}
+
+fn allocate(_: FunctionCall<JsUndefined>) -> VmResult<IteratorInternals> {
+    match PENDING.with(|pending| pending.borrow_mut().take()) {
+        Some(source) => Ok(IteratorInternals { source: Some(source) }),
+        None => JsError::throw(Kind::TypeError, "NativeIterator cannot be constructed from JavaScript")
+    }
+}
+
+fn next(mut call: FunctionCall<JsIterator>) -> JsResult<JsValue> {
+    // The source is taken out for the duration of the call so that a re-entrant
+    // call to `next()` sees an exhausted iterator rather than aliasing it.
+    let value = match call.this_internals_mut(|internals| internals.source.take())? {
+        None => None,
+        Some(mut source) => {
+            let value = source.next(call.scope)?;
+            if value.is_some() {
+                call.this_internals_mut(|internals| internals.source = Some(source))?;
+            }
+            value
+        }
+    };
+    Ok(iter_result(call.scope, value)?.upcast())
+}
+
+// Drops the iterator's source, if it hasn't been already. The source is taken out
+// before it's dropped so that its destructor doesn't run with the internals borrowed.
+fn close_source(call: &mut FunctionCall<JsIterator>) -> VmResult<()> {
+    let source = call.this_internals_mut(|internals| internals.source.take())?;
+    drop(source);
+    Ok(())
+}
+
+fn return_(mut call: FunctionCall<JsIterator>) -> JsResult<JsValue> {
+    close_source(&mut call)?;
+    let value = call.arguments.get(call.scope, 0).unwrap_or_else(|| JsUndefined::new().upcast());
+    let result = JsObject::new(call.scope);
+    result.set("value", value)?;
+    result.set("done", JsBoolean::new(call.scope, true))?;
+    Ok(result.upcast())
+}
+
+fn throw_(mut call: FunctionCall<JsIterator>) -> JsResult<JsValue> {
+    close_source(&mut call)?;
+    let error = call.arguments.get(call.scope, 0).unwrap_or_else(|| JsUndefined::new().upcast());
+    throw(error)
+}
+
+fn iterator(call: FunctionCall<JsIterator>) -> JsResult<JsValue> {
+    Ok(call.arguments.this(call.scope).upcast())
+}
+
+/// A Rust iterator over the values of a JavaScript iterable, driven by the iterable's
+/// `Symbol.iterator` method and its iterator's `next()` method.
+///
+/// Each item is a `JsResult`, since advancing the iterator runs arbitrary JavaScript.
+/// Iteration ends after the first exception.
+pub struct JsIter<'s, 'a: 's, S: Scope<'a> + 's> {
+    scope: &'s mut S,
+    iterator: Handle<'a, JsObject>,
+    next: Handle<'a, JsFunction>,
+    done: bool
+}
+
+impl<'s, 'a: 's, S: Scope<'a> + 's> JsIter<'s, 'a, S> {
+    /// Starts iterating over `iterable`, throwing a `TypeError` if it isn't an iterable
+    /// object.
+    pub fn new<'b>(scope: &'s mut S, iterable: Handle<'b, JsValue>) -> VmResult<JsIter<'s, 'a, S>> {
+        let object = match iterable.downcast::<JsObject>() {
+            Some(object) => object,
+            None => return JsError::throw(Kind::TypeError, "value is not iterable")
+        };
+        let global = scope.global();
+        let symbol = global.get(scope, "Symbol")?.check::<JsObject>()?;
+        let symbol = symbol.get(scope, "iterator")?;
+        let method = match object.get(scope, symbol)?.downcast::<JsFunction>() {
+            Some(method) => method,
+            None => return JsError::throw(Kind::TypeError, "value is not iterable")
+        };
+        let args: Vec<Handle<JsValue>> = vec![];
+        let iterator = match method.call(scope, object, args)?.downcast::<JsObject>() {
+            Some(iterator) => iterator,
+            None => return JsError::throw(Kind::TypeError, "iterator is not an object")
+        };
+        let next = match iterator.get(scope, "next")?.downcast::<JsFunction>() {
+            Some(next) => next,
+            None => return JsError::throw(Kind::TypeError, "iterator has no next() method")
+        };
+        Ok(JsIter { scope: scope, iterator: iterator, next: next, done: false })
+    }
+
+    /// Stops iterating early, calling the iterator's `return()` method if it has one
+    /// so that it can release its resources. Dropping a `JsIter` before it's exhausted
+    /// doesn't call `return()`.
+    pub fn close(mut self) -> VmResult<()> {
+        if self.done {
+            return Ok(());
+        }
+        self.done = true;
+        let method = self.iterator.get(&mut *self.scope, "return")?;
+        if let Some(method) = method.downcast::<JsFunction>() {
+            let args: Vec<Handle<JsValue>> = vec![];
+            method.call(&mut *self.scope, self.iterator, args)?;
+        }
+        Ok(())
+    }
+
+    fn step(&mut self) -> VmResult<Option<Handle<'a, JsValue>>> {
+        let args: Vec<Handle<JsValue>> = vec![];
+        let result = match self.next.call(&mut *self.scope, self.iterator, args)?.downcast::<JsObject>() {
+            Some(result) => result,
+            None => return JsError::throw(Kind::TypeError, "iterator result is not an object")
+        };
+        if is_truthy(result.get(&mut *self.scope, "done")?) {
+            return Ok(None);
+        }
+        result.get(&mut *self.scope, "value").map(Some)
+    }
+}
+
+impl<'s, 'a: 's, S: Scope<'a> + 's> Iterator for JsIter<'s, 'a, S> {
+    type Item = JsResult<'a, JsValue>;
+
+    fn next(&mut self) -> Option<JsResult<'a, JsValue>> {
+        if self.done {
+            return None;
+        }
+        match self.step() {
+            Ok(Some(value)) => Some(Ok(value)),
+            Ok(None) => {
+                self.done = true;
+                None
+            }
+            Err(throw) => {
+                self.done = true;
+                Some(Err(throw))
+            }
+        }
+    }
+}
+
+// JavaScript's `ToBoolean`, as applied to an iterator result's `done` property.
+fn is_truthy(value: Handle<JsValue>) -> bool {
+    if let Some(b) = value.downcast::<JsBoolean>() {
+        b.value()
+    } else if value.is_a::<JsUndefined>() || value.is_a::<JsNull>() {
+        false
+    } else if let Some(n) = value.downcast::<JsNumber>() {
+        let n = n.value();
+        n != 0.0 && !n.is_nan()
+    } else if let Some(s) = value.downcast::<JsString>() {
+        s.size() > 0
+    } else if unsafe { neon_runtime::tag::is_bigint(value.to_raw()) } {
+        // Only `0n` converts to 0 without loss.
+        let mut n = 0;
+        !unsafe { neon_runtime::bigint::to_u64(&mut n, value.to_raw()) } || n != 0
+    } else {
+        true
+    }
+}
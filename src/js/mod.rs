@@ -3,8 +3,16 @@
 pub mod binary;
 pub mod error;
 pub mod class;
-
+pub mod iter;
+pub mod date;
+pub mod stream;
+pub(crate) mod encoding;
+
+use std::borrow::Cow;
+use std::cell::RefCell;
+use std::cmp;
 use std::mem;
+use std::str;
 use std::os::raw::c_void;
 use std::marker::PhantomData;
 use neon_runtime;
@@ -15,19 +23,28 @@ use scope::Scope;
 use vm::{VmResult, Throw, JsResult, Call, This, Kernel};
 use vm::internal::Isolate;
 use js::error::{JsError, Kind};
-use self::internal::{ValueInternal, SuperType, FunctionKernel};
+use convert::{TryFromJs, TryIntoJs};
+use instrument::{self, Event};
+use self::internal::{ValueInternal, SuperType, FunctionKernel, FastFunctionKernel, LazyKernel, LAZY_EXPORT_KEY};
+use self::internal::{Closure, ClosureKernel, drop_closure};
 
 pub(crate) mod internal {
     use std::mem;
     use std::os::raw::c_void;
+    use std::panic::AssertUnwindSafe;
     use neon_runtime;
     use neon_runtime::raw;
     use mem::{Handle, Managed};
-    use vm::{JsResult, CallbackInfo, Call, Kernel};
-    use js::error::convert_panics;
-    use super::Value;
+    use vm::{JsResult, VmResult, CallbackInfo, Call, Kernel};
+    use scope::RootScope;
+    use js::error::{convert_panics, with_native_frame};
+    use instrument::{self, Event};
+    use super::{Value, Object, JsValue, JsObject, JsFunction, JsBoolean, define_property};
 
     pub trait ValueInternal: Managed {
+        // The type's name, as used in the message of a failed `check`.
+        fn name() -> String;
+
         fn is_typeof<Other: Value>(other: Other) -> bool;
 
         fn downcast<Other: Value>(other: Other) -> Option<Self> {
@@ -52,11 +69,16 @@ pub(crate) mod internal {
 
     impl<T: Value> Kernel<()> for FunctionKernel<T> {
         extern "C" fn callback(info: &CallbackInfo) {
+            let _timer = instrument::start(Event::Callback);
             info.scope().with(|scope| {
                 let data = info.data();
                 let FunctionKernel(kernel) = unsafe { Self::from_wrapper(data.to_raw()) };
                 let call = info.as_call(scope);
-                if let Ok(value) = convert_panics(|| { kernel(call) }) {
+                let result = with_native_frame(kernel as usize, || convert_panics(|| {
+                    let _handles = instrument::check_handles(call.scope);
+                    kernel(call)
+                }));
+                if let Ok(value) = result {
                     info.set_return(value);
                 }
             })
@@ -70,9 +92,156 @@ pub(crate) mod internal {
             unsafe { mem::transmute(self.0) }
         }
     }
+
+    // Like `FunctionKernel`, but runs the function directly in the `HandleScope` that
+    // V8 opens around every callback instead of opening another one.
+    #[repr(C)]
+    pub struct FastFunctionKernel<T: Value>(pub fn(Call) -> JsResult<T>);
+
+    impl<T: Value> Kernel<()> for FastFunctionKernel<T> {
+        extern "C" fn callback(info: &CallbackInfo) {
+            let _timer = instrument::start(Event::Callback);
+            let mut scope = info.scope();
+            let data = info.data();
+            let FastFunctionKernel(kernel) = unsafe { Self::from_wrapper(data.to_raw()) };
+            let call = info.as_call(&mut scope);
+            let result = with_native_frame(kernel as usize, || convert_panics(|| {
+                let _handles = instrument::check_handles(call.scope);
+                kernel(call)
+            }));
+            if let Ok(value) = result {
+                info.set_return(value);
+            }
+        }
+
+        unsafe fn from_wrapper(h: raw::Local) -> Self {
+            FastFunctionKernel(mem::transmute(neon_runtime::fun::get_kernel(h)))
+        }
+
+        fn as_ptr(self) -> *mut c_void {
+            unsafe { mem::transmute(self.0) }
+        }
+    }
+
+    pub type Closure<T> = Box<dyn Fn(Call) -> JsResult<T>>;
+
+    // The kernel of a function created from a closure, which is boxed and freed with
+    // `drop_closure` once the function is collected.
+    pub struct ClosureKernel<T: Value>(pub *mut Closure<T>);
+
+    impl<T: Value> Kernel<()> for ClosureKernel<T> {
+        extern "C" fn callback(info: &CallbackInfo) {
+            let _timer = instrument::start(Event::Callback);
+            info.scope().with(|scope| {
+                let data = info.data();
+                let ClosureKernel(closure) = unsafe { Self::from_wrapper(data.to_raw()) };
+                let closure = AssertUnwindSafe(unsafe { &*closure });
+                let call = info.as_call(scope);
+                let result = convert_panics(move || {
+                    let _handles = instrument::check_handles(call.scope);
+                    (&**closure)(call)
+                });
+                if let Ok(value) = result {
+                    info.set_return(value);
+                }
+            })
+        }
+
+        unsafe fn from_wrapper(h: raw::Local) -> Self {
+            ClosureKernel(neon_runtime::fun::get_kernel(h) as *mut Closure<T>)
+        }
+
+        fn as_ptr(self) -> *mut c_void {
+            self.0 as *mut c_void
+        }
+    }
+
+    pub unsafe extern "C" fn drop_closure<T: Value>(closure: *mut c_void) {
+        mem::drop(Box::from_raw(closure as *mut Closure<T>));
+    }
+
+    // The getter of a lazy export, which runs the initializer on first access and
+    // then replaces itself with a data property holding the result.
+    #[repr(C)]
+    pub struct LazyKernel<T: Value>(pub fn(Call) -> JsResult<T>);
+
+    impl<T: Value> Kernel<()> for LazyKernel<T> {
+        extern "C" fn callback(info: &CallbackInfo) {
+            let _timer = instrument::start(Event::Callback);
+            let mut scope = info.scope();
+            let data = info.data();
+            let LazyKernel(kernel) = unsafe { Self::from_wrapper(data.to_raw()) };
+            let callee = info.callee(&mut scope);
+            let this: Handle<JsValue> = Handle::new_internal(JsValue::from_raw(info.this(&mut scope)));
+            let call = info.as_call(&mut scope);
+            if let Ok(value) = convert_panics(|| { kernel(call) }) {
+                let mut scope = info.scope();
+                let memoized = match this.downcast::<JsObject>() {
+                    Some(this) => memoize(&mut scope, callee, this, value.upcast()),
+                    None => Ok(())
+                };
+                if memoized.is_ok() {
+                    info.set_return(value);
+                }
+            }
+        }
+
+        unsafe fn from_wrapper(h: raw::Local) -> Self {
+            LazyKernel(mem::transmute(neon_runtime::fun::get_kernel(h)))
+        }
+
+        fn as_ptr(self) -> *mut c_void {
+            unsafe { mem::transmute(self.0) }
+        }
+    }
+
+    fn memoize<'a>(scope: &mut RootScope<'a>, getter: Handle<JsFunction>, this: Handle<JsObject>, value: Handle<JsValue>) -> VmResult<()> {
+        let key = getter.get_hidden(scope, LAZY_EXPORT_KEY)?;
+        let descriptor = JsObject::new(scope);
+        descriptor.set("value", value)?;
+        descriptor.set("writable", JsBoolean::new(scope, true))?;
+        descriptor.set("enumerable", JsBoolean::new(scope, true))?;
+        descriptor.set("configurable", JsBoolean::new(scope, true))?;
+        define_property(scope, this, key, descriptor)
+    }
+
+    // The hidden value on a lazy export's getter naming the property it defines.
+    pub const LAZY_EXPORT_KEY: &'static str = "neon:lazy_export_key";
+}
+
+// Calls `Object.defineProperty`, which can redefine an accessor as a data property.
+pub(crate) fn define_property<'a, 'b, S: Scope<'a>>(scope: &mut S, object: Handle<'b, JsObject>, key: Handle<'b, JsValue>, descriptor: Handle<'b, JsObject>) -> VmResult<()> {
+    // `Key` is in scope here too, so `Object`'s methods have to be named explicitly.
+    let object_constructor = Object::get(*scope.global(), scope, "Object")?.check::<JsObject>()?;
+    let define = Object::get(*object_constructor, scope, "defineProperty")?.check::<JsFunction>()?;
+    let args: Vec<Handle<JsValue>> = vec![object.upcast(), key, descriptor.upcast()];
+    define.call(scope, object_constructor, args)?;
+    Ok(())
+}
+
+// Calls `Object.freeze`.
+pub(crate) fn freeze<'a, 'b, S: Scope<'a>>(scope: &mut S, object: Handle<'b, JsObject>) -> VmResult<()> {
+    let object_constructor = Object::get(*scope.global(), scope, "Object")?.check::<JsObject>()?;
+    let freeze = Object::get(*object_constructor, scope, "freeze")?.check::<JsFunction>()?;
+    freeze.call(scope, object_constructor, vec![object])?;
+    Ok(())
+}
+
+/// Creates the getter of a lazy export named `key`, as used by `Module::export_lazy`.
+pub(crate) fn lazy_getter<'a, S: Scope<'a>, T: Value>(scope: &mut S, key: &str, init: fn(Call) -> JsResult<T>) -> JsResult<'a, JsFunction> {
+    let getter: Handle<JsFunction> = build(|out| {
+        unsafe {
+            let (callback, kernel) = LazyKernel(init).export();
+            neon_runtime::fun::new(out, scope.isolate().to_raw(), callback, kernel)
+        }
+    })?;
+    let name = JsString::new_or_throw(scope, key)?;
+    getter.set_hidden(LAZY_EXPORT_KEY, name)?;
+    Ok(getter)
 }
 
 pub(crate) fn build<'a, T: Managed, F: FnOnce(&mut raw::Local) -> bool>(init: F) -> JsResult<'a, T> {
+    let _timer = instrument::start(Event::Handle);
     unsafe {
         let mut local: raw::Local = mem::zeroed();
         if init(&mut local) {
@@ -101,6 +270,38 @@ pub trait Value: ValueInternal {
         build(|out| { unsafe { neon_runtime::convert::to_string(out, self.to_raw()) } })
     }
 
+    /// Converts the value to a string with JavaScript's ToString operation, which calls
+    /// an object's `toString` or `valueOf` method and so can throw. This is the same as
+    /// `to_string`, named to match `to_js_number` and `to_js_boolean`.
+    fn to_js_string<'a, T: Scope<'a>>(self, scope: &mut T) -> JsResult<'a, JsString> {
+        self.to_string(scope)
+    }
+
+    /// Converts the value to a number with JavaScript's ToNumber operation, as
+    /// `Number(value)` does for anything but a `BigInt`. This calls an object's `valueOf`
+    /// or `toString` method and so can throw.
+    fn to_js_number<'a, T: Scope<'a>>(self, _: &mut T) -> JsResult<'a, JsNumber> {
+        build(|out| { unsafe { neon_runtime::convert::to_number(out, self.to_raw()) } })
+    }
+
+    /// Converts the value to an object with JavaScript's ToObject operation, boxing
+    /// primitives in wrapper objects such as `String` or `Number` instances, so that
+    /// their methods can be looked up. Objects convert to themselves. Throws a
+    /// `TypeError` for `null` and `undefined`.
+    fn to_object<'a, T: Scope<'a>>(self, _: &mut T) -> JsResult<'a, JsObject> {
+        build(|out| { unsafe { neon_runtime::convert::to_object(out, &self.to_raw()) } })
+    }
+
+    /// Converts the value to a boolean by its truthiness, as `Boolean(value)` does. This
+    /// never runs JavaScript code, so it can't throw.
+    fn to_js_boolean<'a, T: Scope<'a>>(self, _: &mut T) -> Handle<'a, JsBoolean> {
+        unsafe {
+            let mut local: raw::Local = mem::zeroed();
+            neon_runtime::convert::to_boolean(&mut local, self.to_raw());
+            Handle::new_internal(JsBoolean(local))
+        }
+    }
+
     fn as_value<'a, T: Scope<'a>>(self, _: &mut T) -> Handle<'a, JsValue> {
         JsValue::new_internal(self.to_raw())
     }
@@ -124,7 +325,7 @@ pub enum Variant<'a> {
 
 
 /// A JavaScript value of any type.
-#[repr(C)]
+#[repr(transparent)]
 #[derive(Clone, Copy)]
 pub struct JsValue(raw::Local);
 
@@ -137,6 +338,8 @@ impl Managed for JsValue {
 }
 
 impl ValueInternal for JsValue {
+    fn name() -> String { "JsValue".to_string() }
+
     fn is_typeof<Other: Value>(_: Other) -> bool {
         true
     }
@@ -173,7 +376,7 @@ impl JsValue {
 }
 
 /// The JavaScript `undefined` value.
-#[repr(C)]
+#[repr(transparent)]
 #[derive(Clone, Copy)]
 pub struct JsUndefined(raw::Local);
 
@@ -186,7 +389,7 @@ impl JsUndefined {
         unsafe {
             let mut local: raw::Local = mem::zeroed();
             neon_runtime::primitive::undefined(&mut local);
-            Handle::new_internal(JsUndefined(local))
+            Handle::new_unscoped(JsUndefined(local))
         }
     }
 }
@@ -210,13 +413,15 @@ unsafe impl This for JsUndefined {
 }
 
 impl ValueInternal for JsUndefined {
+    fn name() -> String { "JsUndefined".to_string() }
+
     fn is_typeof<Other: Value>(other: Other) -> bool {
         unsafe { neon_runtime::tag::is_undefined(other.to_raw()) }
     }
 }
 
 /// The JavaScript `null` value.
-#[repr(C)]
+#[repr(transparent)]
 #[derive(Clone, Copy)]
 pub struct JsNull(raw::Local);
 
@@ -229,7 +434,7 @@ impl JsNull {
         unsafe {
             let mut local: raw::Local = mem::zeroed();
             neon_runtime::primitive::null(&mut local);
-            Handle::new_internal(JsNull(local))
+            Handle::new_unscoped(JsNull(local))
         }
     }
 }
@@ -243,13 +448,15 @@ impl Managed for JsNull {
 }
 
 impl ValueInternal for JsNull {
+    fn name() -> String { "JsNull".to_string() }
+
     fn is_typeof<Other: Value>(other: Other) -> bool {
         unsafe { neon_runtime::tag::is_null(other.to_raw()) }
     }
 }
 
 /// A JavaScript boolean primitive value.
-#[repr(C)]
+#[repr(transparent)]
 #[derive(Clone, Copy)]
 pub struct JsBoolean(raw::Local);
 
@@ -282,13 +489,15 @@ impl Managed for JsBoolean {
 }
 
 impl ValueInternal for JsBoolean {
+    fn name() -> String { "JsBoolean".to_string() }
+
     fn is_typeof<Other: Value>(other: Other) -> bool {
         unsafe { neon_runtime::tag::is_boolean(other.to_raw()) }
     }
 }
 
 /// A JavaScript string primitive value.
-#[repr(C)]
+#[repr(transparent)]
 #[derive(Clone, Copy)]
 pub struct JsString(raw::Local);
 
@@ -301,6 +510,8 @@ impl Managed for JsString {
 }
 
 impl ValueInternal for JsString {
+    fn name() -> String { "JsString".to_string() }
+
     fn is_typeof<Other: Value>(other: Other) -> bool {
         unsafe { neon_runtime::tag::is_string(other.to_raw()) }
     }
@@ -324,10 +535,109 @@ impl JsString {
         }
     }
 
+    /// Reads the string into `buf`, replacing its contents, so that a loop reading many
+    /// strings can reuse one allocation instead of making a new one for each string.
+    pub fn value_into(self, buf: &mut String) {
+        unsafe {
+            let capacity = neon_runtime::string::utf8_len(self.to_raw());
+            let bytes = buf.as_mut_vec();
+            bytes.clear();
+            bytes.reserve(capacity as usize);
+            let len = neon_runtime::string::data(bytes.as_mut_ptr(), capacity, self.to_raw());
+            bytes.set_len(len as usize);
+        }
+    }
+
+    /// Reads the string into `buf`, such as an array on the stack, if it fits, and only
+    /// allocates a `String` for it if it doesn't.
+    pub fn value_cow<'b>(self, buf: &'b mut [u8]) -> Cow<'b, str> {
+        let size = self.size();
+        if size as usize > buf.len() {
+            return Cow::Owned(self.value());
+        }
+        unsafe {
+            let len = neon_runtime::string::data(buf.as_mut_ptr(), size, self.to_raw());
+            Cow::Borrowed(str::from_utf8_unchecked(&buf[..len as usize]))
+        }
+    }
+
     pub fn new<'a, T: Scope<'a>>(scope: &mut T, val: &str) -> Option<Handle<'a, JsString>> {
         JsString::new_internal(scope.isolate(), val)
     }
 
+    /// Creates a string like `new`, but without checking the length of `val`.
+    ///
+    /// This is unsafe because `val` must be no longer than V8's maximum string length,
+    /// so that creating the string can't fail.
+    pub unsafe fn new_unchecked<'a, T: Scope<'a>>(scope: &mut T, val: &str) -> Handle<'a, JsString> {
+        let mut local: raw::Local = mem::zeroed();
+        new_from_str(&mut local, scope.isolate(), val.as_ptr(), val.len() as i32, val.is_ascii());
+        Handle::new_internal(JsString(local))
+    }
+
+    /// Creates a string from UTF-16 code units, like `TextDecoder` with the `utf-16le` encoding.
+    /// Returns `None` if the string is too long.
+    pub fn from_utf16<'a, T: Scope<'a>>(scope: &mut T, val: &[u16]) -> Option<Handle<'a, JsString>> {
+        let len = small_len(val.len())?;
+        JsString::build_from(|local| unsafe {
+            neon_runtime::string::new_from_utf16(local, scope.isolate().to_raw(), val.as_ptr(), len)
+        })
+    }
+
+    /// Creates a string from Latin-1 bytes. Returns `None` if the string is too long.
+    pub fn from_latin1<'a, T: Scope<'a>>(scope: &mut T, val: &[u8]) -> Option<Handle<'a, JsString>> {
+        let len = small_len(val.len())?;
+        JsString::build_from(|local| unsafe {
+            neon_runtime::string::new_from_latin1(local, scope.isolate().to_raw(), val.as_ptr(), len)
+        })
+    }
+
+    /// Creates a string from UTF-8 bytes that may not be valid, replacing invalid sequences
+    /// with U+FFFD, like `TextDecoder`. Returns `None` if the string is too long.
+    pub fn from_utf8_lossy<'a, T: Scope<'a>>(scope: &mut T, val: &[u8]) -> Option<Handle<'a, JsString>> {
+        let len = small_len(val.len())?;
+        JsString::build_from(|local| unsafe {
+            neon_runtime::string::new(local, scope.isolate().to_raw(), val.as_ptr(), len)
+        })
+    }
+
+    /// The length of the string in UTF-16 code units, i.e. its JavaScript `length`.
+    pub fn utf16_len(self) -> usize {
+        unsafe { neon_runtime::string::len(self.to_raw()) as usize }
+    }
+
+    /// Writes the string's UTF-16 code units into `buf`, returning the number written.
+    pub fn write_utf16(self, buf: &mut [u16]) -> usize {
+        let len = clamp_len(buf.len());
+        unsafe { neon_runtime::string::write_utf16(self.to_raw(), buf.as_mut_ptr(), len) as usize }
+    }
+
+    /// Writes the string as Latin-1 into `buf`, returning the number of bytes written.
+    /// Characters outside of Latin-1 are truncated to their low byte.
+    pub fn write_latin1(self, buf: &mut [u8]) -> usize {
+        let len = clamp_len(buf.len());
+        unsafe { neon_runtime::string::write_latin1(self.to_raw(), buf.as_mut_ptr(), len) as usize }
+    }
+
+    /// Writes the string as UTF-8 into `buf`, like `TextEncoder.prototype.encodeInto`, returning
+    /// the number of bytes written. Characters are never split, and unpaired surrogates are
+    /// replaced with U+FFFD.
+    pub fn write_utf8(self, buf: &mut [u8]) -> usize {
+        let len = clamp_len(buf.len());
+        unsafe { neon_runtime::string::write_utf8(self.to_raw(), buf.as_mut_ptr(), len) as usize }
+    }
+
+    fn build_from<'a, F: FnOnce(&mut raw::Local) -> bool>(init: F) -> Option<Handle<'a, JsString>> {
+        unsafe {
+            let mut local: raw::Local = mem::zeroed();
+            if init(&mut local) {
+                Some(Handle::new_internal(JsString(local)))
+            } else {
+                None
+            }
+        }
+    }
+
     pub fn new_or_throw<'a, T: Scope<'a>>(scope: &mut T, val: &str) -> VmResult<Handle<'a, JsString>> {
         match JsString::new(scope, val) {
             Some(v) => Ok(v),
@@ -342,7 +652,7 @@ impl JsString {
         };
         unsafe {
             let mut local: raw::Local = mem::zeroed();
-            if neon_runtime::string::new(&mut local, isolate.to_raw(), ptr, len) {
+            if new_from_str(&mut local, isolate, ptr, len, val.is_ascii()) {
                 Some(Handle::new_internal(JsString(local)))
             } else {
                 None
@@ -351,6 +661,35 @@ impl JsString {
     }
 }
 
+// A `&str` is always valid UTF-8, so an ASCII one can be copied as Latin-1, sparing
+// V8 from decoding it.
+unsafe fn new_from_str(out: &mut raw::Local, isolate: Isolate, ptr: *const u8, len: i32, ascii: bool) -> bool {
+    if ascii {
+        neon_runtime::string::new_from_latin1(out, isolate.to_raw(), ptr, len)
+    } else {
+        neon_runtime::string::new(out, isolate.to_raw(), ptr, len)
+    }
+}
+
+fn small_len(len: usize) -> Option<i32> {
+    if len > i32::max_value() as usize {
+        None
+    } else {
+        Some(len as i32)
+    }
+}
+
+fn array_len(len: usize) -> VmResult<u32> {
+    if len > u32::max_value() as usize {
+        return JsError::throw(Kind::RangeError, "array length exceeds 2^32 - 1");
+    }
+    Ok(len as u32)
+}
+
+fn clamp_len(len: usize) -> i32 {
+    small_len(len).unwrap_or(i32::max_value())
+}
+
 pub trait ToJsString {
     fn to_js_string<'a, T: Scope<'a>>(&self, scope: &mut T) -> Handle<'a, JsString>;
 }
@@ -392,7 +731,7 @@ fn lower_str_unwrap(s: &str) -> (*const u8, i32) {
 // DEPRECATE(0.2)
 /// A JavaScript number value whose value is known statically to be a
 /// 32-bit integer.
-#[repr(C)]
+#[repr(transparent)]
 #[derive(Clone, Copy)]
 pub struct JsInteger(raw::Local);
 
@@ -436,13 +775,15 @@ impl Managed for JsInteger {
 }
 
 impl ValueInternal for JsInteger {
+    fn name() -> String { "JsInteger".to_string() }
+
     fn is_typeof<Other: Value>(other: Other) -> bool {
         unsafe { neon_runtime::tag::is_integer(other.to_raw()) }
     }
 }
 
 /// A JavaScript number value.
-#[repr(C)]
+#[repr(transparent)]
 #[derive(Clone, Copy)]
 pub struct JsNumber(raw::Local);
 
@@ -464,6 +805,63 @@ impl JsNumber {
             neon_runtime::primitive::number_value(self.to_raw())
         }
     }
+
+    /// Converts the number to a `u32` the way a `Uint32Array` stores it: any fraction is
+    /// truncated, the result wraps modulo 2^32, and `NaN` and the infinities become 0.
+    /// Use `to_u32_strict` to throw a `RangeError` instead.
+    pub fn to_u32(self) -> u32 {
+        wrap_u32(self.value())
+    }
+
+    /// Converts the number to an `i32` the way an `Int32Array` stores it. Use
+    /// `to_i32_strict` to throw a `RangeError` instead.
+    pub fn to_i32(self) -> i32 {
+        wrap_u32(self.value()) as i32
+    }
+
+    /// Converts the number to a `u32`, throwing a `RangeError` if it isn't an integer in
+    /// the range of `u32`. A buffer size or array index that doesn't fit is usually a
+    /// bug, which wrapping would hide.
+    pub fn to_u32_strict(self) -> VmResult<u32> {
+        let n = self.value();
+        if n.fract() == 0.0 && n >= 0.0 && n < 4294967296.0 {
+            Ok(n as u32)
+        } else {
+            out_of_range(n, "u32")
+        }
+    }
+
+    /// Converts the number to an `i32`, throwing a `RangeError` if it isn't an integer
+    /// in the range of `i32`.
+    pub fn to_i32_strict(self) -> VmResult<i32> {
+        let n = self.value();
+        if n.fract() == 0.0 && n >= -2147483648.0 && n < 2147483648.0 {
+            Ok(n as i32)
+        } else {
+            out_of_range(n, "i32")
+        }
+    }
+}
+
+// ECMAScript's `ToUint32`.
+fn wrap_u32(n: f64) -> u32 {
+    if !n.is_finite() {
+        return 0;
+    }
+    let n = n.trunc() % 4294967296.0;
+    (if n < 0.0 { n + 4294967296.0 } else { n }) as u32
+}
+
+fn out_of_range<T>(n: f64, ty: &str) -> VmResult<T> {
+    // Formatted as JavaScript would, rather than as `inf` and `NaN`.
+    let n = if n.is_nan() {
+        String::from("NaN")
+    } else if n.is_infinite() {
+        String::from(if n > 0.0 { "Infinity" } else { "-Infinity" })
+    } else {
+        n.to_string()
+    };
+    JsError::throw(Kind::RangeError, &format!("{} is not an integer in the range of {}", n, ty))
 }
 
 impl Value for JsNumber { }
@@ -475,13 +873,15 @@ impl Managed for JsNumber {
 }
 
 impl ValueInternal for JsNumber {
+    fn name() -> String { "JsNumber".to_string() }
+
     fn is_typeof<Other: Value>(other: Other) -> bool {
         unsafe { neon_runtime::tag::is_number(other.to_raw()) }
     }
 }
 
 /// A JavaScript object.
-#[repr(C)]
+#[repr(transparent)]
 #[derive(Clone, Copy)]
 pub struct JsObject(raw::Local);
 
@@ -498,6 +898,8 @@ unsafe impl This for JsObject {
 }
 
 impl ValueInternal for JsObject {
+    fn name() -> String { "JsObject".to_string() }
+
     fn is_typeof<Other: Value>(other: Other) -> bool {
         unsafe { neon_runtime::tag::is_object(other.to_raw()) }
     }
@@ -541,6 +943,55 @@ impl<'a> Key for &'a str {
     }
 }
 
+/// A property key whose JavaScript string is created once per context and then reused,
+/// avoiding a string allocation on every access to a frequently used property.
+///
+/// ```rust,ignore
+/// static NAME: PropertyKey = PropertyKey::intern("name");
+///
+/// object.set(NAME, value)?;
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct PropertyKey(&'static str);
+
+impl PropertyKey {
+    pub const fn intern(name: &'static str) -> PropertyKey {
+        PropertyKey(name)
+    }
+
+    pub fn name(self) -> &'static str {
+        self.0
+    }
+
+    unsafe fn lookup(self, out: &mut raw::Local) -> bool {
+        let mut isolate = Isolate::current();
+        let raw_isolate = isolate.to_raw();
+        let map = isolate.class_map();
+        if let Some(persistent) = map.get_key(self.0) {
+            neon_runtime::mem::read_persistent(out, raw_isolate, persistent);
+            return true;
+        }
+        let (ptr, len) = lower_str_unwrap(self.0);
+        if !neon_runtime::string::new_internalized(out, raw_isolate, ptr, len) {
+            return false;
+        }
+        map.set_key(self.0, neon_runtime::mem::new_persistent(raw_isolate, *out));
+        true
+    }
+}
+
+impl Key for PropertyKey {
+    unsafe fn get(self, out: &mut raw::Local, obj: raw::Local) -> bool {
+        let mut key: raw::Local = mem::zeroed();
+        self.lookup(&mut key) && neon_runtime::object::get(out, obj, key)
+    }
+
+    unsafe fn set(self, out: &mut bool, obj: raw::Local, val: raw::Local) -> bool {
+        let mut key: raw::Local = mem::zeroed();
+        self.lookup(&mut key) && neon_runtime::object::set(out, obj, key, val)
+    }
+}
+
 /// The trait of all object types.
 pub trait Object: Value {
     fn get<'a, T: Scope<'a>, K: Key>(self, _: &mut T, key: K) -> VmResult<Handle<'a, JsValue>> {
@@ -559,6 +1010,39 @@ pub trait Object: Value {
             Err(Throw)
         }
     }
+
+    /// Reads a value stored with `set_hidden`, or `undefined` if the object has none
+    /// under `key`.
+    fn get_hidden<'a, T: Scope<'a>>(self, _: &mut T, key: &str) -> JsResult<'a, JsValue> {
+        build(|out| { unsafe { neon_runtime::object::get_hidden(out, self.to_raw(), key.as_ptr(), key.len() as i32) } })
+    }
+
+    /// Stores a value on the object under a private symbol named by `key`. Hidden
+    /// values can't be seen or modified from JavaScript, which makes them a cheap
+    /// way to cache data keyed by object identity.
+    fn set_hidden<V: Value>(self, key: &str, val: Handle<V>) -> VmResult<bool> {
+        let mut result = false;
+        if unsafe { neon_runtime::object::set_hidden(&mut result, self.to_raw(), key.as_ptr(), key.len() as i32, val.to_raw()) } {
+            Ok(result)
+        } else {
+            Err(Throw)
+        }
+    }
+
+    /// Calls the object's method `name` with the object as `this`, throwing a
+    /// `TypeError` if the property isn't a function.
+    fn call_method<'a, 'b, T, A, AS>(self, scope: &mut T, name: &str, args: AS) -> JsResult<'a, JsValue>
+        where Self: 'b,
+              T: Scope<'a>,
+              A: Value + 'b,
+              AS: IntoIterator<Item=Handle<'b, A>>
+    {
+        let method = match self.get(scope, name)?.downcast::<JsFunction>() {
+            Some(method) => method,
+            None => return JsError::throw(Kind::TypeError, &format!("{} is not a function", name))
+        };
+        method.call(scope, Handle::new_internal(self), args)
+    }
 }
 
 impl Object for JsObject { }
@@ -583,7 +1067,7 @@ impl JsObject {
 
 /// A JavaScript array object, i.e. a value for which `Array.isArray`
 /// would return `true`.
-#[repr(C)]
+#[repr(transparent)]
 #[derive(Clone, Copy)]
 pub struct JsArray(raw::Local);
 
@@ -614,6 +1098,64 @@ impl JsArray {
         }
     }
 
+    /// Creates an array holding the given values, filled in a single call into the
+    /// runtime.
+    pub fn from_slice<'a, 'b, S: Scope<'a>, T: Value>(scope: &mut S, elements: &[Handle<'b, T>]) -> JsResult<'a, JsArray> {
+        let len = array_len(elements.len())?;
+        let isolate = scope.isolate().to_raw();
+        // A `Handle` has the same representation as the `raw::Local` it wraps, unless
+        // it also records its scope.
+        #[cfg(not(feature = "debug-handles"))]
+        let ptr = elements.as_ptr() as *const raw::Local;
+        #[cfg(feature = "debug-handles")]
+        let locals: Vec<raw::Local> = elements.iter().map(|element| element.to_raw()).collect();
+        #[cfg(feature = "debug-handles")]
+        let ptr = locals.as_ptr();
+        build(|out| { unsafe { neon_runtime::array::from_values(out, isolate, ptr, len) } })
+    }
+
+    /// Creates an array of numbers, filled in a single call into the runtime.
+    pub fn from_f64_slice<'a, S: Scope<'a>>(scope: &mut S, elements: &[f64]) -> JsResult<'a, JsArray> {
+        let len = array_len(elements.len())?;
+        let isolate = scope.isolate().to_raw();
+        build(|out| { unsafe { neon_runtime::array::from_numbers(out, isolate, elements.as_ptr(), len) } })
+    }
+
+    /// Reads the array's elements into Rust values, throwing a `TypeError` naming the
+    /// first element that can't be converted.
+    pub fn to_vec_of<'a, T: TryFromJs, S: Scope<'a>>(self, scope: &mut S) -> VmResult<Vec<T>> {
+        let mut result = Vec::with_capacity(self.len() as usize);
+        let mut i = 0;
+        // As with `to_vec`, the length is re-checked since a getter can change it.
+        while i < self.len() {
+            let element = self.get(scope, i)?;
+            match T::from_js(scope, element)? {
+                Some(element) => result.push(element),
+                None => return JsError::throw(Kind::TypeError, &format!("element {}: expected {}", i, T::describe()))
+            }
+            i += 1;
+        }
+        Ok(result)
+    }
+
+    /// Collects the items of a Rust iterator into a new array, allocated up front with
+    /// the iterator's lower size bound.
+    pub fn from_iter<'a, S, I>(scope: &mut S, iter: I) -> JsResult<'a, JsArray>
+        where S: Scope<'a>,
+              I: IntoIterator,
+              I::Item: TryIntoJs
+    {
+        let iter = iter.into_iter();
+        let array = JsArray::new(scope, cmp::min(iter.size_hint().0, u32::max_value() as usize) as u32);
+        let mut i = 0;
+        for item in iter {
+            let item = item.try_into_js(scope)?;
+            Object::set(*array, i, item)?;
+            i += 1;
+        }
+        Ok(array)
+    }
+
     pub fn len(self) -> u32 {
         unsafe {
             neon_runtime::array::len(self.to_raw())
@@ -630,6 +1172,8 @@ impl Managed for JsArray {
 }
 
 impl ValueInternal for JsArray {
+    fn name() -> String { "JsArray".to_string() }
+
     fn is_typeof<Other: Value>(other: Other) -> bool {
         unsafe { neon_runtime::tag::is_array(other.to_raw()) }
     }
@@ -637,8 +1181,46 @@ impl ValueInternal for JsArray {
 
 impl Object for JsArray { }
 
+/// Builds an array one element at a time, keeping track of the next index. Created
+/// with `Scope::array_with_capacity`, and turned into a `JsArray` with `build`, which
+/// fills the array in a single call into the runtime.
+pub struct ArrayBuilder<'a> {
+    elements: Vec<Handle<'a, JsValue>>
+}
+
+impl<'a> ArrayBuilder<'a> {
+    pub(crate) fn with_capacity(capacity: usize) -> ArrayBuilder<'a> {
+        ArrayBuilder { elements: Vec::with_capacity(capacity) }
+    }
+
+    /// Converts `value` to JavaScript and appends it.
+    pub fn push<S: Scope<'a>, T: TryIntoJs>(&mut self, scope: &mut S, value: T) -> VmResult<()> {
+        let value = value.try_into_js(scope)?;
+        self.elements.push(value.upcast());
+        Ok(())
+    }
+
+    /// Appends a value that is already a JavaScript value.
+    pub fn push_handle<T: Value>(&mut self, value: Handle<'a, T>) {
+        self.elements.push(value.upcast());
+    }
+
+    /// The number of elements pushed so far.
+    pub fn len(&self) -> usize {
+        self.elements.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.elements.is_empty()
+    }
+
+    pub fn build<S: Scope<'a>>(self, scope: &mut S) -> JsResult<'a, JsArray> {
+        JsArray::from_slice(scope, &self.elements)
+    }
+}
+
 /// A JavaScript function object.
-#[repr(C)]
+#[repr(transparent)]
 #[derive(Clone, Copy)]
 pub struct JsFunction<T: Object=JsObject> {
     raw: raw::Local,
@@ -650,15 +1232,13 @@ impl<T: Object> Object for JsFunction<T> { }
 // Maximum number of function arguments in V8.
 const V8_ARGC_LIMIT: usize = 65535;
 
-unsafe fn prepare_call<'a, 'b, S: Scope<'a>, A>(scope: &mut S, args: &mut [Handle<'b, A>]) -> VmResult<(*mut c_void, i32, *mut c_void)>
-    where A: Value + 'b
-{
+unsafe fn prepare_call<'a, S: Scope<'a>>(scope: &mut S, args: &mut [raw::Local]) -> VmResult<(*mut c_void, i32, *mut c_void)> {
     let argv = args.as_mut_ptr();
     let argc = args.len();
     if argc > V8_ARGC_LIMIT {
         return JsError::throw(Kind::RangeError, "too many arguments");
     }
-    let isolate: *mut c_void = mem::transmute(scope.isolate().to_raw());
+    let isolate = scope.isolate().to_raw();
     Ok((isolate, argc as i32, argv as *mut c_void))
 }
 
@@ -666,12 +1246,89 @@ impl JsFunction {
     pub fn new<'a, T: Scope<'a>, U: Value>(scope: &mut T, f: fn(Call) -> JsResult<U>) -> JsResult<'a, JsFunction> {
         build(|out| {
             unsafe {
-                let isolate: *mut c_void = mem::transmute(scope.isolate().to_raw());
+                let isolate = scope.isolate().to_raw();
                 let (callback, kernel) = FunctionKernel(f).export();
                 neon_runtime::fun::new(out, isolate, callback, kernel)
             }
         })
     }
+
+    /// Creates a function that calls `f`, which can be a closure capturing configuration
+    /// or shared state. The closure is freed when the function is garbage collected.
+    /// Since `f` is `FnMut`, a call that reenters the function, e.g. through a callback,
+    /// throws an `Error` instead of running the closure again.
+    pub fn from_closure<'a, T: Scope<'a>, U: Value, F>(scope: &mut T, f: F) -> JsResult<'a, JsFunction>
+        where F: FnMut(Call) -> JsResult<U> + 'static
+    {
+        let f = RefCell::new(f);
+        JsFunction::new_closure(scope, move |call| {
+            // A closure that calls back into JavaScript can be reentered, which would
+            // alias its mutable state.
+            match f.try_borrow_mut() {
+                Ok(mut f) => (&mut *f)(call),
+                Err(_) => JsError::throw(Kind::Error, "a function created from a closure can't be called recursively")
+            }
+        })
+    }
+
+    // Creates a function from a closure that can be reentered, since it doesn't
+    // mutate its state.
+    pub(crate) fn new_closure<'a, T: Scope<'a>, U: Value, F>(scope: &mut T, f: F) -> JsResult<'a, JsFunction>
+        where F: Fn(Call) -> JsResult<U> + 'static
+    {
+        let closure: Closure<U> = Box::new(f);
+        let closure = Box::into_raw(Box::new(closure));
+        let result = build(|out| {
+            unsafe {
+                let isolate = scope.isolate().to_raw();
+                let (callback, kernel) = ClosureKernel(closure).export();
+                neon_runtime::fun::new_closure(out, isolate, callback, kernel, drop_closure::<U>)
+            }
+        });
+        if result.is_err() {
+            unsafe { drop_closure::<U>(closure as *mut c_void); }
+        }
+        result
+    }
+
+    /// Creates a function that owns `state` and advances it with `step` each time it's
+    /// called, e.g. a counter or the continuation of a parser fed one chunk per call.
+    /// This is `JsFunction::from_closure` with a closure that only captures `state`,
+    /// so the state is dropped when the function is garbage collected.
+    pub fn with_state<'a, T: Scope<'a>, S: 'static, U: Value + 'static>(scope: &mut T, state: S, step: for<'c> fn(&mut S, Call<'c>) -> JsResult<'c, U>) -> JsResult<'a, JsFunction> {
+        let mut state = state;
+        JsFunction::from_closure(scope, move |call| step(&mut state, call))
+    }
+
+    /// Creates a function like `JsFunction::new`, but from a template that is cached
+    /// per context and keyed by `f`, so creating many functions from the same Rust
+    /// function only builds one template. V8 instantiates a template once per context,
+    /// which means every call with the same `f` returns the same function object.
+    pub fn new_cached<'a, T: Scope<'a>, U: Value>(scope: &mut T, f: fn(Call) -> JsResult<U>) -> JsResult<'a, JsFunction> {
+        let mut isolate = scope.isolate();
+        let raw_isolate = isolate.to_raw();
+        let (callback, kernel) = FunctionKernel(f).export();
+        let slot = isolate.class_map().template_slot(callback, kernel);
+        build(|out| {
+            unsafe {
+                neon_runtime::fun::new_cached(out, raw_isolate, callback, kernel, slot)
+            }
+        })
+    }
+
+    /// Creates a function like `JsFunction::new`, but whose calls skip opening a
+    /// `HandleScope` of their own, relying on the one V8 opens around every native
+    /// callback. This trims the overhead of small, frequently called functions such
+    /// as accessors; handles created during a call are still released when it returns.
+    pub fn new_fast<'a, T: Scope<'a>, U: Value>(scope: &mut T, f: fn(Call) -> JsResult<U>) -> JsResult<'a, JsFunction> {
+        build(|out| {
+            unsafe {
+                let isolate = scope.isolate().to_raw();
+                let (callback, kernel) = FastFunctionKernel(f).export();
+                neon_runtime::fun::new(out, isolate, callback, kernel)
+            }
+        })
+    }
 }
 
 impl<C: Object> JsFunction<C> {
@@ -680,7 +1337,7 @@ impl<C: Object> JsFunction<C> {
               A: Value + 'b,
               AS: IntoIterator<Item=Handle<'b, A>>
     {
-        let mut args = args.into_iter().collect::<Vec<_>>();
+        let mut args = args.into_iter().map(|arg| arg.to_raw()).collect::<Vec<raw::Local>>();
         let (isolate, argc, argv) = unsafe { prepare_call(scope, &mut args) }?;
         build(|out| {
             unsafe {
@@ -693,7 +1350,7 @@ impl<C: Object> JsFunction<C> {
         where A: Value + 'b,
               AS: IntoIterator<Item=Handle<'b, A>>
     {
-        let mut args = args.into_iter().collect::<Vec<_>>();
+        let mut args = args.into_iter().map(|arg| arg.to_raw()).collect::<Vec<raw::Local>>();
         let (isolate, argc, argv) = unsafe { prepare_call(scope, &mut args) }?;
         build(|out| {
             unsafe {
@@ -701,6 +1358,86 @@ impl<C: Object> JsFunction<C> {
             }
         })
     }
+
+    /// Calls the function with `undefined` as `this`, discarding its result. This suits
+    /// callbacks that are only notified of something, such as event listeners.
+    pub fn exec<'a, 'b, S: Scope<'a>>(self, scope: &mut S, args: &[Handle<'b, JsValue>]) -> VmResult<()> {
+        self.call(scope, JsUndefined::new(), args.iter().cloned())?;
+        Ok(())
+    }
+
+    /// Calls the function with no arguments and `undefined` as `this`.
+    pub fn call0<'a, S: Scope<'a>>(self, scope: &mut S) -> JsResult<'a, JsValue> {
+        let args: Vec<Handle<JsValue>> = vec![];
+        self.call(scope, JsUndefined::new(), args)
+    }
+
+    /// Calls the function with one argument and `undefined` as `this`.
+    pub fn call1<'a, 'b, S: Scope<'a>, A: Value>(self, scope: &mut S, a: Handle<'b, A>) -> JsResult<'a, JsValue> {
+        self.call(scope, JsUndefined::new(), vec![a.upcast::<JsValue>()])
+    }
+
+    /// Calls the function with two arguments and `undefined` as `this`.
+    pub fn call2<'a, 'b, S: Scope<'a>, A: Value, B: Value>(self, scope: &mut S, a: Handle<'b, A>, b: Handle<'b, B>) -> JsResult<'a, JsValue> {
+        self.call(scope, JsUndefined::new(), vec![a.upcast::<JsValue>(), b.upcast()])
+    }
+
+    /// The function's name, which is empty for an anonymous function.
+    pub fn name<'a, S: Scope<'a>>(self, _: &mut S) -> String {
+        unsafe {
+            let mut name: raw::Local = mem::zeroed();
+            neon_runtime::fun::get_name(&mut name, self.to_raw());
+            JsString::downcast(JsValue::from_raw(name)).map(|name| name.value()).unwrap_or_default()
+        }
+    }
+
+    /// Sets the function's name, as reported by its `name` property, in stack traces,
+    /// and by debuggers and profilers. This is most useful for functions created from
+    /// Rust, which are otherwise anonymous.
+    pub fn set_name<'a, S: Scope<'a>>(self, scope: &mut S, name: &str) -> VmResult<()> {
+        let name = JsString::new_or_throw(scope, name)?;
+        unsafe {
+            neon_runtime::fun::set_name(self.to_raw(), name.to_raw());
+        }
+        Ok(())
+    }
+
+    /// The number of parameters the function declares, as reported by its `length`
+    /// property. Functions created from Rust have a length of 0.
+    pub fn length<'a, S: Scope<'a>>(self, scope: &mut S) -> VmResult<u32> {
+        let length = self.get(scope, "length")?;
+        Ok(length.downcast::<JsNumber>().map(|length| length.value() as u32).unwrap_or(0))
+    }
+
+    /// Where the function was defined, or `None` if it wasn't defined in a script,
+    /// e.g. if it was created from Rust or with `Function.prototype.bind`.
+    pub fn location<'a, S: Scope<'a>>(self, _: &mut S) -> Option<FunctionLocation> {
+        unsafe {
+            let mut resource: raw::Local = mem::zeroed();
+            let mut line = 0;
+            let mut column = 0;
+            if !neon_runtime::fun::get_script_location(&mut resource, &mut line, &mut column, self.to_raw()) {
+                return None;
+            }
+            let resource = JsValue::from_raw(resource);
+            Some(FunctionLocation {
+                resource: JsString::downcast(resource).map(|resource| resource.value()).unwrap_or_default(),
+                line: line as u32 + 1,
+                column: column as u32 + 1
+            })
+        }
+    }
+}
+
+/// Where a function was defined in JavaScript source, as reported by `JsFunction::location`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FunctionLocation {
+    /// The name of the script, usually the path or URL of its file.
+    pub resource: String,
+    /// The 1-based line of the function's definition.
+    pub line: u32,
+    /// The 1-based column of the function's definition.
+    pub column: u32
 }
 
 impl<T: Object> Value for JsFunction<T> { }
@@ -717,6 +1454,8 @@ impl<T: Object> Managed for JsFunction<T> {
 }
 
 impl<T: Object> ValueInternal for JsFunction<T> {
+    fn name() -> String { "JsFunction".to_string() }
+
     fn is_typeof<Other: Value>(other: Other) -> bool {
         unsafe { neon_runtime::tag::is_function(other.to_raw()) }
     }
@@ -0,0 +1,272 @@
+//! Node streams backed by Rust data sources and consumers.
+
+use std::cell::RefCell;
+use std::fmt::Display;
+use std::sync::mpsc::{Sender, SendError};
+use neon_runtime::raw;
+use mem::{Handle, Managed};
+use scope::Scope;
+use vm::{JsResult, VmResult, Call, Lock, FunctionCall};
+use vm::internal::LockState;
+use js::{JsValue, JsObject, JsFunction, JsBoolean, JsNull, JsUndefined, Object, Value};
+use js::binary::JsBuffer;
+use js::class::{Class, ClassDescriptor, MethodContext};
+use js::class::internal::{AllocateKernel, MethodKernel};
+use js::error::{JsError, Kind};
+use js::iter::JsIterator;
+
+/// Creates a Node `Readable` stream that produces the chunks of `iter`, ending when
+/// the iterator does.
+///
+/// Chunks are pulled only as the stream asks for them, and pulling stops whenever the
+/// stream's buffer is full, so a slow consumer applies backpressure to the iterator.
+pub fn readable<'a, S, I>(scope: &mut S, iter: I) -> JsResult<'a, JsObject>
+    where S: Scope<'a>,
+          I: Iterator<Item=Vec<u8>> + 'static
+{
+    let source = JsIterator::new(scope, iter, |scope, chunk| Ok(JsBuffer::external(scope, chunk)?.upcast()))?;
+    let stream_module = scope.require("stream")?.check::<JsObject>()?;
+    let constructor = stream_module.get(scope, "Readable")?.check::<JsFunction>()?;
+    let args: Vec<Handle<JsValue>> = vec![];
+    let stream = constructor.construct(scope, args)?;
+    // `_read` is bound to the stream and its source, since Rust functions don't
+    // close over state.
+    let read = JsFunction::new_cached(scope, read_chunks)?;
+    let bind = read.get(scope, "bind")?.check::<JsFunction>()?;
+    let args: Vec<Handle<JsValue>> = vec![JsNull::new().upcast(), stream.upcast(), source.upcast()];
+    let read = bind.call(scope, read, args)?;
+    stream.set("_read", read)?;
+    Ok(stream)
+}
+
+// A readable stream's `_read`, bound to the stream and its source. It pushes chunks
+// until the stream stops asking for them or the source runs out.
+fn read_chunks(call: Call) -> JsResult<JsUndefined> {
+    let scope = call.scope;
+    let stream = call.arguments.require(scope, 0)?.check::<JsObject>()?;
+    let source = call.arguments.require(scope, 1)?.check::<JsObject>()?;
+    let next = source.get(scope, "next")?.check::<JsFunction>()?;
+    let push = stream.get(scope, "push")?.check::<JsFunction>()?;
+    loop {
+        let args: Vec<Handle<JsValue>> = vec![];
+        let result = next.call(scope, source, args)?.check::<JsObject>()?;
+        if result.get(scope, "done")?.check::<JsBoolean>()?.value() {
+            let args: Vec<Handle<JsNull>> = vec![JsNull::new()];
+            push.call(scope, stream, args)?;
+            return Ok(JsUndefined::new());
+        }
+        let chunk = result.get(scope, "value")?;
+        if !push.call(scope, stream, vec![chunk])?.to_js_boolean(scope).value() {
+            return Ok(JsUndefined::new());
+        }
+    }
+}
+
+/// A Rust consumer of the chunks written to a stream created with `writable`.
+pub trait Sink: 'static {
+    /// The error that fails the stream, reported as an `Error` with its message.
+    type Error: Display;
+
+    /// Consumes the next chunk written to the stream.
+    fn write(&mut self, chunk: &[u8]) -> Result<(), Self::Error>;
+
+    /// Called once the stream has ended and every chunk has been written. The sink
+    /// is dropped afterwards.
+    fn finish(&mut self) -> Result<(), Self::Error> {
+        Ok(())
+    }
+}
+
+/// Sends each chunk to the receiving end, which sees the channel close once the
+/// stream has finished.
+impl Sink for Sender<Vec<u8>> {
+    type Error = SendError<Vec<u8>>;
+
+    fn write(&mut self, chunk: &[u8]) -> Result<(), Self::Error> {
+        self.send(chunk.to_vec())
+    }
+}
+
+/// Creates a Node `Writable` stream that delivers the chunks written to it to `sink`.
+///
+/// Strings are written as UTF-8 unless the stream's `setDefaultEncoding` says
+/// otherwise. An error from the sink fails the stream, which emits it as an `error`
+/// event.
+pub fn writable<'a, S, K>(scope: &mut S, sink: K) -> JsResult<'a, JsObject>
+    where S: Scope<'a>,
+          K: Sink
+{
+    new_stream(scope, "Writable", "write", "final", Box::new(SinkProcessor(sink)))
+}
+
+/// A builder for a Node `Transform` stream that passes each chunk written to it
+/// through a Rust function, and makes the results available for reading.
+///
+/// An error from the function fails the stream, which emits it as an `error` event.
+/// Backpressure is handled by the stream: chunks are only transformed as fast as the
+/// output is read.
+pub struct TransformStream<F, E> {
+    transform: F,
+    flush: Option<Box<dyn FnMut() -> Result<Vec<u8>, E>>>
+}
+
+impl<F, E> TransformStream<F, E>
+    where F: FnMut(&[u8]) -> Result<Vec<u8>, E> + 'static,
+          E: Display + 'static
+{
+    /// Starts building a stream that transforms each chunk with `transform`. Empty
+    /// results produce no output.
+    pub fn new(transform: F) -> TransformStream<F, E> {
+        TransformStream { transform: transform, flush: None }
+    }
+
+    /// Sets a function that produces any remaining output once the input has ended.
+    pub fn flush<G>(mut self, flush: G) -> TransformStream<F, E>
+        where G: FnMut() -> Result<Vec<u8>, E> + 'static
+    {
+        self.flush = Some(Box::new(flush));
+        self
+    }
+
+    /// Creates the stream.
+    pub fn build<'a, S: Scope<'a>>(self, scope: &mut S) -> JsResult<'a, JsObject> {
+        new_stream(scope, "Transform", "transform", "flush", Box::new(self))
+    }
+}
+
+impl<F, E> Processor for TransformStream<F, E>
+    where F: FnMut(&[u8]) -> Result<Vec<u8>, E>,
+          E: Display
+{
+    fn process(&mut self, chunk: &[u8]) -> Result<Vec<u8>, String> {
+        (self.transform)(chunk).map_err(|err| err.to_string())
+    }
+
+    fn flush(&mut self) -> Result<Vec<u8>, String> {
+        match self.flush {
+            Some(ref mut flush) => flush().map_err(|err| err.to_string()),
+            None => Ok(Vec::new())
+        }
+    }
+}
+
+// The type-erased Rust side of a stream: each chunk written to the stream is
+// processed into a (possibly empty) chunk to be read from it.
+trait Processor {
+    fn process(&mut self, chunk: &[u8]) -> Result<Vec<u8>, String>;
+
+    fn flush(&mut self) -> Result<Vec<u8>, String>;
+}
+
+struct SinkProcessor<K>(K);
+
+impl<K: Sink> Processor for SinkProcessor<K> {
+    fn process(&mut self, chunk: &[u8]) -> Result<Vec<u8>, String> {
+        self.0.write(chunk).map(|()| Vec::new()).map_err(|err| err.to_string())
+    }
+
+    fn flush(&mut self) -> Result<Vec<u8>, String> {
+        self.0.finish().map(|()| Vec::new()).map_err(|err| err.to_string())
+    }
+}
+
+thread_local! {
+    // The processor for the next `JsStreamProcessor` instance, handed from
+    // `new_stream` to the class's allocator.
+    static PENDING: RefCell<Option<Box<dyn Processor>>> = RefCell::new(None);
+}
+
+// Constructs a `stream` module class, with the processor's methods as the options
+// named `process_key` and `flush_key`.
+fn new_stream<'a, S: Scope<'a>>(scope: &mut S, class_name: &str, process_key: &str, flush_key: &str, processor: Box<dyn Processor>) -> JsResult<'a, JsObject> {
+    PENDING.with(|pending| *pending.borrow_mut() = Some(processor));
+    let class = JsStreamProcessor::class(scope)?;
+    let constructor = class.constructor(scope)?;
+    let args: Vec<Handle<JsValue>> = vec![];
+    let processor = constructor.construct(scope, args);
+    PENDING.with(|pending| pending.borrow_mut().take());
+    let processor = processor?;
+    let options = JsObject::new(scope);
+    for &(key, method) in &[(process_key, "process"), (flush_key, "flush")] {
+        let method = processor.get(scope, method)?.check::<JsFunction>()?;
+        let bind = method.get(scope, "bind")?.check::<JsFunction>()?;
+        let bound = bind.call(scope, method, vec![processor])?;
+        options.set(key, bound)?;
+    }
+    let stream_module = scope.require("stream")?.check::<JsObject>()?;
+    let constructor = stream_module.get(scope, class_name)?.check::<JsFunction>()?;
+    constructor.construct(scope, vec![options])
+}
+
+#[repr(transparent)]
+#[derive(Clone, Copy)]
+struct JsStreamProcessor(raw::Local);
+
+impl Managed for JsStreamProcessor {
+    fn to_raw(self) -> raw::Local { self.0 }
+
+    fn from_raw(h: raw::Local) -> Self { JsStreamProcessor(h) }
+}
+
+// The processor is dropped once the stream has been flushed.
+struct ProcessorInternals {
+    processor: Option<Box<dyn Processor>>
+}
+
+impl Class for JsStreamProcessor {
+    type Internals = ProcessorInternals;
+
+    fn setup<'a, T: Scope<'a>>(_: &mut T) -> VmResult<ClassDescriptor<'a, Self>> {
+        Ok(Self::describe("NativeStreamProcessor", AllocateKernel::new(allocate))
+            .method("process", MethodKernel::new(process))
+            .method("flush", MethodKernel::new(flush)))
+    }
+}
+
+fn allocate(_: FunctionCall<JsUndefined>) -> VmResult<ProcessorInternals> {
+    match PENDING.with(|pending| pending.borrow_mut().take()) {
+        Some(processor) => Ok(ProcessorInternals { processor: Some(processor) }),
+        None => JsError::throw(Kind::TypeError, "NativeStreamProcessor cannot be constructed from JavaScript")
+    }
+}
+
+// Called as `process(chunk, encoding, callback)`.
+fn process(mut call: MethodContext<JsStreamProcessor>) -> JsResult<JsValue> {
+    let mut chunk = call.arguments.require(call.scope, 0)?.check::<JsBuffer>()?;
+    let callback = call.arguments.require(call.scope, 2)?.check::<JsFunction>()?;
+    let result = call.this_internals_mut(|internals| {
+        match internals.processor {
+            Some(ref mut processor) => {
+                let mut state = LockState::new();
+                let data = unsafe { (&mut *chunk).expose(&mut state) };
+                match state.conflict() {
+                    Some(conflict) => Err(conflict),
+                    None => processor.process(data.as_slice::<u8>())
+                }
+            }
+            None => Err("write after the stream was flushed".to_string())
+        }
+    })?;
+    complete(call.scope, callback, result)
+}
+
+// Called as `flush(callback)`.
+fn flush(mut call: MethodContext<JsStreamProcessor>) -> JsResult<JsValue> {
+    let callback = call.arguments.require(call.scope, 0)?.check::<JsFunction>()?;
+    let result = match call.this_internals_mut(|internals| internals.processor.take())? {
+        Some(mut processor) => processor.flush(),
+        None => Ok(Vec::new())
+    };
+    complete(call.scope, callback, result)
+}
+
+// Calls a stream callback with the error or the processed chunk, if there is one.
+fn complete<'a, S: Scope<'a>>(scope: &mut S, callback: Handle<JsFunction>, result: Result<Vec<u8>, String>) -> JsResult<'a, JsValue> {
+    let args: Vec<Handle<JsValue>> = match result {
+        Ok(ref output) if output.is_empty() => vec![JsNull::new().upcast()],
+        Ok(output) => vec![JsNull::new().upcast(), JsBuffer::external(scope, output)?.upcast()],
+        Err(message) => vec![JsError::new(scope, Kind::Error, &message[..])?.upcast()]
+    };
+    callback.call(scope, JsNull::new(), args)?;
+    Ok(JsUndefined::new().upcast())
+}
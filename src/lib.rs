@@ -4,6 +4,19 @@ extern crate neon_runtime;
 extern crate cslice;
 extern crate semver;
 
+#[cfg(feature = "serde")]
+#[macro_use]
+extern crate serde as serde_crate;
+
+#[cfg(feature = "chrono")]
+extern crate chrono;
+
+#[cfg(feature = "backtrace")]
+extern crate backtrace;
+
+#[cfg(feature = "log")]
+extern crate log as log_crate;
+
 #[cfg(test)]
 extern crate rustc_version;
 
@@ -17,6 +30,22 @@ pub mod scope;
 pub mod js;
 pub mod task;
 pub mod meta;
+pub mod convert;
+pub mod instrument;
+pub mod profiler;
+pub mod gc;
+pub mod testing;
+
+#[cfg(feature = "serde")]
+pub mod serde;
+
+#[cfg(feature = "log")]
+pub mod logger;
+
+/// The raw V8 types underlying Neon's handles, for interoperating with other native
+/// libraries. These are unstable and may change with any release of Neon.
+#[cfg(feature = "unstable-raw")]
+pub use neon_runtime::raw;
 
 #[doc(hidden)]
 pub mod macro_internal;
@@ -54,9 +83,9 @@ macro_rules! register_module {
                     dso_handle: *mut u8,
                     filename: *const u8,
                     register_func: Option<extern "C" fn(
-                        $crate::mem::Handle<$crate::js::JsObject>, *mut u8, *mut u8)>,
+                        $crate::macro_internal::runtime::raw::Local, $crate::macro_internal::runtime::raw::Local, *mut u8)>,
                     context_register_func: Option<extern "C" fn(
-                        $crate::mem::Handle<$crate::js::JsObject>, *mut u8, *mut u8, *mut u8)>,
+                        $crate::macro_internal::runtime::raw::Local, $crate::macro_internal::runtime::raw::Local, *mut u8, *mut u8)>,
                     modname: *const u8,
                     priv_data: *mut u8,
                     link: *mut __NodeModule
@@ -68,15 +97,22 @@ macro_rules! register_module {
                     dso_handle: 0 as *mut _,
                     filename: b"neon_source.rs\0" as *const u8,
                     register_func: Some(__register_neon_module),
-                    context_register_func: None,
+                    context_register_func: Some(__register_neon_module_context),
                     modname: b"neon_module\0" as *const u8,
                     priv_data: 0 as *mut _,
                     link: 0 as *mut _
                 };
 
                 extern "C" fn __register_neon_module(
-                        m: $crate::mem::Handle<$crate::js::JsObject>, _: *mut u8, _: *mut u8) {
-                    $crate::vm::Module::initialize(m, __init_neon_module);
+                        m: $crate::macro_internal::runtime::raw::Local, module: $crate::macro_internal::runtime::raw::Local, _: *mut u8) {
+                    $crate::vm::Module::initialize_module(m, module, __init_neon_module);
+                }
+
+                // Node prefers this to `__register_neon_module`, and runs it once for every
+                // context that loads the module, each with its own exports.
+                extern "C" fn __register_neon_module_context(
+                        m: $crate::macro_internal::runtime::raw::Local, module: $crate::macro_internal::runtime::raw::Local, _: *mut u8, _: *mut u8) {
+                    $crate::vm::Module::initialize_module(m, module, __init_neon_module);
                 }
 
                 extern "C" {
@@ -89,6 +125,8 @@ macro_rules! register_module {
                 unsafe {
                     // Set the ABI version based on the NODE_MODULE_VERSION constant provided by the current node headers.
                     __NODE_MODULE.version = $crate::macro_internal::runtime::module::get_version();
+                    $crate::macro_internal::runtime::module::set_initializer(
+                        __register_neon_module_context as *mut ::std::os::raw::c_void);
                     node_module_register(&mut __NODE_MODULE);
                 }
             }
@@ -101,7 +139,7 @@ macro_rules! register_module {
 #[doc(hidden)]
 #[macro_export]
 macro_rules! class_definition {
-    ( $cls:ident ; $cname:ident ; $typ:ty ; $allocator:tt ; $call_ctor:tt ; $new_ctor:tt ; $mnames:tt ; $mdefs:tt ; init($call:pat) $body:block $($rest:tt)* ) => {
+    ( $cls:ident ; $cname:ident ; $typ:ty ; $allocator:tt ; $call_ctor:tt ; $new_ctor:tt ; $mnames:tt ; $mdefs:tt ; $extras:tt ; $hint:tt ; init($call:pat) $body:block $($rest:tt)* ) => {
         class_definition!($cls ;
                           $cname ;
                           $typ ;
@@ -116,10 +154,12 @@ macro_rules! class_definition {
                           $new_ctor ;
                           $mnames ;
                           $mdefs ;
+                          $extras ;
+                          $hint ;
                           $($rest)*);
     };
 
-    ( $cls:ident ; $cname:ident ; $typ:ty ; $allocator:tt ; $call_ctor:tt ; $new_ctor:tt ; ($($mname:tt)*) ; ($($mdef:tt)*) ; method $name:ident($call:pat) $body:block $($rest:tt)* ) => {
+    ( $cls:ident ; $cname:ident ; $typ:ty ; $allocator:tt ; $call_ctor:tt ; $new_ctor:tt ; ($($mname:tt)*) ; ($($mdef:tt)*) ; $extras:tt ; $hint:tt ; method $name:ident($call:pat) $body:block $($rest:tt)* ) => {
         class_definition!($cls ;
                           $cname ;
                           $typ ;
@@ -128,16 +168,78 @@ macro_rules! class_definition {
                           $new_ctor ;
                           ($($mname)* $name) ;
                           ($($mdef)* {
-                              fn _______method_rust_y_u_no_hygienic_items_______($call: $crate::vm::FunctionCall<$cls>) -> $crate::vm::JsResult<$crate::js::JsValue> {
+                              fn _______method_rust_y_u_no_hygienic_items_______($call: $crate::js::class::MethodContext<$cls>) -> $crate::vm::JsResult<$crate::js::JsValue> {
                                   $body
                               }
 
                               $crate::macro_internal::MethodKernel::new(_______method_rust_y_u_no_hygienic_items_______)
                           }) ;
+                          $extras ;
+                          $hint ;
+                          $($rest)*);
+    };
+
+    ( $cls:ident ; $cname:ident ; $typ:ty ; $allocator:tt ; $call_ctor:tt ; $new_ctor:tt ; $mnames:tt ; $mdefs:tt ; ($($extra:tt)*) ; $hint:tt ; iterator($call:pat) $body:block $($rest:tt)* ) => {
+        class_definition!($cls ;
+                          $cname ;
+                          $typ ;
+                          $allocator ;
+                          $call_ctor ;
+                          $new_ctor ;
+                          $mnames ;
+                          $mdefs ;
+                          ($($extra)* .iterator({
+                              fn _______iterator_rust_y_u_no_hygienic_items_______($call: $crate::js::class::MethodContext<$cls>) -> $crate::vm::JsResult<$crate::js::JsValue> {
+                                  $body
+                              }
+
+                              $crate::macro_internal::MethodKernel::new(_______iterator_rust_y_u_no_hygienic_items_______)
+                          })) ;
+                          $hint ;
+                          $($rest)*);
+    };
+
+    ( $cls:ident ; $cname:ident ; $typ:ty ; $allocator:tt ; $call_ctor:tt ; $new_ctor:tt ; $mnames:tt ; $mdefs:tt ; ($($extra:tt)*) ; $hint:tt ; async_iterator($call:pat) $body:block $($rest:tt)* ) => {
+        class_definition!($cls ;
+                          $cname ;
+                          $typ ;
+                          $allocator ;
+                          $call_ctor ;
+                          $new_ctor ;
+                          $mnames ;
+                          $mdefs ;
+                          ($($extra)* .async_iterator({
+                              fn _______async_iterator_rust_y_u_no_hygienic_items_______($call: $crate::js::class::MethodContext<$cls>) -> $crate::vm::JsResult<$crate::js::JsValue> {
+                                  $body
+                              }
+
+                              $crate::macro_internal::MethodKernel::new(_______async_iterator_rust_y_u_no_hygienic_items_______)
+                          })) ;
+                          $hint ;
+                          $($rest)*);
+    };
+
+    ( $cls:ident ; $cname:ident ; $typ:ty ; $allocator:tt ; $call_ctor:tt ; $new_ctor:tt ; $mnames:tt ; $mdefs:tt ; ($($extra:tt)*) ; $hint:tt ; size_hint($internals:pat) $body:block $($rest:tt)* ) => {
+        class_definition!($cls ;
+                          $cname ;
+                          $typ ;
+                          $allocator ;
+                          $call_ctor ;
+                          $new_ctor ;
+                          $mnames ;
+                          $mdefs ;
+                          ($($extra)*) ;
+                          (fn size_hint() -> ::std::option::Option<fn(&$typ) -> usize> {
+                              fn _______size_hint_rust_y_u_no_hygienic_items_______($internals: &$typ) -> usize {
+                                  $body
+                              }
+
+                              ::std::option::Option::Some(_______size_hint_rust_y_u_no_hygienic_items_______)
+                          }) ;
                           $($rest)*);
     };
 
-    ( $cls:ident ; $cname:ident ; $typ:ty ; $allocator:tt ; $call_ctor:tt ; $new_ctor:tt ; $mnames:tt ; $mdefs:tt ; constructor($call:pat) $body:block $($rest:tt)* ) => {
+    ( $cls:ident ; $cname:ident ; $typ:ty ; $allocator:tt ; $call_ctor:tt ; $new_ctor:tt ; $mnames:tt ; $mdefs:tt ; $extras:tt ; $hint:tt ; constructor($call:pat) $body:block $($rest:tt)* ) => {
         class_definition!($cls ;
                           $cname ;
                           $typ ;
@@ -152,10 +254,12 @@ macro_rules! class_definition {
                           }) ;
                           $mnames ;
                           $mdefs ;
+                          $extras ;
+                          $hint ;
                           $($rest)*);
     };
 
-    ( $cls:ident ; $cname:ident ; $typ:ty ; $allocator:tt ; $call_ctor:tt ; $new_ctor:tt ; $mnames:tt ; $mdefs:tt ; call($call:pat) $body:block $($rest:tt)* ) => {
+    ( $cls:ident ; $cname:ident ; $typ:ty ; $allocator:tt ; $call_ctor:tt ; $new_ctor:tt ; $mnames:tt ; $mdefs:tt ; $extras:tt ; $hint:tt ; call($call:pat) $body:block $($rest:tt)* ) => {
         class_definition!($cls ;
                           $cname ;
                           $typ ;
@@ -170,10 +274,12 @@ macro_rules! class_definition {
                           $new_ctor ;
                           $mnames ;
                           $mdefs ;
+                          $extras ;
+                          $hint ;
                           $($rest)*);
     };
 
-    ( $cls:ident ; $cname:ident ; $typ:ty ; $allocator:block ; ($($call_ctor:block)*) ; ($($new_ctor:block)*) ; ($($mname:ident)*) ; ($($mdef:block)*) ; $($rest:tt)* ) => {
+    ( $cls:ident ; $cname:ident ; $typ:ty ; $allocator:block ; ($($call_ctor:block)*) ; ($($new_ctor:block)*) ; ($($mname:ident)*) ; ($($mdef:block)*) ; ($($extra:tt)*) ; ($($hint:item)*) ; $($rest:tt)* ) => {
         impl $crate::js::class::Class for $cls {
             type Internals = $typ;
 
@@ -181,8 +287,11 @@ macro_rules! class_definition {
                 ::std::result::Result::Ok(Self::describe(stringify!($cname), $allocator)
                                              $(.construct($new_ctor))*
                                              $(.call($call_ctor))*
-                                             $(.method(stringify!($mname), $mdef))*)
+                                             $(.method(stringify!($mname), $mdef))*
+                                             $($extra)*)
             }
+
+            $($hint)*
         }
     };
 }
@@ -249,26 +358,26 @@ macro_rules! declare_types {
 
     { $(#[$attr:meta])* pub class $cls:ident as $cname:ident for $typ:ty { $($body:tt)* } $($rest:tt)* } => {
         #[derive(Copy, Clone)]
-        #[repr(C)]
+        #[repr(transparent)]
         $(#[$attr])*
         pub struct $cls($crate::macro_internal::runtime::raw::Local);
 
         impl_managed!($cls);
 
-        class_definition!($cls ; $cname ; $typ ; () ; () ; () ; () ; () ; $($body)*);
+        class_definition!($cls ; $cname ; $typ ; () ; () ; () ; () ; () ; () ; () ; $($body)*);
 
         declare_types! { $($rest)* }
     };
 
     { $(#[$attr:meta])* class $cls:ident as $cname:ident for $typ:ty { $($body:tt)* } $($rest:tt)* } => {
         #[derive(Copy, Clone)]
-        #[repr(C)]
+        #[repr(transparent)]
         $(#[$attr])*
         struct $cls($crate::macro_internal::runtime::raw::Local);
 
         impl_managed!($cls);
 
-        class_definition!($cls ; $cname ; $typ ; () ; () ; () ; () ; () ; $($body)*);
+        class_definition!($cls ; $cname ; $typ ; () ; () ; () ; () ; () ; () ; () ; $($body)*);
 
         declare_types! { $($rest)* }
     };
@@ -279,6 +388,75 @@ macro_rules! declare_types {
 #[cfg(all(windows, not(neon_profile = "release")))]
 compile_error!("Neon only builds with --release. For tests, try `cargo test --release`.");
 
+/// Dispatch a function call to one of several variants based on the number and types
+/// of its arguments, throwing a `TypeError` if none of them match.
+///
+/// Variants are tried in order, so more specific signatures should come first.
+///
+/// Example:
+///
+/// ```rust,ignore
+/// declare_types! {
+///     pub class JsThing for Thing {
+///         init(mut call) {
+///             overload!(call, "Thing", {
+///                 (path: JsString) => {
+///                     Ok(Thing::open(&path.value()))
+///                 }
+///                 (buffer: JsBuffer) => {
+///                     Ok(Thing::from_bytes(buffer.grab(|contents| contents.as_slice().to_vec())))
+///                 }
+///             })
+///         }
+///     }
+/// }
+/// ```
+#[macro_export]
+macro_rules! overload {
+    ($call:ident, $name:expr, { $( ( $($arg:ident : $ty:ty),* ) => $body:block )* }) => {{
+        let __overload_arguments = $crate::macro_internal::arguments(&mut $call);
+        match &__overload_arguments[..] {
+            $(
+                &[$($arg),*] if true $(&& $arg.is_a::<$ty>())* => {
+                    $(let $arg: $crate::mem::Handle<$ty> = $arg.downcast::<$ty>().unwrap();)*
+                    $body
+                }
+            )*
+            _ => {
+                let signatures: &[&str] = &[$(stringify!(($($ty),*))),*];
+                let msg = format!("no overload of {} matches the arguments; expected one of {}", $name, signatures.join(", "));
+                $crate::js::error::JsError::throw($crate::js::error::Kind::TypeError, &msg[..])
+            }
+        }
+    }};
+}
+
+/// Asserts that a JavaScript value is structurally equal to an expected value, given
+/// as JSON text or as a JavaScript value, panicking with the first difference between
+/// them if it isn't. See `neon::testing::diff` for how values are compared.
+///
+/// The comparison runs JavaScript, so it can throw, and the macro propagates the
+/// exception with `?`.
+///
+/// Example:
+///
+/// ```rust,ignore
+/// fn test_point(call: Call) -> VmResult<()> {
+///     let scope = call.scope;
+///     let point = make_point(scope, 1.0, 2.0)?;
+///     assert_js_eq!(scope, point, r#"{ "x": 1, "y": 2 }"#);
+///     Ok(())
+/// }
+/// ```
+#[macro_export]
+macro_rules! assert_js_eq {
+    ($scope:expr, $actual:expr, $expected:expr) => {
+        if let Some(difference) = $crate::testing::diff($scope, $actual, $expected)? {
+            panic!("assertion failed: JavaScript values are not equal\n{}", difference);
+        }
+    };
+}
+
 #[cfg(test)]
 mod tests {
     use std::path::{Path, PathBuf};
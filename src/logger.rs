@@ -0,0 +1,64 @@
+//! A logger for the `log` crate that writes to the JavaScript console, so that the
+//! logging of Rust libraries used by an addon shows up in Node's output. Requires the
+//! `log` feature.
+
+use log_crate::{self, Log, Level, LevelFilter, Metadata, Record, SetLoggerError};
+use scope::{Scope, RootScope};
+use task::Channel;
+use js::{JsString, Object};
+use vm::VmResult;
+
+struct ConsoleLogger {
+    channel: Channel,
+    level: LevelFilter
+}
+
+impl Log for ConsoleLogger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= self.level
+    }
+
+    fn log(&self, record: &Record) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+        let method = match record.level() {
+            Level::Error => "error",
+            Level::Warn => "warn",
+            _ => "log"
+        };
+        let message = format!("[{} {}] {}", record.level(), record.target(), record.args());
+        self.channel.send(move |scope| write(scope, method, &message));
+    }
+
+    fn flush(&self) { }
+}
+
+fn write(scope: &mut RootScope, method: &str, message: &str) -> VmResult<()> {
+    let console = scope.console()?;
+    let message = JsString::new_or_throw(scope, message)?;
+    console.call_method(scope, method, vec![message])?;
+    Ok(())
+}
+
+/// Installs a logger that writes records at every level to the console of the current
+/// JavaScript thread, prefixed with their level and target. Errors are written with
+/// `console.error`, warnings with `console.warn`, and everything else with
+/// `console.log`. This fails if a logger has already been installed.
+///
+/// Records can be logged from any thread. They're written from the event loop in the
+/// order they were logged, so even a record logged on the JavaScript thread shows up
+/// after the native call that logged it returns. The logger doesn't keep the process
+/// running, so records logged just before it exits may be lost.
+pub fn init<'a, S: Scope<'a>>(scope: &mut S) -> Result<(), SetLoggerError> {
+    init_with_level(scope, LevelFilter::Trace)
+}
+
+/// Installs a logger like `init` that only writes records at `level` or above.
+pub fn init_with_level<'a, S: Scope<'a>>(scope: &mut S, level: LevelFilter) -> Result<(), SetLoggerError> {
+    let channel = Channel::new(scope);
+    channel.unref(scope);
+    log_crate::set_boxed_logger(Box::new(ConsoleLogger { channel: channel, level: level }))?;
+    log_crate::set_max_level(level);
+    Ok(())
+}
@@ -4,6 +4,16 @@
 // Used by the class macro.
 pub use js::class::internal::{AllocateKernel, ConstructKernel, ConstructorCallKernel, MethodKernel};
 
+use mem::Handle;
+use vm::{FunctionCall, This};
+use js::JsValue;
+
+// Used by the overload macro.
+pub fn arguments<'a, T: This>(call: &mut FunctionCall<'a, T>) -> Vec<Handle<'a, JsValue>> {
+    let len = call.arguments.len();
+    (0..len).map(|i| call.arguments.get(call.scope, i).unwrap()).collect()
+}
+
 // An alias for neon_runtime so macros can refer to it.
 pub mod runtime {
     pub use neon_runtime::*;
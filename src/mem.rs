@@ -2,16 +2,26 @@
 //!
 //! 
 
+use std::collections::HashMap;
+use std::fmt::{self, Debug, Display, Formatter};
+use std::hash::Hash;
 use std::marker::PhantomData;
+use std::mem;
 use std::ops::{Deref, DerefMut};
+use std::os::raw::c_void;
+use std::sync::{Arc, Mutex};
+use std::thread::{self, ThreadId};
 use neon_runtime;
 use neon_runtime::raw;
-use js::Value;
+use js::{Value, Object, JsValue, JsArray, JsNumber, JsString, JsFunction, JsUndefined};
 use js::internal::SuperType;
 use js::error::{JsError, Kind};
-use vm::{JsResult, Lock};
-use vm::internal::LockState;
-use scope::Scope;
+use vm::{JsResult, VmResult, Lock};
+use vm::internal::{Isolate, LockState};
+use scope::{Scope, InspectOptions};
+#[cfg(feature = "debug-handles")]
+use scope::generation;
+use task::AsyncSignal;
 
 pub trait Managed: Copy {
     fn to_raw(self) -> raw::Local;
@@ -19,10 +29,18 @@ pub trait Managed: Copy {
     fn from_raw(h: raw::Local) -> Self;
 }
 
-#[repr(C)]
+/// A handle to a JavaScript value, valid for as long as the scope it was created in.
+///
+/// With the `debug-handles` feature, each handle also records which scope it was
+/// created in, and panics if it's used after that scope has exited. This can only
+/// happen if `unsafe` code has extended the handle's lifetime, and would otherwise
+/// read whatever value happens to reuse its slot.
+#[cfg_attr(not(feature = "debug-handles"), repr(transparent))]
 #[derive(Clone, Copy)]
 pub struct Handle<'a, T: Managed + 'a> {
     value: T,
+    #[cfg(feature = "debug-handles")]
+    generation: usize,
     phantom: PhantomData<&'a T>
 }
 
@@ -32,6 +50,10 @@ impl<'a, T: Value + 'a> Handle<'a, T> {
     }
 }
 
+/// Handles compare equal if they refer to the same JavaScript value in the engine's
+/// heap, which is closer to pointer equality than to `===`: two handles to equal
+/// strings or numbers can compare unequal. Use `Handle::strict_equals` or
+/// `Handle::same_value` to compare values as JavaScript does.
 impl<'a, T: Managed + 'a> PartialEq for Handle<'a, T> {
     fn eq(&self, other: &Self) -> bool {
         unsafe { neon_runtime::mem::same_handle(self.to_raw(), other.to_raw()) }
@@ -44,47 +66,180 @@ impl<'a, T: Managed + 'a> Handle<'a, T> {
     pub(crate) fn new_internal(value: T) -> Handle<'a, T> {
         Handle {
             value: value,
+            #[cfg(feature = "debug-handles")]
+            generation: generation::current(),
             phantom: PhantomData
         }
     }
+
+    // A handle to a value that isn't allocated in any scope, such as `undefined`,
+    // which stays valid however long the handle is kept.
+    pub(crate) fn new_unscoped(value: T) -> Handle<'a, T> {
+        Handle {
+            value: value,
+            #[cfg(feature = "debug-handles")]
+            generation: 0,
+            phantom: PhantomData
+        }
+    }
+
+    // A handle to `value` that belongs to the same scope as this one.
+    pub(crate) fn derive<U: Managed>(&self, value: U) -> Handle<'a, U> {
+        Handle {
+            value: value,
+            #[cfg(feature = "debug-handles")]
+            generation: self.generation,
+            phantom: PhantomData
+        }
+    }
+
+    // A handle to `value` that belongs to the scope enclosing the current one, for a
+    // value escaping a chained scope.
+    pub(crate) fn new_escaped(value: T) -> Handle<'a, T> {
+        Handle {
+            value: value,
+            #[cfg(feature = "debug-handles")]
+            generation: generation::outer(),
+            phantom: PhantomData
+        }
+    }
+
+    #[cfg(feature = "debug-handles")]
+    fn check_live(&self) {
+        if !generation::is_live(self.generation) {
+            panic!("handle used after the scope it was created in exited");
+        }
+    }
+}
+
+#[cfg(feature = "unstable-raw")]
+impl<'a, T: Value + 'a> Handle<'a, T> {
+    /// Wraps a raw V8 handle, such as one created by another native library, for use in
+    /// `scope`. This is unsafe because the value must really be a `T`, and must stay
+    /// alive for as long as `scope` does.
+    pub unsafe fn from_raw<S: Scope<'a>>(_: &mut S, local: raw::Local) -> Handle<'a, T> {
+        Handle::new_internal(T::from_raw(local))
+    }
+
+    /// The raw V8 handle underlying this one, which is only valid while the handle's
+    /// scope is.
+    pub fn to_raw(self) -> raw::Local {
+        (*self).to_raw()
+    }
 }
 
 impl<'a, T: Value> Handle<'a, T> {
     // This method does not require a scope because it only copies a handle.
     pub fn upcast<U: Value + SuperType<T>>(&self) -> Handle<'a, U> {
-        Handle::new_internal(SuperType::upcast_internal(self.value))
+        self.derive(SuperType::upcast_internal(**self))
     }
 
+    /// Tests whether the value is a `U`, e.g. `value.is_a::<JsNumber>()`. Unlike `check`,
+    /// this never throws, so it's the cheap way to probe a value's type when dispatching
+    /// on it.
     pub fn is_a<U: Value>(&self) -> bool {
-        U::downcast(self.value).is_some()
+        U::downcast(**self).is_some()
     }
 
+    /// Converts the handle to a handle of type `U`, or `None` if the value isn't a `U`.
     pub fn downcast<U: Value>(&self) -> Option<Handle<'a, U>> {
-        U::downcast(self.value).map(Handle::new_internal)
+        U::downcast(**self).map(|v| self.derive(v))
     }
 
+    /// Converts the handle to a handle of type `U`, throwing a `TypeError` if the value
+    /// isn't a `U`. The error names both types, e.g. "expected JsBuffer, got Uint8Array".
     pub fn check<U: Value>(&self) -> JsResult<'a, U> {
-        match U::downcast(self.value) {
-            Some(v) => Ok(Handle::new_internal(v)),
-            None => JsError::throw(Kind::TypeError, "type error")
+        match U::downcast(**self) {
+            Some(v) => Ok(self.derive(v)),
+            None => {
+                let msg = format!("expected {}, got {}", U::name(), self.type_name());
+                JsError::throw(Kind::TypeError, &msg)
+            }
         }
     }
+
+    /// A name for the value's runtime type, for error messages: the name of its
+    /// constructor if it's an object, e.g. `Uint8Array`, `null`, or what `typeof`
+    /// reports for any other value, e.g. `number`.
+    pub fn type_name(&self) -> String {
+        unsafe {
+            let mut local: raw::Local = mem::zeroed();
+            neon_runtime::mem::type_name(&mut local, Isolate::current().to_raw(), self.to_raw());
+            JsString::from_raw(local).value()
+        }
+    }
+
+    /// Compares the value with `other` as JavaScript's `===` operator does.
+    pub fn strict_equals<'b, U: Value, S: Scope<'b>>(&self, _: &mut S, other: Handle<U>) -> bool {
+        unsafe { neon_runtime::mem::strict_equals(self.to_raw(), other.to_raw()) }
+    }
+
+    /// Compares the value with `other` as `Object.is` does, which differs from `===`
+    /// in treating `NaN` as equal to itself and `0` as unequal to `-0`.
+    pub fn same_value<'b, U: Value, S: Scope<'b>>(&self, _: &mut S, other: Handle<U>) -> bool {
+        unsafe { neon_runtime::mem::same_value(self.to_raw(), other.to_raw()) }
+    }
+
+    // Every value is a `JsValue`, but `upcast` can't express that for an arbitrary `T`.
+    fn as_value(&self) -> Handle<'a, JsValue> {
+        self.derive(JsValue::from_raw(self.to_raw()))
+    }
+
+    /// Converts the value to a string as JavaScript's `String(value)` does, calling its
+    /// `toString` method if it's an object. Unlike `Value::to_string`, this works for
+    /// symbols too.
+    pub fn to_display_string<'b, S: Scope<'b>>(&self, scope: &mut S) -> VmResult<String> {
+        let string = scope.global().get(scope, "String")?.check::<JsFunction>()?;
+        let result = string.call(scope, JsUndefined::new(), vec![self.as_value()])?;
+        Ok(result.check::<JsString>()?.value())
+    }
+
+    /// Formats the value for Rust-side logging, as `util.inspect` shows it, e.g.
+    /// `println!("{:?}", value.debug(scope)?)`. Falls back to the display string outside
+    /// of a registered module, where `util` can't be loaded. `Scope::inspect` takes
+    /// options such as the depth to show.
+    pub fn debug<'b, S: Scope<'b>>(&self, scope: &mut S) -> VmResult<DebugValue> {
+        if scope.isolate().class_map().module().is_none() {
+            return Ok(DebugValue(self.to_display_string(scope)?));
+        }
+        Ok(DebugValue(scope.inspect(self.as_value(), InspectOptions::new())?))
+    }
+}
+
+/// A JavaScript value formatted by `Handle::debug`, which can be written with either
+/// `{}` or `{:?}`.
+pub struct DebugValue(String);
+
+impl Debug for DebugValue {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl Display for DebugValue {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        f.write_str(&self.0)
+    }
 }
 
 impl<'a, T: Managed> Deref for Handle<'a, T> {
     type Target = T;
     fn deref<'b>(&'b self) -> &'b T {
+        #[cfg(feature = "debug-handles")]
+        self.check_live();
         &self.value
     }
 }
 
 impl<'a, T: Managed> DerefMut for Handle<'a, T> {
     fn deref_mut<'b>(&'b mut self) -> &'b mut T {
+        #[cfg(feature = "debug-handles")]
+        self.check_live();
         &mut self.value
     }
 }
 
-#[repr(C)]
+#[repr(transparent)]
 #[derive(Clone, Copy)]
 pub struct LockedHandle<'a, T: Value + 'a>(Handle<'a, T>);
 
@@ -105,3 +260,389 @@ impl<'a, T: Value> Lock for LockedHandle<'a, T> {
         self
     }
 }
+
+/// A handle to a JavaScript value that isn't tied to any scope, keeping the value
+/// alive until the `Persistent` is dropped. This lets a module look up a value such
+/// as a constructor or a frequently used string once and reuse it on later calls.
+///
+/// A `Persistent` can only be used on the JavaScript thread that created it.
+pub struct Persistent<T: Value> {
+    ptr: *mut c_void,
+    phantom: PhantomData<T>
+}
+
+impl<T: Value> Persistent<T> {
+    pub fn new<'a, S: Scope<'a>>(scope: &mut S, value: Handle<T>) -> Persistent<T> {
+        let ptr = unsafe { neon_runtime::mem::new_persistent(scope.isolate().to_raw(), value.to_raw()) };
+        Persistent {
+            ptr: ptr,
+            phantom: PhantomData
+        }
+    }
+
+    /// Creates a handle to the value in the given scope.
+    pub fn get<'a, S: Scope<'a>>(&self, scope: &mut S) -> Handle<'a, T> {
+        unsafe {
+            let mut local: raw::Local = mem::zeroed();
+            neon_runtime::mem::read_persistent(&mut local, scope.isolate().to_raw(), self.ptr);
+            Handle::new_internal(T::from_raw(local))
+        }
+    }
+}
+
+impl<T: Value> Drop for Persistent<T> {
+    fn drop(&mut self) {
+        unsafe {
+            neon_runtime::mem::drop_persistent(self.ptr);
+        }
+    }
+}
+
+/// A list of JavaScript values kept alive across calls, e.g. a queue of pending
+/// callbacks. The values are held together by a single persistent array, so this is
+/// cheaper than a `Vec<Persistent<T>>`, and dropping it releases all of them at once.
+///
+/// A `RootedVec` can only be used on the JavaScript thread that created it.
+pub struct RootedVec<T: Value> {
+    array: Persistent<JsArray>,
+    len: u32,
+    phantom: PhantomData<T>
+}
+
+impl<T: Value> RootedVec<T> {
+    pub fn new<'a, S: Scope<'a>>(scope: &mut S) -> RootedVec<T> {
+        let array = JsArray::new(scope, 0);
+        RootedVec {
+            array: Persistent::new(scope, array),
+            len: 0,
+            phantom: PhantomData
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.len as usize
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Adds a value to the end of the list.
+    pub fn push<'a, 'b, S: Scope<'a>>(&mut self, scope: &mut S, value: Handle<'b, T>) -> VmResult<()> {
+        let array = self.array.get(scope);
+        array.set(self.len, value)?;
+        self.len += 1;
+        Ok(())
+    }
+
+    /// Creates a handle to the value at `index`, or returns `None` if it's out of bounds.
+    pub fn get<'a, S: Scope<'a>>(&self, scope: &mut S, index: usize) -> VmResult<Option<Handle<'a, T>>> {
+        if index >= self.len() {
+            return Ok(None);
+        }
+        element(scope, &self.array, index as u32).map(Some)
+    }
+
+    /// Removes the last value from the list and returns a handle to it.
+    pub fn pop<'a, S: Scope<'a>>(&mut self, scope: &mut S) -> VmResult<Option<Handle<'a, T>>> {
+        if self.len == 0 {
+            return Ok(None);
+        }
+        let value = element(scope, &self.array, self.len - 1)?;
+        self.truncate(scope, self.len() - 1)?;
+        Ok(Some(value))
+    }
+
+    /// Removes every value from the list and returns handles to them, in order.
+    pub fn drain<'a, S: Scope<'a>>(&mut self, scope: &mut S) -> VmResult<Vec<Handle<'a, T>>> {
+        let values = (0..self.len).map(|i| element(scope, &self.array, i)).collect::<VmResult<Vec<_>>>()?;
+        self.truncate(scope, 0)?;
+        Ok(values)
+    }
+
+    /// Shortens the list to `len` values, releasing the rest.
+    pub fn truncate<'a, S: Scope<'a>>(&mut self, scope: &mut S, len: usize) -> VmResult<()> {
+        if len >= self.len() {
+            return Ok(());
+        }
+        let array = self.array.get(scope);
+        let length = JsNumber::new(scope, len as f64);
+        array.set("length", length)?;
+        self.len = len as u32;
+        Ok(())
+    }
+}
+
+/// A map from Rust keys to JavaScript values kept alive across calls, e.g. callbacks
+/// registered by id. Like `RootedVec`, the values are held together by a single
+/// persistent array.
+///
+/// A `RootedMap` can only be used on the JavaScript thread that created it.
+pub struct RootedMap<K: Hash + Eq, T: Value> {
+    array: Persistent<JsArray>,
+    slots: HashMap<K, u32>,
+    free: Vec<u32>,
+    phantom: PhantomData<T>
+}
+
+impl<K: Hash + Eq, T: Value> RootedMap<K, T> {
+    pub fn new<'a, S: Scope<'a>>(scope: &mut S) -> RootedMap<K, T> {
+        let array = JsArray::new(scope, 0);
+        RootedMap {
+            array: Persistent::new(scope, array),
+            slots: HashMap::new(),
+            free: Vec::new(),
+            phantom: PhantomData
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.slots.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.slots.is_empty()
+    }
+
+    pub fn contains_key(&self, key: &K) -> bool {
+        self.slots.contains_key(key)
+    }
+
+    /// Sets the value for `key`, replacing any previous value.
+    pub fn insert<'a, 'b, S: Scope<'a>>(&mut self, scope: &mut S, key: K, value: Handle<'b, T>) -> VmResult<()> {
+        let slot = match self.slots.get(&key) {
+            Some(&slot) => slot,
+            None => self.free.pop().unwrap_or(self.slots.len() as u32)
+        };
+        let array = self.array.get(scope);
+        array.set(slot, value)?;
+        self.slots.insert(key, slot);
+        Ok(())
+    }
+
+    /// Creates a handle to the value for `key`, if there is one.
+    pub fn get<'a, S: Scope<'a>>(&self, scope: &mut S, key: &K) -> VmResult<Option<Handle<'a, T>>> {
+        match self.slots.get(key) {
+            Some(&slot) => element(scope, &self.array, slot).map(Some),
+            None => Ok(None)
+        }
+    }
+
+    /// Removes the value for `key` and returns a handle to it, if there was one.
+    pub fn remove<'a, S: Scope<'a>>(&mut self, scope: &mut S, key: &K) -> VmResult<Option<Handle<'a, T>>> {
+        let slot = match self.slots.get(key) {
+            Some(&slot) => slot,
+            None => return Ok(None)
+        };
+        let value = element(scope, &self.array, slot)?;
+        let array = self.array.get(scope);
+        array.set(slot, JsUndefined::new())?;
+        self.slots.remove(key);
+        self.free.push(slot);
+        Ok(Some(value))
+    }
+}
+
+// Reads an element of a rooted collection's array, which only ever holds `T`s.
+fn element<'a, S: Scope<'a>, T: Value>(scope: &mut S, array: &Persistent<JsArray>, index: u32) -> VmResult<Handle<'a, T>> {
+    let array = array.get(scope);
+    let value = array.get(scope, index)?;
+    Ok(Handle::new_internal(T::from_raw(value.to_raw())))
+}
+
+/// A reference to a JavaScript object that doesn't keep it alive, e.g. for a cache
+/// keyed by object identity that shouldn't hold on to its keys.
+///
+/// A `Weak` can only be used on the JavaScript thread that created it.
+pub struct Weak<T: Object> {
+    ptr: *mut c_void,
+    phantom: PhantomData<T>
+}
+
+type WeakCallback = Box<dyn FnMut()>;
+
+impl<T: Object> Weak<T> {
+    pub fn new<'a, S: Scope<'a>>(scope: &mut S, value: Handle<T>) -> Weak<T> {
+        Weak::with_data(scope, value, 0 as *mut c_void)
+    }
+
+    /// Creates a weak reference that calls `on_collected` once the object has been
+    /// garbage collected, unless the `Weak` is dropped first. The callback runs while
+    /// the garbage collector is finishing up, so it can't use JavaScript, but it can
+    /// e.g. evict a cache entry.
+    pub fn with_callback<'a, S, F>(scope: &mut S, value: Handle<T>, on_collected: F) -> Weak<T>
+        where S: Scope<'a>,
+              F: FnOnce() + 'static
+    {
+        let mut on_collected = Some(on_collected);
+        let callback: WeakCallback = Box::new(move || (on_collected.take().unwrap())());
+        Weak::with_data(scope, value, Box::into_raw(Box::new(callback)) as *mut c_void)
+    }
+
+    fn with_data<'a, S: Scope<'a>>(scope: &mut S, value: Handle<T>, data: *mut c_void) -> Weak<T> {
+        let ptr = unsafe {
+            neon_runtime::mem::new_weak(scope.isolate().to_raw(), value.to_raw(), data, run_weak_callback, drop_weak_callback)
+        };
+        Weak {
+            ptr: ptr,
+            phantom: PhantomData
+        }
+    }
+
+    /// Creates a handle to the object in the given scope, or returns `None` if it has
+    /// been collected.
+    pub fn get<'a, S: Scope<'a>>(&self, _: &mut S) -> Option<Handle<'a, T>> {
+        unsafe {
+            let mut local: raw::Local = mem::zeroed();
+            if neon_runtime::mem::read_weak(&mut local, self.ptr) {
+                Some(Handle::new_internal(T::from_raw(local)))
+            } else {
+                None
+            }
+        }
+    }
+}
+
+impl<T: Object> Drop for Weak<T> {
+    fn drop(&mut self) {
+        unsafe {
+            neon_runtime::mem::drop_weak(self.ptr);
+        }
+    }
+}
+
+unsafe extern "C" fn run_weak_callback(callback: *mut c_void) {
+    (*(callback as *mut WeakCallback))();
+}
+
+unsafe extern "C" fn drop_weak_callback(callback: *mut c_void) {
+    mem::drop(Box::from_raw(callback as *mut WeakCallback));
+}
+
+/// A persistent reference to a JavaScript object that, unlike a `Persistent`, can be
+/// sent to and dropped on other threads, e.g. to keep a callback in a long-lived Rust
+/// state machine. The object can only be read back on the JavaScript thread that
+/// rooted it.
+///
+/// Cloning a root is cheap and can be done on any thread: clones share one reference
+/// to the object, which is freed when the last of them is dropped. If that happens on
+/// the root's own thread, the reference is freed right away; on any other thread, it's
+/// queued to be freed the next time the JavaScript thread's event loop runs, without
+/// keeping the loop alive.
+pub struct Root<T: Object> {
+    inner: Arc<RootInner>,
+    phantom: PhantomData<T>
+}
+
+unsafe impl<T: Object> Send for Root<T> { }
+unsafe impl<T: Object> Sync for Root<T> { }
+
+struct RootInner {
+    persistent: *mut c_void,
+    isolate: *mut raw::Isolate,
+    thread: ThreadId,
+    queue: RootQueue
+}
+
+unsafe impl Send for RootInner { }
+unsafe impl Sync for RootInner { }
+
+impl<T: Object> Root<T> {
+    pub fn new<'a, 'b, S: Scope<'a>>(scope: &mut S, value: Handle<'b, T>) -> Root<T> {
+        let queue = RootQueue::current(scope);
+        let isolate = scope.isolate().to_raw();
+        let inner = RootInner {
+            persistent: unsafe { neon_runtime::mem::new_persistent(isolate, value.to_raw()) },
+            isolate: isolate,
+            thread: thread::current().id(),
+            queue: queue
+        };
+        Root {
+            inner: Arc::new(inner),
+            phantom: PhantomData
+        }
+    }
+
+    /// Creates a handle to the object in the given scope. Panics if the scope belongs
+    /// to a different JavaScript thread than the one that rooted the object.
+    pub fn get<'a, S: Scope<'a>>(&self, scope: &mut S) -> Handle<'a, T> where T: 'a {
+        let inner = &*self.inner;
+        assert!(scope.isolate().to_raw() == inner.isolate, "a Root can only be read on the JavaScript thread that created it");
+        unsafe {
+            let mut local: raw::Local = mem::zeroed();
+            neon_runtime::mem::read_persistent(&mut local, inner.isolate, inner.persistent);
+            Handle::new_internal(T::from_raw(local))
+        }
+    }
+
+    /// Creates a handle to the object and drops this root, freeing the reference right
+    /// away if it was the last clone.
+    pub fn into_inner<'a, S: Scope<'a>>(self, scope: &mut S) -> Handle<'a, T> where T: 'a {
+        self.get(scope)
+    }
+}
+
+impl<T: Object> Clone for Root<T> {
+    fn clone(&self) -> Root<T> {
+        Root {
+            inner: self.inner.clone(),
+            phantom: PhantomData
+        }
+    }
+}
+
+impl Drop for RootInner {
+    fn drop(&mut self) {
+        if thread::current().id() == self.thread {
+            unsafe {
+                neon_runtime::mem::drop_persistent(self.persistent);
+            }
+        } else {
+            self.queue.push(self.persistent);
+        }
+    }
+}
+
+// Persistent handles of roots dropped on other threads, waiting to be freed on the
+// JavaScript thread. Each context has one queue, created with its first root.
+#[derive(Clone)]
+pub(crate) struct RootQueue {
+    pending: Arc<Mutex<Vec<usize>>>,
+    signal: AsyncSignal
+}
+
+impl RootQueue {
+    fn current<'a, S: Scope<'a>>(scope: &mut S) -> RootQueue {
+        if let Some(queue) = scope.isolate().class_map().root_queue() {
+            return queue.clone();
+        }
+        let pending = Arc::new(Mutex::new(Vec::new()));
+        let signal = {
+            let pending = pending.clone();
+            AsyncSignal::named(scope, "neon::Root", move |_| {
+                let persistents = mem::replace(&mut *lock(&pending), Vec::new());
+                for persistent in persistents {
+                    unsafe {
+                        neon_runtime::mem::drop_persistent(persistent as *mut c_void);
+                    }
+                }
+                Ok(())
+            })
+        };
+        signal.unref(scope);
+        let queue = RootQueue { pending: pending, signal: signal };
+        scope.isolate().class_map().set_root_queue(queue.clone());
+        queue
+    }
+
+    fn push(&self, persistent: *mut c_void) {
+        lock(&self.pending).push(persistent as usize);
+        self.signal.trigger();
+    }
+}
+
+// Dropping a root must not panic, even if another thread panicked while holding the
+// lock, since the queue is still consistent.
+fn lock(pending: &Mutex<Vec<usize>>) -> ::std::sync::MutexGuard<Vec<usize>> {
+    pending.lock().unwrap_or_else(|err| err.into_inner())
+}
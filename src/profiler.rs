@@ -0,0 +1,54 @@
+//! Access to V8's CPU profiler.
+//!
+//! Native frames appear in a profile under the name of the JavaScript function that
+//! called into Rust. Functions exported from a module are named after their keys, and
+//! `Module::set_name` prefixes those names with the addon's, so a profile shows
+//! `myaddon.parse` rather than an anonymous native frame.
+
+use std::os::raw::c_void;
+use neon_runtime;
+use js::{JsObject, JsString, build};
+use js::error::{JsError, Kind};
+#[cfg_attr(feature = "unstable-raw", allow(unused_imports))]
+use mem::Managed;
+use scope::Scope;
+use vm::{JsResult, VmResult};
+
+/// A running CPU profile, which is discarded if it's dropped without being stopped.
+pub struct Profiler {
+    raw: *mut c_void,
+    title: String
+}
+
+impl Profiler {
+    /// Starts profiling the current isolate under `title`. Throws an `Error` on versions
+    /// of Node before 8, where the profiler isn't available to addons.
+    pub fn start<'a, S: Scope<'a>>(scope: &mut S, title: &str) -> VmResult<Profiler> {
+        let js_title = JsString::new_or_throw(scope, title)?;
+        let raw = unsafe { neon_runtime::profiler::start(scope.isolate().to_raw(), js_title.to_raw()) };
+        if raw.is_null() {
+            return JsError::throw(Kind::Error, "CPU profiling requires Node 8 or later");
+        }
+        Ok(Profiler {
+            raw: raw,
+            title: title.to_string()
+        })
+    }
+
+    /// Stops profiling and returns the profile as an object with `title`, `startTime`,
+    /// and `endTime` properties, in milliseconds, and a `root` node. Each node of the
+    /// call tree has `functionName`, `url`, `lineNumber`, `columnNumber`, `hitCount`,
+    /// and `children` properties.
+    pub fn stop<'a, S: Scope<'a>>(self, scope: &mut S) -> JsResult<'a, JsObject> {
+        let title = JsString::new_or_throw(scope, &self.title)?;
+        build(|out| unsafe { neon_runtime::profiler::stop(out, self.raw, title.to_raw()) })
+    }
+}
+
+impl Drop for Profiler {
+    fn drop(&mut self) {
+        unsafe {
+            neon_runtime::profiler::dispose(self.raw);
+        }
+    }
+}
@@ -4,11 +4,21 @@ use std::mem;
 use std::os::raw::c_void;
 use std::marker::PhantomData;
 use std::cell::Cell;
+use std::path::Path;
 use neon_runtime;
 use neon_runtime::raw;
-use mem::Handle;
-use js::{Value, JsObject};
+// Handles only need `Managed` for `to_raw` when `unstable-raw` doesn't give them
+// their own.
+#[cfg_attr(feature = "unstable-raw", allow(unused_imports))]
+use mem::{Handle, Managed, Persistent};
+use js::{Value, Object, JsValue, JsObject, JsString, JsNumber, JsBoolean, JsFunction, ArrayBuilder};
+use js::binary::JsBuffer;
+use js::encoding;
+use instrument::{self, Event};
+use js::error::{JsError, Kind};
+use vm::{VmResult, JsResult, Lock};
 use vm::internal::Isolate;
+use task::{Timer, once_function};
 use self::internal::ScopeInternal;
 
 pub(crate) mod internal {
@@ -21,6 +31,61 @@ pub(crate) mod internal {
     }
 }
 
+// With the `debug-handles` feature, each scope is numbered when it's entered, and
+// every handle records the number of the innermost scope it was created in, so that
+// handles used after their scope has exited can be caught.
+#[cfg(feature = "debug-handles")]
+pub(crate) mod generation {
+    use std::cell::{Cell, RefCell};
+
+    thread_local! {
+        static NEXT_GENERATION: Cell<usize> = Cell::new(1);
+        // The generations of the scopes that are currently entered, innermost last,
+        // which are always in increasing order.
+        static LIVE_GENERATIONS: RefCell<Vec<usize>> = RefCell::new(Vec::new());
+    }
+
+    /// Marks a scope's generation as live until dropped.
+    pub struct Generation;
+
+    impl Generation {
+        pub fn enter() -> Generation {
+            let generation = NEXT_GENERATION.with(|next| {
+                let generation = next.get();
+                next.set(generation + 1);
+                generation
+            });
+            LIVE_GENERATIONS.with(|live| live.borrow_mut().push(generation));
+            Generation
+        }
+    }
+
+    impl Drop for Generation {
+        fn drop(&mut self) {
+            LIVE_GENERATIONS.with(|live| live.borrow_mut().pop());
+        }
+    }
+
+    /// The generation of the innermost entered scope, or 0, which is never checked,
+    /// for handles created outside of any scope Neon entered, e.g. during module
+    /// initialization.
+    pub fn current() -> usize {
+        LIVE_GENERATIONS.with(|live| live.borrow().last().cloned().unwrap_or(0))
+    }
+
+    /// The generation of the scope enclosing the innermost one.
+    pub fn outer() -> usize {
+        LIVE_GENERATIONS.with(|live| {
+            let live = live.borrow();
+            if live.len() < 2 { 0 } else { live[live.len() - 2] }
+        })
+    }
+
+    pub fn is_live(generation: usize) -> bool {
+        generation == 0 || LIVE_GENERATIONS.with(|live| live.borrow().binary_search(&generation).is_ok())
+    }
+}
+
 pub trait Scope<'a>: ScopeInternal {
     fn nested<T, F: for<'inner> FnOnce(&mut NestedScope<'inner>) -> T>(&self, f: F) -> T;
     fn chained<T, F: for<'inner> FnOnce(&mut ChainedScope<'inner, 'a>) -> T>(&self, f: F) -> T;
@@ -32,6 +97,266 @@ pub trait Scope<'a>: ScopeInternal {
             }
         })
     }
+
+    /// Starts building an array with room for `capacity` elements, to be filled in with
+    /// `ArrayBuilder::push` rather than by setting each index by hand.
+    fn array_with_capacity(&self, capacity: usize) -> ArrayBuilder<'a> {
+        ArrayBuilder::with_capacity(capacity)
+    }
+
+    /// Encodes `data` as a base64 string.
+    fn encode_base64(&mut self, data: &[u8]) -> JsResult<'a, JsString> {
+        JsString::new_or_throw(self, &encoding::encode_base64(data))
+    }
+
+    /// Decodes a base64 string, in either the standard or URL-safe alphabet, into a new
+    /// `Buffer`. Throws a `TypeError` if the string is not valid base64.
+    fn decode_base64<'b>(&mut self, string: Handle<'b, JsString>) -> JsResult<'a, JsBuffer> {
+        let string = string.value();
+        match encoding::base64_decoded_len(string.as_bytes()) {
+            Some(len) => decode_into_buffer(self, len, |out| encoding::decode_base64_into(string.as_bytes(), out), "invalid base64 string"),
+            None => JsError::throw(Kind::TypeError, "invalid base64 string")
+        }
+    }
+
+    /// Encodes `data` as a lowercase hex string.
+    fn encode_hex(&mut self, data: &[u8]) -> JsResult<'a, JsString> {
+        JsString::new_or_throw(self, &encoding::encode_hex(data))
+    }
+
+    /// Decodes a hex string into a new `Buffer`. Throws a `TypeError` if the string is
+    /// not valid hex.
+    fn decode_hex<'b>(&mut self, string: Handle<'b, JsString>) -> JsResult<'a, JsBuffer> {
+        let string = string.value();
+        decode_into_buffer(self, string.len() / 2, |out| encoding::decode_hex_into(string.as_bytes(), out), "invalid hex string")
+    }
+
+    /// Loads a JavaScript module with the addon's own `require` function, so relative
+    /// paths are resolved from the addon, and returns the module's exports. Throws an
+    /// `Error` if the addon wasn't loaded through `register_module!`.
+    fn require(&mut self, path: &str) -> JsResult<'a, JsValue> {
+        let module = match self.isolate().class_map().module() {
+            Some(module) => module.get(self),
+            None => { return JsError::throw(Kind::Error, "require is unavailable outside of a registered module"); }
+        };
+        let require = module.get(self, "require")?.check::<JsFunction>()?;
+        let path = JsString::new_or_throw(self, path)?;
+        require.call(self, module, vec![path])
+    }
+
+    /// Formats `value` with Node's `util.inspect`, the way `console.log` shows it to
+    /// JavaScript developers. Throws an `Error` outside of a registered module, where
+    /// `util` can't be loaded.
+    fn inspect<'b, T: Value>(&mut self, value: Handle<'b, T>, options: InspectOptions) -> VmResult<String> {
+        let util = self.require("util")?.check::<JsObject>()?;
+        let inspect = util.get(self, "inspect")?.check::<JsFunction>()?;
+        let options = options.to_object(self)?;
+        let result = inspect.call(self, util, vec![value.upcast::<JsValue>(), options.upcast()])?;
+        Ok(result.check::<JsString>()?.value())
+    }
+
+    /// The `process` object, which is looked up once per context and then cached.
+    fn process(&mut self) -> JsResult<'a, JsObject> {
+        cached_global(self, "process")
+    }
+
+    /// The `console` object, which is looked up once per context and then cached.
+    fn console(&mut self) -> JsResult<'a, JsObject> {
+        cached_global(self, "console")
+    }
+
+    /// Calls `console.log` with the given arguments.
+    fn console_log<'b, A, AS>(&mut self, args: AS) -> VmResult<()>
+        where A: Value + 'b,
+              AS: IntoIterator<Item=Handle<'b, A>>
+    {
+        let console = self.console()?;
+        console.call_method(self, "log", args)?;
+        Ok(())
+    }
+
+    /// Runs the microtask queue to completion, settling the continuations of any promises
+    /// resolved so far instead of leaving them for Node to run at the end of the tick.
+    /// Exceptions thrown by microtasks are reported to the isolate, not to the caller.
+    fn run_microtasks(&mut self) {
+        unsafe {
+            neon_runtime::scope::run_microtasks(self.isolate().to_raw());
+        }
+    }
+
+    /// Runs `callback` as a microtask, once the JavaScript stack has unwound, in the
+    /// same queue as promise reactions. This defers a call to user code, for example,
+    /// until the native code calling it is no longer on the stack. If the callback
+    /// throws, the exception is reported as uncaught.
+    fn queue_microtask<F>(&mut self, callback: F) -> VmResult<()>
+        where F: FnOnce(&mut RootScope) -> VmResult<()> + 'static
+    {
+        let microtask = once_function(self, callback)?;
+        unsafe {
+            neon_runtime::scope::enqueue_microtask(self.isolate().to_raw(), microtask.to_raw());
+        }
+        Ok(())
+    }
+
+    /// Runs `callback` with `process.nextTick`, once the JavaScript stack has unwound.
+    /// Node usually runs the tick queue ahead of promise reactions. If the callback
+    /// throws, the exception is reported as uncaught.
+    fn next_tick<F>(&mut self, callback: F) -> VmResult<()>
+        where F: FnOnce(&mut RootScope) -> VmResult<()> + 'static
+    {
+        let tick = once_function(self, callback)?;
+        let process = self.process()?;
+        let next_tick = process.get(self, "nextTick")?.check::<JsFunction>()?;
+        next_tick.call(self, process, vec![tick])?;
+        Ok(())
+    }
+
+    /// Runs `callback` on the event loop once `ms` milliseconds have passed, unless the
+    /// returned guard is dropped or cancelled first.
+    fn set_timeout<F>(&mut self, ms: u64, callback: F) -> Timer
+        where F: FnOnce(&mut RootScope) -> VmResult<()> + 'static
+    {
+        let mut callback = Some(callback);
+        Timer::new(self, ms, 0, move |scope| {
+            match callback.take() {
+                Some(callback) => callback(scope),
+                None => Ok(())
+            }
+        })
+    }
+
+    /// Runs `callback` on the event loop every `ms` milliseconds until the returned
+    /// guard is dropped or cancelled.
+    fn set_interval<F>(&mut self, ms: u64, callback: F) -> Timer
+        where F: FnMut(&mut RootScope) -> VmResult<()> + 'static
+    {
+        Timer::new(self, ms, ms, callback)
+    }
+
+    /// The number of handles held by all of the thread's open scopes. Comparing it
+    /// before and after a loop shows how many handles the loop leaves behind, which a
+    /// nested scope would free on each iteration.
+    fn live_handles(&self) -> usize {
+        unsafe { neon_runtime::scope::number_of_handles(self.isolate().to_raw()) }
+    }
+
+    /// Takes a snapshot of the JavaScript heap and writes it to the file at `path`, in
+    /// the `.heapsnapshot` format that Chrome DevTools loads. JavaScript is blocked
+    /// while the snapshot is taken, which can take seconds for a large heap. Throws an
+    /// `Error` if the file can't be written.
+    fn write_heap_snapshot<P: AsRef<Path>>(&mut self, path: P) -> VmResult<()> {
+        let path = path.as_ref();
+        let name = match path.to_str() {
+            Some(name) => name,
+            None => { return JsError::throw(Kind::TypeError, "heap snapshot path is not valid UTF-8"); }
+        };
+        if !unsafe { neon_runtime::profiler::write_heap_snapshot(self.isolate().to_raw(), name.as_ptr(), name.len()) } {
+            return JsError::throw(Kind::Error, &format!("couldn't write a heap snapshot to {}", path.display()));
+        }
+        Ok(())
+    }
+
+    /// The isolate's policy for running microtasks on its own.
+    fn microtask_policy(&self) -> MicrotaskPolicy {
+        match unsafe { neon_runtime::scope::microtasks_policy(self.isolate().to_raw()) } {
+            0 => MicrotaskPolicy::Explicit,
+            1 => MicrotaskPolicy::Scoped,
+            _ => MicrotaskPolicy::Auto
+        }
+    }
+}
+
+/// When an isolate runs its queued microtasks without being asked to.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MicrotaskPolicy {
+    /// Microtasks only run when the embedder, or `Scope::run_microtasks`, asks for them.
+    /// This is what Node uses, running them after each callback into JavaScript.
+    Explicit,
+    /// Microtasks run when the outermost `v8::MicrotasksScope` exits.
+    Scoped,
+    /// Microtasks run whenever the JavaScript call depth drops to zero.
+    Auto
+}
+
+/// Options for `Scope::inspect`. Anything left unset takes `util.inspect`'s default.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct InspectOptions {
+    depth: Option<f64>,
+    colors: Option<bool>,
+    show_hidden: Option<bool>
+}
+
+impl InspectOptions {
+    pub fn new() -> InspectOptions {
+        InspectOptions::default()
+    }
+
+    /// How many levels of nested objects to show before abbreviating them as `[Object]`.
+    pub fn depth(mut self, depth: u32) -> Self {
+        self.depth = Some(depth as f64);
+        self
+    }
+
+    /// Shows nested objects however deeply they're nested.
+    pub fn unlimited_depth(mut self) -> Self {
+        self.depth = Some(f64::INFINITY);
+        self
+    }
+
+    /// Whether to style the output with ANSI color codes.
+    pub fn colors(mut self, colors: bool) -> Self {
+        self.colors = Some(colors);
+        self
+    }
+
+    /// Whether to show non-enumerable properties too.
+    pub fn show_hidden(mut self, show_hidden: bool) -> Self {
+        self.show_hidden = Some(show_hidden);
+        self
+    }
+
+    fn to_object<'a, S: Scope<'a>>(&self, scope: &mut S) -> JsResult<'a, JsObject> {
+        let object = JsObject::new(scope);
+        if let Some(depth) = self.depth {
+            object.set("depth", JsNumber::new(scope, depth))?;
+        }
+        if let Some(colors) = self.colors {
+            object.set("colors", JsBoolean::new(scope, colors))?;
+        }
+        if let Some(show_hidden) = self.show_hidden {
+            object.set("showHidden", JsBoolean::new(scope, show_hidden))?;
+        }
+        Ok(object)
+    }
+}
+
+// Looks up a global object once per isolate and reuses it afterwards.
+fn cached_global<'a, S: Scope<'a>>(scope: &mut S, name: &'static str) -> JsResult<'a, JsObject> {
+    let mut isolate = scope.isolate();
+    if let Some(global) = isolate.class_map().get_global(name) {
+        return Ok(global.get(scope));
+    }
+    let global = scope.global().get(scope, name)?.check::<JsObject>()?;
+    let persistent = Persistent::new(scope, global);
+    isolate.class_map().set_global(name, persistent);
+    Ok(global)
+}
+
+// Decodes directly into the memory of a new buffer, which is only handed back
+// to JavaScript once it has been completely filled in.
+fn decode_into_buffer<'a, S, F>(scope: &mut S, len: usize, decode: F, msg: &str) -> JsResult<'a, JsBuffer>
+    where S: Scope<'a>,
+          F: FnOnce(&mut [u8]) -> bool + Send
+{
+    if len > u32::max_value() as usize {
+        return JsError::throw(Kind::RangeError, "decoded data is too large for a Buffer");
+    }
+    let mut buffer = unsafe { JsBuffer::uninitialized(scope, len as u32)? };
+    if buffer.grab(|mut data| decode(data.as_mut_slice())) {
+        Ok(buffer)
+    } else {
+        JsError::throw(Kind::TypeError, msg)
+    }
 }
 
 fn ensure_active<T: ScopeInternal>(scope: &T) {
@@ -65,7 +390,7 @@ impl<'a, 'outer> ChainedScope<'a, 'outer> {
         unsafe {
             let mut result_local: raw::Local = mem::zeroed();
             neon_runtime::scope::escape(&mut result_local, self.v8, local.to_raw());
-            Handle::new_internal(T::from_raw(result_local))
+            Handle::new_escaped(T::from_raw(result_local))
         }
     }
 }
@@ -83,14 +408,21 @@ impl<'a> RootScope<'a> {
         debug_assert!(unsafe { neon_runtime::scope::size() } <= mem::size_of::<raw::HandleScope>());
         debug_assert!(unsafe { neon_runtime::scope::alignment() } <= mem::align_of::<raw::HandleScope>());
 
+        let _timer = instrument::start(Event::Scope);
         let mut v8_scope = raw::HandleScope::new();
 
         unsafe {
             neon_runtime::scope::enter(&mut v8_scope, self.isolate().to_raw());
         }
 
+        #[cfg(feature = "debug-handles")]
+        let generation = generation::Generation::enter();
+
         let result = f(self);
 
+        #[cfg(feature = "debug-handles")]
+        mem::drop(generation);
+
         unsafe {
             neon_runtime::scope::exit(&mut v8_scope);
         }
@@ -124,6 +456,8 @@ extern "C" fn chained_callback<'a, T, P, F>(out: &mut Box<Option<T>>,
         parent: PhantomData,
         phantom: PhantomData
     };
+    #[cfg(feature = "debug-handles")]
+    let _generation = generation::Generation::enter();
     let result = f(&mut chained);
     **out = Some(result);
 }
@@ -145,6 +479,7 @@ fn chain<'a, T, S, F>(outer: &S, f: F) -> T
           F: for<'inner> FnOnce(&mut ChainedScope<'inner, 'a>) -> T
 {
     ensure_active(outer);
+    let _timer = instrument::start(Event::Scope);
     let closure: Box<F> = Box::new(f);
     let callback: extern "C" fn(&mut Box<Option<T>>, &S, *mut raw::EscapableHandleScope, Box<F>) = chained_callback::<T, S, F>;
     let mut result: Box<Option<T>> = Box::new(None);
@@ -168,6 +503,7 @@ fn nest<'me, T, S, F>(outer: &'me S, f: F) -> T
           F: for<'nested> FnOnce(&mut NestedScope<'nested>) -> T
 {
     ensure_active(outer);
+    let _timer = instrument::start(Event::Scope);
     let closure: Box<F> = Box::new(f);
     let callback: extern "C" fn(&mut Box<Option<T>>, Isolate, Box<F>) = nested_callback::<T, F>;
     let mut result: Box<Option<T>> = Box::new(None);
@@ -178,7 +514,7 @@ fn nest<'me, T, S, F>(outer: &'me S, f: F) -> T
             let out: *mut c_void = mem::transmute(out);
             let closure: *mut c_void = mem::transmute(closure);
             let callback: extern "C" fn(&mut c_void, *mut c_void, *mut c_void) = mem::transmute(callback);
-            let isolate: *mut c_void = mem::transmute(outer.isolate());
+            let isolate: *mut c_void = outer.isolate().to_raw();
             neon_runtime::scope::nested(out, closure, callback, isolate);
         }
         outer.set_active(true);
@@ -196,6 +532,8 @@ extern "C" fn nested_callback<T, F>(out: &mut Box<Option<T>>,
         active: Cell::new(true),
         phantom: PhantomData
     };
+    #[cfg(feature = "debug-handles")]
+    let _generation = generation::Generation::enter();
     let result = f(&mut nested);
     **out = Some(result);
 }
@@ -0,0 +1,565 @@
+//! Conversions between Rust types implementing `serde`'s `Serialize` and `Deserialize`
+//! traits and JavaScript values. Requires the `serde` feature.
+//!
+//! Values are represented the same way as in `serde_json`: structs and maps become objects,
+//! sequences and tuples become arrays, enums are externally tagged, and `None` and `()`
+//! become `null`. Byte arrays become `Buffer`s. All numbers become JavaScript numbers, so
+//! 64-bit integers beyond 2^53 lose precision.
+
+use std::error;
+use std::fmt;
+use std::marker::PhantomData;
+use serde_crate::ser::{self, Serialize};
+use serde_crate::de::{self, Deserialize, DeserializeOwned, Visitor, IntoDeserializer};
+use mem::Handle;
+use scope::Scope;
+use vm::{JsResult, VmResult, Throw};
+use js::{Value, Object, JsValue, JsObject, JsArray, JsString, JsNumber, JsBoolean, JsNull, JsUndefined, JsFunction};
+use js::binary::JsBuffer;
+use js::error::{JsError, Kind};
+
+/// Converts a Rust value to a JavaScript value.
+pub fn to_js_value<'a, S: Scope<'a>, T: Serialize + ?Sized>(scope: &mut S, value: &T) -> JsResult<'a, JsValue> {
+    value.serialize(Serializer::new(scope)).or_else(throw)
+}
+
+/// Converts a JavaScript value to a Rust value, throwing a `TypeError` if the value doesn't
+/// have the expected shape.
+pub fn from_js_value<'a, 'b, S: Scope<'a>, T: DeserializeOwned>(scope: &mut S, value: Handle<'b, JsValue>) -> VmResult<T> {
+    T::deserialize(Deserializer::new(scope, value)).or_else(throw)
+}
+
+fn throw<T>(err: Error) -> VmResult<T> {
+    match err {
+        Error::Js => Err(Throw),
+        Error::Custom(msg) => JsError::throw(Kind::TypeError, &msg)
+    }
+}
+
+/// An error converting between Rust and JavaScript values.
+#[derive(Debug)]
+pub enum Error {
+    /// A JavaScript exception was thrown and is pending.
+    Js,
+    /// The value couldn't be converted.
+    Custom(String)
+}
+
+impl From<Throw> for Error {
+    fn from(_: Throw) -> Error {
+        Error::Js
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Error::Js => f.write_str("javascript error"),
+            Error::Custom(ref msg) => f.write_str(msg)
+        }
+    }
+}
+
+impl error::Error for Error { }
+
+impl ser::Error for Error {
+    fn custom<T: fmt::Display>(msg: T) -> Error {
+        Error::Custom(msg.to_string())
+    }
+}
+
+impl de::Error for Error {
+    fn custom<T: fmt::Display>(msg: T) -> Error {
+        Error::Custom(msg.to_string())
+    }
+}
+
+/// A `serde` serializer producing JavaScript values.
+pub struct Serializer<'s, 'a, S: Scope<'a> + 's> {
+    scope: &'s mut S,
+    phantom: PhantomData<&'a ()>
+}
+
+impl<'s, 'a, S: Scope<'a> + 's> Serializer<'s, 'a, S> {
+    pub fn new(scope: &'s mut S) -> Self {
+        Serializer { scope: scope, phantom: PhantomData }
+    }
+
+    fn string(self, v: &str) -> Result<Handle<'a, JsValue>, Error> {
+        Ok(JsString::new_or_throw(self.scope, v)?.upcast())
+    }
+
+    fn variant(self, variant: &str, value: Handle<'a, JsValue>) -> Result<Handle<'a, JsValue>, Error> {
+        let object = JsObject::new(self.scope);
+        object.set(variant, value)?;
+        Ok(object.upcast())
+    }
+}
+
+impl<'s, 'a, S: Scope<'a> + 's> ser::Serializer for Serializer<'s, 'a, S> {
+    type Ok = Handle<'a, JsValue>;
+    type Error = Error;
+    type SerializeSeq = ArraySerializer<'s, 'a, S>;
+    type SerializeTuple = ArraySerializer<'s, 'a, S>;
+    type SerializeTupleStruct = ArraySerializer<'s, 'a, S>;
+    type SerializeTupleVariant = VariantSerializer<'a, ArraySerializer<'s, 'a, S>>;
+    type SerializeMap = ObjectSerializer<'s, 'a, S>;
+    type SerializeStruct = ObjectSerializer<'s, 'a, S>;
+    type SerializeStructVariant = VariantSerializer<'a, ObjectSerializer<'s, 'a, S>>;
+
+    fn serialize_bool(self, v: bool) -> Result<Self::Ok, Error> {
+        Ok(JsBoolean::new(self.scope, v).upcast())
+    }
+
+    fn serialize_i8(self, v: i8) -> Result<Self::Ok, Error> { self.serialize_f64(v as f64) }
+    fn serialize_i16(self, v: i16) -> Result<Self::Ok, Error> { self.serialize_f64(v as f64) }
+    fn serialize_i32(self, v: i32) -> Result<Self::Ok, Error> { self.serialize_f64(v as f64) }
+    fn serialize_i64(self, v: i64) -> Result<Self::Ok, Error> { self.serialize_f64(v as f64) }
+    fn serialize_u8(self, v: u8) -> Result<Self::Ok, Error> { self.serialize_f64(v as f64) }
+    fn serialize_u16(self, v: u16) -> Result<Self::Ok, Error> { self.serialize_f64(v as f64) }
+    fn serialize_u32(self, v: u32) -> Result<Self::Ok, Error> { self.serialize_f64(v as f64) }
+    fn serialize_u64(self, v: u64) -> Result<Self::Ok, Error> { self.serialize_f64(v as f64) }
+    fn serialize_f32(self, v: f32) -> Result<Self::Ok, Error> { self.serialize_f64(v as f64) }
+
+    fn serialize_f64(self, v: f64) -> Result<Self::Ok, Error> {
+        Ok(JsNumber::new(self.scope, v).upcast())
+    }
+
+    fn serialize_char(self, v: char) -> Result<Self::Ok, Error> {
+        let mut buf = [0; 4];
+        self.string(v.encode_utf8(&mut buf))
+    }
+
+    fn serialize_str(self, v: &str) -> Result<Self::Ok, Error> {
+        self.string(v)
+    }
+
+    fn serialize_bytes(self, v: &[u8]) -> Result<Self::Ok, Error> {
+        Ok(JsBuffer::from_slice(self.scope, v)?.upcast())
+    }
+
+    fn serialize_none(self) -> Result<Self::Ok, Error> {
+        self.serialize_unit()
+    }
+
+    fn serialize_some<T: Serialize + ?Sized>(self, value: &T) -> Result<Self::Ok, Error> {
+        value.serialize(self)
+    }
+
+    fn serialize_unit(self) -> Result<Self::Ok, Error> {
+        Ok(JsNull::new().upcast())
+    }
+
+    fn serialize_unit_struct(self, _: &'static str) -> Result<Self::Ok, Error> {
+        self.serialize_unit()
+    }
+
+    fn serialize_unit_variant(self, _: &'static str, _: u32, variant: &'static str) -> Result<Self::Ok, Error> {
+        self.string(variant)
+    }
+
+    fn serialize_newtype_struct<T: Serialize + ?Sized>(self, _: &'static str, value: &T) -> Result<Self::Ok, Error> {
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_variant<T: Serialize + ?Sized>(self, _: &'static str, _: u32, variant: &'static str, value: &T) -> Result<Self::Ok, Error> {
+        let value = value.serialize(Serializer::new(&mut *self.scope))?;
+        self.variant(variant, value)
+    }
+
+    fn serialize_seq(self, len: Option<usize>) -> Result<Self::SerializeSeq, Error> {
+        let array = JsArray::new(self.scope, len.unwrap_or(0) as u32);
+        Ok(ArraySerializer { scope: self.scope, array: array, index: 0 })
+    }
+
+    fn serialize_tuple(self, len: usize) -> Result<Self::SerializeTuple, Error> {
+        self.serialize_seq(Some(len))
+    }
+
+    fn serialize_tuple_struct(self, _: &'static str, len: usize) -> Result<Self::SerializeTupleStruct, Error> {
+        self.serialize_seq(Some(len))
+    }
+
+    fn serialize_tuple_variant(self, _: &'static str, _: u32, variant: &'static str, len: usize) -> Result<Self::SerializeTupleVariant, Error> {
+        let outer = JsObject::new(self.scope);
+        Ok(VariantSerializer { outer: outer, variant: variant, inner: self.serialize_seq(Some(len))? })
+    }
+
+    fn serialize_map(self, _: Option<usize>) -> Result<Self::SerializeMap, Error> {
+        let object = JsObject::new(self.scope);
+        Ok(ObjectSerializer { scope: self.scope, object: object, key: None })
+    }
+
+    fn serialize_struct(self, _: &'static str, len: usize) -> Result<Self::SerializeStruct, Error> {
+        self.serialize_map(Some(len))
+    }
+
+    fn serialize_struct_variant(self, _: &'static str, _: u32, variant: &'static str, len: usize) -> Result<Self::SerializeStructVariant, Error> {
+        let outer = JsObject::new(self.scope);
+        Ok(VariantSerializer { outer: outer, variant: variant, inner: self.serialize_map(Some(len))? })
+    }
+}
+
+#[doc(hidden)]
+pub struct ArraySerializer<'s, 'a, S: Scope<'a> + 's> {
+    scope: &'s mut S,
+    array: Handle<'a, JsArray>,
+    index: u32
+}
+
+impl<'s, 'a, S: Scope<'a> + 's> ArraySerializer<'s, 'a, S> {
+    fn push<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Error> {
+        let value = value.serialize(Serializer::new(&mut *self.scope))?;
+        self.array.set(self.index, value)?;
+        self.index += 1;
+        Ok(())
+    }
+}
+
+impl<'s, 'a, S: Scope<'a> + 's> ser::SerializeSeq for ArraySerializer<'s, 'a, S> {
+    type Ok = Handle<'a, JsValue>;
+    type Error = Error;
+
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Error> {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<Self::Ok, Error> {
+        Ok(self.array.upcast())
+    }
+}
+
+impl<'s, 'a, S: Scope<'a> + 's> ser::SerializeTuple for ArraySerializer<'s, 'a, S> {
+    type Ok = Handle<'a, JsValue>;
+    type Error = Error;
+
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Error> {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<Self::Ok, Error> {
+        Ok(self.array.upcast())
+    }
+}
+
+impl<'s, 'a, S: Scope<'a> + 's> ser::SerializeTupleStruct for ArraySerializer<'s, 'a, S> {
+    type Ok = Handle<'a, JsValue>;
+    type Error = Error;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Error> {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<Self::Ok, Error> {
+        Ok(self.array.upcast())
+    }
+}
+
+#[doc(hidden)]
+pub struct ObjectSerializer<'s, 'a, S: Scope<'a> + 's> {
+    scope: &'s mut S,
+    object: Handle<'a, JsObject>,
+    key: Option<Handle<'a, JsValue>>
+}
+
+impl<'s, 'a, S: Scope<'a> + 's> ObjectSerializer<'s, 'a, S> {
+    fn field<T: Serialize + ?Sized>(&mut self, key: &str, value: &T) -> Result<(), Error> {
+        let value = value.serialize(Serializer::new(&mut *self.scope))?;
+        self.object.set(key, value)?;
+        Ok(())
+    }
+}
+
+impl<'s, 'a, S: Scope<'a> + 's> ser::SerializeMap for ObjectSerializer<'s, 'a, S> {
+    type Ok = Handle<'a, JsValue>;
+    type Error = Error;
+
+    fn serialize_key<T: Serialize + ?Sized>(&mut self, key: &T) -> Result<(), Error> {
+        self.key = Some(key.serialize(Serializer::new(&mut *self.scope))?);
+        Ok(())
+    }
+
+    fn serialize_value<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Error> {
+        let key = match self.key.take() {
+            Some(key) => key,
+            None => return Err(Error::Custom("map value serialized before its key".to_string()))
+        };
+        let value = value.serialize(Serializer::new(&mut *self.scope))?;
+        self.object.set(key, value)?;
+        Ok(())
+    }
+
+    fn end(self) -> Result<Self::Ok, Error> {
+        Ok(self.object.upcast())
+    }
+}
+
+impl<'s, 'a, S: Scope<'a> + 's> ser::SerializeStruct for ObjectSerializer<'s, 'a, S> {
+    type Ok = Handle<'a, JsValue>;
+    type Error = Error;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, key: &'static str, value: &T) -> Result<(), Error> {
+        self.field(key, value)
+    }
+
+    fn end(self) -> Result<Self::Ok, Error> {
+        Ok(self.object.upcast())
+    }
+}
+
+#[doc(hidden)]
+pub struct VariantSerializer<'a, T> {
+    outer: Handle<'a, JsObject>,
+    variant: &'static str,
+    inner: T
+}
+
+impl<'a, T> VariantSerializer<'a, T> {
+    fn wrap(self, value: Handle<'a, JsValue>) -> Result<Handle<'a, JsValue>, Error> {
+        self.outer.set(self.variant, value)?;
+        Ok(self.outer.upcast())
+    }
+}
+
+impl<'s, 'a, S: Scope<'a> + 's> ser::SerializeTupleVariant for VariantSerializer<'a, ArraySerializer<'s, 'a, S>> {
+    type Ok = Handle<'a, JsValue>;
+    type Error = Error;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Error> {
+        self.inner.push(value)
+    }
+
+    fn end(self) -> Result<Self::Ok, Error> {
+        let array = self.inner.array.upcast();
+        self.wrap(array)
+    }
+}
+
+impl<'s, 'a, S: Scope<'a> + 's> ser::SerializeStructVariant for VariantSerializer<'a, ObjectSerializer<'s, 'a, S>> {
+    type Ok = Handle<'a, JsValue>;
+    type Error = Error;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, key: &'static str, value: &T) -> Result<(), Error> {
+        self.inner.field(key, value)
+    }
+
+    fn end(self) -> Result<Self::Ok, Error> {
+        let object = self.inner.object.upcast();
+        self.wrap(object)
+    }
+}
+
+// How many arrays and objects deep a value is read, which also stops a circular
+// object from being read until the stack overflows.
+const MAX_DEPTH: u32 = 128;
+
+/// A `serde` deserializer reading from a JavaScript value. Values nested more than 128
+/// arrays or objects deep, including circular ones, fail to deserialize.
+pub struct Deserializer<'s, 'a, 'b, S: Scope<'a> + 's> {
+    scope: &'s mut S,
+    value: Handle<'b, JsValue>,
+    depth: u32,
+    phantom: PhantomData<&'a ()>
+}
+
+impl<'s, 'a, 'b, S: Scope<'a> + 's> Deserializer<'s, 'a, 'b, S> {
+    pub fn new(scope: &'s mut S, value: Handle<'b, JsValue>) -> Self {
+        Deserializer::nested(scope, value, 0)
+    }
+
+    fn nested(scope: &'s mut S, value: Handle<'b, JsValue>, depth: u32) -> Self {
+        Deserializer { scope: scope, value: value, depth: depth, phantom: PhantomData }
+    }
+
+    // The depth of the values inside an array or object at this one's depth.
+    fn enter(&self) -> Result<u32, Error> {
+        if self.depth >= MAX_DEPTH {
+            return Err(Error::Custom(format!("value is nested more than {} levels deep, or is circular", MAX_DEPTH)));
+        }
+        Ok(self.depth + 1)
+    }
+}
+
+fn unexpected(value: Handle<JsValue>) -> de::Unexpected<'static> {
+    if value.is_a::<JsFunction>() {
+        de::Unexpected::Other("function")
+    } else {
+        de::Unexpected::Other("object")
+    }
+}
+
+impl<'de, 's, 'a, 'b, S: Scope<'a> + 's> de::Deserializer<'de> for Deserializer<'s, 'a, 'b, S> {
+    type Error = Error;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        let value = self.value;
+        if value.is_a::<JsNull>() || value.is_a::<JsUndefined>() {
+            visitor.visit_unit()
+        } else if let Some(b) = value.downcast::<JsBoolean>() {
+            visitor.visit_bool(b.value())
+        } else if let Some(n) = value.downcast::<JsNumber>() {
+            let n = n.value();
+            // Integral numbers are visited as integers so that they can be read into
+            // integer types, which don't accept floats.
+            if n.fract() == 0.0 && n >= -9007199254740992.0 && n <= 9007199254740992.0 {
+                visitor.visit_i64(n as i64)
+            } else {
+                visitor.visit_f64(n)
+            }
+        } else if let Some(s) = value.downcast::<JsString>() {
+            visitor.visit_string(s.value())
+        } else if let Some(mut buffer) = value.downcast::<JsBuffer>() {
+            use vm::Lock;
+            let bytes = buffer.grab(|data| data.as_slice::<u8>().to_vec());
+            visitor.visit_byte_buf(bytes)
+        } else if let Some(array) = value.downcast::<JsArray>() {
+            let depth = self.enter()?;
+            visitor.visit_seq(ArrayAccess { scope: self.scope, array: array, index: 0, len: array.len(), depth: depth, phantom: PhantomData })
+        } else if let Some(object) = value.downcast::<JsObject>() {
+            if value.is_a::<JsFunction>() {
+                return Err(de::Error::invalid_type(unexpected(value), &visitor));
+            }
+            let depth = self.enter()?;
+            let keys = object.get_own_property_names(self.scope)?;
+            visitor.visit_map(ObjectAccess { scope: self.scope, object: object, keys: keys, index: 0, len: keys.len(), depth: depth, phantom: PhantomData })
+        } else {
+            Err(de::Error::invalid_type(unexpected(value), &visitor))
+        }
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        if self.value.is_a::<JsNull>() || self.value.is_a::<JsUndefined>() {
+            visitor.visit_none()
+        } else {
+            visitor.visit_some(self)
+        }
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(self, _: &'static str, visitor: V) -> Result<V::Value, Error> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(self, _: &'static str, _: &'static [&'static str], visitor: V) -> Result<V::Value, Error> {
+        if let Some(s) = self.value.downcast::<JsString>() {
+            return visitor.visit_enum(s.value().into_deserializer());
+        }
+        let object = match self.value.downcast::<JsObject>() {
+            Some(object) => object,
+            None => return Err(de::Error::invalid_type(unexpected(self.value), &"an enum variant"))
+        };
+        let depth = self.enter()?;
+        let keys = object.get_own_property_names(self.scope)?;
+        if keys.len() != 1 {
+            return Err(de::Error::invalid_value(de::Unexpected::Map, &"an object with a single variant key"));
+        }
+        let key = keys.get(self.scope, 0)?;
+        let variant = key.to_string(self.scope)?.value();
+        let value = object.get(self.scope, key)?;
+        visitor.visit_enum(VariantAccess { scope: self.scope, variant: variant, value: value, depth: depth })
+    }
+
+    forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 u8 u16 u32 u64 f32 f64 char str string bytes byte_buf
+        unit unit_struct seq tuple tuple_struct map struct identifier ignored_any
+    }
+}
+
+struct ArrayAccess<'s, 'a, 'b, S: Scope<'a> + 's> {
+    scope: &'s mut S,
+    array: Handle<'b, JsArray>,
+    index: u32,
+    len: u32,
+    depth: u32,
+    phantom: PhantomData<&'a ()>
+}
+
+impl<'de, 's, 'a, 'b, S: Scope<'a> + 's> de::SeqAccess<'de> for ArrayAccess<'s, 'a, 'b, S> {
+    type Error = Error;
+
+    fn next_element_seed<T: de::DeserializeSeed<'de>>(&mut self, seed: T) -> Result<Option<T::Value>, Error> {
+        if self.index >= self.len {
+            return Ok(None);
+        }
+        let value = self.array.get(self.scope, self.index)?;
+        self.index += 1;
+        seed.deserialize(Deserializer::nested(&mut *self.scope, value, self.depth)).map(Some)
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some((self.len - self.index) as usize)
+    }
+}
+
+struct ObjectAccess<'s, 'a, 'b, S: Scope<'a> + 's> {
+    scope: &'s mut S,
+    object: Handle<'b, JsObject>,
+    keys: Handle<'a, JsArray>,
+    index: u32,
+    len: u32,
+    depth: u32,
+    phantom: PhantomData<&'a ()>
+}
+
+impl<'de, 's, 'a, 'b, S: Scope<'a> + 's> de::MapAccess<'de> for ObjectAccess<'s, 'a, 'b, S> {
+    type Error = Error;
+
+    fn next_key_seed<K: de::DeserializeSeed<'de>>(&mut self, seed: K) -> Result<Option<K::Value>, Error> {
+        if self.index >= self.len {
+            return Ok(None);
+        }
+        let key = self.keys.get(self.scope, self.index)?;
+        let key = key.to_string(self.scope)?.upcast();
+        seed.deserialize(Deserializer::new(&mut *self.scope, key)).map(Some)
+    }
+
+    fn next_value_seed<V: de::DeserializeSeed<'de>>(&mut self, seed: V) -> Result<V::Value, Error> {
+        let key = self.keys.get(self.scope, self.index)?;
+        self.index += 1;
+        let value = self.object.get(self.scope, key)?;
+        seed.deserialize(Deserializer::nested(&mut *self.scope, value, self.depth))
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some((self.len - self.index) as usize)
+    }
+}
+
+struct VariantAccess<'s, 'a, S: Scope<'a> + 's> {
+    scope: &'s mut S,
+    variant: String,
+    value: Handle<'a, JsValue>,
+    depth: u32
+}
+
+impl<'de, 's, 'a, S: Scope<'a> + 's> de::EnumAccess<'de> for VariantAccess<'s, 'a, S> {
+    type Error = Error;
+    type Variant = Self;
+
+    fn variant_seed<V: de::DeserializeSeed<'de>>(self, seed: V) -> Result<(V::Value, Self), Error> {
+        let variant = {
+            let variant: de::value::StrDeserializer<Error> = self.variant.as_str().into_deserializer();
+            seed.deserialize(variant)?
+        };
+        Ok((variant, self))
+    }
+}
+
+impl<'de, 's, 'a, S: Scope<'a> + 's> de::VariantAccess<'de> for VariantAccess<'s, 'a, S> {
+    type Error = Error;
+
+    fn unit_variant(self) -> Result<(), Error> {
+        Deserialize::deserialize(Deserializer::nested(self.scope, self.value, self.depth))
+    }
+
+    fn newtype_variant_seed<T: de::DeserializeSeed<'de>>(self, seed: T) -> Result<T::Value, Error> {
+        seed.deserialize(Deserializer::nested(self.scope, self.value, self.depth))
+    }
+
+    fn tuple_variant<V: Visitor<'de>>(self, _: usize, visitor: V) -> Result<V::Value, Error> {
+        de::Deserializer::deserialize_seq(Deserializer::nested(self.scope, self.value, self.depth), visitor)
+    }
+
+    fn struct_variant<V: Visitor<'de>>(self, _: &'static [&'static str], visitor: V) -> Result<V::Value, Error> {
+        de::Deserializer::deserialize_map(Deserializer::nested(self.scope, self.value, self.depth), visitor)
+    }
+}
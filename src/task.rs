@@ -1,15 +1,29 @@
-//! A trait for defining Rust _tasks_ to be executed in a background thread.
+//! A trait for defining Rust _tasks_ to be executed in a background thread, a
+//! signal and a channel for waking the main thread from other threads, and a way
+//! for other threads to wait on JavaScript promises.
 
+use std::cell::{Cell, RefCell};
+use std::collections::{HashMap, VecDeque};
+use std::error::Error;
+use std::fmt::{self, Debug, Display, Formatter};
 use std::marker::{Send, Sized};
 use std::mem;
 use std::os::raw::c_void;
+use std::rc::Rc;
+use std::sync::{Arc, Mutex, MutexGuard, Condvar};
+use std::sync::mpsc;
+use std::sync::atomic::{AtomicPtr, AtomicUsize, Ordering};
+use std::thread::{self, ThreadId};
 
-use js::{Value, JsFunction};
-use mem::Handle;
+use js::{Value, Object, JsValue, JsObject, JsArray, JsFunction, JsNumber, JsNull, JsUndefined, build};
+use mem::{Handle, Persistent};
 use mem::Managed;
 use scope::{Scope, RootScope};
-use vm::JsResult;
+use vm::{JsResult, VmResult, Call};
+use js::error::{JsError, Kind};
+use convert::{TryFromJs, TryIntoJs};
 use vm::internal::Isolate;
+use instrument::{self, Event};
 use neon_runtime;
 use neon_runtime::raw;
 
@@ -30,6 +44,60 @@ pub trait Task: Send + Sized {
     /// Convert the result of the task to a JavaScript value to be passed to the asynchronous callback. This method is executed on the main thread at some point after the background task is completed.
     fn complete<'a, T: Scope<'a>>(self, scope: &'a mut T, result: Result<Self::Output, Self::Error>) -> JsResult<Self::JsEvent>;
 
+    /// Runs `complete` on the current thread with `result`, as if the task had just
+    /// produced it on the thread pool, and returns the value that would be passed to
+    /// the callback. Since `perform` can be called directly too, this lets both halves
+    /// of a task be unit tested without scheduling it.
+    fn complete_now<'a, S: Scope<'a>>(self, scope: &mut S, result: Result<Self::Output, Self::Error>) -> JsResult<'a, Self::JsEvent> {
+        // `complete` borrows its scope for as long as the scope lives, so it gets a
+        // root scope of its own. Its result is still in the current `HandleScope`,
+        // which the caller's scope owns.
+        let mut root = RootScope::new(scope.isolate());
+        let event = self.complete(&mut root, result)?.to_raw();
+        Ok(Handle::new_internal(<Self::JsEvent as Managed>::from_raw(event)))
+    }
+
+    /// The name the task is reported under as an `async_hooks` resource type. The
+    /// callback runs in the async context the task was scheduled from, so tools like
+    /// `AsyncLocalStorage` can follow the work across the background thread.
+    fn resource_name(&self) -> &str {
+        "neon::Task"
+    }
+
+    /// Passes the task's output to `f` on the background thread if the task succeeds,
+    /// which can fail in turn. The combined task completes by converting `f`'s result
+    /// to JavaScript with `TryIntoJs`, or by throwing an `Error` with the message of
+    /// whichever error occurred.
+    fn and_then<F, U>(self, f: F) -> AndThen<Self, F>
+        where F: Fn(Self::Output) -> Result<U, Self::Error> + Send,
+              U: TryIntoJs + Send,
+              Self::Error: Display
+    {
+        AndThen { task: self, f: f }
+    }
+
+    /// Transforms the task's output with `f` on the background thread. The combined
+    /// task completes by converting `f`'s result to JavaScript with `TryIntoJs`, or by
+    /// throwing an `Error` with the task's error message if it fails.
+    fn map<F, U>(self, f: F) -> Map<Self, F>
+        where F: Fn(Self::Output) -> U + Send,
+              U: TryIntoJs + Send,
+              Self::Error: Display
+    {
+        Map { task: self, f: f }
+    }
+
+    /// Transforms the task's error with `f` on the background thread. The combined task
+    /// completes by converting the task's output to JavaScript with `TryIntoJs`, or by
+    /// throwing an `Error` with `f`'s result as its message.
+    fn map_err<F, E>(self, f: F) -> MapErr<Self, F>
+        where F: Fn(Self::Error) -> E + Send,
+              E: Display + Send,
+              Self::Output: TryIntoJs
+    {
+        MapErr { task: self, f: f }
+    }
+
     /// Schedule a task to be executed on a background thread.
     ///
     /// `callback` should have the following signature:
@@ -37,17 +105,360 @@ pub trait Task: Send + Sized {
     /// ```js
     /// function callback(err, value) {}
     /// ```
+    ///
+    /// Inside `testing::inline_tasks`, the task runs to completion on the current
+    /// thread instead, and `callback` has been called by the time this returns.
     fn schedule(self, callback: Handle<JsFunction>) {
+        let _timer = instrument::start(Event::Task);
         let boxed_self = Box::new(self);
+        // Taken from the boxed task, which doesn't move again, so the name stays
+        // valid even if it points into the task itself.
+        let (name, name_len) = {
+            let name = boxed_self.resource_name();
+            (name.as_ptr(), name.len())
+        };
         let self_raw = Box::into_raw(boxed_self);
         let callback_raw = callback.to_raw();
+        let run = if INLINE_TASKS.with(|inline| inline.get()) {
+            neon_runtime::task::run_now
+        } else {
+            neon_runtime::task::schedule
+        };
         unsafe {
-            neon_runtime::task::schedule(mem::transmute(self_raw),
-                                         perform_task::<Self>,
-                                         complete_task::<Self>,
-                                         callback_raw);
+            run(mem::transmute(self_raw),
+                name,
+                name_len,
+                perform_task::<Self>,
+                complete_task::<Self>,
+                callback_raw);
+        }
+    }
+}
+
+/// A task whose output is passed to a fallible function on the background thread,
+/// created with `Task::and_then`.
+pub struct AndThen<T, F> {
+    task: T,
+    f: F
+}
+
+impl<T, F, U> Task for AndThen<T, F>
+    where T: Task,
+          F: Fn(T::Output) -> Result<U, T::Error> + Send,
+          U: TryIntoJs + Send,
+          T::Error: Display
+{
+    type Output = U;
+    type Error = T::Error;
+    type JsEvent = U::Value;
+
+    fn perform(&self) -> Result<U, T::Error> {
+        self.task.perform().and_then(&self.f)
+    }
+
+    fn complete<'a, S: Scope<'a>>(self, scope: &'a mut S, result: Result<U, T::Error>) -> JsResult<U::Value> {
+        match result {
+            Ok(output) => output.try_into_js(scope),
+            Err(err) => JsError::throw(Kind::Error, &err.to_string())
+        }
+    }
+
+    fn resource_name(&self) -> &str {
+        self.task.resource_name()
+    }
+}
+
+/// A task whose output is transformed on the background thread, created with
+/// `Task::map`.
+pub struct Map<T, F> {
+    task: T,
+    f: F
+}
+
+impl<T, F, U> Task for Map<T, F>
+    where T: Task,
+          F: Fn(T::Output) -> U + Send,
+          U: TryIntoJs + Send,
+          T::Error: Display
+{
+    type Output = U;
+    type Error = T::Error;
+    type JsEvent = U::Value;
+
+    fn perform(&self) -> Result<U, T::Error> {
+        self.task.perform().map(&self.f)
+    }
+
+    fn complete<'a, S: Scope<'a>>(self, scope: &'a mut S, result: Result<U, T::Error>) -> JsResult<U::Value> {
+        match result {
+            Ok(output) => output.try_into_js(scope),
+            Err(err) => JsError::throw(Kind::Error, &err.to_string())
         }
     }
+
+    fn resource_name(&self) -> &str {
+        self.task.resource_name()
+    }
+}
+
+/// A task whose error is transformed on the background thread, created with
+/// `Task::map_err`.
+pub struct MapErr<T, F> {
+    task: T,
+    f: F
+}
+
+impl<T, F, E> Task for MapErr<T, F>
+    where T: Task,
+          F: Fn(T::Error) -> E + Send,
+          E: Display + Send,
+          T::Output: TryIntoJs
+{
+    type Output = T::Output;
+    type Error = E;
+    type JsEvent = <T::Output as TryIntoJs>::Value;
+
+    fn perform(&self) -> Result<T::Output, E> {
+        self.task.perform().map_err(&self.f)
+    }
+
+    fn complete<'a, S: Scope<'a>>(self, scope: &'a mut S, result: Result<T::Output, E>) -> JsResult<Self::JsEvent> {
+        match result {
+            Ok(output) => output.try_into_js(scope),
+            Err(err) => JsError::throw(Kind::Error, &err.to_string())
+        }
+    }
+
+    fn resource_name(&self) -> &str {
+        self.task.resource_name()
+    }
+}
+
+/// A set of tasks that run in parallel on the thread pool and complete together with a
+/// single callback, created with `TaskGroup::spawn`.
+pub struct TaskGroup<T: Task> {
+    tasks: Vec<T>
+}
+
+impl<T: Task> TaskGroup<T> {
+    /// Gathers the tasks into a group. They start running once the group is joined.
+    pub fn spawn<I: IntoIterator<Item=T>>(tasks: I) -> TaskGroup<T> {
+        TaskGroup { tasks: tasks.into_iter().collect() }
+    }
+
+    /// Schedules every task in the group. Once they have all succeeded, `callback` is
+    /// called with an array of their results, in the order the tasks were given:
+    ///
+    /// ```js
+    /// function callback(err, results) {}
+    /// ```
+    ///
+    /// If a task fails, `callback` is called with its error as soon as it completes,
+    /// and the results of the remaining tasks are discarded.
+    pub fn join<'a, 'b, S: Scope<'a>>(self, scope: &mut S, callback: Handle<'b, JsFunction>) -> VmResult<()> {
+        let len = self.tasks.len();
+        let results = JsArray::new(scope, len as u32);
+        if len == 0 {
+            let callback = Persistent::new(scope, callback);
+            let results = Persistent::new(scope, results);
+            return scope.next_tick(move |scope| {
+                let args: Vec<Handle<JsValue>> = vec![JsNull::new().upcast(), results.get(scope).upcast()];
+                callback.get(scope).call(scope, JsNull::new(), args)?;
+                Ok(())
+            });
+        }
+
+        // The group's progress is kept in a JavaScript object that each task's
+        // callback is bound to, along with the task's index.
+        let state = JsObject::new(scope);
+        state.set("callback", callback)?;
+        state.set("results", results)?;
+        state.set("remaining", JsNumber::new(scope, len as f64))?;
+        let complete = JsFunction::new_cached(scope, complete_member)?;
+        let bind = complete.get(scope, "bind")?.check::<JsFunction>()?;
+        for (i, task) in self.tasks.into_iter().enumerate() {
+            let args: Vec<Handle<JsValue>> = vec![JsNull::new().upcast(), state.upcast(), JsNumber::new(scope, i as f64).upcast()];
+            let bound = bind.call(scope, complete, args)?.check::<JsFunction>()?;
+            task.schedule(bound);
+        }
+        Ok(())
+    }
+}
+
+// The callback of a task in a group, bound to the group's state and the task's index.
+fn complete_member(call: Call) -> JsResult<JsUndefined> {
+    let scope = call.scope;
+    let state = call.arguments.require(scope, 0)?.check::<JsObject>()?;
+    let index = call.arguments.require(scope, 1)?.check::<JsNumber>()?.value() as u32;
+    let err = call.arguments.require(scope, 2)?;
+    // The callback is cleared once it has been called, so that a failure is only
+    // reported once.
+    let callback = match state.get(scope, "callback")?.downcast::<JsFunction>() {
+        Some(callback) => callback,
+        None => return Ok(JsUndefined::new())
+    };
+    if !err.is_a::<JsNull>() {
+        state.set("callback", JsUndefined::new())?;
+        callback.call(scope, JsNull::new(), vec![err])?;
+        return Ok(JsUndefined::new());
+    }
+    let results = state.get(scope, "results")?.check::<JsArray>()?;
+    let value = call.arguments.get(scope, 3).unwrap_or_else(|| JsUndefined::new().upcast());
+    results.set(index, value)?;
+    let remaining = state.get(scope, "remaining")?.check::<JsNumber>()?.value() - 1.0;
+    state.set("remaining", JsNumber::new(scope, remaining))?;
+    if remaining <= 0.0 {
+        state.set("callback", JsUndefined::new())?;
+        let args: Vec<Handle<JsValue>> = vec![JsNull::new().upcast(), results.upcast()];
+        callback.call(scope, JsNull::new(), args)?;
+    }
+    Ok(JsUndefined::new())
+}
+
+/// A scheduler that limits how many of its tasks run on the thread pool at once, so
+/// that an addon with thousands of background jobs leaves room in the pool for Node's
+/// own work, such as file system and DNS operations. Tasks start in the order they
+/// were scheduled as earlier ones complete.
+///
+/// Clones share the same limit. A queue keeps running its tasks even after every
+/// handle to it has been dropped.
+#[derive(Clone)]
+pub struct TaskQueue {
+    state: Rc<RefCell<QueueState>>
+}
+
+struct QueueState {
+    id: usize,
+    max_concurrency: usize,
+    running: usize,
+    pending: VecDeque<(Box<dyn ScheduleBoxed>, Persistent<JsFunction>)>
+}
+
+static NEXT_QUEUE_ID: AtomicUsize = AtomicUsize::new(0);
+
+thread_local! {
+    // The queues that have tasks running or waiting, which are kept alive until
+    // they're idle.
+    static BUSY_QUEUES: RefCell<HashMap<usize, Rc<RefCell<QueueState>>>> = RefCell::new(HashMap::new());
+}
+
+trait ScheduleBoxed {
+    fn schedule_boxed(self: Box<Self>, callback: Handle<JsFunction>);
+}
+
+impl<T: Task> ScheduleBoxed for T {
+    fn schedule_boxed(self: Box<Self>, callback: Handle<JsFunction>) {
+        (*self).schedule(callback)
+    }
+}
+
+impl TaskQueue {
+    /// Creates a queue that runs at most `max_concurrency` tasks at once. Panics if
+    /// `max_concurrency` is zero.
+    pub fn new(max_concurrency: usize) -> TaskQueue {
+        assert!(max_concurrency > 0, "a task queue needs a concurrency of at least one");
+        TaskQueue {
+            state: Rc::new(RefCell::new(QueueState {
+                id: NEXT_QUEUE_ID.fetch_add(1, Ordering::SeqCst),
+                max_concurrency: max_concurrency,
+                running: 0,
+                pending: VecDeque::new()
+            }))
+        }
+    }
+
+    /// Schedules `task` to run once there's room, calling `callback` with its result
+    /// as `Task::schedule` does.
+    pub fn schedule<'a, 'b, S, T>(&self, scope: &mut S, task: T, callback: Handle<'b, JsFunction>) -> VmResult<()>
+        where S: Scope<'a>,
+              T: Task + 'static
+    {
+        let callback = Persistent::new(scope, callback);
+        let id = {
+            let mut state = self.state.borrow_mut();
+            state.pending.push_back((Box::new(task), callback));
+            state.id
+        };
+        BUSY_QUEUES.with(|queues| queues.borrow_mut().insert(id, self.state.clone()));
+        start_queued(scope, &self.state)
+    }
+
+    /// The number of the queue's tasks that are running.
+    pub fn running(&self) -> usize {
+        self.state.borrow().running
+    }
+
+    /// The number of the queue's tasks that are waiting for room to run.
+    pub fn pending(&self) -> usize {
+        self.state.borrow().pending.len()
+    }
+}
+
+// Starts as many waiting tasks as the queue has room for. Each task's callback is
+// `complete_queued`, bound to the queue's id and the task's own callback.
+fn start_queued<'a, S: Scope<'a>>(scope: &mut S, state: &Rc<RefCell<QueueState>>) -> VmResult<()> {
+    let complete = JsFunction::new_cached(scope, complete_queued)?;
+    let bind = complete.get(scope, "bind")?.check::<JsFunction>()?;
+    loop {
+        let (id, task, callback) = {
+            let mut state = state.borrow_mut();
+            if state.running >= state.max_concurrency {
+                return Ok(());
+            }
+            match state.pending.pop_front() {
+                Some((task, callback)) => {
+                    state.running += 1;
+                    (state.id, task, callback)
+                }
+                None => return Ok(())
+            }
+        };
+        let args: Vec<Handle<JsValue>> = vec![JsNull::new().upcast(), JsNumber::new(scope, id as f64).upcast(), callback.get(scope).upcast()];
+        let bound = match bind.call(scope, complete, args) {
+            Ok(bound) => bound.check::<JsFunction>()?,
+            Err(throw) => {
+                state.borrow_mut().running -= 1;
+                return Err(throw);
+            }
+        };
+        task.schedule_boxed(bound);
+    }
+}
+
+fn complete_queued(call: Call) -> JsResult<JsUndefined> {
+    let scope = call.scope;
+    let id = call.arguments.require(scope, 0)?.check::<JsNumber>()?.value() as usize;
+    let callback = call.arguments.require(scope, 1)?.check::<JsFunction>()?;
+    let state = BUSY_QUEUES.with(|queues| queues.borrow().get(&id).cloned());
+    if let Some(state) = state {
+        state.borrow_mut().running -= 1;
+        // The next task starts before the callback runs, so that a callback that
+        // throws can't stall the queue.
+        let started = start_queued(scope, &state);
+        let idle = {
+            let state = state.borrow();
+            state.running == 0 && state.pending.is_empty()
+        };
+        if idle {
+            BUSY_QUEUES.with(|queues| queues.borrow_mut().remove(&id));
+        }
+        started?;
+    }
+    let err = call.arguments.require(scope, 2)?;
+    let value = call.arguments.get(scope, 3).unwrap_or_else(|| JsUndefined::new().upcast());
+    callback.call(scope, JsNull::new(), vec![err, value])?;
+    Ok(JsUndefined::new())
+}
+
+thread_local! {
+    // Whether `Task::schedule` runs tasks on the current thread, for tests.
+    static INLINE_TASKS: Cell<bool> = Cell::new(false);
+}
+
+// Turns running tasks inline on or off for the current thread, returning the previous
+// setting.
+pub(crate) fn set_inline_tasks(inline: bool) -> bool {
+    INLINE_TASKS.with(|cell| cell.replace(inline))
 }
 
 unsafe extern "C" fn perform_task<T: Task>(task: *mut c_void) -> *mut c_void {
@@ -57,15 +468,520 @@ unsafe extern "C" fn perform_task<T: Task>(task: *mut c_void) -> *mut c_void {
     mem::transmute(Box::into_raw(Box::new(result)))
 }
 
-unsafe extern "C" fn complete_task<T: Task>(task: *mut c_void, result: *mut c_void, out: &mut raw::Local) {
+unsafe extern "C" fn complete_task<T: Task>(isolate: *mut raw::Isolate, task: *mut c_void,
+                                              result: *mut c_void, out: &mut raw::Local) {
     let result: Result<T::Output, T::Error> = *Box::from_raw(mem::transmute(result));
     let task: Box<T> = Box::from_raw(mem::transmute(task));
 
     // The neon::Task::complete() method installs an outer v8::HandleScope
     // that is responsible for managing the out pointer, so it's safe to
     // create the RootScope here without creating a local v8::HandleScope.
-    let mut scope = RootScope::new(Isolate::current());
+    // The runtime passes in the isolate that scheduled the task, which may
+    // belong to a worker thread rather than the main thread.
+    let mut scope = RootScope::new(Isolate::from_raw(isolate));
     if let Ok(result) = task.complete(&mut scope, result) {
         *out = result.to_raw();
     }
 }
+
+type LoopCallback = Box<dyn FnMut(&mut RootScope) -> VmResult<()>>;
+
+/// A handle to a callback on the JavaScript thread's event loop that can be triggered
+/// from any thread, for bridging events that a `Task` can't express, such as a stream
+/// of results from a long-running native thread.
+///
+/// Triggers that arrive before the callback gets to run are coalesced into one call.
+/// Clones refer to the same callback, and while any of them is alive the event loop
+/// stays open; dropping the last one releases the callback on the JavaScript thread.
+pub struct AsyncSignal {
+    raw: *mut c_void
+}
+
+unsafe impl Send for AsyncSignal { }
+unsafe impl Sync for AsyncSignal { }
+
+impl AsyncSignal {
+    /// Creates a signal that runs `callback` on the current thread's event loop. If the
+    /// callback throws, the exception is reported as uncaught.
+    pub fn new<'a, S, F>(scope: &mut S, callback: F) -> AsyncSignal
+        where S: Scope<'a>,
+              F: FnMut(&mut RootScope) -> VmResult<()> + 'static
+    {
+        AsyncSignal::named(scope, "neon::AsyncSignal", callback)
+    }
+
+    /// Creates a signal like `new`, reported under `name` as an `async_hooks` resource
+    /// type. The callback runs in the async context the signal was created in.
+    pub fn named<'a, S, F>(scope: &mut S, name: &str, callback: F) -> AsyncSignal
+        where S: Scope<'a>,
+              F: FnMut(&mut RootScope) -> VmResult<()> + 'static
+    {
+        let callback: Box<LoopCallback> = Box::new(Box::new(callback));
+        let raw = unsafe {
+            neon_runtime::task::new_async(scope.isolate().to_raw(),
+                                          name.as_ptr(),
+                                          name.len(),
+                                          Box::into_raw(callback) as *mut c_void,
+                                          run_callback,
+                                          drop_callback)
+        };
+        AsyncSignal { raw: raw }
+    }
+
+    /// Wakes the JavaScript thread to run the callback.
+    pub fn trigger(&self) {
+        unsafe {
+            neon_runtime::task::send_async(self.raw);
+        }
+    }
+
+    /// Lets the event loop exit while the signal is still alive, for a signal that only
+    /// does work on behalf of other pending events, such as cleanup. The scope must
+    /// belong to the JavaScript thread that created the signal.
+    pub fn unref<'a, S: Scope<'a>>(&self, _: &mut S) {
+        unsafe {
+            neon_runtime::task::unref_async(self.raw);
+        }
+    }
+}
+
+impl Clone for AsyncSignal {
+    fn clone(&self) -> AsyncSignal {
+        unsafe {
+            neon_runtime::task::retain_async(self.raw);
+        }
+        AsyncSignal { raw: self.raw }
+    }
+}
+
+impl Drop for AsyncSignal {
+    fn drop(&mut self) {
+        unsafe {
+            neon_runtime::task::release_async(self.raw);
+        }
+    }
+}
+
+type ChannelCall = Box<dyn FnMut(&mut RootScope) -> VmResult<()> + Send>;
+
+/// A queue of closures to run on a JavaScript thread's event loop, which can be cloned
+/// and sent to any thread. Closures run in the order they were sent, and if one throws,
+/// the exception is reported as uncaught.
+///
+/// A channel created with `bounded` holds at most a fixed number of closures that
+/// haven't run yet, so that a fast producer can't flood the event loop: `send` waits
+/// for room, while `try_send` hands the closure back instead. Like an `AsyncSignal`,
+/// the channel keeps the event loop open while any clone of it is alive.
+#[derive(Clone)]
+pub struct Channel {
+    queue: Arc<ChannelQueue>,
+    signal: AsyncSignal
+}
+
+struct ChannelQueue {
+    calls: Mutex<VecDeque<ChannelCall>>,
+    not_full: Condvar,
+    capacity: Option<usize>,
+    thread: ThreadId,
+    // The channel's signal, without a reference of its own, for rescheduling calls
+    // left behind by one that threw. It's only used while the signal is running.
+    signal: AtomicPtr<c_void>
+}
+
+impl Channel {
+    /// Creates a channel to the current thread's event loop, with no limit on the
+    /// number of closures waiting to run.
+    pub fn new<'a, S: Scope<'a>>(scope: &mut S) -> Channel {
+        Channel::with_capacity(scope, None)
+    }
+
+    /// Creates a channel to the current thread's event loop that holds at most
+    /// `capacity` closures waiting to run. Panics if `capacity` is zero.
+    pub fn bounded<'a, S: Scope<'a>>(scope: &mut S, capacity: usize) -> Channel {
+        assert!(capacity > 0, "a bounded channel needs a capacity of at least one");
+        Channel::with_capacity(scope, Some(capacity))
+    }
+
+    fn with_capacity<'a, S: Scope<'a>>(scope: &mut S, capacity: Option<usize>) -> Channel {
+        let queue = Arc::new(ChannelQueue {
+            calls: Mutex::new(VecDeque::new()),
+            not_full: Condvar::new(),
+            capacity: capacity,
+            thread: thread::current().id(),
+            signal: AtomicPtr::new(0 as *mut c_void)
+        });
+        let signal = {
+            let queue = queue.clone();
+            AsyncSignal::named(scope, "neon::Channel", move |scope| queue.run(scope))
+        };
+        queue.signal.store(signal.raw, Ordering::SeqCst);
+        Channel { queue: queue, signal: signal }
+    }
+
+    /// Queues `f` to run on the JavaScript thread. If the channel is bounded and full,
+    /// this waits until there's room, so it panics rather than deadlock if it's called
+    /// on the JavaScript thread itself; use `try_send` there.
+    pub fn send<F>(&self, f: F)
+        where F: FnOnce(&mut RootScope) -> VmResult<()> + Send + 'static
+    {
+        let mut calls = lock(&self.queue.calls);
+        if let Some(capacity) = self.queue.capacity {
+            while calls.len() >= capacity {
+                assert!(thread::current().id() != self.queue.thread,
+                        "Channel::send can't wait for room on the JavaScript thread; use try_send");
+                calls = self.queue.not_full.wait(calls).unwrap_or_else(|err| err.into_inner());
+            }
+        }
+        calls.push_back(once(f));
+        mem::drop(calls);
+        self.signal.trigger();
+    }
+
+    /// Queues `f` to run on the JavaScript thread, or gives it back as `Full` if the
+    /// channel is bounded and full.
+    pub fn try_send<F>(&self, f: F) -> Result<(), Full<F>>
+        where F: FnOnce(&mut RootScope) -> VmResult<()> + Send + 'static
+    {
+        let mut calls = lock(&self.queue.calls);
+        if let Some(capacity) = self.queue.capacity {
+            if calls.len() >= capacity {
+                return Err(Full(f));
+            }
+        }
+        calls.push_back(once(f));
+        mem::drop(calls);
+        self.signal.trigger();
+        Ok(())
+    }
+
+    /// Lets the event loop exit while the channel is still alive, as `AsyncSignal::unref`
+    /// does, for a channel that only carries work that can be dropped, such as logging.
+    /// This applies to every clone of the channel, and closures still waiting to run
+    /// when the loop exits are dropped.
+    pub fn unref<'a, S: Scope<'a>>(&self, scope: &mut S) {
+        self.signal.unref(scope);
+    }
+}
+
+impl ChannelQueue {
+    fn run(&self, scope: &mut RootScope) -> VmResult<()> {
+        // Only the calls that were waiting when the loop woke up run now, so a producer
+        // refilling a bounded channel can't keep the loop from moving on.
+        let waiting = lock(&self.calls).len();
+        for _ in 0..waiting {
+            let call = lock(&self.calls).pop_front();
+            let mut call = match call {
+                Some(call) => call,
+                None => break
+            };
+            self.not_full.notify_one();
+            if let Err(err) = call(scope) {
+                if !lock(&self.calls).is_empty() {
+                    unsafe {
+                        neon_runtime::task::send_async(self.signal.load(Ordering::SeqCst));
+                    }
+                }
+                return Err(err);
+            }
+        }
+        Ok(())
+    }
+}
+
+fn once<F>(f: F) -> ChannelCall
+    where F: FnOnce(&mut RootScope) -> VmResult<()> + Send + 'static
+{
+    let mut f = Some(f);
+    Box::new(move |scope: &mut RootScope| (f.take().unwrap())(scope))
+}
+
+// A panic while the lock is held can't leave the queue inconsistent, so poisoning is
+// ignored.
+fn lock(calls: &Mutex<VecDeque<ChannelCall>>) -> MutexGuard<VecDeque<ChannelCall>> {
+    calls.lock().unwrap_or_else(|err| err.into_inner())
+}
+
+/// The error returned by `Channel::try_send` when a bounded channel is full, holding
+/// the closure that wasn't sent.
+pub struct Full<F>(pub F);
+
+impl<F> Debug for Full<F> {
+    fn fmt(&self, fmt: &mut Formatter) -> fmt::Result {
+        fmt.write_str("Full(..)")
+    }
+}
+
+impl<F> Display for Full<F> {
+    fn fmt(&self, fmt: &mut Formatter) -> fmt::Result {
+        fmt.write_str("channel is full")
+    }
+}
+
+impl<F> Error for Full<F> { }
+
+/// Runs `f` on the JavaScript thread of `channel` and blocks the current thread until
+/// it completes, returning its result. This adapts synchronous Rust interfaces to
+/// implementations written in JavaScript.
+///
+/// The JavaScript thread can't wait on itself, so calling this there returns
+/// `BlockError::Deadlock` instead of hanging. If `f` throws, the exception is
+/// reported as uncaught and this returns `BlockError::Threw`.
+pub fn block_on_js<T, F>(channel: &Channel, f: F) -> Result<T, BlockError>
+    where T: Send + 'static,
+          F: FnOnce(&mut RootScope) -> VmResult<T> + Send + 'static
+{
+    if thread::current().id() == channel.queue.thread {
+        return Err(BlockError::Deadlock);
+    }
+    let (tx, rx) = mpsc::channel();
+    channel.send(move |scope| {
+        match f(scope) {
+            Ok(value) => {
+                let _ = tx.send(Ok(value));
+                Ok(())
+            }
+            Err(throw) => {
+                let _ = tx.send(Err(BlockError::Threw));
+                Err(throw)
+            }
+        }
+    });
+    rx.recv().unwrap_or(Err(BlockError::Dropped))
+}
+
+/// The reasons `block_on_js` can fail.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BlockError {
+    /// It was called on the JavaScript thread it would have waited for.
+    Deadlock,
+    /// The closure threw an exception.
+    Threw,
+    /// The closure was dropped without running, because its event loop shut down.
+    Dropped
+}
+
+impl Display for BlockError {
+    fn fmt(&self, fmt: &mut Formatter) -> fmt::Result {
+        fmt.write_str(match *self {
+            BlockError::Deadlock => "can't block the JavaScript thread on itself",
+            BlockError::Threw => "JavaScript threw an exception",
+            BlockError::Dropped => "the JavaScript event loop shut down"
+        })
+    }
+}
+
+impl Error for BlockError { }
+
+/// Subscribes to the settlement of `promise`, or of any other object with a `then`
+/// method, so that its result can be waited for on another thread. A fulfilled value
+/// is converted with `TryFromJs` on the JavaScript thread, as soon as it's available.
+///
+/// The receiver can be moved into a `Task`, whose `perform` then waits for the promise
+/// on the thread pool. That occupies a pool thread for as long as the promise is
+/// pending, so this suits promises that settle promptly.
+pub fn await_promise<'a, 'b, S, T>(scope: &mut S, promise: Handle<'b, JsObject>) -> VmResult<PromiseReceiver<T>>
+    where S: Scope<'a>,
+          T: TryFromJs + Send + 'static
+{
+    let then = match promise.get(scope, "then")?.downcast::<JsFunction>() {
+        Some(then) => then,
+        None => return JsError::throw(Kind::TypeError, "value is not a promise")
+    };
+    let (tx, rx) = mpsc::channel();
+    // Only one of the reactions runs. If the promise is collected without settling,
+    // both are dropped along with their senders, which closes the channel.
+    let on_fulfilled = {
+        let tx = tx.clone();
+        JsFunction::new_closure(scope, move |call| {
+            let scope = call.scope;
+            let value = call.arguments.get(scope, 0).unwrap_or_else(|| JsUndefined::new().upcast());
+            let result = match T::from_js(scope, value) {
+                Ok(Some(value)) => Ok(value),
+                Ok(None) => Err(PromiseError::WrongType(T::describe())),
+                Err(throw) => {
+                    let _ = tx.send(Err(PromiseError::Threw));
+                    return Err(throw);
+                }
+            };
+            let _ = tx.send(result);
+            Ok(JsUndefined::new())
+        })?
+    };
+    let on_rejected = JsFunction::new_closure(scope, move |call| {
+        let scope = call.scope;
+        let reason = call.arguments.get(scope, 0).unwrap_or_else(|| JsUndefined::new().upcast());
+        match reason.to_string(scope) {
+            Ok(message) => {
+                let _ = tx.send(Err(PromiseError::Rejected(message.value())));
+                Ok(JsUndefined::new())
+            }
+            Err(throw) => {
+                let _ = tx.send(Err(PromiseError::Threw));
+                Err(throw)
+            }
+        }
+    })?;
+    let args: Vec<Handle<JsValue>> = vec![on_fulfilled.upcast(), on_rejected.upcast()];
+    then.call(scope, promise, args)?;
+    Ok(PromiseReceiver { receiver: rx, thread: thread::current().id(), taken: Cell::new(false) })
+}
+
+/// The receiving end of `await_promise`, which can be sent to any thread to wait for
+/// the promise's result.
+pub struct PromiseReceiver<T> {
+    receiver: mpsc::Receiver<Result<T, PromiseError>>,
+    thread: ThreadId,
+    taken: Cell<bool>
+}
+
+impl<T> PromiseReceiver<T> {
+    /// Blocks the current thread until the promise settles, returning its converted
+    /// value, or the reason it was rejected. The result can only be taken once, so
+    /// later calls return `PromiseError::Taken`.
+    ///
+    /// A promise can only settle on its JavaScript thread, so waiting there for one
+    /// that hasn't settled yet returns `PromiseError::Deadlock` instead of hanging.
+    pub fn wait(&self) -> Result<T, PromiseError> {
+        if thread::current().id() == self.thread {
+            return self.try_wait().unwrap_or(Err(PromiseError::Deadlock));
+        }
+        if self.taken.get() {
+            return Err(PromiseError::Taken);
+        }
+        self.taken.set(true);
+        self.receiver.recv().unwrap_or(Err(PromiseError::Dropped))
+    }
+
+    /// Returns the promise's result if it has settled, without blocking.
+    pub fn try_wait(&self) -> Option<Result<T, PromiseError>> {
+        if self.taken.get() {
+            return Some(Err(PromiseError::Taken));
+        }
+        let result = match self.receiver.try_recv() {
+            Ok(result) => result,
+            Err(mpsc::TryRecvError::Empty) => return None,
+            Err(mpsc::TryRecvError::Disconnected) => Err(PromiseError::Dropped)
+        };
+        self.taken.set(true);
+        Some(result)
+    }
+}
+
+/// The reasons a promise passed to `await_promise` can fail to produce a value.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PromiseError {
+    /// The promise was rejected, with a reason that converts to this string.
+    Rejected(String),
+    /// The promise was fulfilled with a value that doesn't convert to the expected
+    /// type, which is described by this string.
+    WrongType(String),
+    /// Converting the promise's result threw an exception.
+    Threw,
+    /// The promise was collected without settling.
+    Dropped,
+    /// It was waited for on its own JavaScript thread before settling.
+    Deadlock,
+    /// The result was already taken by an earlier call.
+    Taken
+}
+
+impl Display for PromiseError {
+    fn fmt(&self, fmt: &mut Formatter) -> fmt::Result {
+        match *self {
+            PromiseError::Rejected(ref reason) => write!(fmt, "promise was rejected: {}", reason),
+            PromiseError::WrongType(ref expected) => write!(fmt, "promise was fulfilled with a value that isn't {}", expected),
+            PromiseError::Threw => fmt.write_str("converting the promise's result threw an exception"),
+            PromiseError::Dropped => fmt.write_str("promise was collected without settling"),
+            PromiseError::Deadlock => fmt.write_str("can't wait for a promise on its own JavaScript thread"),
+            PromiseError::Taken => fmt.write_str("promise's result was already taken")
+        }
+    }
+}
+
+impl Error for PromiseError { }
+
+/// A guard for a callback scheduled with `Scope::set_timeout` or `Scope::set_interval`.
+///
+/// Dropping the guard cancels the timer, so a timer that should run on its own has to
+/// be `detach`ed. If the callback throws, the exception is reported as uncaught.
+/// The callback runs in the async context the timer was set from, as a `neon::Timer`
+/// resource for `async_hooks`.
+pub struct Timer {
+    raw: *mut c_void
+}
+
+impl Timer {
+    pub(crate) fn new<'a, S, F>(scope: &mut S, timeout: u64, repeat: u64, callback: F) -> Timer
+        where S: Scope<'a>,
+              F: FnMut(&mut RootScope) -> VmResult<()> + 'static
+    {
+        let callback: Box<LoopCallback> = Box::new(Box::new(callback));
+        let name = "neon::Timer";
+        let raw = unsafe {
+            neon_runtime::task::new_timer(scope.isolate().to_raw(),
+                                          name.as_ptr(),
+                                          name.len(),
+                                          Box::into_raw(callback) as *mut c_void,
+                                          run_callback,
+                                          drop_callback,
+                                          timeout,
+                                          repeat)
+        };
+        Timer { raw: raw }
+    }
+
+    /// Stops the timer, so its callback won't run again.
+    pub fn cancel(&self) {
+        unsafe {
+            neon_runtime::task::stop_timer(self.raw);
+        }
+    }
+
+    /// Lets the timer keep running without the guard. A timeout is released after it
+    /// runs, but an interval runs for as long as the event loop does.
+    pub fn detach(self) {
+        unsafe {
+            neon_runtime::task::detach_timer(self.raw);
+        }
+        mem::forget(self);
+    }
+}
+
+impl Drop for Timer {
+    fn drop(&mut self) {
+        unsafe {
+            neon_runtime::task::close_timer(self.raw);
+        }
+    }
+}
+
+// A function that runs `callback` the first time it's called, for scheduling Rust code
+// with JavaScript's own queues. If the callback throws, so does the function.
+pub(crate) fn once_function<'a, S, F>(scope: &mut S, callback: F) -> JsResult<'a, JsFunction>
+    where S: Scope<'a>,
+          F: FnOnce(&mut RootScope) -> VmResult<()> + 'static
+{
+    let mut callback = Some(callback);
+    let callback: Box<LoopCallback> = Box::new(Box::new(move |scope: &mut RootScope| {
+        match callback.take() {
+            Some(callback) => callback(scope),
+            None => Ok(())
+        }
+    }));
+    let isolate = scope.isolate().to_raw();
+    let data = Box::into_raw(callback) as *mut c_void;
+    build(|out| unsafe {
+        neon_runtime::task::new_once_function(out, isolate, data, run_callback, drop_callback)
+    })
+}
+
+unsafe extern "C" fn run_callback(isolate: *mut raw::Isolate, callback: *mut c_void) {
+    let _timer = instrument::start(Event::Callback);
+    let callback = &mut *(callback as *mut LoopCallback);
+    let mut scope = RootScope::new(Isolate::from_raw(isolate));
+    let _ = callback(&mut scope);
+}
+
+unsafe extern "C" fn drop_callback(callback: *mut c_void) {
+    mem::drop(Box::from_raw(callback as *mut LoopCallback));
+}
@@ -0,0 +1,154 @@
+//! Helpers for the Rust tests of an addon, which run inside Node with the `neon-test`
+//! harness after being registered with `Module::test`.
+
+use mem::{Handle, Managed};
+use scope::Scope;
+use vm::{JsResult, VmResult};
+use js::{Value, Object, JsValue, JsObject, JsArray, JsFunction, JsString};
+use task;
+
+/// Runs `f` with the tasks that it schedules on this thread run inline, so a test of an
+/// asynchronous API doesn't have to wait on the event loop. Within `f`,
+/// `Task::schedule` calls the task's `perform` and `complete` on the current thread and
+/// then calls the callback, all before returning. `TaskGroup` and `TaskQueue` schedule
+/// their tasks with `Task::schedule`, so those run inline too.
+pub fn inline_tasks<T, F: FnOnce() -> T>(f: F) -> T {
+    let _restore = RestoreInlineTasks(task::set_inline_tasks(true));
+    f()
+}
+
+// Restores the previous setting even if `f` panics.
+struct RestoreInlineTasks(bool);
+
+impl Drop for RestoreInlineTasks {
+    fn drop(&mut self) {
+        task::set_inline_tasks(self.0);
+    }
+}
+
+/// A value that `assert_js_eq!` and `diff` compare against: either JSON text, which
+/// is parsed with `JSON.parse`, or a JavaScript value built by the test.
+pub trait Expected {
+    fn to_expected<'a, S: Scope<'a>>(self, scope: &mut S) -> JsResult<'a, JsValue>;
+}
+
+impl<'c> Expected for &'c str {
+    fn to_expected<'a, S: Scope<'a>>(self, scope: &mut S) -> JsResult<'a, JsValue> {
+        let json = scope.global().get(scope, "JSON")?.check::<JsObject>()?;
+        let parse = json.get(scope, "parse")?.check::<JsFunction>()?;
+        let text = JsString::new_or_throw(scope, self)?;
+        parse.call(scope, json, vec![text])
+    }
+}
+
+impl Expected for String {
+    fn to_expected<'a, S: Scope<'a>>(self, scope: &mut S) -> JsResult<'a, JsValue> {
+        self.as_str().to_expected(scope)
+    }
+}
+
+impl<'b, T: Value> Expected for Handle<'b, T> {
+    fn to_expected<'a, S: Scope<'a>>(self, _: &mut S) -> JsResult<'a, JsValue> {
+        Ok(Handle::new_internal(JsValue::from_raw(self.to_raw())))
+    }
+}
+
+/// Compares `actual` with `expected` structurally, returning `None` if they're equal
+/// or a description of the first difference between them if they aren't.
+///
+/// Arrays are equal if they have the same length and equal elements, and objects
+/// if they have the same own enumerable properties with equal values. Anything else
+/// is compared with `===`. The description gives the path to the difference, such as
+/// `$.items[2].name`, followed by both values as JSON.
+pub fn diff<'a, 'b, S, T, E>(scope: &mut S, actual: Handle<'b, T>, expected: E) -> VmResult<Option<String>>
+    where S: Scope<'a>,
+          T: Value,
+          E: Expected
+{
+    let actual = actual.upcast::<JsValue>();
+    let expected = expected.to_expected(scope)?;
+    let difference = match first_difference(scope, "$", actual, expected)? {
+        Some(difference) => difference,
+        None => return Ok(None)
+    };
+    Ok(Some(format!("{}\n  actual:   {}\n  expected: {}",
+                    difference,
+                    stringify(scope, actual)?,
+                    stringify(scope, expected)?)))
+}
+
+fn first_difference<'a, 'b, S: Scope<'a>>(scope: &mut S, path: &str, actual: Handle<'b, JsValue>, expected: Handle<'b, JsValue>) -> VmResult<Option<String>> {
+    if let Some(expected) = expected.downcast::<JsArray>() {
+        let actual = match actual.downcast::<JsArray>() {
+            Some(actual) => actual,
+            None => return mismatch(scope, path, actual, expected.upcast())
+        };
+        if actual.len() != expected.len() {
+            return Ok(Some(format!("at {}: expected an array of length {}, found one of length {}",
+                                   path, expected.len(), actual.len())));
+        }
+        for i in 0..expected.len() {
+            let a = actual.get(scope, i)?;
+            let e = expected.get(scope, i)?;
+            if let Some(difference) = first_difference(scope, &format!("{}[{}]", path, i), a, e)? {
+                return Ok(Some(difference));
+            }
+        }
+        return Ok(None);
+    }
+
+    // Functions are compared by identity, like other values that JSON can't express.
+    if expected.is_a::<JsObject>() && !expected.is_a::<JsFunction>() {
+        let expected = expected.check::<JsObject>()?;
+        let actual = match actual.downcast::<JsObject>() {
+            Some(ref actual) if !actual.is_a::<JsArray>() && !actual.is_a::<JsFunction>() => *actual,
+            _ => return mismatch(scope, path, actual, expected.upcast())
+        };
+        let actual_keys = keys(scope, actual)?;
+        let expected_keys = keys(scope, expected)?;
+        for key in &expected_keys {
+            if !actual_keys.contains(key) {
+                return Ok(Some(format!("at {}: missing property `{}`", path, key)));
+            }
+        }
+        for key in &actual_keys {
+            if !expected_keys.contains(key) {
+                return Ok(Some(format!("at {}: unexpected property `{}`", path, key)));
+            }
+        }
+        for key in &expected_keys {
+            let a = actual.get(scope, key.as_str())?;
+            let e = expected.get(scope, key.as_str())?;
+            if let Some(difference) = first_difference(scope, &format!("{}.{}", path, key), a, e)? {
+                return Ok(Some(difference));
+            }
+        }
+        return Ok(None);
+    }
+
+    if actual.strict_equals(scope, expected) {
+        Ok(None)
+    } else {
+        mismatch(scope, path, actual, expected)
+    }
+}
+
+fn mismatch<'a, 'b, S: Scope<'a>>(scope: &mut S, path: &str, actual: Handle<'b, JsValue>, expected: Handle<'b, JsValue>) -> VmResult<Option<String>> {
+    Ok(Some(format!("at {}: expected {}, found {}", path, stringify(scope, expected)?, stringify(scope, actual)?)))
+}
+
+fn keys<'a, 'b, S: Scope<'a>>(scope: &mut S, object: Handle<'b, JsObject>) -> VmResult<Vec<String>> {
+    let names = object.get_own_property_names(scope)?.to_vec(scope)?;
+    names.into_iter().map(|name| name.to_display_string(scope)).collect()
+}
+
+// Formats a value as JSON, or as `String` does for values JSON can't express, such
+// as `undefined` and functions.
+fn stringify<'a, 'b, S: Scope<'a>>(scope: &mut S, value: Handle<'b, JsValue>) -> VmResult<String> {
+    let json = scope.global().get(scope, "JSON")?.check::<JsObject>()?;
+    let stringify = json.get(scope, "stringify")?.check::<JsFunction>()?;
+    match stringify.call(scope, json, vec![value])?.downcast::<JsString>() {
+        Some(text) => Ok(text.value()),
+        None => value.to_display_string(scope)
+    }
+}
@@ -1,49 +1,155 @@
 //! Abstractions representing the JavaScript virtual machine and its control flow.
 
 use std::mem;
-use std::any::TypeId;
+use std::ptr;
+use std::slice;
+use std::ops::Deref;
+use std::any::{Any, TypeId};
 use std::error::Error;
 use std::fmt::{Display, Formatter, Result as FmtResult};
 use std::marker::PhantomData;
 use std::collections::HashMap;
 use std::os::raw::c_void;
 use std::panic::UnwindSafe;
+use std::cell::{RefCell, RefMut};
 use neon_runtime;
 use neon_runtime::raw;
 use scope::{Scope, RootScope};
-use js::{JsValue, Value, Object, JsObject, JsFunction};
+use scope::internal::ScopeInternal;
+use js::{JsValue, Value, Object, JsObject, JsFunction, JsUndefined, JsNumber, JsString, JsBoolean};
+use js::{lazy_getter, define_property, freeze};
 use js::class::internal::ClassMetadata;
-use js::error::{JsError, Kind};
-use mem::{Handle, Managed};
-use self::internal::LockState;
+use js::error::{JsError, Kind, convert_panics, with_native_frame};
+use mem::{Handle, Managed, Persistent, RootQueue};
+use gc::GcRegistry;
+use convert::{self, TryFromJs, FromArguments, FromOptions};
+use self::internal::{Isolate, LockState};
 
 pub(crate) mod internal {
     use std::mem;
-    use std::collections::HashSet;
+    use std::cell::{Cell, RefCell};
     use std::os::raw::c_void;
     use cslice::CMutSlice;
     use neon_runtime;
     use neon_runtime::raw;
     use super::ClassMap;
 
+    // A buffer locked by a `LockState` that hasn't been released yet.
+    struct Loan {
+        id: usize,
+        kind: &'static str,
+        start: usize,
+        len: usize,
+        site: Option<String>
+    }
+
+    thread_local! {
+        // Every buffer locked on this thread, so that a lock nested inside another,
+        // e.g. a `grab` within a `grab`, is checked against the outer one too.
+        static LOANS: RefCell<Vec<Loan>> = RefCell::new(Vec::new());
+        static NEXT_LOAN: Cell<usize> = Cell::new(0);
+    }
+
     pub struct LockState {
-        buffers: HashSet<usize>
+        loans: Vec<usize>,
+        conflict: Option<String>
     }
 
     impl LockState {
         pub fn new() -> LockState {
-            LockState { buffers: HashSet::new() }
+            LockState { loans: Vec::new(), conflict: None }
+        }
+
+        // Locks a buffer of the kind named by `kind`, such as `JsBuffer`, returning
+        // the slice to expose. If the buffer overlaps one that is already locked, the
+        // conflict is recorded for `conflict` and an empty slice is returned instead,
+        // so that no aliasing slices are created.
+        pub fn use_buffer<'a>(&mut self, kind: &'static str, buf: CMutSlice<'a, u8>) -> CMutSlice<'a, u8> {
+            let start = buf.as_ptr() as usize;
+            let len = buf.len();
+            if len == 0 {
+                return buf;
+            }
+            let conflict = LOANS.with(|loans| {
+                loans.borrow().iter()
+                    .find(|loan| start < loan.start + loan.len && loan.start < start + len)
+                    .map(|loan| describe_conflict(kind, start, len, loan))
+            });
+            if let Some(conflict) = conflict {
+                if self.conflict.is_none() {
+                    self.conflict = Some(conflict);
+                }
+                return unsafe { CMutSlice::new(start as *mut u8, 0) };
+            }
+            let id = NEXT_LOAN.with(|next| {
+                let id = next.get();
+                next.set(id.wrapping_add(1));
+                id
+            });
+            let loan = Loan { id: id, kind: kind, start: start, len: len, site: lock_site() };
+            LOANS.with(|loans| loans.borrow_mut().push(loan));
+            self.loans.push(id);
+            buf
+        }
+
+        // Takes the description of the first conflicting lock, if there was one.
+        pub fn conflict(&mut self) -> Option<String> {
+            self.conflict.take()
         }
+    }
 
-        pub fn use_buffer(&mut self, buf: CMutSlice<u8>) {
-            let p = buf.as_ptr() as usize;
-            if !self.buffers.insert(p) {
-                panic!("attempt to lock heap with duplicate buffers (0x{:x})", p);
+    impl Drop for LockState {
+        fn drop(&mut self) {
+            if self.loans.is_empty() {
+                return;
             }
+            let ids = &self.loans;
+            LOANS.with(|loans| loans.borrow_mut().retain(|loan| !ids.contains(&loan.id)));
         }
     }
 
-    #[repr(C)]
+    fn describe_conflict(kind: &str, start: usize, len: usize, loan: &Loan) -> String {
+        let same = if loan.start == start && loan.len == len { "the same memory as" } else { "memory shared with" };
+        let site = match loan.site {
+            Some(ref site) => format!(". It was locked at {}", site),
+            None if cfg!(debug_assertions) => format!(". Enable the `backtrace` feature to see where it was locked"),
+            None => String::new()
+        };
+        format!("can't lock a {} ({} bytes at 0x{:x}) because it uses {} a {} ({} bytes at 0x{:x}) that is already locked; \
+                 memory can only be locked once at a time{}",
+                kind, len, start, same, loan.kind, loan.len, loan.start, site)
+    }
+
+    // In debug builds with the `backtrace` feature, the innermost Rust frame outside
+    // of Neon and the standard library, which is usually the caller of `grab`.
+    #[cfg(all(debug_assertions, feature = "backtrace"))]
+    fn lock_site() -> Option<String> {
+        let trace = ::backtrace::Backtrace::new();
+        for frame in trace.frames() {
+            for symbol in frame.symbols() {
+                let name = match symbol.name() {
+                    Some(name) => format!("{:#}", name),
+                    None => continue
+                };
+                if name.starts_with("backtrace::") || name.starts_with("neon::") ||
+                   name.starts_with("std::") || name.starts_with("core::") || name.starts_with("<") {
+                    continue;
+                }
+                return Some(match (symbol.filename(), symbol.lineno()) {
+                    (Some(file), Some(line)) => format!("{} ({}:{})", name, file.display(), line),
+                    _ => name
+                });
+            }
+        }
+        None
+    }
+
+    #[cfg(not(all(debug_assertions, feature = "backtrace")))]
+    fn lock_site() -> Option<String> {
+        None
+    }
+
+    #[repr(transparent)]
     #[derive(Clone, Copy)]
     pub struct Isolate(*mut raw::Isolate);
 
@@ -52,29 +158,36 @@ pub(crate) mod internal {
     }
 
     impl Isolate {
+        pub(crate) fn from_raw(ptr: *mut raw::Isolate) -> Isolate {
+            Isolate(ptr)
+        }
+
         pub(crate) fn to_raw(self) -> *mut raw::Isolate {
             let Isolate(ptr) = self;
             ptr
         }
 
+        // The class map is stored per context, so this is the map for the
+        // isolate's currently entered context. The borrow is tied to this
+        // `Isolate`, so callers should drop it before calling back into
+        // JavaScript, which may reach the same map.
         pub(crate) fn class_map(&mut self) -> &mut ClassMap {
             let mut ptr: *mut c_void = unsafe { neon_runtime::class::get_class_map(self.to_raw()) };
             if ptr.is_null() {
                 let b: Box<ClassMap> = Box::new(ClassMap::new());
-                let raw = Box::into_raw(b);
-                ptr = unsafe { mem::transmute(raw) };
-                let free_map: *mut c_void = unsafe { mem::transmute(drop_class_map as usize) };
+                ptr = Box::into_raw(b) as *mut c_void;
+                let free_map = drop_class_map as usize as *mut c_void;
                 unsafe {
                     neon_runtime::class::set_class_map(self.to_raw(), ptr, free_map);
                 }
             }
-            unsafe { mem::transmute(ptr) }
+            unsafe { &mut *(ptr as *mut ClassMap) }
         }
 
+        // Each worker thread runs its own isolate, so this is only meaningful for
+        // the thread's own isolate and must never be cached across threads.
         pub(crate) fn current() -> Isolate {
-            unsafe {
-                mem::transmute(neon_runtime::call::current_isolate())
-            }
+            Isolate::from_raw(unsafe { neon_runtime::call::current_isolate() })
         }
     }
 }
@@ -97,14 +210,42 @@ impl Error for Throw {
 pub type VmResult<T> = Result<T, Throw>;
 pub type JsResult<'b, T> = VmResult<Handle<'b, T>>;
 
+// Per-context state: class metadata, the persistent handles of interned
+// property keys, function templates keyed by their callback and kernel, and
+// the module's instance data and `module` object, cached global objects, the
+// name exported functions are prefixed with, the names they were exported under,
+// the file the module was loaded from, and the queue that frees roots dropped on
+// other threads.
 pub(crate) struct ClassMap {
-    map: HashMap<TypeId, ClassMetadata>
+    map: HashMap<TypeId, ClassMetadata>,
+    keys: HashMap<&'static str, *mut c_void>,
+    templates: HashMap<(usize, usize), *mut c_void>,
+    data: HashMap<TypeId, Box<dyn Any>>,
+    module: Option<Persistent<JsObject>>,
+    globals: HashMap<&'static str, Persistent<JsObject>>,
+    name: Option<String>,
+    function_names: HashMap<usize, String>,
+    class_names: HashMap<TypeId, String>,
+    origin: Option<String>,
+    root_queue: Option<RootQueue>,
+    gc_registry: Option<Box<GcRegistry>>
 }
 
 impl ClassMap {
     fn new() -> ClassMap {
         ClassMap {
-            map: HashMap::new()
+            map: HashMap::new(),
+            keys: HashMap::new(),
+            templates: HashMap::new(),
+            data: HashMap::new(),
+            module: None,
+            globals: HashMap::new(),
+            name: None,
+            function_names: HashMap::new(),
+            class_names: HashMap::new(),
+            origin: None,
+            root_queue: None,
+            gc_registry: None
         }
     }
 
@@ -115,14 +256,127 @@ impl ClassMap {
     pub fn set(&mut self, key: TypeId, val: ClassMetadata) {
         self.map.insert(key, val);
     }
+
+    pub fn get_key(&self, name: &'static str) -> Option<*mut c_void> {
+        self.keys.get(name).cloned()
+    }
+
+    pub fn set_key(&mut self, name: &'static str, persistent: *mut c_void) {
+        self.keys.insert(name, persistent);
+    }
+
+    // The slot holding the cached template for a function, which is null until
+    // the runtime fills it in.
+    pub fn template_slot(&mut self, callback: *mut c_void, kernel: *mut c_void) -> &mut *mut c_void {
+        self.templates.entry((callback as usize, kernel as usize)).or_insert(ptr::null_mut())
+    }
+
+    pub fn module(&self) -> Option<&Persistent<JsObject>> {
+        self.module.as_ref()
+    }
+
+    pub fn set_module(&mut self, module: Persistent<JsObject>) {
+        self.module = Some(module);
+    }
+
+    pub fn name(&self) -> Option<&str> {
+        self.name.as_ref().map(|name| name.as_str())
+    }
+
+    pub fn set_name(&mut self, name: &str) {
+        self.name = Some(name.to_string());
+    }
+
+    pub fn function_name(&self, function: usize) -> Option<&str> {
+        self.function_names.get(&function).map(|name| name.as_str())
+    }
+
+    pub fn set_function_name(&mut self, function: usize, name: String) {
+        self.function_names.entry(function).or_insert(name);
+    }
+
+    pub fn class_name(&self, class: &TypeId) -> Option<&str> {
+        self.class_names.get(class).map(|name| name.as_str())
+    }
+
+    pub fn set_class_name(&mut self, class: TypeId, name: &str) {
+        self.class_names.insert(class, name.to_string());
+    }
+
+    pub fn origin(&self) -> Option<&str> {
+        self.origin.as_ref().map(|origin| origin.as_str())
+    }
+
+    pub fn set_origin(&mut self, origin: String) {
+        self.origin = Some(origin);
+    }
+
+    pub fn root_queue(&self) -> Option<&RootQueue> {
+        self.root_queue.as_ref()
+    }
+
+    pub fn set_root_queue(&mut self, queue: RootQueue) {
+        self.root_queue = Some(queue);
+    }
+
+    pub fn gc_registry(&self) -> Option<&GcRegistry> {
+        self.gc_registry.as_ref().map(|registry| &**registry)
+    }
+
+    pub fn set_gc_registry(&mut self, registry: Box<GcRegistry>) {
+        self.gc_registry = Some(registry);
+    }
+
+    pub fn get_global(&self, name: &'static str) -> Option<&Persistent<JsObject>> {
+        self.globals.get(name)
+    }
+
+    pub fn set_global(&mut self, name: &'static str, global: Persistent<JsObject>) {
+        self.globals.insert(name, global);
+    }
+
+    // Instance data is boxed in a `RefCell` that is never removed from the map,
+    // so it lives as long as the context and nested calls can't alias it.
+    pub fn get_data<T: Any>(&self) -> Option<&RefCell<T>> {
+        self.data.get(&TypeId::of::<T>()).and_then(|data| data.downcast_ref())
+    }
+
+    pub fn set_data<T: Any>(&mut self, data: T) -> Option<T> {
+        if let Some(cell) = self.get_data::<T>() {
+            return Some(cell.replace(data));
+        }
+        self.data.insert(TypeId::of::<T>(), Box::new(RefCell::new(data)));
+        None
+    }
 }
 
-#[repr(C)]
+impl Drop for ClassMap {
+    fn drop(&mut self) {
+        for (_, persistent) in self.keys.drain() {
+            unsafe {
+                neon_runtime::mem::drop_persistent(persistent);
+            }
+        }
+        for (_, template) in self.templates.drain() {
+            if !template.is_null() {
+                unsafe {
+                    neon_runtime::fun::drop_template(template);
+                }
+            }
+        }
+    }
+}
+
+#[repr(transparent)]
 pub(crate) struct CallbackInfo {
     info: raw::FunctionCallbackInfo
 }
 
 impl CallbackInfo {
+    fn as_raw(&self) -> &raw::FunctionCallbackInfo {
+        &self.info
+    }
+
     pub fn data<'a>(&self) -> Handle<'a, JsValue> {
         unsafe {
             let mut local: raw::Local = mem::zeroed();
@@ -132,9 +386,9 @@ impl CallbackInfo {
     }
 
     pub fn scope(&self) -> RootScope {
-        RootScope::new(unsafe {
-            mem::transmute(neon_runtime::call::get_isolate(mem::transmute(self)))
-        })
+        RootScope::new(Isolate::from_raw(unsafe {
+            neon_runtime::call::get_isolate(self.as_raw())
+        }))
     }
 
     pub fn set_return<'a, 'b, T: Value>(&'a self, value: Handle<'b, T>) {
@@ -155,7 +409,7 @@ impl CallbackInfo {
     }
 
     fn kind(&self) -> CallKind {
-        if unsafe { neon_runtime::call::is_construct(mem::transmute(self)) } {
+        if unsafe { neon_runtime::call::is_construct(self.as_raw()) } {
             CallKind::Construct
         } else {
             CallKind::Call
@@ -179,6 +433,12 @@ impl CallbackInfo {
         }
     }
 
+    // Copies the first `len` arguments into `out`, which must have room for them.
+    unsafe fn get_all(&self, out: *mut raw::Local, len: usize) {
+        debug_assert!(len <= self.len() as usize);
+        neon_runtime::call::get_all(&self.info, out, len as i32);
+    }
+
     pub fn require<'b, T: Scope<'b>>(&self, _: &mut T, i: i32) -> JsResult<'b, JsValue> {
         if i < 0 || i >= self.len() {
             return JsError::throw(Kind::TypeError, "not enough arguments");
@@ -193,7 +453,7 @@ impl CallbackInfo {
     pub fn this<'b, T: Scope<'b>>(&self, _: &mut T) -> raw::Local {
         unsafe {
             let mut local: raw::Local = mem::zeroed();
-            neon_runtime::call::this(mem::transmute(&self.info), &mut local);
+            neon_runtime::call::this(self.as_raw(), &mut local);
             local
         }
     }
@@ -201,7 +461,7 @@ impl CallbackInfo {
     pub fn callee<'a, T: Scope<'a>>(&self, _: &mut T) -> Handle<'a, JsFunction> {
         unsafe {
             let mut local: raw::Local = mem::zeroed();
-            neon_runtime::call::callee(mem::transmute(&self.info), &mut local);
+            neon_runtime::call::callee(self.as_raw(), &mut local);
             Handle::new_internal(JsFunction::from_raw(local))
         }
     }
@@ -213,29 +473,343 @@ pub struct Module<'a> {
 }
 
 impl<'a> Module<'a> {
+    #[doc(hidden)]
+    pub fn initialize_module(exports: raw::Local, module: raw::Local, init: fn(Module) -> VmResult<()>) {
+        let exports: Handle<JsObject> = Handle::new_internal(JsObject::from_raw(exports));
+        let module: Handle<JsObject> = Handle::new_internal(JsObject::from_raw(module));
+        let mut scope = RootScope::new(Isolate::from_raw(unsafe { neon_runtime::object::get_isolate(exports.to_raw()) }));
+        if let Ok(filename) = module.get(&mut scope, "filename") {
+            if let Some(filename) = filename.downcast::<JsString>() {
+                scope.isolate().class_map().set_origin(filename.value());
+            }
+        }
+        let module = Persistent::new(&mut scope, module);
+        scope.isolate().class_map().set_module(module);
+        Module::initialize(exports, init);
+    }
+
     pub fn initialize(exports: Handle<JsObject>, init: fn(Module) -> VmResult<()>) {
-        let mut scope = RootScope::new(unsafe { mem::transmute(neon_runtime::object::get_isolate(exports.to_raw())) });
+        let mut scope = RootScope::new(Isolate::from_raw(unsafe { neon_runtime::object::get_isolate(exports.to_raw()) }));
+        // The kernel is passed by reference, since a function pointer can't be cast to a
+        // data pointer. Both it and the scope outlive the synchronous call.
+        let kernel = &init as *const fn(Module) -> VmResult<()> as *mut c_void;
+        let scope = &mut scope as *mut RootScope as *mut c_void;
         unsafe {
-            let kernel: *mut c_void = mem::transmute(init);
-            let callback: extern "C" fn(*mut c_void, *mut c_void, *mut c_void) = mem::transmute(module_callback as usize);
-            let exports: raw::Local = exports.to_raw();
-            let scope: *mut c_void = mem::transmute(&mut scope);
-            neon_runtime::module::exec_kernel(kernel, callback, exports, scope);
+            neon_runtime::module::exec_kernel(kernel, module_callback, exports.to_raw(), scope);
         }
     }
 }
 
 impl<'a> Module<'a> {
     pub fn export<T: Value>(&mut self, key: &str, f: fn(Call) -> JsResult<T>) -> VmResult<()> {
-        let value = JsFunction::new(self.scope, f)?.upcast::<JsValue>();
-        self.exports.set(key, value)?;
+        export_function(self.scope, self.exports, "", key, f)
+    }
+
+    /// Exports a function created with `JsFunction::new_fast`.
+    pub fn export_fast<T: Value>(&mut self, key: &str, f: fn(Call) -> JsResult<T>) -> VmResult<()> {
+        export_function_fast(self.scope, self.exports, "", key, f)
+    }
+
+    /// Exports a function that takes `arity` arguments. Its `length` is `arity`, and
+    /// calling it with fewer arguments throws a `TypeError` without calling `f`, so
+    /// `f` can `require` its first `arity` arguments without checking for them.
+    pub fn export_with_arity<T: Value + 'static>(&mut self, key: &str, arity: u32, f: fn(Call) -> JsResult<T>) -> VmResult<()> {
+        export_function_with_arity(self.scope, self.exports, "", key, arity, f)
+    }
+
+    /// Names the addon. Exported functions are named after their keys, qualified by
+    /// any namespace they're in, and once the addon has a name, functions exported
+    /// afterwards are prefixed with it too. A function exported as `parse` by an addon
+    /// named `myaddon` shows up as `myaddon.parse` in stack traces and CPU profiles.
+    /// A function exported under more than one key keeps the first name.
+    pub fn set_name(&mut self, name: &str) {
+        self.scope.isolate().class_map().set_name(name);
+    }
+
+    /// Loads a JavaScript module relative to the addon, as with `Scope::require`.
+    pub fn require(&mut self, path: &str) -> JsResult<'a, JsValue> {
+        self.scope.require(path)
+    }
+
+    /// Exports a getter that runs `init` the first time `key` is read, replacing
+    /// itself with the result. This defers building expensive exports that many
+    /// users of the module never touch.
+    pub fn export_lazy<T: Value>(&mut self, key: &str, init: fn(Call) -> JsResult<T>) -> VmResult<()> {
+        export_lazy(self.scope, self.exports, key, init)
+    }
+
+    /// Marks the exports with a non-enumerable `__esModule` property, the convention
+    /// for CommonJS modules that stand in for ES modules. When building an addon
+    /// marked this way, `neon build` also writes an `index.mjs` wrapper declaring each
+    /// export other than lazy ones as a named export, so
+    /// `import { foo } from './native/index.mjs'` works.
+    pub fn es_module(&mut self) -> VmResult<()> {
+        let descriptor = JsObject::new(self.scope);
+        descriptor.set("value", JsBoolean::new(self.scope, true))?;
+        let key = JsString::new_or_throw(self.scope, "__esModule")?;
+        define_property(self.scope, self.exports, key.upcast(), descriptor)
+    }
+
+    /// Exports a frozen object mirroring an enum, mapping each variant's name to its
+    /// value and each value back to its name, e.g. `Color.Red === 0` and
+    /// `Color[0] === "Red"`.
+    pub fn export_enum(&mut self, key: &str, variants: &[(&str, i32)]) -> VmResult<()> {
+        export_enum(self.scope, self.exports, key, variants)
+    }
+
+    /// Stores a value as the module's instance data of type `T`, replacing and
+    /// returning any previous value of that type. Instance data lives as long as
+    /// the module's context, and each context that loads the module, such as
+    /// a worker thread's, has its own.
+    pub fn set_instance_data<T: Any>(&mut self, data: T) -> Option<T> {
+        self.scope.isolate().class_map().set_data(data)
+    }
+
+    /// The module's instance data of type `T`, if any has been set.
+    pub fn instance_data<T: Any>(&mut self) -> Option<RefMut<T>> {
+        instance_data(self.scope.isolate())
+    }
+
+    /// Registers a Rust test named `name`, to be run inside Node by the `neon-test`
+    /// harness. The test is called with no arguments, so it gets a real `Call` to
+    /// work with, and fails if it throws or panics.
+    ///
+    /// Tests are kept in a hidden `__neon_tests` export, so an addon that ships
+    /// without them should only register them in test builds, e.g. behind a feature.
+    pub fn test(&mut self, name: &str, f: fn(Call) -> VmResult<()>) -> VmResult<()> {
+        let function = JsFunction::new_closure(self.scope, move |call| {
+            f(call)?;
+            Ok(JsUndefined::new())
+        })?;
+        register_test(self.scope, self.exports, name, function)
+    }
+
+    /// Registers a test like `test`, which runs a module initialization function
+    /// against a fresh exports object. This tests the code that sets up a module
+    /// with a real `Module`.
+    pub fn test_module(&mut self, name: &str, init: fn(Module) -> VmResult<()>) -> VmResult<()> {
+        let function = JsFunction::new_closure(self.scope, move |call| {
+            let scope = call.scope;
+            let exports = JsObject::new(scope);
+            init(Module { exports: exports, scope: scope })?;
+            Ok(JsUndefined::new())
+        })?;
+        register_test(self.scope, self.exports, name, function)
+    }
+
+    /// Finds or creates the nested object at a dot-separated `path` of the exports,
+    /// such as `"foo.bar"`, and returns a namespace for exporting values into it.
+    pub fn namespace<'b>(&'b mut self, path: &str) -> VmResult<Namespace<'b, 'a>> {
+        let exports = find_namespace(self.scope, self.exports, path)?;
+        Ok(Namespace {
+            exports: exports,
+            scope: self.scope,
+            path: path.to_string()
+        })
+    }
+
+    /// Runs another module initialization function, such as one from a dependency,
+    /// against this module's exports. This lets a large addon split its setup into
+    /// several functions that are run in order.
+    pub fn include(&mut self, init: fn(Module) -> VmResult<()>) -> VmResult<()> {
+        let mut scope = RootScope::new(self.scope.isolate());
+        init(Module {
+            exports: self.exports,
+            scope: &mut scope
+        })
+    }
+
+    /// Runs a module initialization function against a new object, and exports that
+    /// object as `key`.
+    pub fn export_module(&mut self, key: &str, init: fn(Module) -> VmResult<()>) -> VmResult<()> {
+        let exports = JsObject::new(self.scope);
+        let mut scope = RootScope::new(self.scope.isolate());
+        init(Module {
+            exports: exports,
+            scope: &mut scope
+        })?;
+        self.exports.set(key, exports)?;
         Ok(())
     }
 }
 
-extern "C" fn module_callback<'a>(kernel: fn(Module) -> VmResult<()>, exports: Handle<'a, JsObject>, scope: &'a mut RootScope<'a>) {
-    let _ = kernel(Module {
-        exports: exports,
+/// A nested object of a module's exports, created with `Module::namespace`.
+pub struct Namespace<'b, 'a: 'b> {
+    pub exports: Handle<'a, JsObject>,
+    pub scope: &'b mut RootScope<'a>,
+    path: String
+}
+
+impl<'b, 'a> Namespace<'b, 'a> {
+    pub fn export<T: Value>(&mut self, key: &str, f: fn(Call) -> JsResult<T>) -> VmResult<()> {
+        export_function(self.scope, self.exports, &self.path, key, f)
+    }
+
+    /// Exports a function created with `JsFunction::new_fast`.
+    pub fn export_fast<T: Value>(&mut self, key: &str, f: fn(Call) -> JsResult<T>) -> VmResult<()> {
+        export_function_fast(self.scope, self.exports, &self.path, key, f)
+    }
+
+    /// Exports a function that checks its number of arguments, as with
+    /// `Module::export_with_arity`.
+    pub fn export_with_arity<T: Value + 'static>(&mut self, key: &str, arity: u32, f: fn(Call) -> JsResult<T>) -> VmResult<()> {
+        export_function_with_arity(self.scope, self.exports, &self.path, key, arity, f)
+    }
+
+    /// Exports a lazily initialized value, as with `Module::export_lazy`.
+    pub fn export_lazy<T: Value>(&mut self, key: &str, init: fn(Call) -> JsResult<T>) -> VmResult<()> {
+        export_lazy(self.scope, self.exports, key, init)
+    }
+
+    /// Exports a frozen enum object, as with `Module::export_enum`.
+    pub fn export_enum(&mut self, key: &str, variants: &[(&str, i32)]) -> VmResult<()> {
+        export_enum(self.scope, self.exports, key, variants)
+    }
+
+    /// Finds or creates a namespace nested within this one.
+    pub fn namespace<'c>(&'c mut self, path: &str) -> VmResult<Namespace<'c, 'a>> {
+        let exports = find_namespace(self.scope, self.exports, path)?;
+        Ok(Namespace {
+            exports: exports,
+            scope: self.scope,
+            path: format!("{}.{}", self.path, path)
+        })
+    }
+}
+
+// The cell outlives the borrow of the class map it's found through, since instance
+// data is never removed from a context, so the caller picks its lifetime.
+fn instance_data<'a, T: Any>(mut isolate: Isolate) -> Option<RefMut<'a, T>> {
+    let cell = isolate.class_map().get_data::<T>().map(|cell| cell as *const RefCell<T>);
+    cell.map(|cell| unsafe { &*cell }.borrow_mut())
+}
+
+fn export_function<'a, T: Value>(scope: &mut RootScope<'a>, exports: Handle<'a, JsObject>, path: &str, key: &str, f: fn(Call) -> JsResult<T>) -> VmResult<()> {
+    let function = JsFunction::new_cached(scope, f)?;
+    name_function(scope, function, f as usize, path, key)?;
+    exports.set(key, function)?;
+    Ok(())
+}
+
+fn export_function_fast<'a, T: Value>(scope: &mut RootScope<'a>, exports: Handle<'a, JsObject>, path: &str, key: &str, f: fn(Call) -> JsResult<T>) -> VmResult<()> {
+    let function = JsFunction::new_fast(scope, f)?;
+    name_function(scope, function, f as usize, path, key)?;
+    exports.set(key, function)?;
+    Ok(())
+}
+
+fn export_function_with_arity<'a, T: Value + 'static>(scope: &mut RootScope<'a>, exports: Handle<'a, JsObject>, path: &str, key: &str, arity: u32, f: fn(Call) -> JsResult<T>) -> VmResult<()> {
+    let name = key.to_string();
+    let function = JsFunction::new_closure(scope, move |call| {
+        let given = call.arguments.len() as u32;
+        if given < arity {
+            let msg = format!("{} expects at least {} argument{}, but got {}",
+                              name, arity, if arity == 1 { "" } else { "s" }, given);
+            return JsError::throw(Kind::TypeError, &msg);
+        }
+        with_native_frame(f as usize, || convert_panics(|| f(call)))
+    })?;
+    let descriptor = JsObject::new(scope);
+    descriptor.set("value", JsNumber::new(scope, arity as f64))?;
+    descriptor.set("configurable", JsBoolean::new(scope, true))?;
+    let length = JsString::new_or_throw(scope, "length")?;
+    define_property(scope, function.upcast(), length.upcast(), descriptor)?;
+    name_function(scope, function, f as usize, path, key)?;
+    exports.set(key, function)?;
+    Ok(())
+}
+
+// Names an exported function after its key, qualified by its namespace's path and
+// the addon's name, if it has one. Exporting the same Rust function twice exports
+// the same cached JavaScript function, which keeps the name it was first given.
+// The name is also recorded for the Rust function, at address `f`, so errors it
+// creates can name it in their stacks.
+fn name_function<'a>(scope: &mut RootScope<'a>, function: Handle<'a, JsFunction>, f: usize, path: &str, key: &str) -> VmResult<()> {
+    if !function.name(scope).is_empty() {
+        return Ok(());
+    }
+    let mut name = String::new();
+    for part in scope.isolate().class_map().name().into_iter().chain(Some(path)).chain(Some(key)) {
+        if !part.is_empty() {
+            if !name.is_empty() {
+                name.push('.');
+            }
+            name.push_str(part);
+        }
+    }
+    scope.isolate().class_map().set_function_name(f, name.clone());
+    function.set_name(scope, &name)
+}
+
+fn export_lazy<'a, T: Value>(scope: &mut RootScope<'a>, exports: Handle<'a, JsObject>, key: &str, init: fn(Call) -> JsResult<T>) -> VmResult<()> {
+    let getter = lazy_getter(scope, key, init)?;
+    let descriptor = JsObject::new(scope);
+    descriptor.set("get", getter)?;
+    descriptor.set("enumerable", JsBoolean::new(scope, true))?;
+    descriptor.set("configurable", JsBoolean::new(scope, true))?;
+    let key = JsString::new_or_throw(scope, key)?;
+    define_property(scope, exports, key.upcast(), descriptor)
+}
+
+// Adds a test to the module's hidden `__neon_tests` object, creating it if needed.
+fn register_test<'a>(scope: &mut RootScope<'a>, exports: Handle<'a, JsObject>, name: &str, test: Handle<'a, JsFunction>) -> VmResult<()> {
+    let tests = match exports.get(scope, "__neon_tests")?.downcast::<JsObject>() {
+        Some(tests) => tests,
+        None => {
+            let tests = JsObject::new(scope);
+            let descriptor = JsObject::new(scope);
+            descriptor.set("value", tests)?;
+            let key = JsString::new_or_throw(scope, "__neon_tests")?;
+            define_property(scope, exports, key.upcast(), descriptor)?;
+            tests
+        }
+    };
+    test.set_name(scope, name)?;
+    tests.set(name, test)?;
+    Ok(())
+}
+
+fn export_enum<'a>(scope: &mut RootScope<'a>, exports: Handle<'a, JsObject>, key: &str, variants: &[(&str, i32)]) -> VmResult<()> {
+    let object = JsObject::new(scope);
+    for &(name, value) in variants {
+        let js_name = JsString::new_or_throw(scope, name)?;
+        object.set(name, JsNumber::new(scope, value as f64))?;
+        object.set(value.to_string().as_str(), js_name)?;
+    }
+    freeze(scope, object)?;
+    exports.set(key, object)?;
+    Ok(())
+}
+
+fn find_namespace<'a>(scope: &mut RootScope<'a>, exports: Handle<'a, JsObject>, path: &str) -> JsResult<'a, JsObject> {
+    let mut object = exports;
+    for name in path.split('.') {
+        if name.is_empty() {
+            return JsError::throw(Kind::TypeError, &format!("invalid namespace path: {}", path));
+        }
+        let value = object.get(scope, name)?;
+        object = if value.is_a::<JsUndefined>() {
+            let child = JsObject::new(scope);
+            object.set(name, child)?;
+            child
+        } else {
+            match value.downcast::<JsObject>() {
+                Some(child) => child,
+                None => {
+                    return JsError::throw(Kind::TypeError, &format!("namespace {} is not an object", name));
+                }
+            }
+        };
+    }
+    Ok(object)
+}
+
+extern "C" fn module_callback(kernel: *mut c_void, exports: raw::Local, scope: *mut c_void) {
+    let (init, scope) = unsafe {
+        (*(kernel as *const fn(Module) -> VmResult<()>), &mut *(scope as *mut RootScope))
+    };
+    let _ = init(Module {
+        exports: Handle::new_internal(JsObject::from_raw(exports)),
         scope: scope
     });
 }
@@ -263,8 +837,250 @@ pub enum CallKind {
 
 impl<'a, T: This> FunctionCall<'a, T> {
     pub fn kind(&self) -> CallKind { self.info.kind() }
+
+    /// The module's instance data of type `T`, if any has been set with
+    /// `Module::set_instance_data`. Panics if the data is already borrowed by an
+    /// enclosing call.
+    pub fn instance_data<U: Any>(&mut self) -> Option<RefMut<U>> {
+        instance_data(self.scope.isolate())
+    }
+
+    /// Reads the `i`th argument as a Rust value, throwing a `TypeError` if it can't be
+    /// converted. Missing arguments are read as `undefined`, so optional arguments can
+    /// be read as an `Option`.
+    pub fn argument<U: TryFromJs>(&mut self, i: i32) -> VmResult<U> {
+        let value = self.arguments.get(self.scope, i).unwrap_or_else(|| JsUndefined::new().upcast());
+        match U::from_js(self.scope, value)? {
+            Some(result) => Ok(result),
+            None => JsError::throw(Kind::TypeError, &format!("argument {}: expected {}", i, U::describe()))
+        }
+    }
+
+    /// Reads the `i`th argument as a `U`, or `None` if it's missing or `undefined`, so
+    /// that leaving off an optional trailing argument and passing `undefined` for it
+    /// mean the same thing. Throws a `TypeError` if it's anything else that isn't a `U`.
+    pub fn argument_opt<U: Value>(&mut self, i: i32) -> VmResult<Option<Handle<'a, U>>> {
+        match self.arguments.get(self.scope, i) {
+            Some(value) => {
+                if value.is_a::<JsUndefined>() {
+                    Ok(None)
+                } else {
+                    value.check::<U>().map(Some)
+                }
+            }
+            None => Ok(None)
+        }
+    }
+
+    /// Reads the `i`th argument as a `U`, or `default` if it's missing or `undefined`.
+    /// Throws a `TypeError` if it's anything else that isn't a `U`.
+    pub fn argument_or<U: Value>(&mut self, i: i32, default: Handle<'a, U>) -> JsResult<'a, U> {
+        Ok(self.argument_opt(i)?.unwrap_or(default))
+    }
+
+    /// Reads the `i`th argument as an `i64`, accepting either a number that is a safe
+    /// integer or a `BigInt`. Throws a `RangeError` if the value is out of range, and a
+    /// `TypeError` if it's neither a number nor a `BigInt`.
+    pub fn argument_i64(&mut self, i: i32) -> VmResult<i64> {
+        const MAX_SAFE_INTEGER: f64 = 9007199254740991.0;
+        let value = self.arguments.require(self.scope, i)?;
+        if let Some(n) = value.downcast::<JsNumber>() {
+            let n = n.value();
+            if n.fract() == 0.0 && n.abs() <= MAX_SAFE_INTEGER {
+                return Ok(n as i64);
+            }
+            return JsError::throw(Kind::RangeError, &format!("argument {}: expected a safe integer or a BigInt", i));
+        }
+        if unsafe { neon_runtime::tag::is_bigint(value.to_raw()) } {
+            let mut result = 0;
+            if unsafe { neon_runtime::bigint::to_i64(&mut result, value.to_raw()) } {
+                return Ok(result);
+            }
+            return JsError::throw(Kind::RangeError, &format!("argument {}: BigInt is out of range for a 64-bit signed integer", i));
+        }
+        JsError::throw(Kind::TypeError, &format!("argument {}: expected a number or a BigInt", i))
+    }
+
+    /// Reads the `i`th argument as a `u64`, accepting either a number that is a
+    /// non-negative safe integer or a `BigInt`. Throws a `RangeError` if the value is out
+    /// of range, and a `TypeError` if it's neither a number nor a `BigInt`.
+    pub fn argument_u64(&mut self, i: i32) -> VmResult<u64> {
+        const MAX_SAFE_INTEGER: f64 = 9007199254740991.0;
+        let value = self.arguments.require(self.scope, i)?;
+        if let Some(n) = value.downcast::<JsNumber>() {
+            let n = n.value();
+            if n.fract() == 0.0 && n >= 0.0 && n <= MAX_SAFE_INTEGER {
+                return Ok(n as u64);
+            }
+            return JsError::throw(Kind::RangeError, &format!("argument {}: expected a non-negative safe integer or a BigInt", i));
+        }
+        if unsafe { neon_runtime::tag::is_bigint(value.to_raw()) } {
+            let mut result = 0;
+            if unsafe { neon_runtime::bigint::to_u64(&mut result, value.to_raw()) } {
+                return Ok(result);
+            }
+            return JsError::throw(Kind::RangeError, &format!("argument {}: BigInt is out of range for a 64-bit unsigned integer", i));
+        }
+        JsError::throw(Kind::TypeError, &format!("argument {}: expected a number or a BigInt", i))
+    }
+
+    /// Reads all of the arguments at once, without allocating for calls with up to
+    /// eight arguments. The list can be iterated by reference or by value, so a
+    /// variadic function can write `for arg in call.argument_list() { ... }`.
+    pub fn argument_list(&self) -> ArgumentList<'a> {
+        let len = self.info.len() as usize;
+        let mut list = ArgumentList {
+            inline: [raw::Local { handle: ptr::null_mut() }; INLINE_ARGUMENTS],
+            spilled: Vec::new(),
+            len: len,
+            #[cfg(feature = "debug-handles")]
+            handles: Vec::new(),
+            phantom: PhantomData
+        };
+        unsafe {
+            if len <= INLINE_ARGUMENTS {
+                self.info.get_all(list.inline.as_mut_ptr(), len);
+            } else {
+                list.spilled.reserve_exact(len);
+                self.info.get_all(list.spilled.as_mut_ptr(), len);
+                list.spilled.set_len(len);
+            }
+        }
+        #[cfg(feature = "debug-handles")]
+        {
+            let handles = {
+                let locals = if len <= INLINE_ARGUMENTS { &list.inline[..len] } else { &list.spilled[..] };
+                locals.iter().map(|&local| Handle::new_internal(JsValue::from_raw(local))).collect()
+            };
+            list.handles = handles;
+        }
+        list
+    }
+
+    /// Reads all of the arguments into a tuple of Rust values, e.g.
+    /// `call.args::<(f64, String, Option<bool>)>()?`, throwing a `TypeError` naming
+    /// the first argument that can't be converted.
+    pub fn args<A: FromArguments>(&mut self) -> VmResult<A> {
+        A::from_arguments(self)
+    }
+
+    /// Reads the `i`th argument as an options object, e.g.
+    /// `call.options::<WatchOptions>(1)?`. A missing or `undefined` argument gives
+    /// `U::default()`. Throws a `TypeError` if the argument isn't an object, if it has
+    /// a property that isn't one of `U::keys()`, or if a property has the wrong type.
+    pub fn options<U: FromOptions>(&mut self, i: i32) -> VmResult<U> {
+        let mut options = U::default();
+        let value = match self.arguments.get(self.scope, i) {
+            Some(value) if !value.is_a::<JsUndefined>() => value,
+            _ => return Ok(options)
+        };
+        let object = match convert::plain_object(value) {
+            Some(object) => object,
+            None => return JsError::throw(Kind::TypeError, &format!("argument {}: expected an options object", i))
+        };
+        for key in object.get_own_property_names(self.scope)?.to_vec(self.scope)? {
+            let key = key.to_string(self.scope)?.value();
+            if !U::keys().contains(&key.as_str()) {
+                return JsError::throw(Kind::TypeError, &format!("argument {}: unknown option {:?}", i, key));
+            }
+        }
+        options.read_options(self.scope, object)?;
+        Ok(options)
+    }
 }
 
+const INLINE_ARGUMENTS: usize = 8;
+
+/// All of the arguments of a function call, read with `FunctionCall::argument_list`.
+/// Dereferences to a slice of handles.
+pub struct ArgumentList<'a> {
+    inline: [raw::Local; INLINE_ARGUMENTS],
+    spilled: Vec<raw::Local>,
+    len: usize,
+    #[cfg(feature = "debug-handles")]
+    handles: Vec<Handle<'a, JsValue>>,
+    phantom: PhantomData<Handle<'a, JsValue>>
+}
+
+impl<'a> Deref for ArgumentList<'a> {
+    type Target = [Handle<'a, JsValue>];
+
+    #[cfg(feature = "debug-handles")]
+    fn deref(&self) -> &[Handle<'a, JsValue>] {
+        &self.handles[..]
+    }
+
+    #[cfg(not(feature = "debug-handles"))]
+    fn deref(&self) -> &[Handle<'a, JsValue>] {
+        let locals = if self.len <= INLINE_ARGUMENTS {
+            &self.inline[..self.len]
+        } else {
+            &self.spilled[..]
+        };
+        // A `Handle<JsValue>` has the same representation as the `raw::Local` it wraps.
+        unsafe { slice::from_raw_parts(locals.as_ptr() as *const Handle<'a, JsValue>, locals.len()) }
+    }
+}
+
+impl<'a, 'b> IntoIterator for &'b ArgumentList<'a> {
+    type Item = &'b Handle<'a, JsValue>;
+    type IntoIter = slice::Iter<'b, Handle<'a, JsValue>>;
+
+    fn into_iter(self) -> slice::Iter<'b, Handle<'a, JsValue>> {
+        self.iter()
+    }
+}
+
+impl<'a> IntoIterator for ArgumentList<'a> {
+    type Item = Handle<'a, JsValue>;
+    type IntoIter = ArgumentIter<'a>;
+
+    fn into_iter(self) -> ArgumentIter<'a> {
+        ArgumentIter {
+            list: self,
+            front: 0,
+            back: 0
+        }
+    }
+}
+
+/// An iterator over the handles of an `ArgumentList`, taken by value.
+pub struct ArgumentIter<'a> {
+    list: ArgumentList<'a>,
+    front: usize,
+    back: usize
+}
+
+impl<'a> Iterator for ArgumentIter<'a> {
+    type Item = Handle<'a, JsValue>;
+
+    fn next(&mut self) -> Option<Handle<'a, JsValue>> {
+        if self.front + self.back >= self.list.len {
+            return None;
+        }
+        let arg = self.list[self.front];
+        self.front += 1;
+        Some(arg)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let remaining = self.list.len - self.front - self.back;
+        (remaining, Some(remaining))
+    }
+}
+
+impl<'a> DoubleEndedIterator for ArgumentIter<'a> {
+    fn next_back(&mut self) -> Option<Handle<'a, JsValue>> {
+        if self.front + self.back >= self.list.len {
+            return None;
+        }
+        self.back += 1;
+        Some(self.list[self.list.len - self.back])
+    }
+}
+
+impl<'a> ExactSizeIterator for ArgumentIter<'a> { }
+
 #[repr(C)]
 pub struct Arguments<'a, T> {
     info: &'a CallbackInfo,
@@ -312,23 +1128,40 @@ pub(crate) trait Kernel<T: Clone + Copy + Sized>: Sized {
     /// Exports the kernel as a pair consisting of the static callback function
     /// and the kernel function, both converted to raw void pointers.
     fn export(self) -> (*mut c_void, *mut c_void) {
-        unsafe {
-            (mem::transmute(Self::callback as usize), self.as_ptr())
-        }
+        (Self::callback as usize as *mut c_void, self.as_ptr())
     }
 }
 
 pub trait Lock: Sized {
     type Internals;
 
+    /// Locks the value's contents for the duration of `f`. Panics if they overlap
+    /// memory that is already locked, such as a buffer locked twice or a typed array
+    /// and the `ArrayBuffer` behind it, since that would alias mutable slices.
     fn grab<F, T>(self, f: F) -> T
         where F: FnOnce(Self::Internals) -> T + Send
     {
         let mut state = LockState::new();
         let internals = unsafe { self.expose(&mut state) };
+        if let Some(conflict) = state.conflict() {
+            panic!("{}", conflict);
+        }
         f(internals)
     }
 
+    /// Locks the value's contents like `grab`, but throws an `Error` describing the
+    /// conflict instead of panicking if they overlap memory that is already locked.
+    fn try_grab<F, T>(self, f: F) -> VmResult<T>
+        where F: FnOnce(Self::Internals) -> T + Send
+    {
+        let mut state = LockState::new();
+        let internals = unsafe { self.expose(&mut state) };
+        match state.conflict() {
+            Some(conflict) => JsError::throw(Kind::Error, &conflict),
+            None => Ok(f(internals))
+        }
+    }
+
     unsafe fn expose(self, state: &mut LockState) -> Self::Internals;
 }
 
@@ -13,4 +13,68 @@ describe('JsArray', function() {
   it('return a JsArray with an string at index 0', function () {
     assert.deepEqual(["hello node"], addon.return_js_array_with_string());
   });
+
+  it('collect a Rust iterator into a JsArray', function () {
+    assert.deepEqual([0, 1, 4, 9], addon.return_js_array_of_squares(4));
+    assert.deepEqual([], addon.return_js_array_of_squares(0));
+  });
+
+  it('read a JsArray into a Vec of Rust values', function () {
+    assert.equal('a-b-c', addon.join_js_array_of_strings(['a', 'b', 'c']));
+    assert.throws(function() { addon.join_js_array_of_strings(['a', 2]); }, TypeError, /element 1: expected a string/);
+  });
+
+  it('consume any JS iterable from Rust', function () {
+    assert.equal(6, addon.sum_js_iterable([1, 2, 3]));
+    assert.equal(6, addon.sum_js_iterable(new Set([1, 2, 3])));
+    function* numbers() { yield 10; yield 20; }
+    assert.equal(30, addon.sum_js_iterable(numbers()));
+    assert.throws(function() { addon.sum_js_iterable({}); }, TypeError, /not iterable/);
+  });
+
+  it('treat a done of 0n as false', function () {
+    if (typeof BigInt === 'undefined') {
+      this.skip();
+    }
+    var iterable = {};
+    iterable[Symbol.iterator] = function() {
+      var values = [1, 2];
+      return {
+        next: function() {
+          return values.length ? { value: values.shift(), done: BigInt(0) } : { done: BigInt(1) };
+        }
+      };
+    };
+    assert.equal(3, addon.sum_js_iterable(iterable));
+  });
+
+  it('propagate exceptions thrown while iterating', function () {
+    function* failing() { yield 1; throw new Error('iteration failed'); }
+    assert.throws(function() { addon.sum_js_iterable(failing()); }, /iteration failed/);
+  });
+
+  it('close a JS iterator early', function () {
+    var closed = false;
+    function* numbers() {
+      try { yield 1; yield 2; } finally { closed = true; }
+    }
+    assert.equal(1, addon.first_of_js_iterable(numbers()));
+    assert(closed);
+  });
+
+  it('build a JsArray from a slice of handles', function () {
+    assert.deepEqual([1, 'two', undefined], addon.return_js_array_from_slice());
+  });
+
+  it('build a JsArray from a slice of numbers', function () {
+    assert.deepEqual([0, 0.5, 1, 1.5], addon.return_js_array_from_f64_slice(4));
+    var large = addon.return_js_array_from_f64_slice(100000);
+    assert.equal(100000, large.length);
+    assert.equal(49999.5, large[99999]);
+  });
+
+  it('build a JsArray by pushing to a builder', function () {
+    assert.deepEqual([1, 3, undefined], addon.return_js_array_from_builder(['a', '', 'abc']));
+    assert.deepEqual([undefined], addon.return_js_array_from_builder([]));
+  });
 });
@@ -0,0 +1,165 @@
+var addon = require('../native');
+var assert = require('chai').assert;
+var v8 = require('v8');
+var vm = require('vm');
+
+v8.setFlagsFromString('--expose_gc');
+var gc = vm.runInNewContext('gc');
+
+describe('JsBuffer', function() {
+  it('return a zero-filled JsBuffer built in Rust', function () {
+    var b = addon.return_js_buffer();
+    assert(Buffer.isBuffer(b));
+    assert.equal(b.length, 16);
+    for (var i = 0; i < b.length; i++) {
+      assert.equal(b[i], 0);
+    }
+  });
+
+  it('return a JsBuffer copied from a Rust slice', function () {
+    var b = addon.return_js_buffer_from_slice();
+    assert(Buffer.isBuffer(b));
+    assert.equal(b.toString(), "hello");
+  });
+
+  it('locks separate buffers together', function () {
+    assert.equal(addon.lock_buffer_and_array_buffer(Buffer.alloc(4), new ArrayBuffer(8)), 12);
+    assert.equal(addon.lock_buffer_and_array_buffer(Buffer.alloc(0), new ArrayBuffer(0)), 0);
+  });
+
+  it('throws when locking a buffer and the ArrayBuffer behind it', function () {
+    var ab = new ArrayBuffer(8);
+    assert.throws(function() { addon.lock_buffer_and_array_buffer(Buffer.from(ab), ab); }, Error,
+                  /^can't lock a JsArrayBuffer \(8 bytes at 0x[0-9a-f]+\) because it uses the same memory as a JsBuffer \(8 bytes at 0x[0-9a-f]+\) that is already locked/);
+    assert.throws(function() { addon.lock_buffer_and_array_buffer(Buffer.from(ab, 2, 4), ab); }, Error,
+                  /because it uses memory shared with a JsBuffer \(4 bytes/);
+    // The locks are released, so the buffer can be locked again.
+    assert.equal(addon.check_js_buffer_length(Buffer.from(ab)), 8);
+  });
+
+  it('panics when a buffer is locked twice in one grab', function () {
+    assert.throws(function() { addon.lock_buffer_twice(Buffer.alloc(4)); }, Error,
+                  /^internal error in native module: can't lock a JsBuffer .* that is already locked; memory can only be locked once at a time/);
+  });
+
+  it('name the expected and actual types when a value is not a JsBuffer', function () {
+    assert.equal(addon.check_js_buffer_length(Buffer.from('abc')), 3);
+    assert.throws(function() { addon.check_js_buffer_length(new ArrayBuffer(4)); }, TypeError, /^expected JsBuffer, got ArrayBuffer$/);
+    assert.throws(function() { addon.check_js_buffer_length('abc'); }, TypeError, /^expected JsBuffer, got string$/);
+    assert.throws(function() { addon.check_js_buffer_length(null); }, TypeError, /^expected JsBuffer, got null$/);
+  });
+
+  it('return a JsBuffer that owns a Rust vector', function () {
+    var b = addon.return_external_js_buffer();
+    assert(Buffer.isBuffer(b));
+    assert.deepEqual(Array.from(b), [0, 1, 2, 3, 4, 5, 6, 7, 8, 9]);
+  });
+
+  it('borrows an ArrayBuffer as a typed slice', function () {
+    var a = new Uint32Array([1, 2, 3, 4]);
+    assert.equal(addon.sum_u32_array_buffer(a.buffer), 10);
+  });
+
+  it('throws when a typed slice has the wrong length', function () {
+    assert.throws(function() { addon.sum_u32_array_buffer(new ArrayBuffer(6)) }, Error, /is not a multiple of 4-byte elements/);
+  });
+
+  it('mutably borrows a Buffer as a byte slice', function () {
+    var b = Buffer.from([1, 2, 255]);
+    addon.increment_buffer_bytes(b);
+    assert.deepEqual(Array.from(b), [2, 3, 0]);
+  });
+
+  it('reads and writes values at byte offsets', function () {
+    var b = Buffer.alloc(16, 0xff);
+    assert.equal(addon.write_buffer_header(b), 0x04030201);
+    assert.equal(b.readUInt32BE(0), 0x01020304);
+    assert.equal(b.readDoubleLE(4), 1.5);
+    assert.equal(b.readUInt32BE(12), 0x01020304);
+  });
+
+  it('throws when reading or writing out of bounds', function () {
+    assert.throws(function() { addon.write_buffer_header(Buffer.alloc(8)) }, Error, /out of bounds/);
+  });
+
+  it('detaches an ArrayBuffer and refuses to lend it afterwards', function () {
+    if (Number(process.versions.modules) < 57) {
+      this.skip();
+    }
+    var a = new Uint32Array([1, 2, 3]);
+    assert.isTrue(addon.detach_array_buffer(a.buffer));
+    assert.equal(a.buffer.byteLength, 0);
+    assert.equal(a.length, 0);
+    assert.throws(function() { addon.sum_u32_array_buffer(a.buffer) }, Error, /detached ArrayBuffer/);
+  });
+
+  it('views an ArrayBuffer region as a Rust struct', function () {
+    var a = new ArrayBuffer(16);
+    var view = new DataView(a);
+    view.setUint32(0, 41, true);
+    view.setFloat64(8, 1.25, true);
+    assert.equal(addon.bump_message_header(a), 42);
+    assert.equal(view.getUint32(0, true), 42);
+    assert.equal(view.getUint8(4), 1);
+    assert.equal(view.getFloat64(8, true), 2.5);
+  });
+
+  it('throws when a struct view does not fit', function () {
+    assert.throws(function() { addon.bump_message_header(new ArrayBuffer(8)) }, Error, /out of bounds/);
+  });
+
+  it('borrows a BigUint64Array as a u64 slice', function () {
+    if (typeof BigUint64Array === 'undefined') {
+      this.skip();
+    }
+    var ids = new BigUint64Array([BigInt(1), BigInt(2), BigInt(3)]);
+    assert.equal(addon.sum_biguint64_array(ids), 6);
+    assert.throws(function() { addon.sum_biguint64_array(new Uint32Array(2)) }, TypeError);
+  });
+
+  it('return a BigInt64Array built in Rust', function () {
+    if (typeof BigInt64Array === 'undefined') {
+      this.skip();
+    }
+    var a = addon.return_bigint64_array();
+    assert(a instanceof BigInt64Array);
+    assert.equal(a[0], BigInt(-1));
+    assert.equal(a[1], BigInt(0));
+    assert.equal(a[2], BigInt("9223372036854775807"));
+  });
+
+  it('encodes and decodes base64', function () {
+    ['', 'f', 'fo', 'foo', 'foob', 'fooba', 'foobar'].forEach(function(s) {
+      var b = Buffer.from(s);
+      var encoded = addon.encode_buffer_base64(b);
+      assert.equal(encoded, b.toString('base64'));
+      assert.deepEqual(addon.decode_buffer_base64(encoded), b);
+    });
+    assert.deepEqual(addon.decode_buffer_base64('-_8'), Buffer.from([0xfb, 0xff]));
+    assert.throws(function() { addon.decode_buffer_base64('a$==') }, TypeError, /invalid base64 string/);
+  });
+
+  it('encodes and decodes hex', function () {
+    var b = Buffer.from([0, 1, 0xab, 0xff]);
+    assert.equal(addon.encode_buffer_hex(b), '0001abff');
+    assert.deepEqual(addon.decode_buffer_hex('0001ABff'), b);
+    assert.throws(function() { addon.decode_buffer_hex('abc') }, TypeError, /invalid hex string/);
+  });
+
+  it('recycles pooled buffers once they are collected', function () {
+    (function() {
+      for (var i = 0; i < 8; i++) {
+        var b = addon.return_pooled_js_buffer();
+        assert.equal(b.length, 64);
+        b.fill(0xff);
+      }
+    })();
+    gc();
+    var available = addon.pooled_js_buffers_available();
+    assert.isAbove(available, 0);
+    assert.isAtMost(available, 4);
+    var b = addon.return_pooled_js_buffer();
+    assert.deepEqual(b, Buffer.alloc(64));
+    assert.equal(addon.pooled_js_buffers_available(), available - 1);
+  });
+});
@@ -2,7 +2,16 @@ var addon = require('../native');
 const User = addon.User;
 const PanickyAllocator = addon.PanickyAllocator;
 const PanickyConstructor = addon.PanickyConstructor;
+const DropCounter = addon.DropCounter;
+const Range = addon.Range;
+const ByteBuffer = addon.ByteBuffer;
+const Point = addon.Point;
 var assert = require('chai').assert;
+var v8 = require('v8');
+var vm = require('vm');
+
+v8.setFlagsFromString('--expose_gc');
+var gc = vm.runInNewContext('gc');
 
 describe('JsClass', function() {
   it('return a JsClass built in Rust', function () {
@@ -23,6 +32,143 @@ describe('JsClass', function() {
     assert.throw(function() { u.get('not_a_field') }, TypeError);
   });
 
+  it('can borrow internals from a method call', function () {
+    var u = new User(1, "some", "thing", "else");
+    assert.equal(u.full_name(), "some thing");
+    u.set_email("other");
+    assert.equal(u.get('email'), "other");
+  });
+
+  it('throws when borrowing internals with the wrong this', function () {
+    var u = new User(1, "some", "thing", "else");
+    assert.throws(function() { u.full_name.call({}) }, TypeError, /^this is not an object of type User\.$/);
+  });
+
+  it('reads a typed this from a method context', function () {
+    var u = new User(1, "some", "thing", "else");
+    assert.equal(u.initials(), "st");
+    assert.throws(function() { u.initials.call(new Point(1, 2)) }, TypeError, /^this is not an object of type User\.$/);
+  });
+
+  it('drops internals when instances are collected', function () {
+    var before = addon.dropped_count();
+    (function() {
+      for (var i = 0; i < 10; i++) {
+        new DropCounter();
+      }
+    })();
+    gc();
+    assert.isAbove(addon.dropped_count(), before);
+  });
+
+  it('reports the size of class internals as external memory', function () {
+    var MB = 1024 * 1024;
+    var before = process.memoryUsage().external;
+    var buffer = new ByteBuffer(64 * MB);
+    var allocated = process.memoryUsage().external;
+    assert.isAtLeast(allocated - before, 64 * MB);
+    buffer.grow(32 * MB);
+    assert.isAtLeast(process.memoryUsage().external - allocated, 32 * MB);
+    buffer = null;
+    gc();
+    assert.isBelow(process.memoryUsage().external, allocated);
+  });
+
+  it('iterates over a class backed by a Rust iterator', function () {
+    var r = new Range(4);
+    var items = [];
+    for (var n of r) {
+      items.push(n);
+    }
+    assert.deepEqual(items, [0, 1, 2, 3]);
+    assert.deepEqual(Array.from(r), [0, 1, 2, 3]);
+  });
+
+  it('finishes a Rust iterator and stays done', function () {
+    var it = new Range(1)[Symbol.iterator]();
+    assert.deepEqual(it.next(), { value: 0, done: false });
+    assert.deepEqual(it.next(), { value: undefined, done: true });
+    assert.deepEqual(it.next(), { value: undefined, done: true });
+  });
+
+  it('does not allow native iterators to be constructed from JS', function () {
+    var it = new Range(1)[Symbol.iterator]();
+    assert.throws(function() { new it.constructor() }, TypeError, /cannot be constructed from JavaScript/);
+  });
+
+  it('lazily consumes an infinite Rust iterator', function () {
+    var it = addon.squares();
+    assert.deepEqual(it.next(), { value: 0, done: false });
+    assert.deepEqual(it.next(), { value: 1, done: false });
+    assert.deepEqual(it.next(), { value: 4, done: false });
+  });
+
+  it('drops a Rust iterator when it is closed with return()', function () {
+    var it = addon.squares();
+    it.next();
+    var before = addon.dropped_count();
+    assert.deepEqual(it.return(42), { value: 42, done: true });
+    assert.equal(addon.dropped_count(), before + 1);
+    assert.deepEqual(it.next(), { value: undefined, done: true });
+    assert.deepEqual(it.return(), { value: undefined, done: true });
+    assert.equal(addon.dropped_count(), before + 1);
+  });
+
+  it('drops a Rust iterator when a for...of loop exits early', function () {
+    var before = addon.dropped_count();
+    var items = [];
+    for (var n of addon.squares()) {
+      if (n > 10) {
+        break;
+      }
+      items.push(n);
+    }
+    assert.deepEqual(items, [0, 1, 4, 9]);
+    assert.equal(addon.dropped_count(), before + 1);
+  });
+
+  it('rethrows from throw() and closes a Rust iterator', function () {
+    var it = addon.squares();
+    var before = addon.dropped_count();
+    var error = new Error('stop');
+    assert.throws(function() { it.throw(error) }, /stop/);
+    assert.equal(addon.dropped_count(), before + 1);
+    assert.deepEqual(it.next(), { value: undefined, done: true });
+  });
+
+  it('exposes an async iterator on a class', function () {
+    if (typeof Symbol.asyncIterator !== 'symbol') {
+      this.skip();
+    }
+    var it = new Range(2)[Symbol.asyncIterator]();
+    assert.deepEqual(it.next(), { value: 1, done: false });
+    assert.deepEqual(it.next(), { value: 0, done: false });
+    assert.deepEqual(it.next(), { value: undefined, done: true });
+  });
+
+  it('can extend a class prototype from Rust', function () {
+    var u = new User(1, "some", "thing", "else");
+    assert.equal(u.kind(), "user");
+  });
+
+  it('can extend a class prototype from JS', function () {
+    Object.getPrototypeOf(new Point(1, 2)).difference = function() {
+      return this.sum() - 2;
+    };
+    assert.equal(new Point(3, 4).difference(), 5);
+    delete Point.prototype.difference;
+  });
+
+  it('dispatches an overloaded constructor on argument types', function () {
+    assert.equal(new Point(1, 2).sum(), 3);
+    assert.equal(new Point([3, 4]).sum(), 7);
+    assert.equal(new Point().sum(), 0);
+  });
+
+  it('throws when no constructor overload matches', function () {
+    assert.throws(function() { new Point("1", 2) }, TypeError, /^no overload of Point matches the arguments; expected one of \(JsNumber, JsNumber\), \(JsArray\), \(\)$/);
+  });
+
   it('converts a Rust panic to a throw in a method', function() {
     var u = new User(1, "some", "thing", "else");
     assert.throws(function() { u.panic() }, Error, /^internal error in native module: User.prototype.panic$/);
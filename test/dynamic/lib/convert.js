@@ -0,0 +1,117 @@
+var addon = require('../native');
+var assert = require('chai').assert;
+
+describe('conversions', function() {
+  it('reads a Vec<f64> argument', function () {
+    assert.equal(addon.sum_number_vec([1, 2, 3.5]), 6.5);
+  });
+
+  it('reads a missing or null argument as None', function () {
+    assert.equal(addon.greet_optional_name('neon'), 'hello, neon');
+    assert.equal(addon.greet_optional_name(null), 'hello, stranger');
+    assert.equal(addon.greet_optional_name(), 'hello, stranger');
+  });
+
+  it('converts tuples to and from arrays', function () {
+    assert.deepEqual(addon.split_pair(['apples', 3]), [3, 'apples']);
+  });
+
+  it('converts HashMaps to and from objects', function () {
+    assert.deepEqual(addon.double_map_values({ a: 1, b: -2 }), { a: 2, b: -4 });
+  });
+
+  it('throws a TypeError describing the expected argument', function () {
+    assert.throws(function() { addon.sum_number_vec([1, 'two']); }, TypeError, /argument 0: expected an array of numbers/);
+    assert.throws(function() { addon.split_pair(['apples', -1]); }, TypeError, /integer in the range of u32/);
+    assert.throws(function() { addon.double_map_values([1, 2]); }, TypeError);
+  });
+
+  it('reads all arguments into a tuple', function () {
+    assert.equal(addon.describe_args(3, 'apples'), '3 apples');
+    assert.equal(addon.describe_args(3, 'apples', true), '3 APPLES');
+    assert.throws(function() { addon.describe_args(3, 4); }, TypeError, /argument 1: expected a string/);
+    assert.throws(function() { addon.describe_args(); }, TypeError, /argument 0: expected a number/);
+  });
+
+  it('converts derived structs to and from objects', function () {
+    assert.deepEqual(addon.scale_rectangle({ width: 2, height: 3, fillColor: 'red' }, 2),
+                     { width: 4, height: 6, fillColor: 'red' });
+    assert.deepEqual(addon.scale_rectangle({ width: 2, height: 3 }, 0.5),
+                     { width: 1, height: 1.5, fillColor: null });
+    assert.throws(function() { addon.scale_rectangle({ width: 2 }, 2); }, TypeError,
+                  /argument 0: expected an object of the form \{ width: a number, height: a number, fillColor: a string, null or undefined \}/);
+  });
+
+  it('reads optional options objects into derived structs', function () {
+    assert.equal(addon.pad_string('abc'), 'abc     ');
+    assert.equal(addon.pad_string('abc', undefined), 'abc     ');
+    assert.equal(addon.pad_string('abc', { width: 5, fill: '.-', padStart: true }), '.-abc');
+    assert.equal(addon.pad_string('abc', { width: 4, fill: undefined }), 'abc ');
+    assert.throws(function() { addon.pad_string('abc', 4); }, TypeError, /argument 1: expected an options object/);
+    assert.throws(function() { addon.pad_string('abc', { width: 4, padEnd: true }); }, TypeError, /argument 1: unknown option "padEnd"/);
+    assert.throws(function() { addon.pad_string('abc', { width: '4' }); }, TypeError, /option "width": expected an integer/);
+  });
+
+  it('converts fixed-size arrays and returns tuples as arrays', function () {
+    assert.deepEqual(addon.describe_color([255, 255, 255, 128]), [1, '#ffffff80']);
+    assert.deepEqual(addon.invert_color([0, 128, 255]), [255, 127, 0]);
+    assert.throws(function() { addon.invert_color([0, 128]); }, TypeError, /expected an array of 3 integers/);
+  });
+
+  it('converts Dates to and from SystemTime and Durations to and from milliseconds', function () {
+    var start = new Date(Date.UTC(2018, 0, 1, 12, 30));
+    var later = addon.postpone_date(start, 90 * 60 * 1000);
+    assert.instanceOf(later, Date);
+    assert.equal(later.getTime(), Date.UTC(2018, 0, 1, 14, 0));
+    assert.equal(addon.postpone_date(new Date(-1500), 500).getTime(), -1000);
+    assert.equal(addon.time_between_dates(start, later), 90 * 60 * 1000);
+    assert.throws(function() { addon.postpone_date(new Date(NaN), 0); }, TypeError, /expected a valid Date/);
+    assert.throws(function() { addon.postpone_date(start, -1); }, TypeError, /non-negative number of milliseconds/);
+  });
+
+  it('converts Dates to chrono DateTimes', function () {
+    assert.equal(addon.format_chrono_date(new Date(Date.UTC(2018, 1, 3, 4, 5, 6, 789))), '2018-02-03T04:05:06.789Z');
+    assert.equal(addon.format_chrono_date(new Date(-1)), '1969-12-31T23:59:59.999Z');
+  });
+
+  it('converts fieldless enums to and from strings', function () {
+    assert.equal(addon.turn_right('North'), 'East');
+    assert.equal(addon.turn_right('West'), 'North');
+    assert.throws(function() { addon.turn_right('Up'); }, TypeError, /expected one of "North", "East", "South", "West"/);
+  });
+
+  it('converts data-carrying enums to and from tagged objects', function () {
+    assert.equal(addon.shape_area({ kind: 'Rectangle', width: 2, height: 3 }), 6);
+    assert.equal(addon.shape_area({ kind: 'empty' }), 0);
+    assert.deepEqual(addon.bounding_square({ kind: 'Circle', radius: 1.5 }), { kind: 'Rectangle', width: 3, height: 3 });
+    assert.deepEqual(addon.bounding_square({ kind: 'empty' }), { kind: 'empty' });
+    assert.throws(function() { addon.shape_area({ kind: 'Circle' }); }, TypeError, /\{ kind: "Circle", radius: a number \}/);
+    assert.throws(function() { addon.shape_area('empty'); }, TypeError);
+  });
+
+  it('coerces values as JavaScript does', function () {
+    assert.deepEqual(addon.coerce_value('12'), ['12', 12, true]);
+    assert.deepEqual(addon.coerce_value(''), ['', 0, false]);
+    assert.deepEqual(addon.coerce_value(null), ['null', 0, false]);
+    assert.deepEqual(addon.coerce_value({ valueOf: function() { return 3; }, toString: function() { return 'three'; } }), ['three', 3, true]);
+    var result = addon.coerce_value(undefined);
+    assert.equal(result[0], 'undefined');
+    assert.isNaN(result[1]);
+    assert.throws(function() {
+      addon.coerce_value({ toString: function() { throw new Error('no string'); } });
+    }, Error, /no string/);
+  });
+
+  it('boxes primitives into objects', function () {
+    var boxed = addon.box_value('abc');
+    assert.equal(typeof boxed[0], 'object');
+    assert.instanceOf(boxed[0], String);
+    assert.equal(boxed[1], 3);
+    assert.instanceOf(addon.box_value(1)[0], Number);
+    var array = [1, 2];
+    assert.strictEqual(addon.box_value(array)[0], array);
+    assert.equal(addon.box_value(array)[1], 2);
+    assert.throws(function() { addon.box_value(null); }, TypeError);
+    assert.throws(function() { addon.box_value(undefined); }, TypeError);
+  });
+});
@@ -0,0 +1,26 @@
+var addon = require('../native');
+var assert = require('chai').assert;
+var path = require('path');
+var url = require('url');
+
+var importModule;
+try {
+  importModule = new Function('specifier', 'return import(specifier);');
+} catch (e) { }
+
+describe('ES module interop', function() {
+  it('mark the exports as an ES module', function () {
+    assert.isTrue(addon.__esModule);
+    assert.notInclude(Object.keys(addon), '__esModule');
+  });
+
+  (importModule && url.pathToFileURL ? it : it.skip)('import named exports from the generated wrapper', function () {
+    var wrapper = url.pathToFileURL(path.resolve(__dirname, '../native/index.mjs')).href;
+    return importModule(wrapper).then(function (ns) {
+      assert.equal(ns.return_js_string(), 'hello node');
+      assert.strictEqual(ns.default, addon);
+      assert.strictEqual(ns.Color, addon.Color);
+      assert.notProperty(ns, 'lazy_table');
+    });
+  });
+});
@@ -1,5 +1,13 @@
 var addon = require('../native');
 var assert = require('chai').assert;
+var fs = require('fs');
+var os = require('os');
+var path = require('path');
+var v8 = require('v8');
+var vm = require('vm');
+
+v8.setFlagsFromString('--expose_gc');
+var gc = vm.runInNewContext('gc');
 
 describe('JsFunction', function() {
   it('return a JsFunction built in Rust', function () {
@@ -10,6 +18,147 @@ describe('JsFunction', function() {
     assert.equal(addon.return_js_function()(41), 42);
   });
 
+  it('reuse a cached JsFunction built in Rust', function () {
+    var f = addon.return_cached_js_function();
+    assert.equal(f(41), 42);
+    assert.strictEqual(addon.return_cached_js_function(), f);
+    assert.notStrictEqual(addon.return_js_function(), addon.return_js_function());
+  });
+
+  it('return a JsFunction built from a Rust closure with its own state', function () {
+    var byOne = addon.make_counter(1);
+    var byTen = addon.make_counter(10);
+    assert.equal(byOne(), 1);
+    assert.equal(byOne(), 2);
+    assert.equal(byTen(), 10);
+    assert.equal(byOne(), 3);
+  });
+
+  it('throws when a closure is called recursively', function () {
+    var caller = addon.make_reentrant_caller();
+    assert.equal(caller(function() { return 7; }), 7);
+    assert.throws(function() { caller(function() { return caller(function() { }); }); }, Error, /^a function created from a closure can't be called recursively$/);
+  });
+
+  it('calls a function created from a Rust function recursively', function () {
+    var caller = addon.make_recursive_caller();
+    assert.equal(caller(function() { return caller(function() { return 7; }); }), 7);
+  });
+
+  it('advances the Rust state of a function on each call', function () {
+    var split = addon.make_line_splitter();
+    assert.deepEqual(split('first li'), []);
+    assert.deepEqual(split('ne\nsecond line\nthi'), ['first line', 'second line']);
+    assert.deepEqual(split('rd\n'), ['third']);
+    assert.deepEqual(addon.make_line_splitter()('other\n'), ['other']);
+  });
+
+  it('drops the state of a closure when its function is collected', function () {
+    var before = addon.dropped_closures();
+    (function() {
+      for (var i = 0; i < 10; i++) {
+        addon.make_counter(1)();
+      }
+    })();
+    gc();
+    assert.isAbove(addon.dropped_closures(), before);
+  });
+
+  it('describes the name, length, and location of a JS function', function () {
+    function add(a, b) { return a + b; }
+    var description = addon.describe_js_function(add);
+    assert.equal(description.name, 'add');
+    assert.equal(description.length, 2);
+    assert.equal(description.resource, __filename);
+    assert.isAbove(description.line, 1);
+    assert.isAbove(description.column, 1);
+  });
+
+  it('calls a function with undefined as this', function () {
+    var calls = [];
+    var results = addon.call_with_undefined_this(function () {
+      'use strict';
+      calls.push({ this: this, args: Array.prototype.slice.call(arguments) });
+      return arguments.length;
+    });
+    assert.deepEqual(results, [0, 1, 2]);
+    assert.deepEqual(calls, [
+      { this: undefined, args: [] },
+      { this: undefined, args: [1] },
+      { this: undefined, args: [1, 'two'] },
+      { this: undefined, args: ['two', 1] }
+    ]);
+  });
+
+  it('registers Rust tests in a hidden export', function () {
+    assert.notInclude(Object.keys(addon), '__neon_tests');
+    var tests = addon.__neon_tests;
+    assert.sameMembers(Object.keys(tests), [
+      'add1 adds one',
+      'square task performs and completes',
+      'tasks run inline',
+      'assert_js_eq compares structurally',
+      'exports are named after their keys'
+    ]);
+    Object.keys(tests).forEach(function (name) {
+      assert.equal(tests[name].name, name);
+      assert.isUndefined(tests[name]());
+    });
+  });
+
+  it('describes a function without a location', function () {
+    var description = addon.describe_js_function(addon.make_counter(1));
+    assert.equal(description.name, '');
+    assert.equal(description.length, 0);
+    assert.isUndefined(description.line);
+  });
+
+  it('names a function created in Rust', function () {
+    var f = addon.return_named_js_function('answer');
+    assert.equal(f.name, 'answer');
+    assert.equal(addon.describe_js_function(f).name, 'answer');
+    assert.equal(f(41), 42);
+  });
+
+  it('export functions from composed module initializers', function () {
+    assert.equal(addon.included_add1(1), 2);
+    assert.equal(addon.arithmetic.add1(1), 2);
+    assert.equal(addon.arithmetic.sub1(1), 0);
+    assert.isUndefined(addon.sub1);
+  });
+
+  it('initialize a module against any exports object', function () {
+    var exports = addon.initialize_fresh_exports();
+    assert.equal(exports.nested.math.add1(1), 2);
+    assert.equal(exports.nested.math.inverse.sub1(1), 0);
+  });
+
+  it('keep per-module instance data across calls', function () {
+    var first = addon.count_module_calls();
+    assert.isAbove(first, 0);
+    assert.equal(addon.count_module_calls(), first + 1);
+  });
+
+  it('build a lazy export on first access only', function () {
+    assert.equal(addon.lazy_table_builds(), 0);
+    var table = addon.lazy_table;
+    assert.deepEqual(table.slice(0, 4), [0, 1, 4, 9]);
+    assert.strictEqual(addon.lazy_table, table);
+    assert.equal(addon.lazy_table_builds(), 1);
+    assert.isUndefined(Object.getOwnPropertyDescriptor(addon, 'lazy_table').get);
+  });
+
+  it('require JS modules relative to the addon', function () {
+    assert.strictEqual(addon.require_js_module('path'), require('path'));
+    assert.equal(addon.require_js_module('../package.json').name, 'tests');
+  });
+
+  it('export functions into nested namespaces', function () {
+    assert.equal(addon.nested.math.add1(1), 2);
+    assert.equal(addon.nested.math.inverse.sub1(1), 0);
+    assert.equal(addon.nested.add1(1), 2);
+  });
+
   it('call a JsFunction built in JS that implements x => x + 1', function () {
     assert.equal(addon.call_js_function(function(x) { return x + 1 }), 17);
   });
@@ -29,4 +178,114 @@ describe('JsFunction', function() {
   it('lets panic override a throw', function() {
     assert.throws(function() { addon.panic_after_throw() }, Error, /^internal error in native module: this should override the RangeError$/);
   });
+
+  it('reads all arguments at once', function() {
+    assert.equal(addon.sum_all_arguments(), 0);
+    assert.equal(addon.sum_all_arguments(1, 2, 3), 6);
+    assert.equal(addon.sum_all_arguments(1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12), 78);
+    assert.throws(function() { addon.sum_all_arguments(1, 'two') }, TypeError);
+  });
+
+  it('iterates over all arguments by value', function() {
+    assert.equal(addon.join_string_arguments(), '');
+    assert.equal(addon.join_string_arguments('a', 'b', 'c'), 'a,b,c');
+    assert.equal(addon.join_string_arguments('1', '2', '3', '4', '5', '6', '7', '8', '9'), '1,2,3,4,5,6,7,8,9');
+    assert.throws(function() { addon.join_string_arguments('a', 2) }, TypeError);
+  });
+
+  it('reads optional arguments with defaults', function() {
+    assert.equal(addon.add_with_default_step(1), 'sum: 2');
+    assert.equal(addon.add_with_default_step(1, undefined), 'sum: 2');
+    assert.equal(addon.add_with_default_step(1, 5), 'sum: 6');
+    assert.equal(addon.add_with_default_step(1, undefined, 'total'), 'total: 2');
+    assert.throws(function() { addon.add_with_default_step(1, '5') }, TypeError);
+    assert.throws(function() { addon.add_with_default_step(1, null) }, TypeError);
+  });
+
+  it('calls a function exported with export_fast', function() {
+    assert.equal(addon.fast_add1(41), 42);
+    for (var i = 0; i < 10000; i++) {
+      assert.equal(addon.fast_add1(i), i + 1);
+    }
+    assert.throws(function() { addon.fast_add1('x') }, TypeError);
+  });
+
+  it('checks the number of arguments of a function exported with an arity', function() {
+    assert.equal(addon.add_two.length, 2);
+    assert.equal(addon.add_two.name, 'tests.add_two');
+    assert.equal(addon.add_two(40, 2), 42);
+    assert.equal(addon.add_two(40, 2, 'extra'), 42);
+    assert.throws(function() { addon.add_two(1) }, TypeError, /^add_two expects at least 2 arguments, but got 1$/);
+    assert.throws(function() { addon.add_two() }, TypeError, /^add_two expects at least 2 arguments, but got 0$/);
+  });
+
+  it('calls a function exported with an arity recursively', function() {
+    assert.equal(addon.call_argument(function() { return addon.call_argument(function() { return 7; }); }), 7);
+  });
+
+  it('runs microtasks before returning', function() {
+    var log = [];
+    addon.call_js_function_and_run_microtasks(function() {
+      Promise.resolve().then(function() { log.push('then'); });
+      log.push('call');
+    });
+    log.push('return');
+    assert.deepEqual(log, ['call', 'then', 'return']);
+  });
+
+  it('reports the microtask policy', function() {
+    assert.include(['explicit', 'scoped', 'auto'], addon.return_microtask_policy());
+  });
+
+  it('names exported functions after their keys', function() {
+    assert.equal(addon.call_js_function.name, 'tests.call_js_function');
+    assert.equal(addon.fast_add1.name, 'tests.fast_add1');
+    assert.equal(addon.nested.math.add1.name, 'tests.included_add1');
+    assert.equal(addon.nested.math.inverse.sub1.name, 'tests.nested.math.inverse.sub1');
+  });
+
+  (Number(process.versions.modules) >= 57 ? it : it.skip)('profiles a function call', function() {
+    var profile = addon.profile_js_function(function() {
+      var end = Date.now() + 20;
+      while (Date.now() < end) {
+        addon.fast_add1(1);
+      }
+    });
+    assert.equal(profile.title, 'profile_js_function');
+    assert.isAtLeast(profile.endTime, profile.startTime);
+    assert.equal(profile.root.functionName, '(root)');
+    assert.isArray(profile.root.children);
+  });
+
+  it('writes a heap snapshot', function() {
+    var file = path.join(os.tmpdir(), 'neon-' + process.pid + '.heapsnapshot');
+    try {
+      addon.write_heap_snapshot(file);
+      var snapshot = JSON.parse(fs.readFileSync(file, 'utf8'));
+      assert.isObject(snapshot.snapshot.meta);
+      assert.isAbove(snapshot.snapshot.node_count, 0);
+      assert.isArray(snapshot.nodes);
+      assert.isArray(snapshot.strings);
+    } finally {
+      if (fs.existsSync(file)) {
+        fs.unlinkSync(file);
+      }
+    }
+  });
+
+  it('throws if a heap snapshot can\'t be written', function() {
+    var file = path.join(os.tmpdir(), 'neon-missing-' + process.pid, 'out.heapsnapshot');
+    assert.throws(function() { addon.write_heap_snapshot(file); }, Error, /couldn't write a heap snapshot/);
+  });
+
+  it('names the native function in the stacks of errors it throws', function() {
+    try {
+      addon.sum_all_arguments(1, 'two');
+    } catch (e) {
+      assert.instanceOf(e, TypeError);
+      assert.match(e.stack.split('\n')[1], /^    at tests\.sum_all_arguments \(.+\.node\)$/);
+      return;
+    }
+    assert.fail('expected sum_all_arguments to throw');
+  });
 });
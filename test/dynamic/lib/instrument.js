@@ -0,0 +1,55 @@
+var addon = require('../native');
+var assert = require('chai').assert;
+var v8 = require('v8');
+var vm = require('vm');
+
+v8.setFlagsFromString('--expose_gc');
+var gc = vm.runInNewContext('gc');
+
+describe('instrumentation', function() {
+  it('counts callbacks, scopes, and handles', function () {
+    addon.reset_neon_diagnostics();
+    addon.return_js_string();
+    addon.return_js_object_with_mixed_content();
+    var stats = addon.neon_diagnostics();
+    // Including the call to reset_neon_diagnostics, but not neon_diagnostics.
+    assert.equal(stats.callbacks.count, 3);
+    assert.isAtLeast(stats.scopes.count, 3);
+    assert.isAtLeast(stats.handles.count, 3);
+    assert.equal(stats.tasks.count, 0);
+    assert.isAtLeast(stats.callbacks.time, 0);
+  });
+
+  it('resets the counters', function () {
+    addon.reset_neon_diagnostics();
+    var stats = addon.neon_diagnostics();
+    // The call to reset_neon_diagnostics is recorded as it returns.
+    assert.equal(stats.callbacks.count, 1);
+    assert.equal(stats.tasks.count, 0);
+  });
+
+  it('counts the handles held by open scopes', function () {
+    var counts = addon.count_live_handles(100);
+    assert.equal(counts.nested, 0);
+    assert.isAtLeast(counts.flat, 100);
+  });
+
+  // Only built with the `debug-handles` feature, in the second run of the suite.
+  (addon.use_handle_after_scope ? it : it.skip)('panics when a handle is used after its scope exits', function () {
+    assert.throws(function() { addon.use_handle_after_scope() }, Error, /^internal error in native module: handle used after the scope it was created in exited$/);
+  });
+
+  it('allows undefined to outlive the scope it was created in', function () {
+    assert.strictEqual(addon.undefined_from_nested_scope(), undefined);
+  });
+
+  (Number(process.versions.modules) >= 64 ? it : it.skip)('runs callbacks around garbage collections', function () {
+    addon.start_counting_gc_cycles();
+    gc();
+    gc();
+    var cycles = addon.stop_counting_gc_cycles();
+    assert.isAtLeast(cycles, 2);
+    gc();
+    assert.equal(addon.stop_counting_gc_cycles(), cycles);
+  });
+});
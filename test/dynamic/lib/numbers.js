@@ -58,4 +58,47 @@ describe('JsNumber', function() {
     });
   });
 
+  describe('64-bit integer arguments', function () {
+    it('accept safe integers', function () {
+      assert.equal(addon.describe_i64_argument(-42), '-42');
+      assert.equal(addon.describe_u64_argument(Number.MAX_SAFE_INTEGER), '9007199254740991');
+    });
+
+    it('accept BigInts beyond 2^53', function () {
+      if (typeof BigInt === 'undefined') {
+        this.skip();
+      }
+      assert.equal(addon.describe_i64_argument(BigInt('-9223372036854775808')), '-9223372036854775808');
+      assert.equal(addon.describe_u64_argument(BigInt('18446744073709551615')), '18446744073709551615');
+      assert.throws(function() { addon.describe_u64_argument(BigInt(-1)); }, RangeError);
+      assert.throws(function() { addon.describe_i64_argument(BigInt('9223372036854775808')); }, RangeError);
+    });
+
+    it('reject unsafe or non-integral numbers', function () {
+      assert.throws(function() { addon.describe_i64_argument(Math.pow(2, 53)); }, RangeError);
+      assert.throws(function() { addon.describe_i64_argument(1.5); }, RangeError);
+      assert.throws(function() { addon.describe_u64_argument(-1); }, RangeError);
+      assert.throws(function() { addon.describe_i64_argument('1'); }, TypeError);
+    });
+  });
+
+  describe('32-bit integer conversions', function () {
+    it('wrap by default, as typed arrays do', function () {
+      [0, 1.9, -1, -1.5, 4294967296, 4294967297, 2147483648, -2147483649, 1e21, NaN, Infinity, -Infinity].forEach(function(n) {
+        assert.deepEqual(addon.wrap_to_u32_and_i32(n), [new Uint32Array([n])[0], new Int32Array([n])[0]], String(n));
+      });
+    });
+
+    it('throw RangeErrors with the strict conversions', function () {
+      assert.equal(addon.strict_to_u32(4294967295), 4294967295);
+      assert.equal(addon.strict_to_i32(-2147483648), -2147483648);
+      assert.throws(function() { addon.strict_to_u32(-1); }, RangeError, /^-1 is not an integer in the range of u32$/);
+      assert.throws(function() { addon.strict_to_u32(1.5); }, RangeError, /^1.5 is not an integer in the range of u32$/);
+      assert.throws(function() { addon.strict_to_u32(4294967296); }, RangeError);
+      assert.throws(function() { addon.strict_to_u32(NaN); }, RangeError, /^NaN is not/);
+      assert.throws(function() { addon.strict_to_i32(2147483648); }, RangeError, /range of i32/);
+      assert.throws(function() { addon.strict_to_i32(-Infinity); }, RangeError, /^-Infinity is not/);
+    });
+  });
+
 });
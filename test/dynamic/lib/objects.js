@@ -1,5 +1,11 @@
 var addon = require('../native');
 var assert = require('chai').assert;
+var util = require('util');
+var v8 = require('v8');
+var vm = require('vm');
+
+v8.setFlagsFromString('--expose_gc');
+var gc = vm.runInNewContext('gc');
 
 describe('JsObject', function() {
   it('return the v8::Global object', function () {
@@ -21,4 +27,198 @@ describe('JsObject', function() {
   it('return a JsObject with mixed content key value pairs', function () {
     assert.deepEqual({number: 9000, string: 'hello node'}, addon.return_js_object_with_mixed_content());
   });
+
+  it('get and set properties with interned keys', function () {
+    var points = [{ x: 1, y: 2 }, { x: -3, y: 0.5 }];
+    assert.deepEqual(addon.translate_js_points(points), [{ x: 2, y: 1 }, { x: -2, y: -0.5 }]);
+    assert.deepEqual(addon.translate_js_points([{ x: 0, y: 0 }]), [{ x: 1, y: -1 }]);
+  });
+
+  it('keep a JsObject alive across calls with a persistent handle', function () {
+    var object = { name: 'cached' };
+    addon.remember_js_object(object);
+    object = null;
+    if (global.gc) global.gc();
+    assert.deepEqual(addon.recall_js_object(), { name: 'cached' });
+    var replacement = {};
+    addon.remember_js_object(replacement);
+    assert.strictEqual(addon.recall_js_object(), replacement);
+  });
+
+  it('watch a JsObject with a weak handle without keeping it alive', function (done) {
+    var before = addon.collected_js_objects();
+    var object = { name: 'watched' };
+    addon.watch_js_object(object);
+    assert.strictEqual(addon.read_watched_js_object(), object);
+    object = null;
+    gc();
+    setImmediate(function () {
+      assert.isUndefined(addon.read_watched_js_object());
+      assert.equal(addon.collected_js_objects(), before + 1);
+      done();
+    });
+  });
+
+  it('keep a queue of callbacks alive in a RootedVec', function () {
+    var calls = [];
+    assert.equal(addon.queue_callback(function (i) { calls.push('a' + i); }), 1);
+    assert.equal(addon.queue_callback(function (i) { calls.push('b' + i); }), 2);
+    gc();
+    addon.flush_callbacks();
+    addon.flush_callbacks();
+    assert.deepEqual(calls, ['a0', 'b1']);
+  });
+
+  it('keep callbacks by id in a RootedMap', function () {
+    addon.register_callback('one', function () { return 1; });
+    addon.register_callback('two', function () { return 2; });
+    addon.register_callback('one', function () { return 'replaced'; });
+    gc();
+    assert.equal(addon.call_registered_callback('two'), 2);
+    assert.equal(addon.call_registered_callback('one'), 'replaced');
+    assert.isUndefined(addon.call_registered_callback('one'));
+    addon.register_callback('three', function () { return 3; });
+    assert.equal(addon.call_registered_callback('three'), 3);
+  });
+
+  it('store hidden values on a JsObject', function () {
+    var object = { visits: 'visible' };
+    assert.equal(addon.count_js_object_visits(object), 1);
+    assert.equal(addon.count_js_object_visits(object), 2);
+    assert.equal(addon.count_js_object_visits({}), 1);
+    assert.deepEqual(Object.getOwnPropertyNames(object), ['visits']);
+    assert.deepEqual(Object.getOwnPropertySymbols(object), []);
+    assert.equal(object.visits, 'visible');
+  });
+
+  it('read the cached process object', function () {
+    assert.equal(addon.return_process_pid(), process.pid);
+    assert.equal(addon.return_process_pid(), process.pid);
+  });
+
+  it('calls a method of an object with the object as this', function () {
+    var counter = { count: 1, add: function(n, m) { return this.count + n + m; } };
+    assert.equal(addon.call_object_method(counter, 'add', 2, 3), 6);
+    assert.throws(function() { addon.call_object_method(counter, 'count') }, TypeError, /^count is not a function$/);
+    assert.throws(function() { addon.call_object_method(counter, 'missing') }, TypeError, /^missing is not a function$/);
+  });
+
+  it('finds no difference between structurally equal values', function () {
+    assert.isNull(addon.diff_js_values({ a: [1, 'two', null], b: { c: true } }, '{ "b": { "c": true }, "a": [1, "two", null] }'));
+    assert.isNull(addon.diff_js_values(3, '3'));
+  });
+
+  it('describes the first difference between values', function () {
+    assert.equal(addon.diff_js_values({ items: [{ name: 'a' }, { name: 'b' }] }, '{ "items": [{ "name": "a" }, { "name": "c" }] }'),
+                 'at $.items[1].name: expected "c", found "b"\n' +
+                 '  actual:   {"items":[{"name":"a"},{"name":"b"}]}\n' +
+                 '  expected: {"items":[{"name":"a"},{"name":"c"}]}');
+    assert.match(addon.diff_js_values([1, 2], '[1, 2, 3]'), /^at \$: expected an array of length 3, found one of length 2\n/);
+    assert.match(addon.diff_js_values({ a: 1 }, '{ "a": 1, "b": 2 }'), /^at \$: missing property `b`\n/);
+    assert.match(addon.diff_js_values({ a: 1, c: 3 }, '{ "a": 1 }'), /^at \$: unexpected property `c`\n/);
+    assert.match(addon.diff_js_values({ a: undefined }, '{ "a": null }'), /^at \$\.a: expected null, found undefined\n/);
+    assert.match(addon.diff_js_values('1', '1'), /^at \$: expected 1, found "1"\n/);
+  });
+
+  it('log to the console', function () {
+    var log = console.log;
+    var logged = [];
+    console.log = function() { logged.push(Array.prototype.slice.call(arguments)); };
+    try {
+      addon.log_to_console('hello', 42);
+    } finally {
+      console.log = log;
+    }
+    assert.deepEqual(logged, [['hello', 42]]);
+  });
+
+  it('writes log records to the console', function (done) {
+    var methods = ['log', 'warn', 'error'];
+    var originals = {};
+    var logged = [];
+    methods.forEach(function (method) {
+      originals[method] = console[method];
+      console[method] = function(message) { logged.push([method, message]); };
+    });
+    function restore() {
+      methods.forEach(function (method) { console[method] = originals[method]; });
+    }
+    try {
+      addon.init_logger();
+      assert.isFalse(addon.init_logger());
+      addon.log_records();
+      assert.deepEqual(logged, []);
+    } catch (e) {
+      restore();
+      throw e;
+    }
+    setTimeout(function () {
+      restore();
+      assert.deepEqual(logged, [
+        ['log', '[INFO tests::js::objects] info from the JS thread'],
+        ['warn', '[WARN tests::js::objects] warning from the JS thread'],
+        ['error', '[ERROR tests::js::objects] error from the JS thread'],
+        ['warn', '[WARN background] warning from another thread']
+      ]);
+      done();
+    }, 20);
+  });
+
+  it('export a frozen enum object', function () {
+    assert.equal(addon.Color.Red, 0);
+    assert.equal(addon.Color.Blue, -1);
+    assert.equal(addon.Color[1], 'Green');
+    assert.equal(addon.Color[-1], 'Blue');
+    assert.isTrue(Object.isFrozen(addon.Color));
+    assert.deepEqual(Object.keys(addon.Color).sort(), ['-1', '0', '1', 'Blue', 'Green', 'Red']);
+  });
+
+  it('round-trips an object through a raw handle', function () {
+    var o = { a: 1 };
+    assert.strictEqual(addon.round_trip_raw_handle(o), o);
+  });
+
+  it('probes the types of values without throwing', function () {
+    assert.equal(addon.classify_value(undefined), 'nothing');
+    assert.equal(addon.classify_value(null), 'nothing');
+    assert.equal(addon.classify_value(false), 'boolean');
+    assert.equal(addon.classify_value(1.5), 'number');
+    assert.equal(addon.classify_value('s'), 'string');
+    assert.equal(addon.classify_value([1]), 'array');
+    assert.equal(addon.classify_value(function() {}), 'function');
+    assert.equal(addon.classify_value({}), 'object');
+    assert.equal(addon.classify_value(Symbol()), 'other');
+  });
+
+  it('formats values for logging', function () {
+    assert.equal(addon.describe_for_log('hi'), "hi / 'hi'");
+    assert.equal(addon.describe_for_log([1, 'a']), "1,a / [ 1, 'a' ]");
+    assert.equal(addon.describe_for_log({ a: 1 }), '[object Object] / { a: 1 }');
+    assert.equal(addon.describe_for_log(Symbol('s')), 'Symbol(s) / Symbol(s)');
+    assert.equal(addon.describe_for_log({ toString: function() { return 'custom'; } }).split(' / ')[0], 'custom');
+  });
+
+  it('formats values with util.inspect options', function () {
+    var value = { a: { b: { c: { d: 1 } } }, s: 'x' };
+    var results = addon.inspect_with_options(value);
+    assert.equal(results[0], util.inspect(value));
+    assert.equal(results[1], util.inspect(value, { depth: 0 }));
+    assert.include(results[1], '[Object]');
+    assert.equal(results[2], util.inspect(value, { depth: Infinity }));
+    assert.include(results[2], 'd: 1');
+    assert.equal(results[3], util.inspect(value, { colors: true }));
+    assert.include(results[3], '\u001b[');
+    assert.equal(addon.inspect_with_options([1])[4], util.inspect([1], { showHidden: true }));
+    assert.include(addon.inspect_with_options([1])[4], 'length');
+  });
+
+  it('compares values with === and SameValue', function () {
+    var o = {};
+    assert.deepEqual(addon.compare_values('a' + 'b', 'ab'), [true, true]);
+    assert.deepEqual(addon.compare_values(o, o), [true, true]);
+    assert.deepEqual(addon.compare_values(o, {}), [false, false]);
+    assert.deepEqual(addon.compare_values(NaN, NaN), [false, true]);
+    assert.deepEqual(addon.compare_values(0, -0), [true, false]);
+    assert.deepEqual(addon.compare_values(1, '1'), [false, false]);
+  });
 });
@@ -0,0 +1,31 @@
+var addon = require('../native');
+var assert = require('chai').assert;
+
+describe('serde', function() {
+  it('round-trips a struct through Rust', function () {
+    var order = { id: 7, items: ['book'], note: null, status: 'Pending' };
+    assert.deepEqual(addon.ship_order(order), {
+      id: 7,
+      items: ['book', 'receipt'],
+      note: null,
+      status: { Shipped: { tracking: 'TRACK-7' } }
+    });
+  });
+
+  it('accepts an externally tagged enum variant', function () {
+    var order = { id: 8, items: [], note: 'fragile', status: { Shipped: { tracking: 'XYZ' } } };
+    assert.deepEqual(addon.ship_order(order).status, { Shipped: { tracking: 'XYZ' } });
+  });
+
+  it('throws a TypeError for a value with the wrong shape', function () {
+    assert.throws(function() { addon.ship_order({ id: 'seven', items: [], status: 'Pending' }); }, TypeError);
+    assert.throws(function() { addon.ship_order({ id: 9, items: [] }); }, TypeError, /status/);
+  });
+
+  it('reads nested values but rejects circular ones', function () {
+    assert.equal(addon.count_linked_nodes({ next: { next: { next: null } } }), 3);
+    var node = {};
+    node.next = node;
+    assert.throws(function() { addon.count_linked_nodes(node); }, TypeError, /circular/);
+  });
+});
@@ -0,0 +1,72 @@
+var addon = require('../native');
+var assert = require('chai').assert;
+
+describe('streams', function() {
+  it('reads the chunks of a Rust iterator', function (done) {
+    var chunks = [];
+    var stream = addon.readable_from_iterator(3, 2);
+    stream.on('data', function (chunk) { chunks.push(chunk); });
+    stream.on('end', function () {
+      assert.equal(Buffer.concat(chunks).toString('hex'), '000001010202');
+      done();
+    });
+  });
+
+  it('stops pulling from a Rust iterator when the stream is full', function (done) {
+    var stream = addon.readable_from_iterator(100, 1024);
+    stream.read(0);
+    setImmediate(function () {
+      assert.isAtMost(addon.pulled_chunks(), 17);
+      var length = 0;
+      stream.on('data', function (chunk) { length += chunk.length; });
+      stream.on('end', function () {
+        assert.equal(length, 100 * 1024);
+        assert.equal(addon.pulled_chunks(), 100);
+        done();
+      });
+    });
+  });
+
+  it('writes chunks into a Rust channel', function (done) {
+    var stream = addon.writable_to_thread(function (received) {
+      assert.equal(received, 'hello, world');
+      done();
+    });
+    stream.write('hello');
+    stream.write(Buffer.from(', '));
+    stream.end('world');
+  });
+
+  it('fails a stream when its Rust sink fails', function (done) {
+    var stream = addon.picky_writable();
+    stream.on('error', function (err) {
+      assert.instanceOf(err, Error);
+      assert.equal(err.message, 'can\'t write "bad"');
+      done();
+    });
+    stream.write('good');
+    stream.write('bad');
+  });
+
+  it('transforms chunks with a Rust function', function (done) {
+    var stream = addon.shouting_transform();
+    var output = '';
+    stream.on('data', function (chunk) { output += chunk; });
+    stream.on('end', function () {
+      assert.equal(output, 'HELLO, WORLD!');
+      done();
+    });
+    stream.write('hello, ');
+    stream.end('world');
+  });
+
+  it('fails a transform stream when its Rust function fails', function (done) {
+    var stream = addon.shouting_transform();
+    stream.on('error', function (err) {
+      assert.equal(err.message, 'can\'t transform "bad"');
+      done();
+    });
+    stream.resume();
+    stream.write('bad');
+  });
+});
@@ -5,4 +5,39 @@ describe('JsString', function() {
   it('should return a JsString built in Rust', function () {
     assert.equal(addon.return_js_string(), "hello node");
   });
+
+  it('should build ASCII and non-ASCII strings without checks', function () {
+    assert.equal(addon.concat_js_strings_unchecked("hello ", "node"), "hello node");
+    assert.equal(addon.concat_js_strings_unchecked("caf\u00e9 ", "\ud83d\ude00"), "caf\u00e9 \ud83d\ude00");
+    assert.equal(addon.concat_js_strings_unchecked("", ""), "");
+  });
+
+  it('should round-trip a string through UTF-16 code units', function () {
+    assert.equal(addon.reverse_utf16("abc"), "cba");
+    assert.equal(addon.reverse_utf16("h\u00e9llo"), "oll\u00e9h");
+  });
+
+  it('should round-trip a string through Latin-1', function () {
+    assert.equal(addon.latin1_round_trip("caf\u00e9"), "caf\u00e9");
+  });
+
+  it('should encode a string into a buffer as UTF-8', function () {
+    var b = Buffer.alloc(8);
+    assert.equal(addon.encode_into_buffer("h\u00e9", b), 3);
+    assert.equal(b.slice(0, 3).toString(), "h\u00e9");
+    assert.equal(addon.encode_into_buffer("\u00e9\u00e9\u00e9\u00e9\u00e9", b), 8);
+  });
+
+  it('should decode a buffer with invalid UTF-8', function () {
+    assert.equal(addon.decode_buffer_lossy(Buffer.from([0x68, 0xff, 0x69])), "h\ufffdi");
+  });
+
+  it('should read short strings without allocating and long ones with', function () {
+    assert.equal(addon.join_small_strings(["a", "h\u00e9llo", "", "a string longer than sixteen bytes"]),
+                 "a,h\u00e9llo,,a string longer than sixteen bytes");
+  });
+
+  it('should read strings into a reused buffer', function () {
+    assert.equal(addon.longest_string(["abc", "h\u00e9llo world", "xy"]), "h\u00e9llo world");
+  });
 });
@@ -1,5 +1,6 @@
 var addon = require('../native');
 var assert = require('chai').assert;
+var async_hooks = require('async_hooks');
 
 describe('Task', function() {
   it('completes a successful task', function (done) {
@@ -39,4 +40,253 @@ describe('Task', function() {
       });
     });
   });
+
+  it('runs a signal triggered from another thread', function (done) {
+    addon.signal_from_thread(function (n) {
+      assert.equal(n, 1);
+      done();
+    });
+  });
+
+  it('completes a chained and mapped task', function (done) {
+    addon.perform_mapped_task(function (err, n) {
+      assert.isNull(err);
+      assert.strictEqual(n, 36);
+      done();
+    });
+  });
+
+  it('fails a chained task with a mapped error', function (done) {
+    addon.perform_chained_failing_task(function (err) {
+      assert.instanceOf(err, Error);
+      assert.equal(err.message, 'wrapped: 17 is too large');
+      done();
+    });
+  });
+
+  it('joins the results of a task group in order', function (done) {
+    addon.square_in_parallel([1, 2, 3, 4], function (err, results) {
+      assert.isNull(err);
+      assert.deepEqual(results, [1, 4, 9, 16]);
+      done();
+    });
+  });
+
+  it('fails a task group with the first error', function (done) {
+    var calls = 0;
+    addon.square_in_parallel([1, 0, 3], function (err, results) {
+      calls++;
+      assert.instanceOf(err, Error);
+      assert.equal(err.message, "can't square zero");
+      assert.isUndefined(results);
+      setTimeout(function () {
+        assert.equal(calls, 1);
+        done();
+      }, 20);
+    });
+  });
+
+  it('joins an empty task group asynchronously', function (done) {
+    var returned = false;
+    addon.square_in_parallel([], function (err, results) {
+      assert.isTrue(returned);
+      assert.deepEqual(results, []);
+      done();
+    });
+    returned = true;
+  });
+
+  it('limits how many tasks in a queue run at once', function (done) {
+    var results = [];
+    addon.sleep_in_queue(6, 2, function (err, n) {
+      assert.isNull(err);
+      results.push(n);
+      if (results.length === 6) {
+        assert.deepEqual(results.slice().sort(), [0, 1, 2, 3, 4, 5]);
+        assert.isAtMost(addon.peak_queued_sleeps(), 2);
+        done();
+      }
+    });
+  });
+
+  it('sends a rooted callback to another thread and back', function (done) {
+    addon.call_root_from_thread(done);
+  });
+
+  it('drops a root on another thread', function (done) {
+    function f() { }
+    assert.strictEqual(addon.drop_root_on_thread(f), f);
+    // The dropped root is freed once the event loop gets to it.
+    setTimeout(done, 10);
+  });
+
+  it('shares a cloned root between threads', function (done) {
+    var received = [];
+    addon.call_root_from_threads(4, function (i) {
+      received.push(i);
+      if (received.length === 4) {
+        assert.sameMembers(received, [0, 1, 2, 3]);
+        done();
+      }
+    });
+  });
+
+  it('runs closures sent through a bounded channel in order', function (done) {
+    var received = [];
+    addon.send_through_bounded_channel(5, function (i) {
+      received.push(i);
+      if (received.length === 5) {
+        assert.deepEqual(received, [0, 1, 2, 3, 4]);
+        done();
+      }
+    });
+  });
+
+  it('refuses closures once a bounded channel is full', function () {
+    assert.equal(addon.fill_bounded_channel(), 2);
+  });
+
+  it('blocks a thread on a JavaScript call', function (done) {
+    addon.add_on_js_from_thread(function (a, b) { return a + b; }, function (n) {
+      assert.equal(n, 30);
+      done();
+    });
+  });
+
+  it('refuses to block the JavaScript thread on itself', function () {
+    assert.equal(addon.block_on_js_thread(), "can't block the JavaScript thread on itself");
+  });
+
+  it('completes a task without scheduling it', function () {
+    assert.equal(addon.complete_square_task(16), 16);
+    assert.throws(function () { addon.complete_square_task('no good') }, Error, /^no good$/);
+  });
+
+  it('runs tasks inline', function () {
+    var results = [];
+    addon.square_inline([2, 0, 5], function (err, n) {
+      results.push(err ? err.message : n);
+    });
+    assert.deepEqual(results, [4, "can't square zero", 25]);
+  });
+
+  it('schedules tasks normally again after running them inline', function (done) {
+    addon.square_inline([], function () {});
+    var sync = true;
+    addon.perform_async_task(function (err, n) {
+      assert.isFalse(sync);
+      assert.equal(n, 17);
+      done();
+    });
+    sync = false;
+  });
+
+  it('waits for a promise from a background task', function (done) {
+    var promise = new Promise(function (resolve) {
+      setTimeout(function () { resolve(21) }, 10);
+    });
+    addon.double_promise_in_task(promise, function (err, n) {
+      if (err) {
+        done(err);
+      } else {
+        assert.equal(n, 42);
+        done();
+      }
+    });
+  });
+
+  it('reports a rejected promise to a background task', function (done) {
+    addon.double_promise_in_task(Promise.reject(new Error('nope')), function (err) {
+      assert.instanceOf(err, Error);
+      assert.equal(err.message, 'promise was rejected: Error: nope');
+      done();
+    });
+  });
+
+  it('reports a promise fulfilled with the wrong type to a background task', function (done) {
+    addon.double_promise_in_task(Promise.resolve('21'), function (err) {
+      assert.instanceOf(err, Error);
+      assert.equal(err.message, "promise was fulfilled with a value that isn't a number");
+      done();
+    });
+  });
+
+  it('does not wait for a pending promise on the JS thread', function () {
+    var promise = new Promise(function () {});
+    assert.equal(addon.wait_for_promise_on_js_thread(promise), "can't wait for a promise on its own JavaScript thread");
+  });
+
+  it('defers callbacks to a microtask and the next tick', function (done) {
+    var calls = [];
+    addon.defer_callbacks(function (kind) {
+      calls.push(kind);
+      if (calls.length === 3) {
+        assert.equal(calls[0], 'sync');
+        assert.sameMembers(calls.slice(1), ['microtask', 'tick']);
+        done();
+      }
+    });
+    assert.deepEqual(calls, ['sync']);
+  });
+
+  it('runs a native timeout', function (done) {
+    var start = Date.now();
+    addon.set_native_timeout(20, function () {
+      assert.isAtLeast(Date.now() - start, 15);
+      done();
+    });
+  });
+
+  it('does not run a cancelled timeout', function (done) {
+    addon.set_cancelled_timeout(function () {
+      done(new Error('cancelled timeout ran'));
+    });
+    setTimeout(done, 20);
+  });
+
+  it('runs a native interval until it is stopped', function (done) {
+    var calls = [];
+    addon.start_native_interval(1, function (n) {
+      calls.push(n);
+      if (n === 3) {
+        addon.stop_native_interval();
+        setTimeout(function () {
+          assert.deepEqual(calls, [1, 2, 3]);
+          done();
+        }, 20);
+      }
+    });
+  });
+
+  (async_hooks.AsyncLocalStorage ? it : it.skip)('completes a task in the async context it was scheduled from', function (done) {
+    var storage = new async_hooks.AsyncLocalStorage();
+    storage.run('task', function () {
+      addon.perform_async_task(function (err, n) {
+        assert.equal(storage.getStore(), 'task');
+        done(err);
+      });
+    });
+  });
+
+  (async_hooks.AsyncLocalStorage ? it : it.skip)('runs a signal in the async context it was created in', function (done) {
+    var storage = new async_hooks.AsyncLocalStorage();
+    storage.run('signal', function () {
+      addon.signal_from_thread(function () {
+        assert.equal(storage.getStore(), 'signal');
+        done();
+      });
+    });
+  });
+
+  (Number(process.versions.modules) >= 57 ? it : it.skip)('reports tasks to async_hooks', function (done) {
+    var types = [];
+    var hook = async_hooks.createHook({
+      init: function (id, type) { types.push(type); }
+    }).enable();
+    addon.perform_async_task(function (err) {
+      hook.disable();
+      assert.include(types, 'neon::Task');
+      done(err);
+    });
+  });
 });
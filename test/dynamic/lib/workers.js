@@ -0,0 +1,94 @@
+var addon = require('../native');
+var assert = require('chai').assert;
+
+var worker_threads;
+try {
+  worker_threads = require('worker_threads');
+} catch (e) { }
+
+describe('worker threads', function() {
+  (worker_threads ? it : it.skip)('initialize the module separately in each worker', function (done) {
+    var source = [
+      "var worker_threads = require('worker_threads');",
+      "var addon = require(worker_threads.workerData);",
+      "addon.count_module_calls();",
+      "worker_threads.parentPort.postMessage([addon.return_js_string(), addon.count_module_calls()]);"
+    ].join('\n');
+    var worker = new worker_threads.Worker(source, { eval: true, workerData: require.resolve('../native') });
+    var before = addon.count_module_calls();
+    worker.on('error', done);
+    worker.on('message', function (message) {
+      assert.deepEqual(message, ['hello node', 2]);
+      assert.equal(addon.count_module_calls(), before + 1);
+      done();
+    });
+  });
+
+  (worker_threads ? it : it.skip)('complete tasks on the worker that scheduled them', function (done) {
+    var source = [
+      "var worker_threads = require('worker_threads');",
+      "var addon = require(worker_threads.workerData);",
+      "addon.perform_async_task(function (err, n) {",
+      "  worker_threads.parentPort.postMessage(err ? err.message : n);",
+      "});"
+    ].join('\n');
+    var worker = new worker_threads.Worker(source, { eval: true, workerData: require.resolve('../native') });
+    worker.on('error', done);
+    worker.on('message', function (message) {
+      assert.equal(message, 17);
+      done();
+    });
+  });
+
+  (worker_threads ? it : it.skip)('keep the classes of each worker separate', function (done) {
+    // The sender hands one of its instances to the receiver, if it can be cloned at
+    // all, and the receiver's own class has to reject it as `this`. Both workers exit
+    // with their instances still alive, which drops them as the module is torn down.
+    var source = [
+      "var worker_threads = require('worker_threads');",
+      "var data = worker_threads.workerData;",
+      "var addon = require(data.addon);",
+      "var user = new addon.User(1, 'some', 'thing', 'else');",
+      "var own = user.full_name();",
+      "if (data.send) {",
+      "  try { data.port.postMessage(user); } catch (e) { data.port.postMessage(null); }",
+      "  data.port.close();",
+      "  worker_threads.parentPort.postMessage(own);",
+      "} else {",
+      "  data.port.once('message', function (other) {",
+      "    var rejected = other === null;",
+      "    try { addon.User.prototype.full_name.call(other); } catch (e) { rejected = rejected || e instanceof TypeError; }",
+      "    data.port.close();",
+      "    worker_threads.parentPort.postMessage([own, rejected]);",
+      "  });",
+      "}"
+    ].join('\n');
+    var addonPath = require.resolve('../native');
+    var channel = new worker_threads.MessageChannel();
+    var sender = new worker_threads.Worker(source, {
+      eval: true,
+      workerData: { addon: addonPath, port: channel.port1, send: true },
+      transferList: [channel.port1]
+    });
+    var receiver = new worker_threads.Worker(source, {
+      eval: true,
+      workerData: { addon: addonPath, port: channel.port2, send: false },
+      transferList: [channel.port2]
+    });
+    var messages = {};
+    var exited = 0;
+    sender.on('message', function (message) { messages.sender = message; });
+    receiver.on('message', function (message) { messages.receiver = message; });
+    [sender, receiver].forEach(function (worker) {
+      worker.on('error', done);
+      worker.on('exit', function (code) {
+        assert.equal(code, 0);
+        if (++exited === 2) {
+          assert.equal(messages.sender, 'some thing');
+          assert.deepEqual(messages.receiver, ['some thing', true]);
+          done();
+        }
+      });
+    });
+  });
+});
@@ -1,6 +1,8 @@
 use neon::vm::{Call, JsResult};
 use neon::mem::Handle;
-use neon::js::{JsNumber, JsString, JsArray, Object};
+use neon::js::{JsNumber, JsString, JsArray, JsValue, JsUndefined, Object};
+use neon::js::iter::JsIter;
+use neon::scope::Scope;
 
 pub fn return_js_array(call: Call) -> JsResult<JsArray> {
     Ok(JsArray::new(call.scope, 0))
@@ -19,3 +21,65 @@ pub fn return_js_array_with_string(call: Call) -> JsResult<JsArray> {
     array.set(0, JsString::new(scope, "hello node").unwrap())?;
     Ok(array)
 }
+
+pub fn return_js_array_of_squares(mut call: Call) -> JsResult<JsArray> {
+    let n = call.argument::<u32>(0)?;
+    JsArray::from_iter(call.scope, (0..n).map(|i| i * i))
+}
+
+pub fn join_js_array_of_strings(call: Call) -> JsResult<JsString> {
+    let scope = call.scope;
+    let array = call.arguments.require(scope, 0)?.check::<JsArray>()?;
+    let strings: Vec<String> = array.to_vec_of(scope)?;
+    Ok(JsString::new(scope, &strings.join("-")).unwrap())
+}
+
+pub fn sum_js_iterable(call: Call) -> JsResult<JsNumber> {
+    let scope = call.scope;
+    let iterable = call.arguments.require(scope, 0)?;
+    let mut sum = 0.0;
+    for value in JsIter::new(scope, iterable)? {
+        sum += value?.check::<JsNumber>()?.value();
+    }
+    Ok(JsNumber::new(scope, sum))
+}
+
+pub fn first_of_js_iterable(call: Call) -> JsResult<JsValue> {
+    let scope = call.scope;
+    let iterable = call.arguments.require(scope, 0)?;
+    let mut iter = JsIter::new(scope, iterable)?;
+    let first = match iter.next() {
+        Some(value) => value?,
+        None => JsUndefined::new().upcast()
+    };
+    iter.close()?;
+    Ok(first)
+}
+
+pub fn return_js_array_from_slice(call: Call) -> JsResult<JsArray> {
+    let scope = call.scope;
+    let values: Vec<Handle<JsValue>> = vec![
+        JsNumber::new(scope, 1.0).upcast(),
+        JsString::new(scope, "two").unwrap().upcast(),
+        JsUndefined::new().upcast()
+    ];
+    JsArray::from_slice(scope, &values)
+}
+
+pub fn return_js_array_from_builder(mut call: Call) -> JsResult<JsArray> {
+    let words = call.argument::<Vec<String>>(0)?;
+    let mut builder = call.scope.array_with_capacity(words.len() + 1);
+    for word in words {
+        if !word.is_empty() {
+            builder.push(call.scope, word.len() as u32)?;
+        }
+    }
+    builder.push_handle(JsUndefined::new());
+    builder.build(call.scope)
+}
+
+pub fn return_js_array_from_f64_slice(mut call: Call) -> JsResult<JsArray> {
+    let len = call.argument::<u32>(0)?;
+    let values: Vec<f64> = (0..len).map(|i| i as f64 / 2.0).collect();
+    JsArray::from_f64_slice(call.scope, &values)
+}
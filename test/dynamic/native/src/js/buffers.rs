@@ -0,0 +1,165 @@
+use std::cell::RefCell;
+use neon::vm::{Call, JsResult, Lock};
+use neon::js::{JsNumber, JsUndefined, JsBoolean, JsString};
+use neon::scope::Scope;
+use neon::js::binary::{JsBuffer, JsArrayBuffer, JsBigInt64Array, JsBigUint64Array, BufferPool};
+
+#[repr(C)]
+#[derive(Clone, Copy, BufferLayout)]
+pub struct MessageHeader {
+    id: u32,
+    flags: [u8; 4],
+    timestamp: f64,
+}
+
+pub fn return_js_buffer(call: Call) -> JsResult<JsBuffer> {
+    JsBuffer::new(call.scope, 16)
+}
+
+pub fn return_js_buffer_from_slice(call: Call) -> JsResult<JsBuffer> {
+    JsBuffer::from_slice(call.scope, b"hello")
+}
+
+pub fn check_js_buffer_length(call: Call) -> JsResult<JsNumber> {
+    let scope = call.scope;
+    let mut buffer = call.arguments.require(scope, 0)?.check::<JsBuffer>()?;
+    let len = buffer.grab(|data| data.len());
+    Ok(JsNumber::new(scope, len as f64))
+}
+
+pub fn lock_buffer_and_array_buffer(call: Call) -> JsResult<JsNumber> {
+    let scope = call.scope;
+    let mut buffer = call.arguments.require(scope, 0)?.check::<JsBuffer>()?;
+    let mut array_buffer = call.arguments.require(scope, 1)?.check::<JsArrayBuffer>()?;
+    let len = (&mut *buffer, &mut *array_buffer).try_grab(|(a, b)| a.len() + b.len())?;
+    Ok(JsNumber::new(scope, len as f64))
+}
+
+pub fn lock_buffer_twice(call: Call) -> JsResult<JsNumber> {
+    let scope = call.scope;
+    let mut buffer = call.arguments.require(scope, 0)?.check::<JsBuffer>()?;
+    let mut alias = buffer;
+    let len = (&mut *buffer, &mut *alias).grab(|(a, b)| a.len() + b.len());
+    Ok(JsNumber::new(scope, len as f64))
+}
+
+pub fn sum_u32_array_buffer(call: Call) -> JsResult<JsNumber> {
+    let scope = call.scope;
+    let mut buffer = call.arguments.require(scope, 0)?.check::<JsArrayBuffer>()?;
+    let sum = buffer.grab(|data| {
+        data.as_slice::<u32>().iter().fold(0, |sum, &n| sum + n)
+    });
+    Ok(JsNumber::new(scope, sum as f64))
+}
+
+pub fn increment_buffer_bytes(call: Call) -> JsResult<JsUndefined> {
+    let scope = call.scope;
+    let mut buffer = call.arguments.require(scope, 0)?.check::<JsBuffer>()?;
+    buffer.grab(|mut data| {
+        for byte in data.as_mut_slice::<u8>() {
+            *byte = byte.wrapping_add(1);
+        }
+    });
+    Ok(JsUndefined::new())
+}
+
+pub fn return_external_js_buffer(call: Call) -> JsResult<JsBuffer> {
+    let data: Vec<u8> = (0..10).collect();
+    JsBuffer::external(call.scope, data)
+}
+
+pub fn write_buffer_header(call: Call) -> JsResult<JsNumber> {
+    let scope = call.scope;
+    let mut buffer = call.arguments.require(scope, 0)?.check::<JsBuffer>()?;
+    let n = buffer.grab(|mut data| {
+        data.fill(0);
+        data.write_u32_be(0, 0x01020304);
+        data.write_f64_le(4, 1.5);
+        data.copy_within(0..4, 12);
+        data.read_u32_le(12)
+    });
+    Ok(JsNumber::new(scope, n as f64))
+}
+
+pub fn detach_array_buffer(call: Call) -> JsResult<JsBoolean> {
+    let scope = call.scope;
+    let buffer = call.arguments.require(scope, 0)?.check::<JsArrayBuffer>()?;
+    buffer.detach(scope)?;
+    Ok(JsBoolean::new(scope, buffer.is_detached()))
+}
+
+thread_local! {
+    static POOL: RefCell<Option<BufferPool>> = RefCell::new(None);
+}
+
+pub fn return_pooled_js_buffer(call: Call) -> JsResult<JsBuffer> {
+    let scope = call.scope;
+    POOL.with(|pool| {
+        let mut pool = pool.borrow_mut();
+        if pool.is_none() {
+            *pool = Some(BufferPool::new(scope, 64, 4));
+        }
+        pool.as_ref().unwrap().get(scope)
+    })
+}
+
+pub fn pooled_js_buffers_available(call: Call) -> JsResult<JsNumber> {
+    let available = POOL.with(|pool| pool.borrow().as_ref().map(|pool| pool.available()).unwrap_or(0));
+    Ok(JsNumber::new(call.scope, available as f64))
+}
+
+pub fn bump_message_header(call: Call) -> JsResult<JsNumber> {
+    let scope = call.scope;
+    let mut buffer = call.arguments.require(scope, 0)?.check::<JsArrayBuffer>()?;
+    let id = buffer.grab(|mut data| {
+        let header = data.view_mut::<MessageHeader>(0);
+        header.id += 1;
+        header.flags[0] = 1;
+        header.timestamp *= 2.0;
+        header.id
+    });
+    Ok(JsNumber::new(scope, id as f64))
+}
+
+pub fn sum_biguint64_array(call: Call) -> JsResult<JsNumber> {
+    let scope = call.scope;
+    let mut array = call.arguments.require(scope, 0)?.check::<JsBigUint64Array>()?;
+    let sum = array.grab(|ids| ids.iter().fold(0, |sum, &id| sum + id));
+    Ok(JsNumber::new(scope, sum as f64))
+}
+
+pub fn return_bigint64_array(call: Call) -> JsResult<JsBigInt64Array> {
+    let scope = call.scope;
+    let mut array = JsBigInt64Array::new(scope, 3)?;
+    array.grab(|timestamps| {
+        timestamps[0] = -1;
+        timestamps[2] = i64::max_value();
+    });
+    Ok(array)
+}
+
+pub fn encode_buffer_base64(call: Call) -> JsResult<JsString> {
+    let scope = call.scope;
+    let mut buffer = call.arguments.require(scope, 0)?.check::<JsBuffer>()?;
+    let bytes = buffer.grab(|data| data.as_slice().to_vec());
+    scope.encode_base64(&bytes)
+}
+
+pub fn decode_buffer_base64(call: Call) -> JsResult<JsBuffer> {
+    let scope = call.scope;
+    let string = call.arguments.require(scope, 0)?.check::<JsString>()?;
+    scope.decode_base64(string)
+}
+
+pub fn encode_buffer_hex(call: Call) -> JsResult<JsString> {
+    let scope = call.scope;
+    let mut buffer = call.arguments.require(scope, 0)?.check::<JsBuffer>()?;
+    let bytes = buffer.grab(|data| data.as_slice().to_vec());
+    scope.encode_hex(&bytes)
+}
+
+pub fn decode_buffer_hex(call: Call) -> JsResult<JsBuffer> {
+    let scope = call.scope;
+    let string = call.arguments.require(scope, 0)?.check::<JsString>()?;
+    scope.decode_hex(string)
+}
@@ -1,6 +1,9 @@
-use neon::js::{JsString, JsNumber};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use neon::js::{JsString, JsNumber, JsUndefined, JsValue, JsArray, Object};
+use neon::js::iter::JsIterator;
 use neon::mem::Handle;
-use neon::vm::Lock;
+use neon::vm::{Call, JsResult, VmResult, Lock};
+use neon::js::class::MethodContext;
 use neon::js::error::{JsError, Kind};
 
 pub struct User {
@@ -10,8 +13,47 @@ pub struct User {
   email: String,
 }
 
+pub struct Point {
+  x: f64,
+  y: f64,
+}
+
 type Unit = ();
 
+static DROPPED: AtomicUsize = AtomicUsize::new(0);
+
+pub struct DropCounter;
+
+impl Drop for DropCounter {
+  fn drop(&mut self) {
+    DROPPED.fetch_add(1, Ordering::SeqCst);
+  }
+}
+
+pub fn user_kind(call: Call) -> JsResult<JsString> {
+  JsString::new_or_throw(call.scope, "user")
+}
+
+pub fn dropped_count(call: Call) -> JsResult<JsNumber> {
+  Ok(JsNumber::new(call.scope, DROPPED.load(Ordering::SeqCst) as f64))
+}
+
+pub fn squares(call: Call) -> JsResult<JsValue> {
+  let counter = DropCounter;
+  let squares = (0u64..).map(move |n| {
+    let _ = &counter;
+    n * n
+  });
+  Ok(JsIterator::from_iter(call.scope, squares)?.upcast())
+}
+
+fn user_initials(call: &mut MethodContext<JsUser>) -> VmResult<String> {
+  let mut user = call.this()?;
+  Ok(user.grab(|user| {
+    user.first_name.chars().take(1).chain(user.last_name.chars().take(1)).collect()
+  }))
+}
+
 declare_types! {
   pub class JsPanickyAllocator for Unit {
     init(_) {
@@ -33,6 +75,79 @@ declare_types! {
     }
   }
 
+  pub class JsDropCounter for DropCounter {
+    init(_) {
+      Ok(DropCounter)
+    }
+  }
+
+  pub class JsByteBuffer as ByteBuffer for Vec<u8> {
+    init(call) {
+      let scope = call.scope;
+      let len = call.arguments.require(scope, 0)?.check::<JsNumber>()?.value() as usize;
+      Ok(vec![0; len])
+    }
+
+    size_hint(bytes) {
+      bytes.capacity()
+    }
+
+    method grow(mut call) {
+      let additional = call.arguments.require(call.scope, 0)?.check::<JsNumber>()?.value() as usize;
+      call.this_internals_mut(|bytes| {
+        let len = bytes.len();
+        bytes.resize(len + additional, 0);
+        bytes.shrink_to_fit();
+      })?;
+      Ok(JsUndefined::new().upcast())
+    }
+  }
+
+  pub class JsRange as Range for Vec<f64> {
+    init(call) {
+      let scope = call.scope;
+      let end = call.arguments.require(scope, 0)?.check::<JsNumber>()?.value() as u32;
+      Ok((0..end).map(|n| n as f64).collect())
+    }
+
+    iterator(mut call) {
+      let items = call.this_internals(|items| items.clone())?;
+      Ok(JsIterator::new(call.scope, items.into_iter(), |scope, n| {
+        Ok(JsNumber::new(scope, n).upcast::<JsValue>())
+      })?.upcast())
+    }
+
+    async_iterator(mut call) {
+      let items = call.this_internals(|items| items.clone())?;
+      Ok(JsIterator::new(call.scope, items.into_iter().rev(), |scope, n| {
+        Ok(JsNumber::new(scope, n).upcast::<JsValue>())
+      })?.upcast())
+    }
+  }
+
+  pub class JsPoint for Point {
+    init(mut call) {
+      overload!(call, "Point", {
+        (x: JsNumber, y: JsNumber) => {
+          Ok(Point { x: x.value(), y: y.value() })
+        }
+        (coords: JsArray) => {
+          let x = coords.get(call.scope, 0)?.check::<JsNumber>()?.value();
+          let y = coords.get(call.scope, 1)?.check::<JsNumber>()?.value();
+          Ok(Point { x: x, y: y })
+        }
+        () => {
+          Ok(Point { x: 0.0, y: 0.0 })
+        }
+      })
+    }
+
+    method sum(mut call) {
+      let sum = call.this_internals(|point| point.x + point.y)?;
+      Ok(JsNumber::new(call.scope, sum).upcast())
+    }
+  }
+
   pub class JsUser for User {
     init(call) {
       let scope = call.scope;
@@ -75,6 +190,24 @@ declare_types! {
       }
     }
 
+    method full_name(mut call) {
+      let full_name = call.this_internals(|user| {
+        format!("{} {}", user.first_name, user.last_name)
+      })?;
+      Ok(JsString::new_or_throw(call.scope, &full_name[..])?.upcast())
+    }
+
+    method initials(mut call) {
+      let initials = user_initials(&mut call)?;
+      Ok(JsString::new_or_throw(call.scope, &initials[..])?.upcast())
+    }
+
+    method set_email(mut call) {
+      let email: String = call.arguments.require(call.scope, 0)?.check::<JsString>()?.value();
+      call.this_internals_mut(|user| { user.email = email; })?;
+      Ok(JsUndefined::new().upcast())
+    }
+
     method panic(_) {
       panic!("User.prototype.panic")
     }
@@ -0,0 +1,174 @@
+use std::collections::HashMap;
+use std::time::{Duration, SystemTime};
+use chrono::{DateTime, Utc, SecondsFormat};
+use neon::vm::{Call, JsResult};
+use neon::js::{Value, Object, JsNumber, JsString, JsArray, JsObject, JsValue};
+use neon::mem::Handle;
+use neon::js::date::JsDate;
+use neon::convert::TryIntoJs;
+
+pub fn sum_number_vec(mut call: Call) -> JsResult<JsNumber> {
+    let numbers = call.argument::<Vec<f64>>(0)?;
+    Ok(JsNumber::new(call.scope, numbers.iter().sum()))
+}
+
+pub fn greet_optional_name(mut call: Call) -> JsResult<JsString> {
+    let name = call.argument::<Option<String>>(0)?.unwrap_or(String::from("stranger"));
+    format!("hello, {}", name).try_into_js(call.scope)
+}
+
+pub fn split_pair(mut call: Call) -> JsResult<JsArray> {
+    let (name, count) = call.argument::<(String, u32)>(0)?;
+    (count, name).try_into_js(call.scope)
+}
+
+pub fn double_map_values(mut call: Call) -> JsResult<JsObject> {
+    let map = call.argument::<HashMap<String, i32>>(0)?;
+    let doubled: HashMap<String, i32> = map.into_iter().map(|(k, v)| (k, v * 2)).collect();
+    doubled.try_into_js(call.scope)
+}
+
+pub fn describe_args(mut call: Call) -> JsResult<JsString> {
+    let (count, label, loud) = call.args::<(f64, String, Option<bool>)>()?;
+    let description = format!("{} {}", count, label);
+    if loud.unwrap_or(false) {
+        description.to_uppercase().try_into_js(call.scope)
+    } else {
+        description.try_into_js(call.scope)
+    }
+}
+
+#[derive(ToJsObject, FromJsObject)]
+struct Rectangle {
+    width: f64,
+    height: f64,
+    #[neon(rename = "fillColor")]
+    fill_color: Option<String>
+}
+
+pub fn scale_rectangle(mut call: Call) -> JsResult<JsObject> {
+    let (rect, factor) = call.args::<(Rectangle, f64)>()?;
+    let scaled = Rectangle {
+        width: rect.width * factor,
+        height: rect.height * factor,
+        fill_color: rect.fill_color
+    };
+    scaled.try_into_js(call.scope)
+}
+
+#[derive(FromJsOptions)]
+struct PadOptions {
+    width: u32,
+    fill: String,
+    #[neon(rename = "padStart")]
+    pad_start: bool
+}
+
+impl Default for PadOptions {
+    fn default() -> PadOptions {
+        PadOptions { width: 8, fill: String::from(" "), pad_start: false }
+    }
+}
+
+pub fn pad_string(mut call: Call) -> JsResult<JsString> {
+    let s = call.argument::<String>(0)?;
+    let options = call.options::<PadOptions>(1)?;
+    let count = (options.width as usize).saturating_sub(s.chars().count());
+    let padding: String = options.fill.chars().cycle().take(count).collect();
+    let padded = if options.pad_start { padding + &s } else { s + &padding };
+    padded.try_into_js(call.scope)
+}
+
+pub fn describe_color(mut call: Call) -> JsResult<JsArray> {
+    let [r, g, b, a] = call.argument::<[u8; 4]>(0)?;
+    let brightness = (r as f64 + g as f64 + b as f64) / (3.0 * 255.0);
+    let hex = format!("#{:02x}{:02x}{:02x}{:02x}", r, g, b, a);
+    (brightness, hex).try_into_js(call.scope)
+}
+
+pub fn invert_color(mut call: Call) -> JsResult<JsArray> {
+    let rgb = call.argument::<[u8; 3]>(0)?;
+    [255 - rgb[0], 255 - rgb[1], 255 - rgb[2]].try_into_js(call.scope)
+}
+
+pub fn postpone_date(mut call: Call) -> JsResult<JsDate> {
+    let (time, delay) = call.args::<(SystemTime, Duration)>()?;
+    (time + delay).try_into_js(call.scope)
+}
+
+pub fn time_between_dates(mut call: Call) -> JsResult<JsNumber> {
+    let (start, end) = call.args::<(SystemTime, SystemTime)>()?;
+    let elapsed = end.duration_since(start).unwrap_or(Duration::from_secs(0));
+    elapsed.try_into_js(call.scope)
+}
+
+pub fn format_chrono_date(mut call: Call) -> JsResult<JsString> {
+    let date = call.argument::<DateTime<Utc>>(0)?;
+    date.to_rfc3339_opts(SecondsFormat::Millis, true).try_into_js(call.scope)
+}
+
+#[derive(ToJsEnum, FromJsEnum)]
+enum Direction {
+    North,
+    East,
+    South,
+    West
+}
+
+pub fn turn_right(mut call: Call) -> JsResult<JsString> {
+    let next = match call.argument::<Direction>(0)? {
+        Direction::North => Direction::East,
+        Direction::East => Direction::South,
+        Direction::South => Direction::West,
+        Direction::West => Direction::North
+    };
+    next.try_into_js(call.scope)
+}
+
+#[derive(ToJsEnum, FromJsEnum)]
+#[neon(tag = "kind")]
+enum Shape {
+    Circle { radius: f64 },
+    Rectangle { width: f64, height: f64 },
+    #[neon(rename = "empty")]
+    Empty
+}
+
+pub fn shape_area(mut call: Call) -> JsResult<JsNumber> {
+    let area = match call.argument::<Shape>(0)? {
+        Shape::Circle { radius } => ::std::f64::consts::PI * radius * radius,
+        Shape::Rectangle { width, height } => width * height,
+        Shape::Empty => 0.0
+    };
+    Ok(JsNumber::new(call.scope, area))
+}
+
+pub fn bounding_square(mut call: Call) -> JsResult<JsObject> {
+    let square = match call.argument::<Shape>(0)? {
+        Shape::Circle { radius } => Shape::Rectangle { width: radius * 2.0, height: radius * 2.0 },
+        Shape::Rectangle { width, height } => {
+            let side = width.max(height);
+            Shape::Rectangle { width: side, height: side }
+        }
+        Shape::Empty => Shape::Empty
+    };
+    square.try_into_js(call.scope)
+}
+
+pub fn coerce_value(call: Call) -> JsResult<JsArray> {
+    let value = call.arguments.require(call.scope, 0)?;
+    let coerced: Vec<Handle<JsValue>> = vec![
+        value.to_js_string(call.scope)?.upcast(),
+        value.to_js_number(call.scope)?.upcast(),
+        value.to_js_boolean(call.scope).upcast()
+    ];
+    JsArray::from_slice(call.scope, &coerced)
+}
+
+pub fn box_value(call: Call) -> JsResult<JsArray> {
+    let value = call.arguments.require(call.scope, 0)?;
+    let object = value.to_object(call.scope)?;
+    let length = object.get(call.scope, "length")?;
+    let boxed: Vec<Handle<JsValue>> = vec![object.upcast(), length];
+    JsArray::from_slice(call.scope, &boxed)
+}
@@ -1,7 +1,10 @@
-use neon::vm::{Call, JsResult, This, FunctionCall};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use neon::vm::{Call, JsResult, VmResult, This, FunctionCall, Module};
 use neon::mem::Handle;
-use neon::js::{JsNumber, JsNull, JsFunction, Object, JsValue, JsUndefined, JsString, Value};
+use neon::scope::{Scope, MicrotaskPolicy};
+use neon::js::{JsNumber, JsArray, JsNull, JsFunction, JsObject, Object, JsValue, JsUndefined, JsString, Value};
 use neon::js::error::{JsError, Kind};
+use neon::profiler::Profiler;
 
 fn add1(call: Call) -> JsResult<JsNumber> {
     let scope = call.scope;
@@ -9,10 +12,179 @@ fn add1(call: Call) -> JsResult<JsNumber> {
     Ok(JsNumber::new(scope, x + 1.0))
 }
 
+fn sub1(call: Call) -> JsResult<JsNumber> {
+    let scope = call.scope;
+    let x = call.arguments.require(scope, 0)?.check::<JsNumber>()?.value();
+    Ok(JsNumber::new(scope, x - 1.0))
+}
+
+pub fn init_arithmetic(mut m: Module) -> VmResult<()> {
+    m.export("add1", add1)?;
+    m.export("sub1", sub1)?;
+    Ok(())
+}
+
+pub fn init_included(mut m: Module) -> VmResult<()> {
+    m.export("included_add1", add1)?;
+    m.set_instance_data(CallCounter(0));
+    Ok(())
+}
+
+pub fn initialize_fresh_exports(call: Call) -> JsResult<JsObject> {
+    let exports = JsObject::new(call.scope);
+    Module::initialize(exports, init_namespaces);
+    Ok(exports)
+}
+
+struct CallCounter(u32);
+
+pub fn count_module_calls(mut call: Call) -> JsResult<JsNumber> {
+    let count = match call.instance_data::<CallCounter>() {
+        Some(mut counter) => {
+            counter.0 += 1;
+            counter.0
+        }
+        None => 0
+    };
+    Ok(JsNumber::new(call.scope, count as f64))
+}
+
+pub fn init_namespaces(mut m: Module) -> VmResult<()> {
+    {
+        let mut math = m.namespace("nested.math")?;
+        math.export("add1", add1)?;
+        math.namespace("inverse")?.export("sub1", sub1)?;
+    }
+    m.namespace("nested")?.export("add1", add1)?;
+    Ok(())
+}
+
+static LAZY_TABLE_BUILDS: AtomicUsize = AtomicUsize::new(0);
+
+pub fn build_lazy_table(call: Call) -> JsResult<JsArray> {
+    LAZY_TABLE_BUILDS.fetch_add(1, Ordering::SeqCst);
+    let squares: Vec<f64> = (0..10).map(|i| (i * i) as f64).collect();
+    JsArray::from_f64_slice(call.scope, &squares)
+}
+
+pub fn lazy_table_builds(call: Call) -> JsResult<JsNumber> {
+    Ok(JsNumber::new(call.scope, LAZY_TABLE_BUILDS.load(Ordering::SeqCst) as f64))
+}
+
+pub fn require_js_module(call: Call) -> JsResult<JsValue> {
+    let scope = call.scope;
+    let path = call.arguments.require(scope, 0)?.check::<JsString>()?.value();
+    scope.require(&path)
+}
+
 pub fn return_js_function(call: Call) -> JsResult<JsFunction> {
     JsFunction::new(call.scope, add1)
 }
 
+static DROPPED_CLOSURES: AtomicUsize = AtomicUsize::new(0);
+
+struct ClosureState {
+    count: f64,
+    step: f64
+}
+
+impl Drop for ClosureState {
+    fn drop(&mut self) {
+        DROPPED_CLOSURES.fetch_add(1, Ordering::SeqCst);
+    }
+}
+
+pub fn make_counter(call: Call) -> JsResult<JsFunction> {
+    let scope = call.scope;
+    let step = call.arguments.require(scope, 0)?.check::<JsNumber>()?.value();
+    let mut state = ClosureState { count: 0.0, step: step };
+    JsFunction::from_closure(scope, move |call| {
+        state.count += state.step;
+        Ok(JsNumber::new(call.scope, state.count))
+    })
+}
+
+pub fn call_argument(call: Call) -> JsResult<JsValue> {
+    let scope = call.scope;
+    let f = call.arguments.require(scope, 0)?.check::<JsFunction>()?;
+    let args: Vec<Handle<JsValue>> = vec![];
+    f.call(scope, JsNull::new(), args)
+}
+
+pub fn make_reentrant_caller(call: Call) -> JsResult<JsFunction> {
+    JsFunction::from_closure(call.scope, call_argument)
+}
+
+pub fn make_recursive_caller(call: Call) -> JsResult<JsFunction> {
+    JsFunction::new(call.scope, call_argument)
+}
+
+// Splits text fed to it in chunks into lines, returning the lines each chunk completes.
+fn split_lines<'c>(partial: &mut String, call: Call<'c>) -> JsResult<'c, JsArray> {
+    let scope = call.scope;
+    let chunk = call.arguments.require(scope, 0)?.check::<JsString>()?.value();
+    partial.push_str(&chunk);
+    let mut lines = Vec::new();
+    while let Some(end) = partial.find('\n') {
+        lines.push(partial[..end].to_string());
+        partial.drain(..end + 1);
+    }
+    let array = JsArray::new(scope, lines.len() as u32);
+    for (i, line) in lines.iter().enumerate() {
+        array.set(i as u32, JsString::new_or_throw(scope, line)?)?;
+    }
+    Ok(array)
+}
+
+pub fn make_line_splitter(call: Call) -> JsResult<JsFunction> {
+    JsFunction::with_state(call.scope, String::new(), split_lines)
+}
+
+pub fn dropped_closures(call: Call) -> JsResult<JsNumber> {
+    Ok(JsNumber::new(call.scope, DROPPED_CLOSURES.load(Ordering::SeqCst) as f64))
+}
+
+pub fn call_with_undefined_this(call: Call) -> JsResult<JsArray> {
+    let scope = call.scope;
+    let f = call.arguments.require(scope, 0)?.check::<JsFunction>()?;
+    let one = JsNumber::new(scope, 1.0);
+    let two = JsString::new_or_throw(scope, "two")?;
+    let results = JsArray::new(scope, 3);
+    results.set(0, f.call0(scope)?)?;
+    results.set(1, f.call1(scope, one)?)?;
+    results.set(2, f.call2(scope, one, two)?)?;
+    f.exec(scope, &[two.upcast(), one.upcast()])?;
+    Ok(results)
+}
+
+pub fn describe_js_function(call: Call) -> JsResult<JsObject> {
+    let scope = call.scope;
+    let f = call.arguments.require(scope, 0)?.check::<JsFunction>()?;
+    let result = JsObject::new(scope);
+    let name = f.name(scope);
+    result.set("name", JsString::new_or_throw(scope, &name)?)?;
+    let length = f.length(scope)?;
+    result.set("length", JsNumber::new(scope, length as f64))?;
+    if let Some(location) = f.location(scope) {
+        result.set("resource", JsString::new_or_throw(scope, &location.resource)?)?;
+        result.set("line", JsNumber::new(scope, location.line as f64))?;
+        result.set("column", JsNumber::new(scope, location.column as f64))?;
+    }
+    Ok(result)
+}
+
+pub fn return_named_js_function(call: Call) -> JsResult<JsFunction> {
+    let scope = call.scope;
+    let name = call.arguments.require(scope, 0)?.check::<JsString>()?.value();
+    let f = JsFunction::new(scope, add1)?;
+    f.set_name(scope, &name)?;
+    Ok(f)
+}
+
+pub fn return_cached_js_function(call: Call) -> JsResult<JsFunction> {
+    JsFunction::new_cached(call.scope, add1)
+}
+
 pub fn call_js_function(call: Call) -> JsResult<JsNumber> {
     let scope = call.scope;
     let f = call.arguments.require(scope, 0)?.check::<JsFunction>()?;
@@ -20,6 +192,40 @@ pub fn call_js_function(call: Call) -> JsResult<JsNumber> {
     f.call(scope, JsNull::new(), args)?.check::<JsNumber>()
 }
 
+pub fn call_js_function_and_run_microtasks(call: Call) -> JsResult<JsValue> {
+    let scope = call.scope;
+    let f = call.arguments.require(scope, 0)?.check::<JsFunction>()?;
+    let args: Vec<Handle<JsValue>> = vec![];
+    let result = f.call(scope, JsNull::new(), args)?;
+    scope.run_microtasks();
+    Ok(result)
+}
+
+pub fn return_microtask_policy(call: Call) -> JsResult<JsString> {
+    let name = match call.scope.microtask_policy() {
+        MicrotaskPolicy::Explicit => "explicit",
+        MicrotaskPolicy::Scoped => "scoped",
+        MicrotaskPolicy::Auto => "auto"
+    };
+    Ok(JsString::new(call.scope, name).unwrap())
+}
+
+pub fn profile_js_function(call: Call) -> JsResult<JsObject> {
+    let scope = call.scope;
+    let f = call.arguments.require(scope, 0)?.check::<JsFunction>()?;
+    let profiler = Profiler::start(scope, "profile_js_function")?;
+    let args: Vec<Handle<JsValue>> = vec![];
+    f.call(scope, JsNull::new(), args)?;
+    profiler.stop(scope)
+}
+
+pub fn write_heap_snapshot(call: Call) -> JsResult<JsUndefined> {
+    let scope = call.scope;
+    let path = call.arguments.require(scope, 0)?.check::<JsString>()?.value();
+    scope.write_heap_snapshot(path)?;
+    Ok(JsUndefined::new())
+}
+
 pub fn construct_js_function(call: Call) -> JsResult<JsNumber> {
     let scope = call.scope;
     let f = call.arguments.require(scope, 0)?.check::<JsFunction>()?;
@@ -54,3 +260,58 @@ pub fn panic_after_throw(_: Call) -> JsResult<JsUndefined> {
     JsError::throw::<()>(Kind::RangeError, "entering throw state with a RangeError").unwrap_err();
     panic!("this should override the RangeError")
 }
+
+pub fn sum_all_arguments(call: Call) -> JsResult<JsNumber> {
+    let args = call.argument_list();
+    let mut sum = 0.0;
+    for arg in &args {
+        sum += arg.check::<JsNumber>()?.value();
+    }
+    Ok(JsNumber::new(call.scope, sum))
+}
+
+pub fn join_string_arguments(call: Call) -> JsResult<JsString> {
+    let strings = call.argument_list()
+        .into_iter()
+        .map(|arg| arg.check::<JsString>().map(|s| s.value()))
+        .collect::<VmResult<Vec<String>>>()?;
+    Ok(JsString::new(call.scope, &strings.join(",")).unwrap())
+}
+
+pub fn add_with_default_step(mut call: Call) -> JsResult<JsString> {
+    let x = call.arguments.require(call.scope, 0)?.check::<JsNumber>()?.value();
+    let one = JsNumber::new(call.scope, 1.0);
+    let step = call.argument_or::<JsNumber>(1, one)?.value();
+    let label = match call.argument_opt::<JsString>(2)? {
+        Some(label) => label.value(),
+        None => "sum".to_string()
+    };
+    Ok(JsString::new(call.scope, &format!("{}: {}", label, x + step)).unwrap())
+}
+
+pub fn fast_add1(call: Call) -> JsResult<JsNumber> {
+    add1(call)
+}
+
+pub fn add_two(call: Call) -> JsResult<JsNumber> {
+    let scope = call.scope;
+    let x = call.arguments.require(scope, 0)?.check::<JsNumber>()?.value();
+    let y = call.arguments.require(scope, 1)?.check::<JsNumber>()?.value();
+    Ok(JsNumber::new(scope, x + y))
+}
+
+pub fn test_add1(call: Call) -> VmResult<()> {
+    let scope = call.scope;
+    let f = JsFunction::new(scope, add1)?;
+    let two = JsNumber::new(scope, 2.0);
+    let result = f.call1(scope, two)?.check::<JsNumber>()?;
+    assert_eq!(result.value(), 3.0);
+    Ok(())
+}
+
+pub fn test_module_exports(mut m: Module) -> VmResult<()> {
+    m.export("add1", add1)?;
+    let exported = m.exports.get(m.scope, "add1")?.check::<JsFunction>()?;
+    assert_eq!(exported.name(m.scope), "add1");
+    Ok(())
+}
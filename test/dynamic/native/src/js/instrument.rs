@@ -0,0 +1,78 @@
+use neon::vm::{Call, JsResult};
+#[cfg(feature = "debug-handles")]
+use neon::mem::Handle;
+use neon::js::{JsObject, JsNumber, JsUndefined, Object};
+use neon::scope::Scope;
+use neon::instrument;
+use neon::gc::{self, GcCallbackId, GcEvent, GcPhase};
+use std::cell::Cell;
+#[cfg(feature = "debug-handles")]
+use std::mem;
+
+pub fn neon_diagnostics(call: Call) -> JsResult<JsObject> {
+    instrument::diagnostics(call.scope)
+}
+
+pub fn reset_neon_diagnostics(_: Call) -> JsResult<JsUndefined> {
+    instrument::reset();
+    Ok(JsUndefined::new())
+}
+
+// Extends a handle's lifetime past its scope, which only `unsafe` code can do.
+#[cfg(feature = "debug-handles")]
+fn leak_handle<'a, 'b>(handle: Handle<'a, JsNumber>) -> Handle<'b, JsNumber> {
+    unsafe { mem::transmute(handle) }
+}
+
+#[cfg(feature = "debug-handles")]
+pub fn use_handle_after_scope(call: Call) -> JsResult<JsNumber> {
+    let scope = call.scope;
+    let leaked = scope.nested(|scope| leak_handle(JsNumber::new(scope, 1.0)));
+    Ok(JsNumber::new(scope, leaked.value()))
+}
+
+pub fn undefined_from_nested_scope(call: Call) -> JsResult<JsUndefined> {
+    let undefined = call.scope.nested(|_| JsUndefined::new());
+    Ok(undefined)
+}
+
+pub fn count_live_handles(call: Call) -> JsResult<JsObject> {
+    let scope = call.scope;
+    let n = call.arguments.require(scope, 0)?.check::<JsNumber>()?.value() as u32;
+    let before = scope.live_handles();
+    for i in 0..n {
+        scope.nested(|scope| { JsNumber::new(scope, i as f64); });
+    }
+    let nested = scope.live_handles() - before;
+    for i in 0..n {
+        JsNumber::new(scope, i as f64);
+    }
+    let flat = scope.live_handles() - before - nested;
+    let result = JsObject::new(scope);
+    result.set("nested", JsNumber::new(scope, nested as f64))?;
+    result.set("flat", JsNumber::new(scope, flat as f64))?;
+    Ok(result)
+}
+
+thread_local! {
+    static GC_CALLBACK: Cell<Option<GcCallbackId>> = Cell::new(None);
+    static GC_CYCLES: Cell<u32> = Cell::new(0);
+}
+
+pub fn start_counting_gc_cycles(call: Call) -> JsResult<JsUndefined> {
+    GC_CYCLES.with(|cycles| cycles.set(0));
+    let id = gc::add_callback(call.scope, |event: GcEvent| {
+        if event.phase == GcPhase::Epilogue {
+            GC_CYCLES.with(|cycles| cycles.set(cycles.get() + 1));
+        }
+    });
+    GC_CALLBACK.with(|callback| callback.set(Some(id)));
+    Ok(JsUndefined::new())
+}
+
+pub fn stop_counting_gc_cycles(call: Call) -> JsResult<JsNumber> {
+    if let Some(id) = GC_CALLBACK.with(|callback| callback.take()) {
+        gc::remove_callback(call.scope, id);
+    }
+    Ok(JsNumber::new(call.scope, GC_CYCLES.with(|cycles| cycles.get()) as f64))
+}
@@ -1,5 +1,6 @@
 use neon::vm::{Call, JsResult};
-use neon::js::{JsNumber, JsInteger};
+use neon::js::{JsNumber, JsInteger, JsString, JsArray};
+use neon::convert::TryIntoJs;
 use neon::mem::Handle;
 
 pub fn return_js_number(call: Call) -> JsResult<JsNumber> {
@@ -51,4 +52,28 @@ pub fn accept_and_return_negative_js_number(call: Call) -> JsResult<JsNumber> {
 pub fn accept_and_return_js_integer(call: Call) -> JsResult<JsInteger> {
     let x: Handle<JsInteger> = call.arguments.require(call.scope, 0)?.check::<JsInteger>()?;
     Ok(x)
-}
\ No newline at end of file
+}
+pub fn describe_i64_argument(mut call: Call) -> JsResult<JsString> {
+    let n = call.argument_i64(0)?;
+    Ok(JsString::new(call.scope, &n.to_string()).unwrap())
+}
+
+pub fn describe_u64_argument(mut call: Call) -> JsResult<JsString> {
+    let n = call.argument_u64(0)?;
+    Ok(JsString::new(call.scope, &n.to_string()).unwrap())
+}
+
+pub fn wrap_to_u32_and_i32(call: Call) -> JsResult<JsArray> {
+    let n = call.arguments.require(call.scope, 0)?.check::<JsNumber>()?;
+    vec![n.to_u32() as f64, n.to_i32() as f64].try_into_js(call.scope)
+}
+
+pub fn strict_to_u32(call: Call) -> JsResult<JsNumber> {
+    let n = call.arguments.require(call.scope, 0)?.check::<JsNumber>()?.to_u32_strict()?;
+    Ok(JsNumber::new(call.scope, n as f64))
+}
+
+pub fn strict_to_i32(call: Call) -> JsResult<JsNumber> {
+    let n = call.arguments.require(call.scope, 0)?.check::<JsNumber>()?.to_i32_strict()?;
+    Ok(JsNumber::new(call.scope, n as f64))
+}
@@ -1,7 +1,13 @@
-use neon::vm::{Call, JsResult};
-use neon::mem::Handle;
-use neon::js::{JsNumber, JsString, JsObject, Object};
-use neon::scope::Scope;
+use neon::vm::{Call, JsResult, VmResult};
+use std::cell::{Cell, RefCell};
+use neon::mem::{Handle, Persistent, Weak, RootedVec, RootedMap};
+use neon::js::{JsNumber, JsString, JsObject, JsArray, JsValue, JsUndefined, JsNull, JsBoolean, JsFunction, Object, PropertyKey};
+use neon::scope::{Scope, InspectOptions};
+use neon::convert::TryIntoJs;
+use neon::raw;
+use neon::testing;
+use neon::logger;
+use std::thread;
 
 pub fn return_js_global_object(call: Call) -> JsResult<JsObject> {
     let scope = call.scope;
@@ -33,3 +39,253 @@ pub fn return_js_object_with_string(call: Call) -> JsResult<JsObject> {
     js_object.set("string", JsString::new(scope, "hello node").unwrap())?;
     Ok(js_object)
 }
+
+static X: PropertyKey = PropertyKey::intern("x");
+static Y: PropertyKey = PropertyKey::intern("y");
+
+pub fn translate_js_points(call: Call) -> JsResult<JsArray> {
+    let scope = call.scope;
+    let points = call.arguments.require(scope, 0)?.check::<JsArray>()?.to_vec(scope)?;
+    let result = JsArray::new(scope, points.len() as u32);
+    for (i, point) in points.into_iter().enumerate() {
+        let point = point.check::<JsObject>()?;
+        let x = point.get(scope, X)?.check::<JsNumber>()?.value();
+        let y = point.get(scope, Y)?.check::<JsNumber>()?.value();
+        let translated = JsObject::new(scope);
+        translated.set(X, JsNumber::new(scope, x + 1.0))?;
+        translated.set(Y, JsNumber::new(scope, y - 1.0))?;
+        result.set(i as u32, translated)?;
+    }
+    Ok(result)
+}
+
+thread_local! {
+    static REMEMBERED: RefCell<Option<Persistent<JsObject>>> = RefCell::new(None);
+}
+
+pub fn remember_js_object(call: Call) -> JsResult<JsUndefined> {
+    let scope = call.scope;
+    let object = call.arguments.require(scope, 0)?.check::<JsObject>()?;
+    let persistent = Persistent::new(scope, object);
+    REMEMBERED.with(|cell| *cell.borrow_mut() = Some(persistent));
+    Ok(JsUndefined::new())
+}
+
+pub fn recall_js_object(call: Call) -> JsResult<JsValue> {
+    let scope = call.scope;
+    Ok(REMEMBERED.with(|cell| {
+        match *cell.borrow() {
+            Some(ref persistent) => persistent.get(scope).upcast(),
+            None => JsUndefined::new().upcast()
+        }
+    }))
+}
+
+thread_local! {
+    static WATCHED: RefCell<Option<Weak<JsObject>>> = RefCell::new(None);
+    static COLLECTED: Cell<u32> = Cell::new(0);
+}
+
+pub fn watch_js_object(call: Call) -> JsResult<JsUndefined> {
+    let scope = call.scope;
+    let object = call.arguments.require(scope, 0)?.check::<JsObject>()?;
+    let weak = Weak::with_callback(scope, object, || COLLECTED.with(|count| count.set(count.get() + 1)));
+    WATCHED.with(|cell| *cell.borrow_mut() = Some(weak));
+    Ok(JsUndefined::new())
+}
+
+pub fn read_watched_js_object(call: Call) -> JsResult<JsValue> {
+    let scope = call.scope;
+    Ok(WATCHED.with(|cell| {
+        match cell.borrow().as_ref().and_then(|weak| weak.get(scope)) {
+            Some(object) => object.upcast(),
+            None => JsUndefined::new().upcast()
+        }
+    }))
+}
+
+pub fn collected_js_objects(call: Call) -> JsResult<JsNumber> {
+    Ok(JsNumber::new(call.scope, COLLECTED.with(|count| count.get()) as f64))
+}
+
+thread_local! {
+    static QUEUED: RefCell<Option<RootedVec<JsFunction>>> = RefCell::new(None);
+    static REGISTERED: RefCell<Option<RootedMap<String, JsFunction>>> = RefCell::new(None);
+}
+
+pub fn queue_callback(call: Call) -> JsResult<JsNumber> {
+    let scope = call.scope;
+    let f = call.arguments.require(scope, 0)?.check::<JsFunction>()?;
+    let len = QUEUED.with(|cell| -> VmResult<usize> {
+        let mut queued = cell.borrow_mut();
+        let queued = queued.get_or_insert_with(|| RootedVec::new(scope));
+        queued.push(scope, f)?;
+        Ok(queued.len())
+    })?;
+    Ok(JsNumber::new(scope, len as f64))
+}
+
+pub fn flush_callbacks(call: Call) -> JsResult<JsUndefined> {
+    let scope = call.scope;
+    let callbacks = QUEUED.with(|cell| {
+        match *cell.borrow_mut() {
+            Some(ref mut queued) => queued.drain(scope),
+            None => Ok(vec![])
+        }
+    })?;
+    for (i, f) in callbacks.into_iter().enumerate() {
+        let args = vec![JsNumber::new(scope, i as f64)];
+        f.call(scope, JsNull::new(), args)?;
+    }
+    Ok(JsUndefined::new())
+}
+
+pub fn register_callback(call: Call) -> JsResult<JsUndefined> {
+    let scope = call.scope;
+    let id = call.arguments.require(scope, 0)?.check::<JsString>()?.value();
+    let f = call.arguments.require(scope, 1)?.check::<JsFunction>()?;
+    REGISTERED.with(|cell| {
+        let mut registered = cell.borrow_mut();
+        registered.get_or_insert_with(|| RootedMap::new(scope)).insert(scope, id, f)
+    })?;
+    Ok(JsUndefined::new())
+}
+
+pub fn call_registered_callback(call: Call) -> JsResult<JsValue> {
+    let scope = call.scope;
+    let id = call.arguments.require(scope, 0)?.check::<JsString>()?.value();
+    let f = REGISTERED.with(|cell| {
+        match *cell.borrow_mut() {
+            Some(ref mut registered) => registered.remove(scope, &id),
+            None => Ok(None)
+        }
+    })?;
+    match f {
+        Some(f) => {
+            let args: Vec<Handle<JsValue>> = vec![];
+            f.call(scope, JsNull::new(), args)
+        }
+        None => Ok(JsUndefined::new().upcast())
+    }
+}
+
+pub fn count_js_object_visits(call: Call) -> JsResult<JsNumber> {
+    let scope = call.scope;
+    let object = call.arguments.require(scope, 0)?.check::<JsObject>()?;
+    let visits = match object.get_hidden(scope, "visits")?.downcast::<JsNumber>() {
+        Some(n) => n.value() + 1.0,
+        None => 1.0
+    };
+    let visits = JsNumber::new(scope, visits);
+    object.set_hidden("visits", visits)?;
+    Ok(visits)
+}
+
+pub fn return_process_pid(call: Call) -> JsResult<JsValue> {
+    let scope = call.scope;
+    let process = scope.process()?;
+    process.get(scope, "pid")
+}
+
+pub fn log_to_console(call: Call) -> JsResult<JsUndefined> {
+    let args = call.argument_list();
+    call.scope.console_log(args.iter().cloned())?;
+    Ok(JsUndefined::new())
+}
+
+pub fn call_object_method(call: Call) -> JsResult<JsValue> {
+    let args: Vec<Handle<JsValue>> = call.argument_list().iter().skip(2).cloned().collect();
+    let scope = call.scope;
+    let object = call.arguments.require(scope, 0)?.check::<JsObject>()?;
+    let name = call.arguments.require(scope, 1)?.check::<JsString>()?.value();
+    object.call_method(scope, &name, args)
+}
+
+pub fn round_trip_raw_handle(call: Call) -> JsResult<JsObject> {
+    let scope = call.scope;
+    let object = call.arguments.require(scope, 0)?.check::<JsObject>()?;
+    let local: raw::Local = object.to_raw();
+    Ok(unsafe { Handle::from_raw(scope, local) })
+}
+
+pub fn classify_value(call: Call) -> JsResult<JsString> {
+    let value = call.arguments.require(call.scope, 0)?;
+    let kind = if value.is_a::<JsUndefined>() || value.is_a::<JsNull>() {
+        "nothing"
+    } else if value.is_a::<JsBoolean>() {
+        "boolean"
+    } else if value.is_a::<JsNumber>() {
+        "number"
+    } else if value.is_a::<JsString>() {
+        "string"
+    } else if value.is_a::<JsArray>() {
+        "array"
+    } else if value.is_a::<JsFunction>() {
+        "function"
+    } else if value.is_a::<JsObject>() {
+        "object"
+    } else {
+        "other"
+    };
+    JsString::new_or_throw(call.scope, kind)
+}
+
+pub fn describe_for_log(call: Call) -> JsResult<JsString> {
+    let value = call.arguments.require(call.scope, 0)?;
+    let description = format!("{} / {:?}", value.to_display_string(call.scope)?, value.debug(call.scope)?);
+    JsString::new_or_throw(call.scope, &description)
+}
+
+pub fn inspect_with_options(call: Call) -> JsResult<JsArray> {
+    let value = call.arguments.require(call.scope, 0)?;
+    let plain = call.scope.inspect(value, InspectOptions::new())?;
+    let shallow = call.scope.inspect(value, InspectOptions::new().depth(0))?;
+    let deep = call.scope.inspect(value, InspectOptions::new().unlimited_depth())?;
+    let colored = call.scope.inspect(value, InspectOptions::new().colors(true))?;
+    let hidden = call.scope.inspect(value, InspectOptions::new().show_hidden(true))?;
+    vec![plain, shallow, deep, colored, hidden].try_into_js(call.scope)
+}
+
+pub fn compare_values(call: Call) -> JsResult<JsArray> {
+    let a = call.arguments.require(call.scope, 0)?;
+    let b = call.arguments.require(call.scope, 1)?;
+    let comparisons = (a.strict_equals(call.scope, b), a.same_value(call.scope, b));
+    comparisons.try_into_js(call.scope)
+}
+
+pub fn init_logger(call: Call) -> JsResult<JsBoolean> {
+    let scope = call.scope;
+    let installed = logger::init(scope).is_ok();
+    Ok(JsBoolean::new(scope, installed))
+}
+
+pub fn log_records(_: Call) -> JsResult<JsUndefined> {
+    info!("info from the JS thread");
+    warn!("warning from the JS thread");
+    error!("error from the JS thread");
+    thread::spawn(|| warn!(target: "background", "warning from another thread")).join().unwrap();
+    Ok(JsUndefined::new())
+}
+
+pub fn diff_js_values(call: Call) -> JsResult<JsValue> {
+    let scope = call.scope;
+    let actual = call.arguments.require(scope, 0)?;
+    let expected = call.arguments.require(scope, 1)?.check::<JsString>()?.value();
+    match testing::diff(scope, actual, expected)? {
+        Some(difference) => Ok(JsString::new_or_throw(scope, &difference)?.upcast()),
+        None => Ok(JsNull::new().upcast())
+    }
+}
+
+pub fn test_assert_js_eq(call: Call) -> VmResult<()> {
+    let scope = call.scope;
+    let object = JsObject::new(scope);
+    let name = JsString::new_or_throw(scope, "neon")?;
+    object.set("name", name)?;
+    let tags = vec!["a", "b"].try_into_js(scope)?;
+    object.set("tags", tags)?;
+    assert_js_eq!(scope, object, r#"{ "name": "neon", "tags": ["a", "b"] }"#);
+    let expected = JsArray::from_iter(scope, vec!["a", "b"])?;
+    assert_js_eq!(scope, tags, expected);
+    Ok(())
+}
@@ -0,0 +1,46 @@
+use neon::vm::{Call, JsResult};
+use neon::js::{JsValue, JsNumber};
+use neon::serde::{to_js_value, from_js_value};
+
+#[derive(Serialize, Deserialize)]
+enum Status {
+    Pending,
+    Shipped { tracking: String }
+}
+
+#[derive(Serialize, Deserialize)]
+struct Order {
+    id: u32,
+    items: Vec<String>,
+    note: Option<String>,
+    status: Status
+}
+
+pub fn ship_order(call: Call) -> JsResult<JsValue> {
+    let scope = call.scope;
+    let value = call.arguments.require(scope, 0)?;
+    let mut order: Order = from_js_value(scope, value)?;
+    order.items.push(String::from("receipt"));
+    order.status = match order.status {
+        Status::Pending => Status::Shipped { tracking: format!("TRACK-{}", order.id) },
+        shipped => shipped
+    };
+    to_js_value(scope, &order)
+}
+
+#[derive(Deserialize)]
+struct Node {
+    next: Option<Box<Node>>
+}
+
+pub fn count_linked_nodes(call: Call) -> JsResult<JsNumber> {
+    let scope = call.scope;
+    let value = call.arguments.require(scope, 0)?;
+    let mut node: Node = from_js_value(scope, value)?;
+    let mut count = 1;
+    while let Some(next) = node.next {
+        node = *next;
+        count += 1;
+    }
+    Ok(JsNumber::new(scope, count as f64))
+}
@@ -0,0 +1,72 @@
+use std::cell::Cell;
+use std::sync::mpsc;
+use std::thread;
+use neon::vm::{Call, JsResult};
+use neon::mem::Root;
+use neon::js::{JsNumber, JsObject, JsString, JsFunction, JsNull};
+use neon::js::stream::{self, Sink, TransformStream};
+use neon::task::Channel;
+
+thread_local! {
+    static PULLED_CHUNKS: Cell<u32> = Cell::new(0);
+}
+
+pub fn readable_from_iterator(call: Call) -> JsResult<JsObject> {
+    let scope = call.scope;
+    let count = call.arguments.require(scope, 0)?.check::<JsNumber>()?.value() as u32;
+    let size = call.arguments.require(scope, 1)?.check::<JsNumber>()?.value() as usize;
+    PULLED_CHUNKS.with(|pulled| pulled.set(0));
+    let chunks = (0..count).map(move |i| {
+        PULLED_CHUNKS.with(|pulled| pulled.set(pulled.get() + 1));
+        vec![i as u8; size]
+    });
+    stream::readable(scope, chunks)
+}
+
+pub fn pulled_chunks(call: Call) -> JsResult<JsNumber> {
+    Ok(JsNumber::new(call.scope, PULLED_CHUNKS.with(|pulled| pulled.get()) as f64))
+}
+
+pub fn writable_to_thread(call: Call) -> JsResult<JsObject> {
+    let scope = call.scope;
+    let f = call.arguments.require(scope, 0)?.check::<JsFunction>()?;
+    let f = Root::new(scope, f);
+    let channel = Channel::new(scope);
+    let (tx, rx) = mpsc::channel::<Vec<u8>>();
+    thread::spawn(move || {
+        let received: Vec<u8> = rx.iter().flat_map(|chunk| chunk).collect();
+        channel.send(move |scope| {
+            let f = f.into_inner(scope);
+            let args = vec![JsString::new_or_throw(scope, &String::from_utf8_lossy(&received))?];
+            f.call(scope, JsNull::new(), args)?;
+            Ok(())
+        });
+    });
+    stream::writable(scope, tx)
+}
+
+struct PickySink;
+
+impl Sink for PickySink {
+    type Error = String;
+
+    fn write(&mut self, chunk: &[u8]) -> Result<(), String> {
+        if chunk == b"bad" {
+            return Err(format!("can't write {:?}", "bad"));
+        }
+        Ok(())
+    }
+}
+
+pub fn picky_writable(call: Call) -> JsResult<JsObject> {
+    stream::writable(call.scope, PickySink)
+}
+
+pub fn shouting_transform(call: Call) -> JsResult<JsObject> {
+    TransformStream::new(|chunk: &[u8]| {
+        if chunk == b"bad" {
+            return Err(format!("can't transform {:?}", "bad"));
+        }
+        Ok(chunk.to_ascii_uppercase())
+    }).flush(|| Ok(b"!".to_vec())).build(call.scope)
+}
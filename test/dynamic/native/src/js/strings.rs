@@ -1,6 +1,79 @@
-use neon::vm::{Call, JsResult};
-use neon::js::JsString;
+use neon::vm::{Call, JsResult, Lock};
+use neon::js::{JsString, JsNumber, JsArray};
+use neon::js::binary::JsBuffer;
 
 pub fn return_js_string(call: Call) -> JsResult<JsString> {
     Ok(JsString::new(call.scope, "hello node").unwrap())
 }
+
+pub fn concat_js_strings_unchecked(call: Call) -> JsResult<JsString> {
+    let scope = call.scope;
+    let a = call.arguments.require(scope, 0)?.check::<JsString>()?.value();
+    let b = call.arguments.require(scope, 1)?.check::<JsString>()?.value();
+    Ok(unsafe { JsString::new_unchecked(scope, &(a + &b)) })
+}
+
+pub fn reverse_utf16(call: Call) -> JsResult<JsString> {
+    let scope = call.scope;
+    let string = call.arguments.require(scope, 0)?.check::<JsString>()?;
+    let mut units = vec![0; string.utf16_len()];
+    let len = string.write_utf16(&mut units);
+    units.truncate(len);
+    units.reverse();
+    Ok(JsString::from_utf16(scope, &units).unwrap())
+}
+
+pub fn latin1_round_trip(call: Call) -> JsResult<JsString> {
+    let scope = call.scope;
+    let string = call.arguments.require(scope, 0)?.check::<JsString>()?;
+    let mut bytes = vec![0; string.utf16_len()];
+    let len = string.write_latin1(&mut bytes);
+    Ok(JsString::from_latin1(scope, &bytes[..len]).unwrap())
+}
+
+pub fn encode_into_buffer(call: Call) -> JsResult<JsNumber> {
+    let scope = call.scope;
+    let string = call.arguments.require(scope, 0)?.check::<JsString>()?;
+    let mut buffer = call.arguments.require(scope, 1)?.check::<JsBuffer>()?;
+    // The string can't be read while the buffer is grabbed, so it's encoded into a
+    // copy first.
+    let mut bytes = vec![0; buffer.grab(|data| data.len())];
+    let written = string.write_utf8(&mut bytes);
+    buffer.grab(|mut data| data.as_mut_slice()[..written].copy_from_slice(&bytes[..written]));
+    Ok(JsNumber::new(scope, written as f64))
+}
+
+pub fn decode_buffer_lossy(call: Call) -> JsResult<JsString> {
+    let scope = call.scope;
+    let mut buffer = call.arguments.require(scope, 0)?.check::<JsBuffer>()?;
+    let bytes = buffer.grab(|data| data.as_slice().to_vec());
+    Ok(JsString::from_utf8_lossy(scope, &bytes).unwrap())
+}
+
+pub fn join_small_strings(call: Call) -> JsResult<JsString> {
+    let scope = call.scope;
+    let strings = call.arguments.require(scope, 0)?.check::<JsArray>()?.to_vec(scope)?;
+    let mut stack = [0u8; 16];
+    let mut joined = String::new();
+    for (i, string) in strings.into_iter().enumerate() {
+        if i > 0 {
+            joined.push(',');
+        }
+        joined.push_str(&string.check::<JsString>()?.value_cow(&mut stack));
+    }
+    Ok(JsString::new(scope, &joined).unwrap())
+}
+
+pub fn longest_string(call: Call) -> JsResult<JsString> {
+    let scope = call.scope;
+    let strings = call.arguments.require(scope, 0)?.check::<JsArray>()?.to_vec(scope)?;
+    let mut buf = String::new();
+    let mut longest = String::new();
+    for string in strings {
+        string.check::<JsString>()?.value_into(&mut buf);
+        if buf.len() > longest.len() {
+            longest = buf.clone();
+        }
+    }
+    Ok(JsString::new(scope, &longest).unwrap())
+}
@@ -1,8 +1,17 @@
-use neon::vm::{Call, JsResult};
-use neon::scope::{Scope};
-use neon::js::{JsUndefined, JsNumber, JsFunction};
+use std::cell::RefCell;
+use std::rc::Rc;
+use std::thread;
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Duration;
+use neon::vm::{Call, JsResult, VmResult};
+use neon::scope::{Scope, RootScope};
+use neon::mem::{Handle, Persistent, Root};
+use neon::js::{JsUndefined, JsNumber, JsString, JsFunction, JsNull, JsArray, JsObject, Value};
 use neon::js::error::{Kind, JsError};
-use neon::task::Task;
+use neon::task::{Task, TaskGroup, TaskQueue, AsyncSignal, Timer, Channel, block_on_js};
+use neon::task::{PromiseReceiver, PromiseError, await_promise};
+use neon::testing;
 
 struct SuccessTask;
 
@@ -47,3 +56,382 @@ pub fn perform_failing_task(call: Call) -> JsResult<JsUndefined> {
     FailureTask.schedule(f);
     Ok(JsUndefined::new())
 }
+
+pub fn perform_mapped_task(call: Call) -> JsResult<JsUndefined> {
+    let f = call.arguments.require(call.scope, 0)?.check::<JsFunction>()?;
+    SuccessTask.and_then(|n| Ok(n + 1)).map(|n| n * 2).schedule(f);
+    Ok(JsUndefined::new())
+}
+
+pub fn perform_chained_failing_task(call: Call) -> JsResult<JsUndefined> {
+    let f = call.arguments.require(call.scope, 0)?.check::<JsFunction>()?;
+    SuccessTask.and_then(|n| Err::<i32, _>(format!("{} is too large", n))).map_err(|err| format!("wrapped: {}", err)).schedule(f);
+    Ok(JsUndefined::new())
+}
+
+struct SquareTask(u32);
+
+impl Task for SquareTask {
+    type Output = u32;
+    type Error = String;
+    type JsEvent = JsNumber;
+
+    fn perform(&self) -> Result<u32, String> {
+        match self.0 {
+            0 => Err(format!("can't square zero")),
+            n => Ok(n * n)
+        }
+    }
+
+    fn complete<'a, T: Scope<'a>>(self, scope: &'a mut T, result: Result<u32, String>) -> JsResult<JsNumber> {
+        match result {
+            Ok(n) => Ok(JsNumber::new(scope, n as f64)),
+            Err(err) => JsError::throw(Kind::Error, &err)
+        }
+    }
+}
+
+pub fn complete_square_task(call: Call) -> JsResult<JsNumber> {
+    let scope = call.scope;
+    let arg = call.arguments.require(scope, 0)?;
+    let result = match arg.downcast::<JsNumber>() {
+        Some(n) => Ok(n.value() as u32),
+        None => Err(arg.to_string(scope)?.value())
+    };
+    SquareTask(0).complete_now(scope, result)
+}
+
+pub fn test_square_task(call: Call) -> VmResult<()> {
+    let task = SquareTask(7);
+    let result = task.perform();
+    assert_eq!(result, Ok(49));
+    let event = task.complete_now(call.scope, result)?;
+    assert_eq!(event.value(), 49.0);
+    Ok(())
+}
+
+pub fn square_inline(call: Call) -> JsResult<JsUndefined> {
+    let scope = call.scope;
+    let numbers = call.arguments.require(scope, 0)?.check::<JsArray>()?.to_vec_of::<u32, _>(scope)?;
+    let f = call.arguments.require(scope, 1)?.check::<JsFunction>()?;
+    testing::inline_tasks(|| {
+        for n in numbers {
+            SquareTask(n).schedule(f);
+        }
+    });
+    Ok(JsUndefined::new())
+}
+
+pub fn test_inline_task(call: Call) -> VmResult<()> {
+    let squares = Rc::new(RefCell::new(Vec::new()));
+    let results = squares.clone();
+    let callback = JsFunction::from_closure(call.scope, move |call: Call| {
+        let n = call.arguments.require(call.scope, 1)?.check::<JsNumber>()?;
+        results.borrow_mut().push(n.value());
+        Ok(JsUndefined::new())
+    })?;
+    testing::inline_tasks(|| {
+        SquareTask(3).schedule(callback);
+        SquareTask(4).schedule(callback);
+    });
+    assert_eq!(*squares.borrow(), vec![9.0, 16.0]);
+    Ok(())
+}
+
+pub fn square_in_parallel(call: Call) -> JsResult<JsUndefined> {
+    let scope = call.scope;
+    let numbers = call.arguments.require(scope, 0)?.check::<JsArray>()?.to_vec_of::<u32, _>(scope)?;
+    let f = call.arguments.require(scope, 1)?.check::<JsFunction>()?;
+    TaskGroup::spawn(numbers.into_iter().map(SquareTask)).join(scope, f)?;
+    Ok(JsUndefined::new())
+}
+
+static RUNNING_SLEEPS: AtomicUsize = AtomicUsize::new(0);
+static PEAK_SLEEPS: AtomicUsize = AtomicUsize::new(0);
+
+struct SleepTask(u32);
+
+impl Task for SleepTask {
+    type Output = u32;
+    type Error = ();
+    type JsEvent = JsNumber;
+
+    fn perform(&self) -> Result<u32, ()> {
+        let running = RUNNING_SLEEPS.fetch_add(1, Ordering::SeqCst) + 1;
+        let mut peak = PEAK_SLEEPS.load(Ordering::SeqCst);
+        while running > peak {
+            match PEAK_SLEEPS.compare_exchange(peak, running, Ordering::SeqCst, Ordering::SeqCst) {
+                Ok(_) => break,
+                Err(current) => peak = current
+            }
+        }
+        thread::sleep(Duration::from_millis(10));
+        RUNNING_SLEEPS.fetch_sub(1, Ordering::SeqCst);
+        Ok(self.0)
+    }
+
+    fn complete<'a, T: Scope<'a>>(self, scope: &'a mut T, result: Result<u32, ()>) -> JsResult<JsNumber> {
+        Ok(JsNumber::new(scope, result.unwrap() as f64))
+    }
+}
+
+pub fn sleep_in_queue(call: Call) -> JsResult<JsUndefined> {
+    let scope = call.scope;
+    let count = call.arguments.require(scope, 0)?.check::<JsNumber>()?.value() as u32;
+    let max = call.arguments.require(scope, 1)?.check::<JsNumber>()?.value() as usize;
+    let f = call.arguments.require(scope, 2)?.check::<JsFunction>()?;
+    PEAK_SLEEPS.store(0, Ordering::SeqCst);
+    let queue = TaskQueue::new(max);
+    for i in 0..count {
+        queue.schedule(scope, SleepTask(i), f)?;
+    }
+    Ok(JsUndefined::new())
+}
+
+pub fn peak_queued_sleeps(call: Call) -> JsResult<JsNumber> {
+    Ok(JsNumber::new(call.scope, PEAK_SLEEPS.load(Ordering::SeqCst) as f64))
+}
+
+pub fn signal_from_thread(call: Call) -> JsResult<JsUndefined> {
+    let scope = call.scope;
+    let f = call.arguments.require(scope, 0)?.check::<JsFunction>()?;
+    let callback = Persistent::new(scope, f);
+    let mut count = 0;
+    let signal = AsyncSignal::new(scope, move |scope| {
+        count += 1;
+        let f = callback.get(scope);
+        let args: Vec<Handle<JsNumber>> = vec![JsNumber::new(scope, count as f64)];
+        f.call(scope, JsNull::new(), args)?;
+        Ok(())
+    });
+    thread::spawn(move || signal.trigger());
+    Ok(JsUndefined::new())
+}
+
+pub fn call_root_from_thread(call: Call) -> JsResult<JsUndefined> {
+    let scope = call.scope;
+    let f = call.arguments.require(scope, 0)?.check::<JsFunction>()?;
+    let root = Root::new(scope, f);
+    let slot: Arc<Mutex<Option<Root<JsFunction>>>> = Arc::new(Mutex::new(None));
+    let signal = {
+        let slot = slot.clone();
+        AsyncSignal::new(scope, move |scope| {
+            if let Some(root) = slot.lock().unwrap().take() {
+                let f = root.into_inner(scope);
+                let args: Vec<Handle<JsNull>> = vec![];
+                f.call(scope, JsNull::new(), args)?;
+            }
+            Ok(())
+        })
+    };
+    thread::spawn(move || {
+        *slot.lock().unwrap() = Some(root);
+        signal.trigger();
+    });
+    Ok(JsUndefined::new())
+}
+
+pub fn drop_root_on_thread(call: Call) -> JsResult<JsFunction> {
+    let scope = call.scope;
+    let f = call.arguments.require(scope, 0)?.check::<JsFunction>()?;
+    let root = Root::new(scope, f);
+    let copy = root.clone();
+    thread::spawn(move || drop(root)).join().unwrap();
+    Ok(copy.get(scope))
+}
+
+pub fn call_root_from_threads(call: Call) -> JsResult<JsUndefined> {
+    let scope = call.scope;
+    let count = call.arguments.require(scope, 0)?.check::<JsNumber>()?.value() as u32;
+    let f = call.arguments.require(scope, 1)?.check::<JsFunction>()?;
+    let f = Root::new(scope, f);
+    let channel = Channel::new(scope);
+    for i in 0..count {
+        let f = f.clone();
+        let channel = channel.clone();
+        thread::spawn(move || {
+            let copy = f.clone();
+            drop(f);
+            channel.send(move |scope| {
+                let f = copy.into_inner(scope);
+                let args: Vec<Handle<JsNumber>> = vec![JsNumber::new(scope, i as f64)];
+                f.call(scope, JsNull::new(), args)?;
+                Ok(())
+            });
+        });
+    }
+    Ok(JsUndefined::new())
+}
+
+pub fn send_through_bounded_channel(call: Call) -> JsResult<JsUndefined> {
+    let scope = call.scope;
+    let count = call.arguments.require(scope, 0)?.check::<JsNumber>()?.value() as u32;
+    let f = call.arguments.require(scope, 1)?.check::<JsFunction>()?;
+    let callback = Root::new(scope, f);
+    let channel = Channel::bounded(scope, 1);
+    thread::spawn(move || {
+        for i in 0..count {
+            let callback = callback.clone();
+            channel.send(move |scope| {
+                let f = callback.get(scope);
+                let args: Vec<Handle<JsNumber>> = vec![JsNumber::new(scope, i as f64)];
+                f.call(scope, JsNull::new(), args)?;
+                Ok(())
+            });
+        }
+    });
+    Ok(JsUndefined::new())
+}
+
+pub fn fill_bounded_channel(call: Call) -> JsResult<JsNumber> {
+    let scope = call.scope;
+    let channel = Channel::bounded(scope, 2);
+    let mut sent = 0;
+    while channel.try_send(|_| Ok(())).is_ok() {
+        sent += 1;
+    }
+    Ok(JsNumber::new(scope, sent as f64))
+}
+
+pub fn add_on_js_from_thread(call: Call) -> JsResult<JsUndefined> {
+    let scope = call.scope;
+    let add = call.arguments.require(scope, 0)?.check::<JsFunction>()?;
+    let add = Root::new(scope, add);
+    let done = call.arguments.require(scope, 1)?.check::<JsFunction>()?;
+    let done = Root::new(scope, done);
+    let channel = Channel::new(scope);
+    thread::spawn(move || {
+        let sum = block_on_js(&channel, move |scope| {
+            let add = add.into_inner(scope);
+            let args: Vec<Handle<JsNumber>> = vec![JsNumber::new(scope, 1.0), JsNumber::new(scope, 2.0)];
+            Ok(add.call(scope, JsNull::new(), args)?.check::<JsNumber>()?.value())
+        }).unwrap();
+        channel.send(move |scope| {
+            let done = done.into_inner(scope);
+            let args: Vec<Handle<JsNumber>> = vec![JsNumber::new(scope, sum * 10.0)];
+            done.call(scope, JsNull::new(), args)?;
+            Ok(())
+        });
+    });
+    Ok(JsUndefined::new())
+}
+
+pub fn block_on_js_thread(call: Call) -> JsResult<JsString> {
+    let scope = call.scope;
+    let channel = Channel::new(scope);
+    let err = block_on_js(&channel, |_| Ok(())).unwrap_err();
+    JsString::new_or_throw(scope, &err.to_string())
+}
+
+struct DoublePromiseTask(PromiseReceiver<f64>);
+
+impl Task for DoublePromiseTask {
+    type Output = f64;
+    type Error = PromiseError;
+    type JsEvent = JsNumber;
+
+    fn perform(&self) -> Result<f64, PromiseError> {
+        self.0.wait().map(|n| n * 2.0)
+    }
+
+    fn complete<'a, T: Scope<'a>>(self, scope: &'a mut T, result: Result<f64, PromiseError>) -> JsResult<JsNumber> {
+        match result {
+            Ok(n) => Ok(JsNumber::new(scope, n)),
+            Err(err) => JsError::throw(Kind::Error, &err.to_string())
+        }
+    }
+}
+
+pub fn double_promise_in_task(call: Call) -> JsResult<JsUndefined> {
+    let scope = call.scope;
+    let promise = call.arguments.require(scope, 0)?.check::<JsObject>()?;
+    let f = call.arguments.require(scope, 1)?.check::<JsFunction>()?;
+    DoublePromiseTask(await_promise(scope, promise)?).schedule(f);
+    Ok(JsUndefined::new())
+}
+
+pub fn wait_for_promise_on_js_thread(call: Call) -> JsResult<JsString> {
+    let scope = call.scope;
+    let promise = call.arguments.require(scope, 0)?.check::<JsObject>()?;
+    let receiver: PromiseReceiver<f64> = await_promise(scope, promise)?;
+    let err = receiver.wait().unwrap_err();
+    JsString::new_or_throw(scope, &err.to_string())
+}
+
+pub fn defer_callbacks(call: Call) -> JsResult<JsUndefined> {
+    let scope = call.scope;
+    let f = call.arguments.require(scope, 0)?.check::<JsFunction>()?;
+    for &(kind, microtask) in &[("microtask", true), ("tick", false)] {
+        let callback = Persistent::new(scope, f);
+        let deferred = move |scope: &mut RootScope| {
+            let f = callback.get(scope);
+            let args = vec![JsString::new_or_throw(scope, kind)?];
+            f.call(scope, JsNull::new(), args)?;
+            Ok(())
+        };
+        if microtask {
+            scope.queue_microtask(deferred)?;
+        } else {
+            scope.next_tick(deferred)?;
+        }
+    }
+    let args = vec![JsString::new_or_throw(scope, "sync")?];
+    f.call(scope, JsNull::new(), args)?;
+    Ok(JsUndefined::new())
+}
+
+pub fn set_native_timeout(call: Call) -> JsResult<JsUndefined> {
+    let scope = call.scope;
+    let ms = call.arguments.require(scope, 0)?.check::<JsNumber>()?.value();
+    let f = call.arguments.require(scope, 1)?.check::<JsFunction>()?;
+    let callback = Persistent::new(scope, f);
+    scope.set_timeout(ms as u64, move |scope| {
+        let f = callback.get(scope);
+        let args: Vec<Handle<JsNull>> = vec![];
+        f.call(scope, JsNull::new(), args)?;
+        Ok(())
+    }).detach();
+    Ok(JsUndefined::new())
+}
+
+pub fn set_cancelled_timeout(call: Call) -> JsResult<JsUndefined> {
+    let scope = call.scope;
+    let f = call.arguments.require(scope, 0)?.check::<JsFunction>()?;
+    let callback = Persistent::new(scope, f);
+    let timer = scope.set_timeout(0, move |scope| {
+        let f = callback.get(scope);
+        let args: Vec<Handle<JsNull>> = vec![];
+        f.call(scope, JsNull::new(), args)?;
+        Ok(())
+    });
+    timer.cancel();
+    Ok(JsUndefined::new())
+}
+
+thread_local! {
+    static INTERVAL: RefCell<Option<Timer>> = RefCell::new(None);
+}
+
+pub fn start_native_interval(call: Call) -> JsResult<JsUndefined> {
+    let scope = call.scope;
+    let ms = call.arguments.require(scope, 0)?.check::<JsNumber>()?.value();
+    let f = call.arguments.require(scope, 1)?.check::<JsFunction>()?;
+    let callback = Persistent::new(scope, f);
+    let mut count = 0;
+    let timer = scope.set_interval(ms as u64, move |scope| {
+        count += 1;
+        let f = callback.get(scope);
+        let args: Vec<Handle<JsNumber>> = vec![JsNumber::new(scope, count as f64)];
+        f.call(scope, JsNull::new(), args)?;
+        Ok(())
+    });
+    INTERVAL.with(|interval| *interval.borrow_mut() = Some(timer));
+    Ok(JsUndefined::new())
+}
+
+pub fn stop_native_interval(_: Call) -> JsResult<JsUndefined> {
+    // Take the timer out first, so it's dropped without the cell still borrowed.
+    let timer = INTERVAL.with(|interval| interval.borrow_mut().take());
+    drop(timer);
+    Ok(JsUndefined::new())
+}
@@ -1,5 +1,12 @@
 #[macro_use]
 extern crate neon;
+#[macro_use]
+extern crate neon_derive;
+#[macro_use]
+extern crate serde_derive;
+extern crate chrono;
+#[macro_use]
+extern crate log;
 
 mod js {
     pub mod strings;
@@ -9,22 +16,41 @@ mod js {
     pub mod functions;
     pub mod classes;
     pub mod tasks;
+    pub mod buffers;
+    pub mod serde;
+    pub mod convert;
+    pub mod instrument;
+    pub mod streams;
 }
 
-use js::strings::return_js_string;
+use js::strings::*;
 use js::numbers::*;
 use js::arrays::*;
 use js::objects::*;
 use js::functions::*;
 use js::classes::*;
 use js::tasks::*;
+use js::buffers::*;
+use js::serde::*;
+use js::convert::*;
+use js::instrument::*;
+use js::streams::*;
 
 use neon::mem::Handle;
-use neon::js::{JsFunction, Object};
+use neon::js::{JsFunction, JsObject, Object};
 use neon::js::class::{Class, JsClass};
 
 register_module!(m, {
+    m.set_name("tests");
+
     m.export("return_js_string", return_js_string)?;
+    m.export("concat_js_strings_unchecked", concat_js_strings_unchecked)?;
+    m.export("reverse_utf16", reverse_utf16)?;
+    m.export("latin1_round_trip", latin1_round_trip)?;
+    m.export("encode_into_buffer", encode_into_buffer)?;
+    m.export("decode_buffer_lossy", decode_buffer_lossy)?;
+    m.export("join_small_strings", join_small_strings)?;
+    m.export("longest_string", longest_string)?;
 
     m.export("return_js_number", return_js_number)?;
     m.export("return_large_js_number", return_large_js_number)?;
@@ -39,33 +65,197 @@ register_module!(m, {
     m.export("accept_and_return_negative_js_number", accept_and_return_negative_js_number)?;
     // DEPRECATE(0.2)
     m.export("accept_and_return_js_integer", accept_and_return_js_integer)?;
+    m.export("describe_i64_argument", describe_i64_argument)?;
+    m.export("describe_u64_argument", describe_u64_argument)?;
+    m.export("wrap_to_u32_and_i32", wrap_to_u32_and_i32)?;
+    m.export("strict_to_u32", strict_to_u32)?;
+    m.export("strict_to_i32", strict_to_i32)?;
 
     m.export("return_js_array", return_js_array)?;
     m.export("return_js_array_with_number", return_js_array_with_number)?;
     m.export("return_js_array_with_string", return_js_array_with_string)?;
+    m.export("return_js_array_of_squares", return_js_array_of_squares)?;
+    m.export("join_js_array_of_strings", join_js_array_of_strings)?;
+    m.export("sum_js_iterable", sum_js_iterable)?;
+    m.export("first_of_js_iterable", first_of_js_iterable)?;
+    m.export("return_js_array_from_slice", return_js_array_from_slice)?;
+    m.export("return_js_array_from_f64_slice", return_js_array_from_f64_slice)?;
+    m.export("return_js_array_from_builder", return_js_array_from_builder)?;
 
     m.export("return_js_global_object", return_js_global_object)?;
     m.export("return_js_object", return_js_object)?;
     m.export("return_js_object_with_number", return_js_object_with_number)?;
     m.export("return_js_object_with_string", return_js_object_with_string)?;
     m.export("return_js_object_with_mixed_content", return_js_object_with_mixed_content)?;
+    m.export("translate_js_points", translate_js_points)?;
+    m.export("remember_js_object", remember_js_object)?;
+    m.export("recall_js_object", recall_js_object)?;
+    m.export("watch_js_object", watch_js_object)?;
+    m.export("read_watched_js_object", read_watched_js_object)?;
+    m.export("collected_js_objects", collected_js_objects)?;
+    m.export("queue_callback", queue_callback)?;
+    m.export("flush_callbacks", flush_callbacks)?;
+    m.export("register_callback", register_callback)?;
+    m.export("call_registered_callback", call_registered_callback)?;
+    m.export("count_js_object_visits", count_js_object_visits)?;
+    m.export("return_process_pid", return_process_pid)?;
+    m.export("log_to_console", log_to_console)?;
+    m.export("call_object_method", call_object_method)?;
+    m.export("diff_js_values", diff_js_values)?;
+    m.export("init_logger", init_logger)?;
+    m.export("log_records", log_records)?;
+    m.export("round_trip_raw_handle", round_trip_raw_handle)?;
+    m.export("classify_value", classify_value)?;
+    m.export("describe_for_log", describe_for_log)?;
+    m.export("inspect_with_options", inspect_with_options)?;
+    m.export("compare_values", compare_values)?;
+    m.export_enum("Color", &[("Red", 0), ("Green", 1), ("Blue", -1)])?;
 
     m.export("return_js_function", return_js_function)?;
+    m.export("return_cached_js_function", return_cached_js_function)?;
+    m.export("describe_js_function", describe_js_function)?;
+    m.export("call_with_undefined_this", call_with_undefined_this)?;
+    m.export("return_named_js_function", return_named_js_function)?;
+    m.export("make_counter", make_counter)?;
+    m.export("make_reentrant_caller", make_reentrant_caller)?;
+    m.export("make_recursive_caller", make_recursive_caller)?;
+    m.export("dropped_closures", dropped_closures)?;
+    m.export("make_line_splitter", make_line_splitter)?;
+    m.export("count_module_calls", count_module_calls)?;
+    m.export("require_js_module", require_js_module)?;
+    m.export_lazy("lazy_table", build_lazy_table)?;
+    m.export("lazy_table_builds", lazy_table_builds)?;
     m.export("call_js_function", call_js_function)?;
+    m.export("call_js_function_and_run_microtasks", call_js_function_and_run_microtasks)?;
+    m.export("return_microtask_policy", return_microtask_policy)?;
+    m.export("profile_js_function", profile_js_function)?;
+    m.export("write_heap_snapshot", write_heap_snapshot)?;
     m.export("construct_js_function", construct_js_function)?;
 
     m.export("check_string_and_number", check_string_and_number)?;
+    m.export("sum_all_arguments", sum_all_arguments)?;
+    m.export("join_string_arguments", join_string_arguments)?;
+    m.export("add_with_default_step", add_with_default_step)?;
+    m.export_fast("fast_add1", fast_add1)?;
+    m.export_with_arity("add_two", 2, add_two)?;
+    m.export_with_arity("call_argument", 1, call_argument)?;
+
+    m.export("return_js_buffer", return_js_buffer)?;
+    m.export("return_js_buffer_from_slice", return_js_buffer_from_slice)?;
+    m.export("check_js_buffer_length", check_js_buffer_length)?;
+    m.export("lock_buffer_and_array_buffer", lock_buffer_and_array_buffer)?;
+    m.export("lock_buffer_twice", lock_buffer_twice)?;
+    m.export("return_external_js_buffer", return_external_js_buffer)?;
+    m.export("sum_u32_array_buffer", sum_u32_array_buffer)?;
+    m.export("increment_buffer_bytes", increment_buffer_bytes)?;
+    m.export("write_buffer_header", write_buffer_header)?;
+    m.export("detach_array_buffer", detach_array_buffer)?;
+    m.export("bump_message_header", bump_message_header)?;
+    m.export("sum_biguint64_array", sum_biguint64_array)?;
+    m.export("return_bigint64_array", return_bigint64_array)?;
+    m.export("encode_buffer_base64", encode_buffer_base64)?;
+    m.export("decode_buffer_base64", decode_buffer_base64)?;
+    m.export("encode_buffer_hex", encode_buffer_hex)?;
+    m.export("decode_buffer_hex", decode_buffer_hex)?;
+    m.export("return_pooled_js_buffer", return_pooled_js_buffer)?;
+    m.export("pooled_js_buffers_available", pooled_js_buffers_available)?;
+
+    m.export("ship_order", ship_order)?;
+    m.export("count_linked_nodes", count_linked_nodes)?;
+
+    m.export("sum_number_vec", sum_number_vec)?;
+    m.export("greet_optional_name", greet_optional_name)?;
+    m.export("split_pair", split_pair)?;
+    m.export("double_map_values", double_map_values)?;
+    m.export("describe_args", describe_args)?;
+    m.export("scale_rectangle", scale_rectangle)?;
+    m.export("pad_string", pad_string)?;
+    m.export("describe_color", describe_color)?;
+    m.export("invert_color", invert_color)?;
+    m.export("postpone_date", postpone_date)?;
+    m.export("time_between_dates", time_between_dates)?;
+    m.export("format_chrono_date", format_chrono_date)?;
+    m.export("turn_right", turn_right)?;
+    m.export("shape_area", shape_area)?;
+    m.export("bounding_square", bounding_square)?;
+    m.export("coerce_value", coerce_value)?;
+    m.export("box_value", box_value)?;
 
     m.export("perform_async_task", perform_async_task)?;
     m.export("perform_failing_task", perform_failing_task)?;
+    m.export("perform_mapped_task", perform_mapped_task)?;
+    m.export("perform_chained_failing_task", perform_chained_failing_task)?;
+    m.export("square_in_parallel", square_in_parallel)?;
+    m.export("square_inline", square_inline)?;
+    m.export("sleep_in_queue", sleep_in_queue)?;
+    m.export("peak_queued_sleeps", peak_queued_sleeps)?;
+    m.export("signal_from_thread", signal_from_thread)?;
+    m.export("call_root_from_thread", call_root_from_thread)?;
+    m.export("drop_root_on_thread", drop_root_on_thread)?;
+    m.export("call_root_from_threads", call_root_from_threads)?;
+    m.export("send_through_bounded_channel", send_through_bounded_channel)?;
+    m.export("fill_bounded_channel", fill_bounded_channel)?;
+    m.export("add_on_js_from_thread", add_on_js_from_thread)?;
+    m.export("block_on_js_thread", block_on_js_thread)?;
+    m.export("complete_square_task", complete_square_task)?;
+    m.export("double_promise_in_task", double_promise_in_task)?;
+    m.export("wait_for_promise_on_js_thread", wait_for_promise_on_js_thread)?;
+    m.export("defer_callbacks", defer_callbacks)?;
+    m.export("set_native_timeout", set_native_timeout)?;
+    m.export("set_cancelled_timeout", set_cancelled_timeout)?;
+    m.export("start_native_interval", start_native_interval)?;
+    m.export("stop_native_interval", stop_native_interval)?;
+
+    m.export("dropped_count", dropped_count)?;
+    m.export("squares", squares)?;
 
     m.export("panic", panic)?;
     m.export("panic_after_throw", panic_after_throw)?;
 
+    m.include(init_included)?;
+    m.include(init_namespaces)?;
+    m.export_module("arithmetic", init_arithmetic)?;
+    m.export("initialize_fresh_exports", initialize_fresh_exports)?;
+
+    m.export("neon_diagnostics", neon_diagnostics)?;
+    m.export("reset_neon_diagnostics", reset_neon_diagnostics)?;
+    m.export("count_live_handles", count_live_handles)?;
+    #[cfg(feature = "debug-handles")]
+    m.export("use_handle_after_scope", use_handle_after_scope)?;
+    m.export("undefined_from_nested_scope", undefined_from_nested_scope)?;
+    m.export("start_counting_gc_cycles", start_counting_gc_cycles)?;
+    m.export("stop_counting_gc_cycles", stop_counting_gc_cycles)?;
+
+    m.export("readable_from_iterator", readable_from_iterator)?;
+    m.export("pulled_chunks", pulled_chunks)?;
+    m.export("writable_to_thread", writable_to_thread)?;
+    m.export("picky_writable", picky_writable)?;
+    m.export("shouting_transform", shouting_transform)?;
+
     let class: Handle<JsClass<JsUser>> = JsUser::class(m.scope)?;
     let constructor: Handle<JsFunction<JsUser>> = class.constructor(m.scope)?;
     m.exports.set("User", constructor)?;
 
+    let prototype: Handle<JsObject> = JsUser::prototype(m.scope)?;
+    let kind: Handle<JsFunction> = JsFunction::new(m.scope, user_kind)?;
+    prototype.set("kind", kind)?;
+
+    let class: Handle<JsClass<JsDropCounter>> = JsDropCounter::class(m.scope)?;
+    let constructor: Handle<JsFunction<JsDropCounter>> = class.constructor(m.scope)?;
+    m.exports.set("DropCounter", constructor)?;
+
+    let class: Handle<JsClass<JsByteBuffer>> = JsByteBuffer::class(m.scope)?;
+    let constructor: Handle<JsFunction<JsByteBuffer>> = class.constructor(m.scope)?;
+    m.exports.set("ByteBuffer", constructor)?;
+
+    let class: Handle<JsClass<JsRange>> = JsRange::class(m.scope)?;
+    let constructor: Handle<JsFunction<JsRange>> = class.constructor(m.scope)?;
+    m.exports.set("Range", constructor)?;
+
+    let class: Handle<JsClass<JsPoint>> = JsPoint::class(m.scope)?;
+    let constructor: Handle<JsFunction<JsPoint>> = class.constructor(m.scope)?;
+    m.exports.set("Point", constructor)?;
+
     let class: Handle<JsClass<JsPanickyAllocator>> = JsPanickyAllocator::class(m.scope)?;
     let constructor: Handle<JsFunction<JsPanickyAllocator>> = class.constructor(m.scope)?;
     m.exports.set("PanickyAllocator", constructor)?;
@@ -74,5 +264,13 @@ register_module!(m, {
     let constructor: Handle<JsFunction<JsPanickyConstructor>> = class.constructor(m.scope)?;
     m.exports.set("PanickyConstructor", constructor)?;
 
+    m.test("add1 adds one", test_add1)?;
+    m.test("square task performs and completes", test_square_task)?;
+    m.test("tasks run inline", test_inline_task)?;
+    m.test("assert_js_eq compares structurally", test_assert_js_eq)?;
+    m.test_module("exports are named after their keys", test_module_exports)?;
+
+    m.es_module()?;
+
     Ok(())
 });